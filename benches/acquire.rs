@@ -0,0 +1,167 @@
+// Uncontended acquire and release on locks whose events reach nothing, the
+// cases where tracing should cost as little as possible, and one lock read
+// and written from several threads of a multi-threaded runtime at once, next
+// to plain tokio doing the same. Then a lock whose events reach a subscriber,
+// one at a time and in batches. The log fallback is silenced throughout, so
+// no case writes to stderr.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rwlock_trace::{Fallback, LockConfig, RwLock};
+
+use tokio::runtime::Runtime;
+
+use std::{
+    future::Future,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+const THREADS: usize = 4;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("runtime")
+}
+
+fn bench_lock(c: &mut Criterion, case: &str, lock: &RwLock<u64>) {
+    let runtime = runtime();
+    c.bench_function(&format!("{}/read", case), |b| {
+        b.to_async(&runtime)
+            .iter(|| async { drop(lock.read().await) })
+    });
+    c.bench_function(&format!("{}/write", case), |b| {
+        b.to_async(&runtime)
+            .iter(|| async { **lock.write().await += 1 })
+    });
+}
+
+// Each iteration is one read on every thread, so the time per iteration
+// grows with whatever the threads contend on.
+fn bench_shared_reads(c: &mut Criterion, case: &str, lock: &Arc<RwLock<u64>>) {
+    c.bench_function(&format!("{}/shared_read", case), |b| {
+        b.iter_custom(|iters| {
+            let start = Instant::now();
+            let threads: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    let lock = lock.clone();
+                    thread::spawn(move || {
+                        runtime().block_on(async {
+                            for _ in 0..iters {
+                                drop(lock.read().await);
+                            }
+                        })
+                    })
+                })
+                .collect();
+            for thread in threads {
+                thread.join().expect("bench thread");
+            }
+            start.elapsed()
+        })
+    });
+}
+
+// Each iteration is one write from every task, spread over the worker
+// threads, so every acquire after the first in a round queues.
+fn bench_contended<F, Fut>(c: &mut Criterion, case: &str, write: F)
+where
+    F: Fn() -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(THREADS)
+        .build()
+        .expect("runtime");
+    c.bench_function(&format!("{}/contended_write", case), |b| {
+        b.to_async(&runtime).iter_custom(|iters| {
+            let write = write.clone();
+            async move {
+                let start = Instant::now();
+                let tasks: Vec<_> = (0..THREADS)
+                    .map(|_| {
+                        let write = write.clone();
+                        tokio::spawn(async move {
+                            for _ in 0..iters {
+                                write().await;
+                            }
+                        })
+                    })
+                    .collect();
+                for task in tasks {
+                    task.await.expect("bench task");
+                }
+                start.elapsed()
+            }
+        })
+    });
+}
+
+fn bench_contended_lock(c: &mut Criterion, case: &str, lock: &Arc<RwLock<u64>>) {
+    let lock = lock.clone();
+    bench_contended(c, case, move || {
+        let lock = lock.clone();
+        async move { **lock.write().await += 1 }
+    });
+}
+
+fn acquire(c: &mut Criterion) {
+    rwlock_trace::set_fallback(Fallback::Silent);
+
+    let runtime = runtime();
+    let untraced = tokio::sync::RwLock::new(0u64);
+    c.bench_function("untraced/read", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { drop(untraced.read().await) })
+    });
+    c.bench_function("untraced/write", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { *untraced.write().await += 1 })
+    });
+    let untraced = Arc::new(untraced);
+    bench_contended(c, "untraced", move || {
+        let untraced = untraced.clone();
+        async move { *untraced.write().await += 1 }
+    });
+
+    // No logger, no subscriber and no other sink: every event is dropped.
+    let unobserved = Arc::new(RwLock::new_named(0u64, "bench.unobserved"));
+    bench_lock(c, "unobserved", &unobserved);
+    bench_shared_reads(c, "unobserved", &unobserved);
+    bench_contended_lock(c, "unobserved", &unobserved);
+
+    let disabled = Arc::new(RwLock::new_named(0u64, "bench.disabled"));
+    {
+        let _off = rwlock_trace::disable_all();
+        bench_lock(c, "disabled", &disabled);
+        bench_shared_reads(c, "disabled", &disabled);
+        bench_contended_lock(c, "disabled", &disabled);
+    }
+
+    // A subscriber drained on a thread of its own, so its channel never fills.
+    let mut events = rwlock_trace::subscribe();
+    thread::spawn(move || while events.blocking_recv().is_some() {});
+    let subscribed = RwLock::new_named(0u64, "bench.subscribed");
+    bench_lock(c, "subscribed", &subscribed);
+    {
+        let _batching = rwlock_trace::batch_events(256, Duration::from_millis(10));
+        bench_lock(c, "batched", &subscribed);
+    }
+
+    // A directive turns this one lock off while a subscriber takes the rest.
+    let _events = rwlock_trace::subscribe();
+    rwlock_trace::set_directives("bench.filtered=off").expect("directive");
+    let filtered = RwLock::new_named(0u64, "bench.filtered");
+    bench_lock(c, "filtered", &filtered);
+    rwlock_trace::set_directives("").expect("directive");
+
+    let muted = LockConfig::builder()
+        .name("bench.muted")
+        .muted(true)
+        .build(0u64);
+    bench_lock(c, "muted", &muted);
+}
+
+criterion_group!(benches, acquire);
+criterion_main!(benches);
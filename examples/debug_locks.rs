@@ -0,0 +1,61 @@
+// Serves `rwlock_trace::snapshot()` at http://127.0.0.1:3000/debug/locks while
+// a few tasks contend on a lock. A real service would mount the same handler
+// on its admin router; with the `serde` feature the snapshot can also be
+// handed straight to a JSON serializer instead of `to_json`.
+
+use rwlock_trace::RwLock;
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let sessions = Arc::new(RwLock::new_named(Vec::<u64>::new(), "sessions"));
+
+    for worker in 0..4u64 {
+        let sessions = sessions.clone();
+        tokio::spawn(async move {
+            loop {
+                if worker == 0 {
+                    let mut guard = sessions.write().await;
+                    guard.push(worker);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                } else {
+                    let _guard = sessions.read().await;
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        });
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:3000").await?;
+    println!("serving http://127.0.0.1:3000/debug/locks");
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            let mut request = [0; 1024];
+            let read = stream.read(&mut request).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..read]);
+
+            let response = if request.starts_with("GET /debug/locks ") {
+                let body = rwlock_trace::snapshot().to_json();
+                format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                    .to_owned()
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
+        });
+    }
+}
@@ -0,0 +1,79 @@
+// Serves `rwlock_trace::debug_router()` at http://127.0.0.1:3000/debug while a
+// few tasks contend on two locks. Run with `--check` to fetch every route once
+// and exit, failing if any of them does not answer 200.
+
+use rwlock_trace::RwLock;
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+const ROUTES: [&str; 6] = [
+    "/debug/locks",
+    "/debug/locks/sessions",
+    "/debug/dump",
+    "/debug/held",
+    "/debug/summary",
+    "/debug/metrics",
+];
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let sessions = Arc::new(RwLock::new_named(Vec::<u64>::new(), "sessions").with_event_ring(64));
+    let config = Arc::new(RwLock::new_named(0u64, "config"));
+
+    for worker in 0..4u64 {
+        let sessions = sessions.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            loop {
+                if worker == 0 {
+                    let mut guard = sessions.write().await;
+                    guard.push(worker);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                } else {
+                    let _config = config.read().await;
+                    let _guard = sessions.read().await;
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        });
+    }
+
+    let app = axum::Router::new().nest("/debug", rwlock_trace::debug_router());
+    let listener = TcpListener::bind("127.0.0.1:3000").await?;
+    let server = tokio::spawn(async move { axum::serve(listener, app).await });
+
+    if std::env::args().any(|arg| arg == "--check") {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        for route in ROUTES {
+            let response = get(route).await?;
+            let status = response.lines().next().unwrap_or_default().to_owned();
+            println!("{} -> {} ({} bytes)", route, status, response.len());
+            if !status.contains(" 200 ") {
+                eprintln!("{}", response);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    println!("serving http://127.0.0.1:3000/debug/{{locks,dump,held,summary,metrics}}");
+    server.await.expect("server task panicked")
+}
+
+async fn get(route: &str) -> std::io::Result<String> {
+    let mut stream = TcpStream::connect("127.0.0.1:3000").await?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nhost: 127.0.0.1\r\nconnection: close\r\n\r\n",
+        route
+    );
+    stream.write_all(request.as_bytes()).await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    Ok(response)
+}
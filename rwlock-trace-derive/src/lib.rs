@@ -0,0 +1,124 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Field, Fields, LitStr, Type};
+
+// Generates `new_traced`, which takes one argument per field. Lock fields take
+// the value to protect and are named `Struct.field`; other fields are passed
+// through unchanged.
+#[proc_macro_derive(TracedLocks, attributes(traced))]
+pub fn derive_traced_locks(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "TracedLocks can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut args = Vec::new();
+    let mut inits = Vec::new();
+    for (position, field) in fields.iter().enumerate() {
+        let (arg, label) = match &field.ident {
+            Some(ident) => (ident.clone(), ident.to_string()),
+            None => (format_ident!("field{}", position), position.to_string()),
+        };
+        let ty = &field.ty;
+        match lock_name(field, &format!("{}.{}", input.ident, label))? {
+            Some(name) => {
+                let lock = quote!(<#ty as ::rwlock_trace::__private::NamedLock>);
+                args.push(quote!(#arg: #lock::Inner));
+                inits.push(quote!(#lock::new_named(#arg, #name)));
+            }
+            None => {
+                args.push(quote!(#arg: #ty));
+                inits.push(quote!(#arg));
+            }
+        }
+    }
+
+    let body = match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|field| &field.ident);
+            quote!(Self { #(#idents: #inits),* })
+        }
+        Fields::Unnamed(_) => quote!(Self(#(#inits),*)),
+        Fields::Unit => quote!(Self),
+    };
+
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #[allow(clippy::too_many_arguments)]
+            #vis fn new_traced(#(#args),*) -> Self {
+                #body
+            }
+        }
+    })
+}
+
+// Fields whose type is spelled `RwLock<..>` are traced unless skipped; any
+// other type, such as an alias, needs `#[traced(rwlock)]`.
+fn lock_name(field: &Field, default: &str) -> syn::Result<Option<String>> {
+    let mut traced = is_rwlock(&field.ty);
+    let mut forced = false;
+    let mut skip = false;
+    let mut name = None;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("traced"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rwlock") {
+                forced = true;
+                Ok(())
+            } else if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else if meta.path.is_ident("mutex") {
+                Err(meta.error("rwlock-trace has no traced Mutex; use a traced RwLock or `skip`"))
+            } else {
+                Err(meta.error("expected `rwlock`, `name = \"..\"` or `skip`"))
+            }
+        })?;
+        if skip && (forced || name.is_some()) {
+            return Err(Error::new_spanned(
+                attr,
+                "`skip` cannot be combined with `rwlock` or `name`",
+            ));
+        }
+    }
+
+    traced |= forced || name.is_some();
+    if skip || !traced {
+        return Ok(None);
+    }
+    Ok(Some(name.unwrap_or_else(|| default.to_owned())))
+}
+
+fn is_rwlock(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "RwLock"),
+        _ => false,
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    config::{self, Idx},
+    event,
+    phase::{Mode, Phase},
+    registry::LockMeta,
+    READ, WRITE,
+};
+
+use log::Level;
+
+use std::{fmt, panic::Location};
+
+type Allowed = Box<dyn Fn(&Location<'_>) -> bool + Send + Sync>;
+
+// Which call sites may acquire a lock in one mode. An upgradable read is
+// checked as a read when taken and as a write where it is upgraded.
+pub(crate) struct AccessRule {
+    allowed: Allowed,
+    description: String,
+}
+
+impl AccessRule {
+    pub(crate) fn new<F>(allowed: F) -> Self
+    where
+        F: Fn(&Location<'_>) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            allowed: Box::new(allowed),
+            description: "rejected by its predicate".to_owned(),
+        }
+    }
+
+    // `myapp::maintenance` allows `src/maintenance.rs` and everything under
+    // `src/maintenance/` of the `myapp` crate. Locations only carry files, so
+    // modules declared inline or through `#[path]` need the predicate form.
+    pub(crate) fn modules(modules: &[&str]) -> Self {
+        let modules: Vec<String> = modules.iter().map(|module| module.to_string()).collect();
+        let description = format!("outside modules {:?}", modules);
+        Self {
+            allowed: Box::new(move |caller| {
+                modules
+                    .iter()
+                    .any(|module| in_module(caller.file(), module))
+            }),
+            description,
+        }
+    }
+}
+
+impl fmt::Debug for AccessRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AccessRule({})", self.description)
+    }
+}
+
+pub(crate) fn check(meta: &LockMeta, mode: Mode, idx: u64, caller: &'static Location<'static>) {
+    let rule = match mode {
+        READ => meta.read_access.get(),
+        WRITE => meta.write_access.get(),
+        _ => None,
+    };
+    let rule = match rule {
+        Some(rule) if !(rule.allowed)(caller) => rule,
+        _ => return,
+    };
+    let message = format!(
+        "[ACCESS VIOLATION] [{}] Acquire ({}:{}) {}",
+        mode,
+        meta.name(),
+        Idx(idx),
+        rule.description
+    );
+    if config::strict_access() {
+        panic!(
+            "{}: {}:{}:{}",
+            message,
+            caller.file(),
+            caller.line(),
+            caller.column()
+        );
+    }
+    event::emit_message(
+        Level::Error,
+        meta,
+        mode,
+        Phase::Access,
+        idx,
+        caller,
+        message,
+    );
+}
+
+// Any `src` directory can be a crate root: the package being built sits at a
+// relative `src/`, a workspace member under its own directory and a dependency
+// under a `name-version` checkout. The crate is checked against that directory
+// when there is one, with `-` and `_` treated alike.
+fn in_module(file: &str, module: &str) -> bool {
+    let file = file.replace('\\', "/");
+    let components: Vec<&str> = file
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect();
+    let mut segments = module.split("::");
+    let krate = segments.next().unwrap_or_default();
+    let path: Vec<&str> = segments.collect();
+    components
+        .iter()
+        .enumerate()
+        .filter(|(_, component)| **component == "src")
+        .any(|(at, _)| {
+            let crate_matches = at == 0 || crate_dir(components[at - 1], krate);
+            crate_matches && under(&components[at + 1..], &path)
+        })
+}
+
+fn crate_dir(dir: &str, krate: &str) -> bool {
+    let dir = dir.replace('-', "_");
+    let krate = krate.replace('-', "_");
+    match dir.strip_prefix(&krate) {
+        Some("") => true,
+        Some(version) => version
+            .strip_prefix('_')
+            .is_some_and(|version| version.starts_with(|c: char| c.is_ascii_digit())),
+        None => false,
+    }
+}
+
+// `files` is the path below `src`, `path` the module below the crate root.
+fn under(files: &[&str], path: &[&str]) -> bool {
+    let (last, parents) = match path.split_last() {
+        Some(split) => split,
+        None => return true,
+    };
+    if files.len() <= parents.len() || files[..parents.len()] != *parents {
+        return false;
+    }
+    let next = files[parents.len()];
+    if files.len() == parents.len() + 1 {
+        next.strip_suffix(".rs") == Some(*last)
+    } else {
+        next == *last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::in_module;
+    use crate::{Mode, Phase, RwLock};
+
+    #[test]
+    fn matches_files_below_the_crate_root() {
+        assert!(in_module("src/maintenance.rs", "myapp::maintenance"));
+        assert!(in_module("src/maintenance/jobs.rs", "myapp::maintenance"));
+        assert!(in_module(
+            "src/maintenance/jobs/nightly.rs",
+            "myapp::maintenance"
+        ));
+        assert!(in_module(
+            "src/maintenance/jobs/mod.rs",
+            "myapp::maintenance::jobs"
+        ));
+        assert!(in_module("./src/maintenance.rs", "myapp::maintenance"));
+        assert!(in_module("src\\maintenance\\jobs.rs", "myapp::maintenance"));
+        assert!(!in_module("src/maintenance_old.rs", "myapp::maintenance"));
+        assert!(!in_module(
+            "src/handlers/maintenance.rs",
+            "myapp::maintenance"
+        ));
+        assert!(!in_module("src/maintenance.rs", "myapp::maintenance::jobs"));
+        assert!(in_module("src/lib.rs", "myapp"));
+    }
+
+    #[test]
+    fn matches_nested_workspace_members_by_crate() {
+        assert!(in_module("myapp/src/maintenance.rs", "myapp::maintenance"));
+        assert!(in_module(
+            "crates/my-app/src/maintenance/jobs.rs",
+            "my_app::maintenance"
+        ));
+        assert!(in_module(
+            "services/api/myapp/src/maintenance.rs",
+            "myapp::maintenance"
+        ));
+        assert!(!in_module(
+            "crates/other/src/maintenance.rs",
+            "myapp::maintenance"
+        ));
+        assert!(!in_module(
+            "crates/myapp-cli/src/maintenance.rs",
+            "myapp::maintenance"
+        ));
+        // A `src` directory inside a module is not mistaken for a crate root.
+        assert!(!in_module(
+            "myapp/src/tools/src/maintenance.rs",
+            "myapp::maintenance"
+        ));
+    }
+
+    #[test]
+    fn matches_dependency_checkouts_with_a_version() {
+        let registry = "/home/me/.cargo/registry/src/index.crates.io-6f17d22bba15001f";
+        let file = format!("{}/my-dep-1.2.3/src/pool/conn.rs", registry);
+        assert!(in_module(&file, "my_dep::pool"));
+        assert!(!in_module(&file, "my_dep::cache"));
+        let file = format!("{}/my-dep-extra-1.0.0/src/pool.rs", registry);
+        assert!(!in_module(&file, "my_dep::pool"));
+    }
+
+    #[tokio::test]
+    async fn upgrades_are_checked_against_write_restrictions() {
+        let lock = RwLock::new_named(0u8, "access-upgrade")
+            .restrict_writes_to_modules(&["myapp::maintenance"]);
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.phase == Phase::Access
+        });
+
+        let upgradable = lock.upgradable_read().await;
+        assert!(
+            events.try_recv().is_err(),
+            "an upgradable read is only a read"
+        );
+        drop(upgradable.upgrade().await);
+
+        let violation = events.try_recv().expect("the upgrade was not checked");
+        assert_eq!(violation.mode, Mode::Write);
+        assert!(violation.message.contains("[ACCESS VIOLATION]"));
+        assert_eq!(violation.location.file(), file!());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::summary::{self, Row};
+
+use std::{fmt, sync::RwLock, time::Duration};
+
+static THRESHOLDS: RwLock<AdvisoryThresholds> = RwLock::new(AdvisoryThresholds::new());
+
+// Locks with fewer acquisitions than `min_acquisitions` are not judged at all;
+// a handful of uses says nothing about the access pattern.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdvisoryThresholds {
+    pub min_acquisitions: u64,
+    pub read_mostly_pct: f64,
+    pub read_mostly_hold: Duration,
+    pub high_contention_pct: f64,
+}
+
+impl AdvisoryThresholds {
+    pub const fn new() -> Self {
+        Self {
+            min_acquisitions: 100,
+            read_mostly_pct: 99.0,
+            read_mostly_hold: Duration::from_micros(1),
+            high_contention_pct: 10.0,
+        }
+    }
+}
+
+impl Default for AdvisoryThresholds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn set_advisory_thresholds(thresholds: AdvisoryThresholds) {
+    *THRESHOLDS.write().unwrap_or_else(|e| e.into_inner()) = thresholds;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Finding {
+    ReadMostly {
+        read_pct: f64,
+        p99_hold: Duration,
+    },
+    WriteOnly {
+        writes: u64,
+    },
+    HighContention {
+        contended_pct: f64,
+        suggestion: &'static str,
+    },
+    NeverContended {
+        acquisitions: u64,
+    },
+}
+
+// `created_at` is the lock's construction site, when it was recorded.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Advisory {
+    pub lock_id: u64,
+    pub name: String,
+    pub created_at: Option<String>,
+    pub finding: Finding,
+}
+
+impl fmt::Display for Advisory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({})", self.name)?;
+        if let Some(site) = &self.created_at {
+            write!(f, " created at {}", site)?;
+        }
+        match &self.finding {
+            Finding::ReadMostly { read_pct, p99_hold } => write!(
+                f,
+                ": {:.1}% reads, p99 hold {:?}; consider arc-swap or RCU",
+                read_pct, p99_hold
+            ),
+            Finding::WriteOnly { writes } => write!(
+                f,
+                ": only ever written ({} writes); a Mutex would be simpler",
+                writes
+            ),
+            Finding::HighContention {
+                contended_pct,
+                suggestion,
+            } => write!(f, ": {:.1}% contended; {}", contended_pct, suggestion),
+            Finding::NeverContended { acquisitions } => write!(
+                f,
+                ": never contended in {} acquisitions; the lock may not be needed",
+                acquisitions
+            ),
+        }
+    }
+}
+
+// Reads the same rows as `summary`, so locks retired under an exit guard are
+// judged too.
+pub fn advisories() -> Vec<Advisory> {
+    let thresholds = *THRESHOLDS.read().unwrap_or_else(|e| e.into_inner());
+    summary::rows()
+        .iter()
+        .flat_map(|row| findings(row, &thresholds))
+        .collect()
+}
+
+fn findings(row: &Row, thresholds: &AdvisoryThresholds) -> Vec<Advisory> {
+    let stats = &row.stats;
+    let acquisitions = stats.acquisitions();
+    if acquisitions == 0 || acquisitions < thresholds.min_acquisitions {
+        return Vec::new();
+    }
+
+    let mut findings = Vec::new();
+    let read_pct = stats.reads as f64 * 100.0 / acquisitions as f64;
+    if stats.reads == 0 {
+        findings.push(Finding::WriteOnly {
+            writes: stats.writes,
+        });
+    } else if read_pct >= thresholds.read_mostly_pct
+        && stats.p99_hold <= thresholds.read_mostly_hold
+    {
+        findings.push(Finding::ReadMostly {
+            read_pct,
+            p99_hold: stats.p99_hold,
+        });
+    }
+
+    let contended_pct = stats.contended_pct();
+    if stats.contended == 0 {
+        findings.push(Finding::NeverContended { acquisitions });
+    } else if contended_pct >= thresholds.high_contention_pct {
+        let suggestion = if stats.reads > stats.writes {
+            "readers queue behind writers; shorten or batch the writes"
+        } else {
+            "writers queue behind each other; shard the data or shorten the holds"
+        };
+        findings.push(Finding::HighContention {
+            contended_pct,
+            suggestion,
+        });
+    }
+
+    let created_at = row
+        .created_at
+        .map(|site| format!("{}:{}:{}", site.file(), site.line(), site.column()));
+    findings
+        .into_iter()
+        .map(|finding| Advisory {
+            lock_id: row.id,
+            name: row.name.clone(),
+            created_at: created_at.clone(),
+            finding,
+        })
+        .collect()
+}
@@ -0,0 +1,215 @@
+use crate::{
+    guard, owned,
+    traced::{RawAsyncRwLock, RawOwnedRwLock},
+    TracedLock,
+};
+
+use std::{future::Future, sync::Arc};
+
+// A `TracedLock` around `async_lock::RwLock`, for code running on smol
+// or async-std. Like `sync`, it sits beside the tokio type instead of
+// replacing it.
+pub type RwLock<T> = TracedLock<T, ::async_lock::RwLock<T>>;
+pub type RwLockReadGuard<'a, T> = guard::RwLockReadGuard<'a, T, ::async_lock::RwLock<T>>;
+pub type RwLockWriteGuard<'a, T> = guard::RwLockWriteGuard<'a, T, ::async_lock::RwLock<T>>;
+pub type OwnedRwLockReadGuard<T> = owned::OwnedRwLockReadGuard<T, T, ::async_lock::RwLock<T>>;
+pub type OwnedRwLockWriteGuard<T> = owned::OwnedRwLockWriteGuard<T, ::async_lock::RwLock<T>>;
+
+impl<T: ?Sized> RawAsyncRwLock<T> for ::async_lock::RwLock<T> {
+    type ReadGuard<'a>
+        = ::async_lock::RwLockReadGuard<'a, T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = ::async_lock::RwLockWriteGuard<'a, T>
+    where
+        Self: 'a;
+
+    const KIND: &'static str = "async_lock";
+
+    fn new(inner: T) -> Self
+    where
+        T: Sized,
+    {
+        ::async_lock::RwLock::new(inner)
+    }
+
+    fn raw_read(&self) -> impl Future<Output = Self::ReadGuard<'_>> {
+        self.read()
+    }
+
+    fn raw_write(&self) -> impl Future<Output = Self::WriteGuard<'_>> {
+        self.write()
+    }
+
+    fn try_raw_read(&self) -> Option<Self::ReadGuard<'_>> {
+        self.try_read()
+    }
+
+    fn try_raw_write(&self) -> Option<Self::WriteGuard<'_>> {
+        self.try_write()
+    }
+
+    fn into_inner(self) -> T
+    where
+        T: Sized,
+    {
+        ::async_lock::RwLock::into_inner(self)
+    }
+}
+
+impl<T> RawOwnedRwLock<T> for ::async_lock::RwLock<T> {
+    type OwnedReadGuard = ::async_lock::RwLockReadGuardArc<T>;
+    type OwnedWriteGuard = ::async_lock::RwLockWriteGuardArc<T>;
+
+    async fn raw_read_owned(self: Arc<Self>) -> Self::OwnedReadGuard {
+        self.read_arc().await
+    }
+
+    async fn raw_write_owned(self: Arc<Self>) -> Self::OwnedWriteGuard {
+        self.write_arc().await
+    }
+
+    fn try_raw_read_owned(self: Arc<Self>) -> Option<Self::OwnedReadGuard> {
+        self.try_read_arc()
+    }
+
+    fn try_raw_write_owned(self: Arc<Self>) -> Option<Self::OwnedWriteGuard> {
+        self.try_write_arc()
+    }
+}
+
+// Driven by `futures`' executor, with no tokio runtime anywhere.
+#[cfg(test)]
+mod tests {
+    use super::RwLock;
+    use crate::{Mode, OwnedRwLockWriteGuard, Phase, RwLockWriteGuard};
+
+    use futures::{executor, future};
+
+    use std::{sync::Arc, task::Poll, thread};
+
+    fn drain(events: &mut tokio::sync::mpsc::Receiver<crate::LockEvent>) -> Vec<(Mode, Phase)> {
+        std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| (event.mode, event.phase))
+            .collect()
+    }
+
+    #[test]
+    fn guards_are_traced_off_tokio() {
+        let lock = RwLock::new_named(vec![1u8], "async-lock-traced");
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| event.lock_id == id);
+        assert!(format!("{:?}", lock).contains("kind: \"async_lock\""));
+
+        executor::block_on(async {
+            assert_eq!(lock.read().await[0], 1);
+            lock.write().await.push(2);
+            let mut second = RwLockWriteGuard::map(lock.write().await, |value| &mut value[1]);
+            *second += 1;
+            drop(second);
+            let upgradable = lock.upgradable_read().await;
+            upgradable.upgrade().await.push(4);
+        });
+
+        use Phase::{Acquire, Got, Map, Release, Upgrade};
+        let (read, write) = (Mode::Read, Mode::Write);
+        assert_eq!(
+            drain(&mut events),
+            [
+                (Mode::Lock, Phase::FirstUse),
+                (read, Acquire),
+                (read, Got),
+                (read, Release),
+                (write, Acquire),
+                (write, Got),
+                (write, Release),
+                (write, Acquire),
+                (write, Got),
+                (write, Map),
+                (write, Release),
+                (read, Acquire),
+                (read, Got),
+                (read, Release),
+                (write, Upgrade),
+                (write, Got),
+                (write, Release),
+            ]
+        );
+        let stats = lock.stats();
+        assert_eq!((stats.reads, stats.writes, stats.releases), (2, 3, 5));
+        assert_eq!(lock.into_inner(), [1, 3, 4]);
+    }
+
+    #[test]
+    fn writes_that_only_read_are_reported() {
+        let lock = RwLock::new_named(0u8, "async-lock-unmutated");
+        let id = lock.id();
+        let mut unmutated = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.phase == Phase::Unmutated
+        });
+
+        executor::block_on(async {
+            assert_eq!(**lock.write().await, 0);
+            **lock.write().await += 1;
+        });
+        let event = unmutated.try_recv().expect("no unmutated report");
+        assert_eq!(event.idx, 1);
+        assert!(unmutated.try_recv().is_err());
+    }
+
+    #[test]
+    fn owned_guards_transfer_between_threads() {
+        let lock = Arc::new(RwLock::new_named(0u8, "async-lock-owned"));
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| {
+            event.lock_id == id && matches!(event.phase, Phase::Handoff | Phase::Resume)
+        });
+
+        let write = executor::block_on(lock.clone().write_owned());
+        let token = write.transfer("to-worker");
+        thread::spawn(move || {
+            let mut write = token.redeem();
+            *write += 1;
+            let mut mapped = OwnedRwLockWriteGuard::map(write, |value| value);
+            *mapped += 1;
+        })
+        .join()
+        .unwrap();
+        assert_eq!(
+            drain(&mut events),
+            [(Mode::Write, Phase::Handoff), (Mode::Write, Phase::Resume)]
+        );
+
+        let read = lock.clone().try_read_owned().unwrap();
+        assert!(lock.clone().try_write_owned().is_none());
+        assert_eq!(*read, 2);
+        drop(read);
+        let stats = lock.stats();
+        assert_eq!((stats.handoffs, stats.migrations), (1, 0));
+    }
+
+    #[test]
+    fn a_queued_writer_is_counted_as_contended() {
+        let lock = RwLock::new_named(0u8, "async-lock-contended");
+        let read = executor::block_on(lock.read());
+        let mut yielded = false;
+        let release = async move {
+            future::poll_fn(|cx| {
+                if yielded {
+                    return Poll::Ready(());
+                }
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            })
+            .await;
+            drop(read);
+        };
+        let ((write, report), ()) = executor::block_on(future::join(lock.write_verbose(), release));
+        drop(write);
+        assert!(report.contended);
+        assert_eq!(lock.stats().contended, 1);
+        assert_eq!(lock.waiter_count(), 0);
+    }
+}
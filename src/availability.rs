@@ -0,0 +1,225 @@
+use crate::{clock::Instant, event::LockEvent};
+
+use tokio::sync::{futures::Notified, Notify};
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+type ReleaseCallback = Arc<dyn Fn(&LockEvent) + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct Availability {
+    holders: AtomicUsize,
+    readers: AtomicUsize,
+    peak_readers: AtomicUsize,
+    // As `peak_readers`, since the periodic stats last took it.
+    interval_peak_readers: AtomicUsize,
+    unlocked: Notify,
+    callbacks: Mutex<Vec<ReleaseCallback>>,
+    drain: Mutex<Option<Drain>>,
+}
+
+// The read releases since the lock was last acquired, and when the first of
+// them came.
+#[derive(Debug, Clone, Copy)]
+struct Drain {
+    readers: usize,
+    since: Instant,
+}
+
+impl Availability {
+    pub(crate) fn acquired(&self) {
+        self.holders.fetch_add(1, Ordering::AcqRel);
+    }
+
+    // Returns the readers now active, this one included.
+    pub(crate) fn reader_acquired(&self) -> usize {
+        let readers = self.readers.fetch_add(1, Ordering::AcqRel) + 1;
+        self.peak_readers.fetch_max(readers, Ordering::Relaxed);
+        self.interval_peak_readers
+            .fetch_max(readers, Ordering::Relaxed);
+        self.drain().take();
+        readers
+    }
+
+    // The most readers ever active at once; a resync does not lower it.
+    pub(crate) fn peak_readers(&self) -> usize {
+        self.peak_readers.load(Ordering::Relaxed)
+    }
+
+    // Starts the next interval from the readers active now, so a read held
+    // across the boundary counts in both.
+    pub(crate) fn take_interval_peak_readers(&self) -> usize {
+        let readers = self.readers.load(Ordering::Acquire);
+        self.interval_peak_readers.swap(readers, Ordering::Relaxed)
+    }
+
+    // Returns the readers still active once this one has left, or None if the
+    // count was already zero, in which case it is left there.
+    pub(crate) fn reader_released(&self) -> Option<usize> {
+        let remaining = decrement(&self.readers)?;
+        let mut drain = self.drain();
+        let drain = drain.get_or_insert(Drain {
+            readers: 0,
+            since: Instant::now(),
+        });
+        drain.readers += 1;
+        Some(remaining)
+    }
+
+    // For a writer that just got the lock: the readers that released it in a
+    // row just before, with no acquire between them, and how long from the
+    // first release to `now`. None when the last event was not a read release.
+    pub(crate) fn writer_acquired(&self, now: Instant) -> Option<(usize, Duration)> {
+        self.drain()
+            .take()
+            .map(|drain| (drain.readers, now.saturating_duration_since(drain.since)))
+    }
+
+    // An uncounted release still reaches the callbacks but leaves the holder
+    // count alone. Returns false if the count was already zero.
+    pub(crate) fn released(&self, event: Option<LockEvent>, counted: bool) -> bool {
+        let remaining = if counted {
+            decrement(&self.holders)
+        } else {
+            Some(self.holders())
+        };
+        if let Some(event) = event {
+            for callback in self.callbacks() {
+                callback(&event);
+            }
+        }
+        if remaining.unwrap_or(0) == 0 {
+            self.unlocked.notify_waiters();
+        }
+        remaining.is_some()
+    }
+
+    // Returns the holder and reader counts it replaced.
+    pub(crate) fn resync(&self, holders: usize, readers: usize) -> (usize, usize) {
+        let holders_before = self.holders.swap(holders, Ordering::AcqRel);
+        let readers_before = self.readers.swap(readers, Ordering::AcqRel);
+        self.drain().take();
+        if holders == 0 {
+            self.unlocked.notify_waiters();
+        }
+        (holders_before, readers_before)
+    }
+
+    pub(crate) fn holders(&self) -> usize {
+        self.holders.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn is_unlocked(&self) -> bool {
+        self.holders.load(Ordering::Acquire) == 0
+    }
+
+    // Only observes the crate's own holder count and never touches the inner
+    // lock, so waiting here does not take a place in its queue. Another task
+    // may acquire the lock between the wakeup and the caller acting on it.
+    pub(crate) async fn wait_unlocked(&self) {
+        let notified = self.unlocked.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.is_unlocked() {
+            return;
+        }
+        notified.await;
+    }
+
+    // For waiting on several locks at once; enable it before checking
+    // `is_unlocked`, as `wait_unlocked` does.
+    pub(crate) fn notified(&self) -> Notified<'_> {
+        self.unlocked.notified()
+    }
+
+    pub(crate) fn add_callback(&self, callback: ReleaseCallback) {
+        self.callbacks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(callback);
+    }
+
+    pub(crate) fn has_callbacks(&self) -> bool {
+        !self
+            .callbacks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+
+    fn drain(&self) -> MutexGuard<'_, Option<Drain>> {
+        self.drain.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn callbacks(&self) -> Vec<ReleaseCallback> {
+        self.callbacks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+fn decrement(count: &AtomicUsize) -> Option<usize> {
+    count
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+        .ok()
+        .map(|before| before - 1)
+}
+
+impl fmt::Debug for Availability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Availability")
+            .field("holders", &self.holders)
+            .field("readers", &self.readers)
+            .field("peak_readers", &self.peak_readers)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{OwnedRwLockReadGuard, RwLock};
+
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn the_reader_count_returns_to_zero() {
+        let lock = Arc::new(RwLock::new_named(vec![0u8], "availability-stress"));
+        let tasks: Vec<_> = (0..64)
+            .map(|task| {
+                let lock = lock.clone();
+                tokio::spawn(async move {
+                    for round in 0..20 {
+                        match (task + round) % 4 {
+                            0 => drop(lock.read().await),
+                            1 => {
+                                let read = lock.clone().read_owned().await;
+                                drop(OwnedRwLockReadGuard::map(read, |value| &value[0]));
+                            }
+                            2 => {
+                                let read = lock.clone().read_owned().await;
+                                let mapped =
+                                    OwnedRwLockReadGuard::try_map(read, |value| value.get(1));
+                                drop(mapped.expect_err("nothing to map to"));
+                            }
+                            _ => drop(lock.upgradable_read().await.upgrade().await),
+                        }
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let report = lock.resync_counters();
+        assert_eq!((report.holders_before, report.readers_before), (0, 0));
+        assert!(!report.changed());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::fallback;
+
+use log::Level;
+use tokio::{runtime::Handle, task::JoinHandle, time::MissedTickBehavior};
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// Periodic work for the sweeper and the batcher: a task when started inside
+// a runtime, a thread of its own when started outside one, say from `main`
+// before the runtime is built. The first tick comes one interval in, as
+// there is nothing to sweep or flush at the start.
+#[derive(Debug)]
+pub(crate) enum Ticker {
+    Task(JoinHandle<()>),
+    Thread(Arc<AtomicBool>, thread::Thread),
+}
+
+impl Ticker {
+    pub(crate) fn every<F>(interval: Duration, mut tick: F) -> Self
+    where
+        F: FnMut() + Send + 'static,
+    {
+        if Handle::try_current().is_ok() {
+            return Ticker::Task(tokio::spawn(async move {
+                let start = tokio::time::Instant::now() + interval;
+                let mut ticks = tokio::time::interval_at(start, interval);
+                ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    ticks.tick().await;
+                    tick();
+                }
+            }));
+        }
+        let stopped = Arc::new(AtomicBool::new(false));
+        let flag = stopped.clone();
+        let spawned = thread::Builder::new()
+            .name("rwlock-trace".to_owned())
+            .spawn(move || {
+                let mut next = Instant::now() + interval;
+                while !flag.load(Ordering::Acquire) {
+                    let now = Instant::now();
+                    if now < next {
+                        thread::park_timeout(next - now);
+                        continue;
+                    }
+                    tick();
+                    next = Instant::now() + interval;
+                }
+            });
+        match spawned {
+            Ok(handle) => Ticker::Thread(stopped, handle.thread().clone()),
+            Err(error) => {
+                fallback::log!(
+                    Level::Warn,
+                    "[META] could not start a ticker thread: {}",
+                    error
+                );
+                Ticker::Thread(stopped, thread::current())
+            }
+        }
+    }
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        match self {
+            Ticker::Task(task) => task.abort(),
+            Ticker::Thread(stopped, thread) => {
+                stopped.store(true, Ordering::Release);
+                thread.unpark();
+            }
+        }
+    }
+}
+
+// `tokio::time::sleep`, which follows a paused clock, inside a runtime; it
+// panics outside one, where a thread waits out the duration instead. The
+// runtime is looked for on the first poll, where the timer is armed.
+pub(crate) async fn sleep(duration: Duration) {
+    if Handle::try_current().is_ok() {
+        return tokio::time::sleep(duration).await;
+    }
+    let (done, wait) = tokio::sync::oneshot::channel();
+    // Should the thread not start, the sender is dropped with it and the
+    // wait ends at once.
+    let _ = thread::Builder::new()
+        .name("rwlock-trace-timer".to_owned())
+        .spawn(move || {
+            thread::sleep(duration);
+            let _ = done.send(());
+        });
+    let _ = wait.await;
+}
+
+// Spawns `future` when there is a runtime to spawn it on; None otherwise.
+pub(crate) fn try_spawn<F>(future: F) -> Option<JoinHandle<()>>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    Handle::try_current()
+        .ok()
+        .map(|runtime| runtime.spawn(future))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sleep, Ticker};
+    use crate::{fallback, Mode, OwnedRwLockWriteGuard, Phase, RwLock};
+
+    use futures::executor::block_on;
+    use log::Level;
+
+    use std::{
+        cell::RefCell,
+        io::{self, Write},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    const READ: [(Mode, Phase); 3] = [
+        (Mode::Read, Phase::Acquire),
+        (Mode::Read, Phase::Got),
+        (Mode::Read, Phase::Release),
+    ];
+    const WRITE: [(Mode, Phase); 3] = [
+        (Mode::Write, Phase::Acquire),
+        (Mode::Write, Phase::Got),
+        (Mode::Write, Phase::Release),
+    ];
+
+    fn drain(events: &mut tokio::sync::mpsc::Receiver<crate::LockEvent>) -> Vec<(Mode, Phase)> {
+        std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| (event.mode, event.phase))
+            .collect()
+    }
+
+    #[test]
+    fn acquires_outside_a_runtime_are_traced() {
+        let lock = Arc::new(RwLock::new_named(0u8, "background-blocking"));
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| event.lock_id == id);
+
+        block_on(async {
+            drop(lock.read().await);
+            **lock.write().await = 1;
+            drop(lock.clone().read_owned().await);
+        });
+        let writer = lock.clone();
+        thread::spawn(move || block_on(async { *writer.write_owned().await = 2 }))
+            .join()
+            .unwrap();
+
+        let mut expected = vec![(Mode::Lock, Phase::FirstUse)];
+        expected.extend(READ);
+        expected.extend(WRITE);
+        expected.extend(READ);
+        expected.extend(WRITE);
+        assert_eq!(drain(&mut events), expected);
+        assert_eq!(block_on(lock.read()).to_owned(), 2);
+    }
+
+    thread_local! {
+        static LATE: RefCell<Option<OwnedRwLockWriteGuard<u8>>> = const { RefCell::new(None) };
+    }
+
+    // Left in a thread-local, the guard is dropped as the thread exits, after
+    // the crate's own thread-locals may already be gone.
+    #[test]
+    fn owned_guards_dropped_at_thread_exit_release_the_lock() {
+        let lock = Arc::new(RwLock::new_named(0u8, "background-late-drop"));
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| event.lock_id == id);
+
+        let owned = lock.clone();
+        thread::spawn(move || {
+            let mut guard = block_on(owned.write_owned());
+            *guard = 1;
+            LATE.with(|late| *late.borrow_mut() = Some(guard));
+        })
+        .join()
+        .unwrap();
+
+        let mut expected = vec![(Mode::Lock, Phase::FirstUse)];
+        expected.extend(WRITE);
+        assert_eq!(drain(&mut events), expected);
+        let read = lock.try_read_owned().expect("the lock is still held");
+        assert_eq!(*read, 1);
+    }
+
+    #[test]
+    fn tickers_and_sleeps_use_threads_outside_a_runtime() {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let counted = ticks.clone();
+        let ticker = Ticker::every(Duration::from_millis(1), move || {
+            counted.fetch_add(1, Ordering::Relaxed);
+        });
+        assert!(matches!(ticker, Ticker::Thread(..)));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while ticks.load(Ordering::Relaxed) < 3 {
+            assert!(Instant::now() < deadline, "the ticker thread never ticked");
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(ticker);
+        thread::sleep(Duration::from_millis(20));
+        let stopped = ticks.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(ticks.load(Ordering::Relaxed), stopped);
+
+        let start = Instant::now();
+        block_on(sleep(Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        drop(crate::spawn_stall_detector(
+            Duration::from_millis(1),
+            Duration::from_millis(1),
+        ));
+    }
+
+    // Stands in for a closed stderr.
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn the_stderr_fallback_never_panics() {
+        let mut out = Vec::new();
+        fallback::write_record(
+            &mut out,
+            true,
+            "target",
+            Level::Warn,
+            format_args!("message"),
+        );
+        fallback::write_record(
+            &mut out,
+            false,
+            "target",
+            Level::Info,
+            format_args!("again"),
+        );
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("INFO  rwlock_trace: [META] no logger is installed"));
+        assert_eq!(
+            &lines[1..],
+            ["WARN  target: message", "INFO  target: again"]
+        );
+
+        thread::spawn(|| {
+            fallback::write_record(
+                &mut Closed,
+                true,
+                "target",
+                Level::Warn,
+                format_args!("lost"),
+            )
+        })
+        .join()
+        .expect("a closed stderr panicked");
+    }
+}
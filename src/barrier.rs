@@ -0,0 +1,279 @@
+use crate::{background, fallback, severity, task::TaskKey, LockConfig};
+
+use tokio::sync::BarrierWaitResult;
+
+use std::{
+    fmt,
+    future::{self, Future},
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+    task::Poll,
+    time::Duration,
+};
+
+type Site = (&'static Location<'static>, TaskKey);
+
+// Call sites are only kept with a watchdog; without one, `wait` costs a single
+// atomic add on top of tokio's barrier.
+#[derive(Debug, Default)]
+struct Sites {
+    generation: u64,
+    current: Vec<Site>,
+    previous: Vec<&'static Location<'static>>,
+}
+
+pub struct Barrier {
+    inner: tokio::sync::Barrier,
+    name: String,
+    parties: u64,
+    // Every arrival ever; the generation and position within it follow from
+    // dividing by `parties`, so reuse needs no reset.
+    arrivals: AtomicU64,
+    watchdog: Option<Duration>,
+    warned: AtomicU64,
+    sites: Mutex<Sites>,
+}
+
+impl Barrier {
+    pub fn new(parties: usize) -> Barrier {
+        Self::new_named(parties, LockConfig::generated_name("barrier"))
+    }
+
+    pub fn new_named(parties: usize, name: impl Into<String>) -> Barrier {
+        Barrier {
+            inner: tokio::sync::Barrier::new(parties),
+            name: name.into(),
+            parties: parties.max(1) as u64,
+            arrivals: AtomicU64::new(0),
+            watchdog: None,
+            warned: AtomicU64::new(0),
+            sites: Mutex::new(Sites::default()),
+        }
+    }
+
+    // Warns once per generation when a waiter has been parked this long,
+    // listing who has arrived and who arrived last time but not yet this time.
+    pub fn with_watchdog(mut self, threshold: Duration) -> Self {
+        self.watchdog = Some(threshold);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn parties(&self) -> usize {
+        self.parties as usize
+    }
+
+    #[track_caller]
+    pub fn wait(&self) -> impl Future<Output = BarrierWaitResult> + '_ {
+        let caller = Location::caller();
+
+        async move {
+            let task = TaskKey::current();
+            let arrival = self.arrivals.fetch_add(1, Ordering::Relaxed);
+            let generation = arrival / self.parties;
+            let position = arrival % self.parties + 1;
+            fallback::log!(
+                severity::severity_policy().acquire,
+                "[BARRIER] Arrive ({}) {}/{} generation={} {}: {}:{}:{}",
+                self.name,
+                position,
+                self.parties,
+                generation,
+                task,
+                caller.file(),
+                caller.line(),
+                caller.column(),
+            );
+            if self.watchdog.is_some() {
+                self.sites().arrive(generation, (caller, task));
+            }
+
+            let result = match self.watchdog {
+                Some(threshold) => self.wait_watched(generation, threshold).await,
+                None => self.inner.wait().await,
+            };
+
+            if result.is_leader() {
+                fallback::log!(
+                    severity::severity_policy().release,
+                    "[BARRIER] Released ({}) generation={} leader {}: {}:{}:{}",
+                    self.name,
+                    generation,
+                    task,
+                    caller.file(),
+                    caller.line(),
+                    caller.column(),
+                );
+            }
+            result
+        }
+    }
+
+    async fn wait_watched(&self, generation: u64, threshold: Duration) -> BarrierWaitResult {
+        let wait = self.inner.wait();
+        tokio::pin!(wait);
+        let deadline = background::sleep(threshold);
+        tokio::pin!(deadline);
+        let mut expired = false;
+
+        future::poll_fn(|cx| {
+            if let Poll::Ready(result) = wait.as_mut().poll(cx) {
+                return Poll::Ready(result);
+            }
+            if !expired && deadline.as_mut().poll(cx).is_ready() {
+                expired = true;
+                self.report_stalled(generation, threshold);
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn report_stalled(&self, generation: u64, threshold: Duration) {
+        // Every parked waiter's timer fires; only the first reports.
+        if self.warned.fetch_max(generation + 1, Ordering::Relaxed) > generation {
+            return;
+        }
+        let sites = self.sites();
+        if sites.generation != generation {
+            return;
+        }
+        let arrived: Vec<String> = sites
+            .current
+            .iter()
+            .map(|(site, task)| {
+                format!(
+                    "{} at {}:{}:{}",
+                    task,
+                    site.file(),
+                    site.line(),
+                    site.column()
+                )
+            })
+            .collect();
+        // Participants often share a call site, so each arrival only accounts
+        // for one of last generation's.
+        let mut unmatched: Vec<_> = sites.current.iter().map(|(site, _)| *site).collect();
+        let outstanding: Vec<String> = sites
+            .previous
+            .iter()
+            .filter(
+                |site| match unmatched.iter().position(|current| current == *site) {
+                    Some(position) => {
+                        unmatched.swap_remove(position);
+                        false
+                    }
+                    None => true,
+                },
+            )
+            .map(|site| format!("{}:{}:{}", site.file(), site.line(), site.column()))
+            .collect();
+        fallback::log!(
+            severity::severity_policy().anomaly,
+            "[BARRIER] Stalled ({}) {}/{} arrived after {:?} generation={}\
+             \n    arrived: [{}]\n    outstanding (arrived last generation): [{}]",
+            self.name,
+            sites.current.len(),
+            self.parties,
+            threshold,
+            generation,
+            arrived.join(", "),
+            outstanding.join(", "),
+        );
+    }
+
+    fn sites(&self) -> MutexGuard<'_, Sites> {
+        self.sites.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Sites {
+    fn arrive(&mut self, generation: u64, site: Site) {
+        if generation > self.generation {
+            self.previous = self.current.drain(..).map(|(site, _)| site).collect();
+            self.generation = generation;
+        }
+        if generation == self.generation {
+            self.current.push(site);
+        }
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Barrier")
+            .field("name", &self.name)
+            .field("parties", &self.parties)
+            .field("arrivals", &self.arrivals.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Barrier;
+
+    use std::{
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn each_generation_has_one_leader() {
+        let barrier = Arc::new(Barrier::new_named(3, "barrier-generations"));
+        let parties: Vec<_> = (0..3)
+            .map(|_| {
+                let barrier = barrier.clone();
+                tokio::spawn(async move {
+                    let mut led = 0;
+                    for _ in 0..4 {
+                        led += barrier.wait().await.is_leader() as u32;
+                    }
+                    led
+                })
+            })
+            .collect();
+        let mut leaders = 0;
+        for party in parties {
+            leaders += party.await.unwrap();
+        }
+        assert_eq!(leaders, 4);
+        assert_eq!(barrier.arrivals.load(Ordering::Relaxed), 12);
+        assert!(barrier.sites().current.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_watchdog_compares_against_the_last_generation() {
+        let barrier = Arc::new(
+            Barrier::new_named(2, "barrier-watchdog").with_watchdog(Duration::from_secs(1)),
+        );
+        let spawned = |barrier: &Arc<Barrier>| {
+            let barrier = barrier.clone();
+            tokio::spawn(async move { barrier.wait().await.is_leader() })
+        };
+
+        let first = spawned(&barrier);
+        let led = barrier.wait().await.is_leader();
+        assert_ne!(led, first.await.unwrap());
+        assert_eq!(barrier.warned.load(Ordering::Relaxed), 0);
+
+        let second = spawned(&barrier);
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(barrier.warned.load(Ordering::Relaxed), 2);
+        {
+            let sites = barrier.sites();
+            assert_eq!(sites.generation, 1);
+            assert_eq!(sites.previous.len(), 2);
+            assert_eq!(sites.current.len(), 1);
+            assert!(sites.previous.contains(&sites.current[0].0));
+        }
+        let led = barrier.wait().await.is_leader();
+        assert_ne!(led, second.await.unwrap());
+    }
+}
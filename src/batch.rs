@@ -0,0 +1,301 @@
+use crate::{
+    background::Ticker,
+    event::{self, LockEvent},
+    severity,
+};
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+// Zero while no batcher runs: events then go straight to the subscribers.
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+static BUFFERS: Mutex<Vec<Weak<Buffer>>> = Mutex::new(Vec::new());
+// The running batcher's ticker, under the generation of the call that
+// started it.
+static BATCHER: Mutex<Option<(u64, Ticker)>> = Mutex::new(None);
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static LOCAL: Local = Local::new();
+}
+
+// Under test, batching covers only the threads that opt in, so it does not
+// hold back the events other tests are waiting on.
+#[cfg(test)]
+thread_local! {
+    static OPTED_IN: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+// A thread's pending events. A flush publishes while holding the mutex, so a
+// timed flush and the thread's own cannot overtake each other.
+struct Buffer {
+    events: Mutex<Vec<LockEvent>>,
+}
+
+// Owns the thread's buffer, and flushes what is left when the thread exits.
+struct Local(Arc<Buffer>);
+
+impl Local {
+    fn new() -> Self {
+        let buffer = Arc::new(Buffer {
+            events: Mutex::new(Vec::new()),
+        });
+        let mut buffers = BUFFERS.lock().unwrap_or_else(|e| e.into_inner());
+        buffers.retain(|buffer| buffer.strong_count() > 0);
+        buffers.push(Arc::downgrade(&buffer));
+        Self(buffer)
+    }
+}
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        self.0.flush();
+    }
+}
+
+impl Buffer {
+    fn flush(&self) {
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        if !events.is_empty() {
+            event::publish_batch(events.drain(..));
+        }
+    }
+}
+
+// While the handle lives, subscriber events queue in a buffer per thread of
+// up to `capacity` events and reach the channels a batch at a time, taking
+// the subscriber list once per batch instead of once per event. A buffer is
+// flushed when full, every `interval` by a task of its own (a thread, when
+// started outside a runtime), and at once for an anomaly: any event outside
+// the acquire, got and release family, or one of those escalated past its
+// base level by a slow wait or hold.
+// The log, recorder, history and rings are not batched.
+//
+// Each thread's events keep their order; across threads a batch can land
+// after a later one, and `seq` gives the order back. Dropping the handle
+// stops batching and flushes every buffer.
+//
+// There is one batcher per process: a second call replaces the first, whose
+// ticker stops and whose capacity and interval give way to the new ones.
+// Dropping a replaced handle then does nothing; only the latest one stops
+// batching.
+pub fn batch_events(capacity: usize, interval: Duration) -> EventBatchHandle {
+    let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+    let mut batcher = BATCHER.lock().unwrap_or_else(|e| e.into_inner());
+    CAPACITY.store(capacity.max(1), Ordering::Release);
+    *batcher = Some((generation, Ticker::every(interval, flush_events)));
+    EventBatchHandle { generation }
+}
+
+#[derive(Debug)]
+#[must_use = "batching stops when this handle is dropped"]
+pub struct EventBatchHandle {
+    generation: u64,
+}
+
+impl Drop for EventBatchHandle {
+    fn drop(&mut self) {
+        let mut batcher = BATCHER.lock().unwrap_or_else(|e| e.into_inner());
+        if !matches!(*batcher, Some((generation, _)) if generation == self.generation) {
+            return;
+        }
+        *batcher = None;
+        CAPACITY.store(0, Ordering::Release);
+        drop(batcher);
+        flush_events();
+    }
+}
+
+// Hands every thread's buffered events to the subscribers now, say before
+// reading a channel in a test.
+pub fn flush_events() {
+    let buffers: Vec<Arc<Buffer>> = BUFFERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    for buffer in buffers {
+        buffer.flush();
+    }
+}
+
+pub(crate) fn enabled() -> bool {
+    CAPACITY.load(Ordering::Acquire) != 0
+}
+
+// Gives the event back when batching is off or the thread is exiting, for
+// the caller to publish on its own.
+pub(crate) fn push(event: LockEvent) -> Option<LockEvent> {
+    let capacity = CAPACITY.load(Ordering::Acquire);
+    #[cfg(test)]
+    let capacity = match OPTED_IN.try_with(std::cell::Cell::get) {
+        Ok(true) => capacity,
+        _ => 0,
+    };
+    if capacity == 0 {
+        return Some(event);
+    }
+    let mut pending = Some(event);
+    let _ = LOCAL.try_with(|local| {
+        let event = match pending.take() {
+            Some(event) => event,
+            None => return,
+        };
+        let urgent = severity::severity_policy().is_anomaly(event.phase, event.level);
+        let mut events = local.0.events.lock().unwrap_or_else(|e| e.into_inner());
+        events.push(event);
+        if urgent || events.len() >= capacity {
+            event::publish_batch(events.drain(..));
+        }
+    });
+    pending
+}
+
+// Publishes what this thread has queued, for events sent around the buffer
+// that must not overtake it.
+pub(crate) fn flush_current() {
+    if !enabled() {
+        return;
+    }
+    let _ = LOCAL.try_with(|local| local.0.flush());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{batch_events, enabled, EventBatchHandle, OPTED_IN};
+    use crate::{LockEvent, Phase, RwLock};
+
+    use futures::executor::block_on;
+    use tokio::sync::mpsc::Receiver;
+
+    use std::{
+        sync::{Mutex, MutexGuard},
+        thread,
+        time::Duration,
+    };
+
+    // There is one batcher per process, so these take turns.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    // Never ticks during a test, so only the flushes under test publish.
+    fn batched(capacity: usize) -> (MutexGuard<'static, ()>, EventBatchHandle) {
+        let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        OPTED_IN.with(|opted_in| opted_in.set(true));
+        (serial, batch_events(capacity, Duration::from_secs(3600)))
+    }
+
+    // A lock already used once, so its first-use anomaly is out of the way,
+    // with a subscriber that has seen nothing yet.
+    fn used_lock(name: &str) -> (RwLock<u8>, Receiver<LockEvent>) {
+        let lock = RwLock::new_named(0u8, name);
+        **block_on(lock.write()) = 1;
+        super::flush_events();
+        let id = lock.id();
+        (
+            lock,
+            crate::subscribe_where(move |event| event.lock_id == id),
+        )
+    }
+
+    fn drain(events: &mut Receiver<LockEvent>) -> Vec<LockEvent> {
+        std::iter::from_fn(|| events.try_recv().ok()).collect()
+    }
+
+    fn phases(events: &[LockEvent]) -> Vec<Phase> {
+        events.iter().map(|event| event.phase).collect()
+    }
+
+    #[test]
+    fn a_full_buffer_is_flushed_and_each_thread_keeps_its_order() {
+        let (_serial, _batching) = batched(4);
+        let (lock, mut events) = used_lock("batch-full");
+
+        **block_on(lock.write()) = 2;
+        assert!(drain(&mut events).is_empty());
+        let guard = block_on(lock.write());
+        let full = drain(&mut events);
+        assert_eq!(
+            phases(&full),
+            [Phase::Acquire, Phase::Got, Phase::Release, Phase::Acquire]
+        );
+        drop(guard);
+
+        let lock = std::sync::Arc::new(lock);
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let lock = lock.clone();
+                thread::spawn(move || {
+                    OPTED_IN.with(|opted_in| opted_in.set(true));
+                    for value in 0..3 {
+                        **block_on(lock.write()) = value;
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        super::flush_events();
+        let rest = drain(&mut events);
+        let mut threads = Vec::new();
+        for event in &rest {
+            if !threads.contains(&event.thread) {
+                threads.push(event.thread);
+            }
+        }
+        assert_eq!(threads.len(), 3);
+        for thread in threads {
+            let own: Vec<_> = rest.iter().filter(|event| event.thread == thread).collect();
+            assert!(own.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+            let idxs: Vec<u64> = own.iter().map(|event| event.idx).collect();
+            assert!(idxs.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", idxs);
+        }
+    }
+
+    #[test]
+    fn an_anomaly_flushes_at_once() {
+        let (_serial, _batching) = batched(100);
+        let (lock, mut events) = used_lock("batch-anomaly");
+
+        **block_on(lock.write()) = 2;
+        assert!(drain(&mut events).is_empty());
+        drop(block_on(lock.write()));
+        let flushed = phases(&drain(&mut events));
+        assert_eq!(
+            &flushed[..5],
+            [
+                Phase::Acquire,
+                Phase::Got,
+                Phase::Release,
+                Phase::Acquire,
+                Phase::Got,
+            ]
+        );
+        assert!(flushed[5..].contains(&Phase::Unmutated), "{:?}", flushed);
+    }
+
+    #[test]
+    fn only_the_latest_handle_stops_batching() {
+        let (_serial, first) = batched(100);
+        let (lock, mut events) = used_lock("batch-drop");
+
+        let second = batch_events(100, Duration::from_secs(3600));
+        drop(first);
+        assert!(enabled());
+        **block_on(lock.write()) = 2;
+        assert!(drain(&mut events).is_empty());
+
+        drop(second);
+        assert!(!enabled());
+        assert_eq!(
+            phases(&drain(&mut events)),
+            [Phase::Acquire, Phase::Got, Phase::Release]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use crate::{
+    config, fallback,
+    phase::{Mode, Phase},
+    redact::{self, Export},
+    registry::LockMeta,
+};
+
+use log::Level;
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufWriter, Read, Write},
+    panic::Location,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// Codes are positions in these tables, so new variants go at the end or old
+// captures decode wrongly.
+pub(crate) const MODES: [Mode; 4] = [Mode::Read, Mode::Write, Mode::Lock, Mode::Meta];
+pub(crate) const PHASES: [Phase; 31] = [
+    Phase::Acquire,
+    Phase::Waiting,
+    Phase::Got,
+    Phase::Release,
+    Phase::GiveUp,
+    Phase::Cancelled,
+    Phase::Upgrade,
+    Phase::Throttle,
+    Phase::Await,
+    Phase::Order,
+    Phase::Unmutated,
+    Phase::FirstUse,
+    Phase::Reset,
+    Phase::Wrap,
+    Phase::Untraced,
+    Phase::Destroy,
+    Phase::Dropped,
+    Phase::Inversion,
+    Phase::Created,
+    Phase::Handoff,
+    Phase::Resume,
+    Phase::Stuck,
+    Phase::Blocking,
+    Phase::Map,
+    Phase::MapFailed,
+    Phase::Fence,
+    Phase::Init,
+    Phase::Heartbeat,
+    Phase::Quiesce,
+    Phase::Access,
+    Phase::Rename,
+];
+
+const EVENT: u8 = 0;
+const FILE: u8 = 1;
+const LOCK: u8 = 2;
+
+// tag, phase, mode, level, file, line, column, timestamp, lock id, idx
+const EVENT_LEN: usize = 40;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+// File paths and lock names are written once, the first time a record
+// refers to them, so each event record stays the same size.
+struct Sink {
+    writer: BufWriter<Box<dyn Write + Send>>,
+    files: HashMap<&'static str, u32>,
+    locks: HashSet<u64>,
+    // Locks this sink has recorded a Created event for.
+    announced: HashSet<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryEvent {
+    pub timestamp: SystemTime,
+    pub lock_id: u64,
+    pub name: String,
+    pub mode: Mode,
+    pub phase: Phase,
+    pub idx: u64,
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub level: Level,
+}
+
+// Every event from here on is also written to `writer` as a fixed-size
+// record, replacing any earlier sink. Records are buffered; call
+// `flush_binary_sink` or `disable_binary_sink` before reading the capture.
+pub fn enable_binary_sink<W>(writer: W)
+where
+    W: Write + Send + 'static,
+{
+    let mut sink = sink();
+    if let Some(previous) = sink.as_mut() {
+        let _ = previous.writer.flush();
+    }
+    *sink = Some(Sink {
+        writer: BufWriter::new(Box::new(writer)),
+        files: HashMap::new(),
+        locks: HashSet::new(),
+        announced: HashSet::new(),
+    });
+    ENABLED.store(true, Ordering::Release);
+}
+
+pub fn flush_binary_sink() -> io::Result<()> {
+    match sink().as_mut() {
+        Some(sink) => sink.writer.flush(),
+        None => Ok(()),
+    }
+}
+
+pub fn disable_binary_sink() -> io::Result<()> {
+    ENABLED.store(false, Ordering::Release);
+    match sink().take() {
+        Some(mut sink) => sink.writer.flush(),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+pub(crate) fn record(
+    level: Level,
+    meta: &LockMeta,
+    mode: Mode,
+    phase: Phase,
+    idx: u64,
+    caller: &'static Location<'static>,
+) {
+    let mut guard = sink();
+    let sink = match guard.as_mut() {
+        Some(sink) => sink,
+        None => return,
+    };
+    if phase == Phase::Created && !sink.announced.insert(meta.id) {
+        return;
+    }
+    if let Err(error) = sink.write(level, meta, mode, phase, idx, caller) {
+        // A failed writer is dropped rather than retried on every event.
+        *guard = None;
+        ENABLED.store(false, Ordering::Release);
+        drop(guard);
+        fallback::log!(
+            Level::Error,
+            "[META] binary sink failed and was disabled: {}",
+            error
+        );
+    }
+}
+
+impl Sink {
+    fn write(
+        &mut self,
+        level: Level,
+        meta: &LockMeta,
+        mode: Mode,
+        phase: Phase,
+        idx: u64,
+        caller: &'static Location<'static>,
+    ) -> io::Result<()> {
+        let next = self.files.len() as u32;
+        let file = *self.files.entry(caller.file()).or_insert(next);
+        if file == next {
+            self.writer.write_all(&[FILE])?;
+            self.writer.write_all(&file.to_le_bytes())?;
+            write_str(&mut self.writer, caller.file())?;
+        }
+        // A rename writes the lock's record again, and readers keep the last.
+        if self.locks.insert(meta.id) || phase == Phase::Rename {
+            self.writer.write_all(&[LOCK])?;
+            self.writer.write_all(&meta.id.to_le_bytes())?;
+            write_str(
+                &mut self.writer,
+                &redact::redact(Export::Binary, &meta.name()),
+            )?;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut record = [0; EVENT_LEN];
+        record[0] = EVENT;
+        record[1] = code(&PHASES, phase);
+        record[2] = code(&MODES, mode);
+        record[3] = level as u8;
+        record[4..8].copy_from_slice(&file.to_le_bytes());
+        record[8..12].copy_from_slice(&caller.line().to_le_bytes());
+        record[12..16].copy_from_slice(&caller.column().to_le_bytes());
+        record[16..24].copy_from_slice(&(timestamp.as_nanos() as u64).to_le_bytes());
+        record[24..32].copy_from_slice(&meta.id.to_le_bytes());
+        record[32..40].copy_from_slice(&idx.to_le_bytes());
+        self.writer.write_all(&record)
+    }
+}
+
+// `LockEvent` points at a `&'static Location`, which cannot be rebuilt from a
+// file, so replayed events carry the site as plain data instead.
+pub fn replay_binary<R: Read>(reader: R) -> io::Result<Vec<BinaryEvent>> {
+    let mut reader = io::BufReader::new(reader);
+    let mut files: HashMap<u32, String> = HashMap::new();
+    let mut locks: HashMap<u64, String> = HashMap::new();
+    let mut events = Vec::new();
+
+    loop {
+        let mut tag = [0; 1];
+        if reader.read(&mut tag)? == 0 {
+            return Ok(events);
+        }
+        match tag[0] {
+            FILE => {
+                let id = u32::from_le_bytes(read_array(&mut reader)?);
+                files.insert(id, read_str(&mut reader)?);
+            }
+            LOCK => {
+                let id = u64::from_le_bytes(read_array(&mut reader)?);
+                locks.insert(id, read_str(&mut reader)?);
+            }
+            EVENT => {
+                let rest: [u8; EVENT_LEN - 1] = read_array(&mut reader)?;
+                let mut record = [0; EVENT_LEN];
+                record[1..].copy_from_slice(&rest);
+                let field = |range: std::ops::Range<usize>| {
+                    let mut bytes = [0; 8];
+                    bytes[..range.len()].copy_from_slice(&record[range]);
+                    u64::from_le_bytes(bytes)
+                };
+                let lock_id = field(24..32);
+                events.push(BinaryEvent {
+                    timestamp: UNIX_EPOCH + Duration::from_nanos(field(16..24)),
+                    lock_id,
+                    name: locks.get(&lock_id).cloned().unwrap_or_default(),
+                    mode: decode(&MODES, record[2])?,
+                    phase: decode(&PHASES, record[1])?,
+                    idx: field(32..40),
+                    file: files
+                        .get(&(field(4..8) as u32))
+                        .cloned()
+                        .unwrap_or_default(),
+                    line: field(8..12) as u32,
+                    column: field(12..16) as u32,
+                    level: config::level_from_usize(usize::from(record[3])),
+                });
+            }
+            other => return Err(invalid(format!("unknown record tag {}", other))),
+        }
+    }
+}
+
+fn sink() -> MutexGuard<'static, Option<Sink>> {
+    SINK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn code<T: PartialEq>(table: &[T], value: T) -> u8 {
+    table
+        .iter()
+        .position(|known| *known == value)
+        .unwrap_or(u8::MAX as usize) as u8
+}
+
+fn decode<T: Copy>(table: &[T], code: u8) -> io::Result<T> {
+    table
+        .get(usize::from(code))
+        .copied()
+        .ok_or_else(|| invalid(format!("unknown code {}", code)))
+}
+
+fn write_str(writer: &mut impl Write, value: &str) -> io::Result<()> {
+    writer.write_all(&(value.len() as u32).to_le_bytes())?;
+    writer.write_all(value.as_bytes())
+}
+
+fn read_str(reader: &mut impl Read) -> io::Result<String> {
+    let len = u32::from_le_bytes(read_array(reader)?) as usize;
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| invalid(e.to_string()))
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
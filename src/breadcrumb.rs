@@ -0,0 +1,56 @@
+use crate::{config::Idx, registry::LockMeta, task::TaskKey};
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+// Tasks that release and never acquire again leave an entry behind; past this
+// many the map starts over, costing at most one breadcrumb per task.
+const MAX_TASKS: usize = 4096;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static LAST_RELEASED: Mutex<Option<HashMap<TaskKey, (String, u64)>>> = Mutex::new(None);
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn last_released() -> MutexGuard<'static, Option<HashMap<TaskKey, (String, u64)>>> {
+    LAST_RELEASED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// An acquisition made after the same task (or thread, outside tokio) last
+// released a lock carries `after-release (<name>:<idx>)`, so one task's lock
+// sequence can be followed through an interleaved log.
+pub fn set_release_breadcrumbs(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        *last_released() = None;
+    }
+}
+
+pub(crate) fn released(meta: &LockMeta, idx: u64) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut last = last_released();
+    let last = last.get_or_insert_with(HashMap::new);
+    if last.len() >= MAX_TASKS {
+        last.clear();
+    }
+    last.insert(TaskKey::current(), (meta.name().to_string(), idx));
+}
+
+// Taken, not read, so only the first acquisition after a release points back
+// at it.
+pub(crate) fn take() -> Option<String> {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return None;
+    }
+    let (name, idx) = last_released().as_mut()?.remove(&TaskKey::current())?;
+    Some(format!(" after-release ({}:{})", name, Idx(idx)))
+}
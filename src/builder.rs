@@ -0,0 +1,272 @@
+use crate::{
+    event,
+    registry::{LockMeta, Metadata},
+    ring::EventRing,
+    scope::Scope,
+    stats,
+    traced::RawAsyncRwLock,
+    RwLock, TracedLock,
+};
+
+use log::Level;
+use tokio::sync::Semaphore;
+
+use std::{
+    any::Any,
+    fmt,
+    panic::Location,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+
+// Settings only: nothing is registered until a lock is built, so one config
+// can stamp out any number of locks with identical behaviour. Every
+// constructor in the crate goes through here.
+#[derive(Debug, Clone, Default)]
+pub struct LockConfig {
+    name: Option<String>,
+    scope: Option<Scope>,
+    order_level: Option<u32>,
+    level: Option<Level>,
+    muted: bool,
+    sample_rate: u64,
+    slow_wait: Option<Duration>,
+    slow_hold: Option<Duration>,
+    heartbeat: Option<(Duration, Duration)>,
+    backtrace: Option<bool>,
+    max_readers: Option<usize>,
+    acquire_delay: Duration,
+    allow_unmutated_writes: bool,
+    event_ring: Option<usize>,
+    metadata: Option<Metadata>,
+}
+
+impl LockConfig {
+    pub fn builder() -> LockConfig {
+        LockConfig::default()
+    }
+
+    // Shared by every lock built from this config; without one each lock gets
+    // a generated name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn scope(mut self, scope: &Scope) -> Self {
+        self.scope = Some(scope.clone());
+        self
+    }
+
+    // Position in the declared lock order, as in `new_named_with_level`.
+    pub fn order_level(mut self, level: u32) -> Self {
+        self.order_level = Some(level);
+        self
+    }
+
+    // Level, muting and sampling sit on a scope of the lock's own, layered on
+    // the one it is created in.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    pub fn muted(mut self, muted: bool) -> Self {
+        self.muted = muted;
+        self
+    }
+
+    pub fn sample_rate(mut self, every: u64) -> Self {
+        self.sample_rate = every;
+        self
+    }
+
+    // Replace the severity policy's warn thresholds for this lock only.
+    pub fn slow_wait(mut self, threshold: Duration) -> Self {
+        self.slow_wait = Some(threshold);
+        self
+    }
+
+    pub fn slow_hold(mut self, threshold: Duration) -> Self {
+        self.slow_hold = Some(threshold);
+        self
+    }
+
+    // For locks held long on purpose: a hold past `after` logs `Still holding`
+    // every `every`, so a slow hold shows it is alive. Needs `spawn_sweeper`,
+    // whose interval bounds how closely the beats keep time.
+    pub fn heartbeat(mut self, after: Duration, every: Duration) -> Self {
+        self.heartbeat = Some((after, every));
+        self
+    }
+
+    // Overrides `set_backtraces` for this lock's events.
+    pub fn backtrace(mut self, enabled: bool) -> Self {
+        self.backtrace = Some(enabled);
+        self
+    }
+
+    // Only the tokio-backed lock caps its readers; the other backends ignore it.
+    pub fn max_readers(mut self, max_readers: usize) -> Self {
+        self.max_readers = Some(max_readers);
+        self
+    }
+
+    pub fn acquire_delay(mut self, delay: Duration) -> Self {
+        self.acquire_delay = delay;
+        self
+    }
+
+    pub fn allow_unmutated_writes(mut self) -> Self {
+        self.allow_unmutated_writes = true;
+        self
+    }
+
+    // See `RwLock::with_event_ring`.
+    pub fn event_ring(mut self, capacity: usize) -> Self {
+        self.event_ring = Some(capacity);
+        self
+    }
+
+    // Correlation data of the caller's own, a shard or tenant id say, for
+    // `RwLock::metadata` to hand back. Every lock the config builds shares
+    // the one value. It stays out of the trace; see `displayed_metadata`.
+    pub fn metadata<M>(mut self, metadata: M) -> Self
+    where
+        M: Any + Send + Sync,
+    {
+        self.metadata = Some(Metadata {
+            value: Arc::new(metadata),
+            shown: None,
+        });
+        self
+    }
+
+    // As `metadata`, and its Display, rendered once here, is shown as
+    // `metadata=` on the lock's Created event and in its snapshot.
+    pub fn displayed_metadata<M>(mut self, metadata: M) -> Self
+    where
+        M: Any + fmt::Display + Send + Sync,
+    {
+        let shown = metadata.to_string();
+        self.metadata = Some(Metadata {
+            value: Arc::new(metadata),
+            shown: Some(shown),
+        });
+        self
+    }
+
+    #[track_caller]
+    pub fn build<T>(&self, inner: T) -> RwLock<T> {
+        self.rwlock(inner)
+    }
+
+    #[track_caller]
+    pub fn rwlock<T>(&self, inner: T) -> RwLock<T> {
+        self.rwlock_at(inner, Location::caller())
+    }
+
+    pub(crate) fn rwlock_at<T, L: RawAsyncRwLock<T>>(
+        &self,
+        inner: T,
+        caller: &'static Location<'static>,
+    ) -> TracedLock<T, L> {
+        let (scope, name) = self.target();
+        let mut lock = TracedLock::with_scope(inner, scope, name, self.order_level);
+        if let Some(max_readers) = self.max_readers {
+            lock.readers = Some(Arc::new(Semaphore::new(max_readers)));
+        }
+        self.configure(&lock.meta, caller);
+        lock
+    }
+
+    #[cfg(feature = "parking-lot")]
+    #[track_caller]
+    pub fn sync_rwlock<T>(&self, inner: T) -> crate::sync::RwLock<T> {
+        let (scope, name) = self.target();
+        let lock = crate::sync::RwLock::with_scope(inner, scope, name, self.order_level);
+        self.configure(&lock.meta, Location::caller());
+        lock
+    }
+
+    #[cfg(feature = "async-lock")]
+    #[track_caller]
+    pub fn async_lock_rwlock<T>(&self, inner: T) -> crate::async_lock::RwLock<T> {
+        self.traced_lock(inner)
+    }
+
+    #[track_caller]
+    pub fn traced_lock<T, L: RawAsyncRwLock<T>>(&self, inner: T) -> TracedLock<T, L> {
+        self.rwlock_at(inner, Location::caller())
+    }
+
+    fn target(&self) -> (Scope, String) {
+        let parent = self.scope.clone().unwrap_or_else(Scope::root);
+        let scope = if self.level.is_some() || self.muted || self.sample_rate != 0 {
+            let scope = parent.detached_child();
+            if let Some(level) = self.level {
+                scope.set_level(level);
+            }
+            scope.set_muted(self.muted);
+            scope.set_sample_rate(self.sample_rate);
+            scope
+        } else {
+            parent
+        };
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => Self::generated_name("lock"),
+        };
+        (scope, name)
+    }
+
+    // A random `adjective-noun` pair, or `<kind>-<n>` from a process-wide
+    // counter without the `random-names` feature.
+    pub(crate) fn generated_name(kind: &str) -> String {
+        #[cfg(feature = "random-names")]
+        {
+            let _ = kind;
+            names::Generator::default().next().unwrap()
+        }
+        #[cfg(not(feature = "random-names"))]
+        {
+            static NEXT: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+            format!("{}-{}", kind, NEXT.fetch_add(1, Ordering::Relaxed))
+        }
+    }
+
+    // `cache.rs:42` for a lock built at line 42 of `src/cache.rs`. Stable
+    // across runs as long as the constructor does not move.
+    pub(crate) fn site_name(caller: &Location<'_>) -> String {
+        let file = caller.file().rsplit(['/', '\\']).next().unwrap_or_default();
+        format!("{}:{}", file, caller.line())
+    }
+
+    // Announces the lock once its settings are in place, so the Created event
+    // carries them.
+    fn configure(&self, meta: &LockMeta, caller: &'static Location<'static>) {
+        if let Some(threshold) = self.slow_wait {
+            let _ = meta.slow_wait.set(threshold);
+        }
+        if let Some(threshold) = self.slow_hold {
+            let _ = meta.slow_hold.set(threshold);
+        }
+        if let Some(heartbeat) = self.heartbeat {
+            let _ = meta.heartbeat.set(heartbeat);
+        }
+        if let Some(enabled) = self.backtrace {
+            let _ = meta.backtrace.set(enabled);
+        }
+        if let Some(capacity) = self.event_ring {
+            let _ = meta.event_ring.set(EventRing::new(capacity));
+        }
+        if let Some(metadata) = &self.metadata {
+            let _ = meta.metadata.set(metadata.clone());
+        }
+        meta.acquire_delay
+            .store(stats::duration_nanos(self.acquire_delay), Ordering::Relaxed);
+        meta.unmutated_writes_ok
+            .store(self.allow_unmutated_writes, Ordering::Relaxed);
+        event::created(meta, caller);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{capture, fallback};
+
+use log::Level;
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    panic::Location,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+const DEFINITION: &str = "[META] CallSite #";
+
+static COMPACT: AtomicBool = AtomicBool::new(false);
+static TABLE: Mutex<Option<Table>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Table {
+    ids: HashMap<&'static Location<'static>, u32>,
+    // (lock id, site) pairs whose first Got has been logged in full.
+    detailed: HashSet<(u64, &'static Location<'static>)>,
+}
+
+// Shortens the log for hot call sites. Each site is numbered on first use by
+// a `[META] CallSite #42 = src/handler.rs:88:5 via myapp::session::refresh`
+// line, the frame named only with backtraces on, and later lines end in `#42`
+// instead of the location. The first Got of each lock at each site is still
+// logged in full, with the lock's type and thresholds and any backtrace; no
+// other line carries a backtrace. Off by default, which is full detail
+// throughout. Events handed to subscribers, the recorder, rings and the
+// binary sink keep their locations; `CallSiteDecoder` expands the log.
+pub fn set_compact_callsites(enabled: bool) {
+    COMPACT.store(enabled, Ordering::Relaxed);
+}
+
+pub(crate) fn compact() -> bool {
+    COMPACT.load(Ordering::Relaxed)
+}
+
+fn table() -> MutexGuard<'static, Option<Table>> {
+    TABLE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// The site's id, writing its definition to `target` first if it has none
+// yet, and whether this is the first Got of the lock there.
+pub(crate) fn reference(
+    target: &str,
+    level: Level,
+    lock_id: u64,
+    caller: &'static Location<'static>,
+    got: bool,
+) -> (u32, bool) {
+    let known = table()
+        .as_ref()
+        .and_then(|table| table.ids.get(caller).copied());
+    // Resolved outside the table's lock, since it walks the stack.
+    let via = match known {
+        Some(_) => None,
+        None => capture::caller_frame(),
+    };
+    let mut table = table();
+    let table = table.get_or_insert_with(Table::default);
+    let next = table.ids.len() as u32 + 1;
+    let id = *table.ids.entry(caller).or_insert_with(|| {
+        // Written under the lock, so no line can refer to the id before it.
+        let via = via.map(|name| format!(" via {}", name)).unwrap_or_default();
+        fallback::write(
+            target,
+            level,
+            format_args!(
+                "{}{} = {}:{}:{}{}",
+                DEFINITION,
+                next,
+                caller.file(),
+                caller.line(),
+                caller.column(),
+                via
+            ),
+        );
+        next
+    });
+    let first = got && table.detailed.insert((lock_id, caller));
+    (id, first)
+}
+
+// Puts locations back into a log written with `set_compact_callsites`, one
+// line at a time and in order, so each definition is read before the lines
+// that refer to it. Lines are matched on their content alone, so whatever
+// the logger wraps around them is kept.
+#[derive(Debug, Default)]
+pub struct CallSiteDecoder {
+    sites: HashMap<u32, String>,
+}
+
+impl CallSiteDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Definition lines come back as they are. A first-Got line keeps its
+    // location and loses the `#id` after it; a later line gets the location
+    // in place of its `#id`.
+    pub fn expand<'a>(&mut self, line: &'a str) -> Cow<'a, str> {
+        if let Some(at) = line.find(DEFINITION) {
+            let definition = &line[at + DEFINITION.len()..];
+            if let Some((id, site)) = definition.split_once(" = ") {
+                if let Ok(id) = id.parse() {
+                    let site = site.split(" via ").next().unwrap_or(site);
+                    self.sites.insert(id, site.trim_end().to_owned());
+                }
+            }
+            return Cow::Borrowed(line);
+        }
+        let (head, id) = match line.trim_end().rsplit_once(" #") {
+            Some((head, id)) => (head, id),
+            None => return Cow::Borrowed(line),
+        };
+        let site = match id.parse::<u32>().ok().and_then(|id| self.sites.get(&id)) {
+            Some(site) => site,
+            None => return Cow::Borrowed(line),
+        };
+        if head.ends_with(site.as_str()) {
+            Cow::Owned(head.to_owned())
+        } else if let Some(message) = head.strip_suffix(':') {
+            Cow::Owned(format!("{}: {}", message, site))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{
+    background,
+    clock::Instant,
+    config::Idx,
+    event,
+    guard::Acquisition,
+    phase::{Mode, Phase},
+    registry::{self, LockMeta},
+    OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, READ,
+    WRITE,
+};
+
+use log::Level;
+
+use std::{
+    error::Error,
+    fmt::{self, Write},
+    future::{self, Future},
+    panic::Location,
+    sync::Arc,
+    task::Poll,
+    time::Duration,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("lock acquisition was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+impl<T> RwLock<T> {
+    // Gives up as soon as `signal` resolves. Dropping the acquire future
+    // removes its pending entry, so the lock is never taken after this
+    // returns `Err`.
+    #[track_caller]
+    pub fn read_or_cancel<F>(
+        &self,
+        signal: F,
+    ) -> impl Future<Output = Result<RwLockReadGuard<'_, T>, Cancelled>>
+    where
+        F: Future<Output = ()>,
+    {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(READ, Acquisition::new(caller));
+            let cancel = Cancel::new(&self.meta, &acquisition, READ, "signal");
+            cancel.run(self.acquire_read(acquisition), signal).await
+        }
+    }
+
+    #[track_caller]
+    pub fn write_or_cancel<F>(
+        &self,
+        signal: F,
+    ) -> impl Future<Output = Result<RwLockWriteGuard<'_, T>, Cancelled>>
+    where
+        F: Future<Output = ()>,
+    {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(WRITE, Acquisition::new(caller));
+            let cancel = Cancel::new(&self.meta, &acquisition, WRITE, "signal");
+            cancel.run(self.acquire_write(acquisition), signal).await
+        }
+    }
+
+    #[track_caller]
+    pub fn write_timeout(
+        &self,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<RwLockWriteGuard<'_, T>, Cancelled>> {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(WRITE, Acquisition::new(caller));
+            let cancel = Cancel::new(&self.meta, &acquisition, WRITE, "timeout");
+            cancel
+                .run(self.acquire_write(acquisition), background::sleep(timeout))
+                .await
+        }
+    }
+
+    // Each timed-out attempt is logged louder than the last: debug first, then
+    // warn, and error for the final one before giving up.
+    #[track_caller]
+    pub fn write_with_retries(
+        &self,
+        per_attempt: Duration,
+        max: u32,
+    ) -> impl Future<Output = Result<RwLockWriteGuard<'_, T>, Cancelled>> {
+        let caller = Location::caller();
+
+        async move {
+            let max = max.max(1);
+            for attempt in 1..=max {
+                let acquisition = self.begin(WRITE, Acquisition::new(caller));
+                let mut cancel = Cancel::new(&self.meta, &acquisition, WRITE, "timeout");
+                cancel.attempt = Some((attempt, max));
+                cancel.level = match attempt {
+                    attempt if attempt == max => Level::Error,
+                    1 => Level::Debug,
+                    _ => Level::Warn,
+                };
+                let acquire = self.acquire_write(acquisition);
+                if let Ok(guard) = cancel.run(acquire, background::sleep(per_attempt)).await {
+                    return Ok(guard);
+                }
+            }
+            Err(Cancelled)
+        }
+    }
+
+    // Owned counterparts of `write_timeout` for tasks that need `'static`
+    // guards. They give up with the same Cancelled event.
+    #[track_caller]
+    pub fn read_owned_timeout(
+        self: Arc<Self>,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<OwnedRwLockReadGuard<T>, Cancelled>> {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(READ, Acquisition::new(caller));
+            let meta = self.meta.clone();
+            let cancel = Cancel::new(&meta, &acquisition, READ, "timeout");
+            cancel
+                .run(
+                    self.acquire_read_owned(acquisition),
+                    background::sleep(timeout),
+                )
+                .await
+        }
+    }
+
+    #[track_caller]
+    pub fn write_owned_timeout(
+        self: Arc<Self>,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<OwnedRwLockWriteGuard<T>, Cancelled>> {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(WRITE, Acquisition::new(caller));
+            let meta = self.meta.clone();
+            let cancel = Cancel::new(&meta, &acquisition, WRITE, "timeout");
+            cancel
+                .run(
+                    self.acquire_write_owned(acquisition),
+                    background::sleep(timeout),
+                )
+                .await
+        }
+    }
+
+    #[cfg(feature = "tokio-util")]
+    #[track_caller]
+    pub fn read_with_cancel<'a>(
+        &'a self,
+        token: &'a tokio_util::sync::CancellationToken,
+    ) -> impl Future<Output = Result<RwLockReadGuard<'a, T>, Cancelled>> + 'a {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(READ, Acquisition::new(caller));
+            let cancel = Cancel::new(&self.meta, &acquisition, READ, "token");
+            cancel
+                .run(self.acquire_read(acquisition), token.cancelled())
+                .await
+        }
+    }
+
+    #[cfg(feature = "tokio-util")]
+    #[track_caller]
+    pub fn write_with_cancel<'a>(
+        &'a self,
+        token: &'a tokio_util::sync::CancellationToken,
+    ) -> impl Future<Output = Result<RwLockWriteGuard<'a, T>, Cancelled>> + 'a {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(WRITE, Acquisition::new(caller));
+            let cancel = Cancel::new(&self.meta, &acquisition, WRITE, "token");
+            cancel
+                .run(self.acquire_write(acquisition), token.cancelled())
+                .await
+        }
+    }
+
+    // For shutdown paths that only care whether the lock was taken.
+    #[cfg(feature = "tokio-util")]
+    #[track_caller]
+    pub fn read_cancellable<'a>(
+        &'a self,
+        token: &'a tokio_util::sync::CancellationToken,
+    ) -> impl Future<Output = Option<RwLockReadGuard<'a, T>>> + 'a {
+        let acquire = self.read_with_cancel(token);
+        async move { acquire.await.ok() }
+    }
+
+    #[cfg(feature = "tokio-util")]
+    #[track_caller]
+    pub fn write_cancellable<'a>(
+        &'a self,
+        token: &'a tokio_util::sync::CancellationToken,
+    ) -> impl Future<Output = Option<RwLockWriteGuard<'a, T>>> + 'a {
+        let acquire = self.write_with_cancel(token);
+        async move { acquire.await.ok() }
+    }
+}
+
+// What the Cancelled event needs, copied out before the acquisition moves
+// into the acquire future.
+struct Cancel<'a> {
+    meta: &'a LockMeta,
+    mode: Mode,
+    by: &'static str,
+    idx: u64,
+    caller: &'static Location<'static>,
+    sampled: bool,
+    level: Level,
+    start: Instant,
+    attempt: Option<(u32, u32)>,
+}
+
+impl<'a> Cancel<'a> {
+    fn new(meta: &'a LockMeta, acquisition: &Acquisition, mode: Mode, by: &'static str) -> Self {
+        Self {
+            meta,
+            mode,
+            by,
+            idx: acquisition.idx,
+            caller: acquisition.caller,
+            sampled: acquisition.sampled,
+            level: acquisition.level(meta, Phase::Cancelled),
+            start: Instant::now(),
+            attempt: None,
+        }
+    }
+
+    async fn run<G, A, S>(self, acquire: A, signal: S) -> Result<G, Cancelled>
+    where
+        A: Future<Output = G>,
+        S: Future<Output = ()>,
+    {
+        // Both futures are dropped at the end of this block, so a cancelled
+        // acquire has left the pending registry before the holders are read.
+        let guard = {
+            tokio::pin!(acquire);
+            tokio::pin!(signal);
+            future::poll_fn(|cx| {
+                if let Poll::Ready(guard) = acquire.as_mut().poll(cx) {
+                    return Poll::Ready(Some(guard));
+                }
+                signal.as_mut().poll(cx).map(|()| None)
+            })
+            .await
+        };
+
+        guard.ok_or_else(|| {
+            self.report();
+            Cancelled
+        })
+    }
+
+    fn report(&self) {
+        if !self.sampled {
+            return;
+        }
+        let mut message = format!(
+            "[{}] Cancelled by {} ({}:{}) waited={:?} holders=[",
+            self.mode,
+            self.by,
+            self.meta.name(),
+            Idx(self.idx),
+            self.start.elapsed(),
+        );
+        for (index, holder) in registry::held_on(self.meta.id).iter().enumerate() {
+            let _ = write!(
+                message,
+                "{}{} by {} at {}:{}:{}",
+                if index == 0 { "" } else { ", " },
+                holder.mode,
+                holder.task,
+                holder.caller.file(),
+                holder.caller.line(),
+                holder.caller.column(),
+            );
+        }
+        message.push(']');
+        if let Some((attempt, max)) = self.attempt {
+            let _ = write!(message, " attempt={}/{}", attempt, max);
+        }
+        event::emit_message(
+            self.level,
+            self.meta,
+            self.mode,
+            Phase::Cancelled,
+            self.idx,
+            self.caller,
+            message,
+        );
+    }
+}
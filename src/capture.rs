@@ -0,0 +1,228 @@
+#[cfg(feature = "backtrace")]
+use crate::fallback;
+
+#[cfg(feature = "backtrace")]
+use backtrace::{Backtrace, BacktraceFrame};
+#[cfg(feature = "backtrace")]
+use log::Level;
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static DEPTH: AtomicUsize = AtomicUsize::new(usize::MAX);
+static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+static HIDE_INTERNAL: AtomicBool = AtomicBool::new(true);
+
+pub fn set_backtraces(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn set_backtrace_depth(depth: usize) {
+    DEPTH.store(depth, Ordering::Relaxed);
+}
+
+// On by default: frames in this crate and in the tokio lock it wraps are left
+// out of captured stacks wherever they appear. Off shows every frame past the
+// capture itself, which helps when the crate is what is being debugged.
+pub fn hide_internal_frames(hide: bool) {
+    HIDE_INTERNAL.store(hide, Ordering::Relaxed);
+}
+
+// Without the `backtrace` feature the setters still exist but nothing is ever
+// captured, so events carry only their `Location`.
+pub(crate) fn enabled() -> bool {
+    cfg!(feature = "backtrace") && ENABLED.load(Ordering::Relaxed) && available()
+}
+
+fn available() -> bool {
+    !UNAVAILABLE.load(Ordering::Relaxed)
+}
+
+// A stripped binary resolves every frame to a bare address. Those stacks only
+// confuse, so capturing stops for the rest of the process.
+#[cfg(feature = "backtrace")]
+fn mark_unavailable() {
+    if !UNAVAILABLE.swap(true, Ordering::Relaxed) {
+        fallback::log!(
+            Level::Warn,
+            "[WARN] backtraces unavailable (stripped binary?); only locations will be logged"
+        );
+    }
+}
+
+struct Line {
+    name: String,
+    location: Option<(String, u32)>,
+    #[cfg(feature = "backtrace")]
+    named: bool,
+}
+
+// `leading` is true until the first frame is kept. The drop glue that led
+// into a release is only noise there, next to the crate's own frames.
+fn is_hidden(name: &str, leading: bool, hide_internal: bool) -> bool {
+    let name = name.trim_start_matches('<');
+    if leading && name.starts_with("backtrace::") {
+        return true;
+    }
+    hide_internal
+        && (name.starts_with("rwlock_trace::")
+            || name.starts_with("tokio::sync::")
+            || (leading && name.starts_with("core::ptr::drop_in_place")))
+}
+
+#[cfg(feature = "backtrace")]
+fn resolve(frame: &BacktraceFrame) -> Vec<Line> {
+    resolve_ip(frame.ip())
+}
+
+#[cfg(feature = "backtrace")]
+fn resolve_ip(ip: *mut std::ffi::c_void) -> Vec<Line> {
+    let mut lines = Vec::new();
+    backtrace::resolve(ip, |symbol| {
+        lines.push(Line {
+            named: symbol.name().is_some(),
+            name: symbol
+                .name()
+                .map(|name| format!("{:#}", name))
+                .unwrap_or_else(|| format!("{:?}", ip)),
+            location: symbol
+                .filename()
+                .zip(symbol.lineno())
+                .map(|(file, line)| (file.display().to_string(), line)),
+        });
+    });
+    lines
+}
+
+#[cfg(not(feature = "backtrace"))]
+fn capture_lines() -> Vec<Line> {
+    Vec::new()
+}
+
+// Frames are resolved one at a time so that only the hidden frames and the
+// requested depth are ever symbolized.
+#[cfg(feature = "backtrace")]
+fn capture_lines() -> Vec<Line> {
+    let depth = DEPTH.load(Ordering::Relaxed);
+    let hide_internal = HIDE_INTERNAL.load(Ordering::Relaxed);
+    let frames: Vec<BacktraceFrame> = Backtrace::new_unresolved().into();
+
+    let mut lines = Vec::new();
+    let mut skipping = true;
+    // Frames walked that resolved to a symbol name, and that did not.
+    let (mut named, mut unnamed) = (0, 0);
+
+    for frame in &frames {
+        if lines.len() >= depth {
+            break;
+        }
+        let resolved = resolve(frame);
+        if resolved.iter().any(|line| line.named) {
+            named += 1;
+        } else {
+            unnamed += 1;
+        }
+        for line in resolved {
+            if is_hidden(&line.name, skipping, hide_internal) {
+                continue;
+            }
+            skipping = false;
+            if lines.len() >= depth {
+                break;
+            }
+            lines.push(line);
+        }
+    }
+
+    // Stripped binaries still name the few frames in shared libraries such
+    // as libc, so a majority of bare addresses is the signal.
+    if unnamed > named {
+        mark_unavailable();
+        return Vec::new();
+    }
+    lines
+}
+
+pub(crate) fn capture() -> String {
+    let mut out = String::new();
+    // A lock's own `backtrace` setting bypasses `enabled`.
+    if !available() {
+        return out;
+    }
+
+    for (index, line) in capture_lines().into_iter().enumerate() {
+        let _ = write!(out, "\n  {:>3}: {}", index, line.name);
+        if let Some((file, line)) = line.location {
+            let _ = write!(out, " at {}:{}", file, line);
+        }
+    }
+
+    out
+}
+
+// The innermost frame `capture` would show, with backtraces on.
+pub(crate) fn caller_frame() -> Option<String> {
+    if !enabled() {
+        return None;
+    }
+    capture_lines().into_iter().next().map(|line| line.name)
+}
+
+// Unsymbolized return addresses, innermost first; cheap enough for the
+// acquire path. Resolve them later with `symbolize`.
+#[cfg(feature = "backtrace")]
+pub(crate) fn capture_ips() -> Vec<usize> {
+    Backtrace::new_unresolved()
+        .frames()
+        .iter()
+        .map(|frame| frame.ip() as usize)
+        .collect()
+}
+
+#[cfg(not(feature = "backtrace"))]
+pub(crate) fn capture_ips() -> Vec<usize> {
+    Vec::new()
+}
+
+// Outermost frame first, as folded-stack tools expect. `names` caches symbols
+// across calls, since most stacks share most of their frames.
+pub(crate) fn symbolize(ips: &[usize], names: &mut HashMap<usize, Vec<String>>) -> Vec<String> {
+    let depth = DEPTH.load(Ordering::Relaxed);
+    let hide_internal = HIDE_INTERNAL.load(Ordering::Relaxed);
+    let mut out = Vec::new();
+    let mut skipping = true;
+
+    for ip in ips {
+        let frame = names.entry(*ip).or_insert_with(|| symbol_names(*ip));
+        for name in frame.iter() {
+            if is_hidden(name, skipping, hide_internal) {
+                continue;
+            }
+            skipping = false;
+            if out.len() >= depth {
+                break;
+            }
+            out.push(name.clone());
+        }
+    }
+
+    out.reverse();
+    out
+}
+
+#[cfg(feature = "backtrace")]
+fn symbol_names(ip: usize) -> Vec<String> {
+    resolve_ip(ip as *mut std::ffi::c_void)
+        .into_iter()
+        .map(|line| line.name)
+        .collect()
+}
+
+#[cfg(not(feature = "backtrace"))]
+fn symbol_names(ip: usize) -> Vec<String> {
+    vec![format!("{:#x}", ip)]
+}
@@ -0,0 +1,135 @@
+// Every duration the crate measures goes through tokio's clock, so hold and
+// wait times follow `tokio::time::pause()` and `advance()` (tokio's
+// `test-util` feature). Outside a paused runtime this is `std::time::Instant`.
+#[cfg(not(feature = "test-clock"))]
+pub(crate) use tokio::time::Instant;
+
+// With `test-clock` the crate measures against a process-wide virtual clock
+// that only moves when told to, so a hold of exactly 5ms logs as exactly 5ms
+// on any runtime. Timeouts and sleeps still run on tokio's clock.
+#[cfg(feature = "test-clock")]
+pub(crate) use test_clock::Instant;
+
+#[cfg(feature = "test-clock")]
+pub mod test_clock {
+    use std::{
+        ops::Add,
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    };
+
+    static NOW: AtomicU64 = AtomicU64::new(0);
+
+    // Shared by every test in the process; tests that read durations should
+    // not run in parallel with ones that move the clock.
+    pub fn advance(by: Duration) {
+        NOW.fetch_add(crate::stats::duration_nanos(by), Ordering::SeqCst);
+    }
+
+    // Jumps to `elapsed` past the clock's origin. Moving it backwards makes
+    // in-flight durations read as zero rather than wrap.
+    pub fn set(elapsed: Duration) {
+        NOW.store(crate::stats::duration_nanos(elapsed), Ordering::SeqCst);
+    }
+
+    pub fn now() -> Duration {
+        Duration::from_nanos(NOW.load(Ordering::SeqCst))
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub(crate) struct Instant(Duration);
+
+    impl Instant {
+        pub(crate) fn now() -> Self {
+            Instant(now())
+        }
+
+        pub(crate) fn elapsed(&self) -> Duration {
+            Self::now().saturating_duration_since(*self)
+        }
+
+        pub(crate) fn duration_since(&self, earlier: Instant) -> Duration {
+            self.saturating_duration_since(earlier)
+        }
+
+        pub(crate) fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+            self.0.saturating_sub(earlier.0)
+        }
+    }
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, by: Duration) -> Instant {
+            Instant(self.0 + by)
+        }
+    }
+
+    // The only tests in the crate that move this clock.
+    #[cfg(test)]
+    mod tests {
+        use super::advance;
+        use crate::{Phase, RwLock};
+
+        use std::time::Duration;
+
+        #[tokio::test]
+        async fn holds_and_waits_read_the_virtual_clock() {
+            let lock = RwLock::new_named(0u8, "test-clock-held");
+            let id = lock.id();
+            let mut events = crate::subscribe_where(move |event| {
+                event.lock_id == id && matches!(event.phase, Phase::Got | Phase::Release)
+            });
+            let guard = lock.write().await;
+            advance(Duration::from_millis(5));
+            drop(guard);
+
+            let got = events.try_recv().unwrap();
+            assert_eq!(got.waited, Some(Duration::ZERO));
+            let release = events.try_recv().unwrap();
+            assert_eq!(release.held, Some(Duration::from_millis(5)));
+            let stats = lock.stats();
+            assert_eq!(stats.max_hold, Duration::from_millis(5));
+            assert_eq!(stats.max_wait, Duration::ZERO);
+        }
+    }
+}
+
+// Tokio's paused clock only drives durations while `test-clock` is off.
+#[cfg(all(test, not(feature = "test-clock")))]
+mod tests {
+    use crate::{spawn_sweeper, Phase, RwLock};
+
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn paused_time_drives_hold_durations() {
+        let lock = RwLock::new_named(0u8, "clock-held");
+        let id = lock.id();
+        let mut releases = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.phase == Phase::Release
+        });
+        let guard = lock.write().await;
+        tokio::time::advance(Duration::from_secs(5)).await;
+        drop(guard);
+        let release = releases.try_recv().unwrap();
+        assert_eq!(release.held, Some(Duration::from_secs(5)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn the_sweeper_reports_stuck_holds_on_paused_time() {
+        let lock = RwLock::new_named(0u8, "clock-stuck");
+        let id = lock.id();
+        let mut stuck =
+            crate::subscribe_where(move |event| event.lock_id == id && event.phase == Phase::Stuck);
+        let _sweeper = spawn_sweeper(Duration::from_millis(100));
+        let _guard = lock.write().await;
+
+        tokio::time::sleep(Duration::from_millis(950)).await;
+        assert!(stuck.try_recv().is_err());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let report = stuck
+            .try_recv()
+            .expect("no stuck report past the first rung");
+        assert!(report.message.contains(" held 1s "), "{}", report.message);
+    }
+}
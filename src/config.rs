@@ -0,0 +1,260 @@
+use crate::{directives::Directive, verdict};
+
+use log::Level;
+
+use std::{
+    cell::RefCell,
+    fmt, mem,
+    sync::{Arc, RwLock},
+};
+
+// Every global setting, published whole: a change copies the current
+// snapshot, edits the copy and swaps it in under a new verdict generation.
+// Readers keep the snapshot for their thread and only go back to the shared
+// one when the generation moves, so reading a setting takes no lock and
+// touches no shared counter.
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    pub(crate) logging: bool,
+    pub(crate) audit_level: Level,
+    pub(crate) idx_format: IdxFormat,
+    pub(crate) strict_levels: bool,
+    pub(crate) strict_access: bool,
+    pub(crate) type_name_on_acquire: bool,
+    pub(crate) work_warn: usize,
+    // Empty filters nothing.
+    pub(crate) directives: Vec<Directive>,
+    // None traces every lock.
+    pub(crate) trace_only: Option<Vec<String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            logging: true,
+            audit_level: Level::Warn,
+            idx_format: IdxFormat::Decimal,
+            strict_levels: false,
+            strict_access: false,
+            type_name_on_acquire: false,
+            work_warn: 0,
+            directives: Vec::new(),
+            trace_only: None,
+        }
+    }
+}
+
+static CURRENT: RwLock<Option<Arc<Config>>> = RwLock::new(None);
+
+thread_local! {
+    static CACHED: RefCell<Option<(u64, Arc<Config>)>> = const { RefCell::new(None) };
+}
+
+fn load() -> Arc<Config> {
+    let current = CURRENT.read().unwrap_or_else(|e| e.into_inner());
+    current.clone().unwrap_or_default()
+}
+
+pub(crate) fn with<R>(read: impl FnOnce(&Config) -> R) -> R {
+    // Read first, so a snapshot is never older than the generation it is
+    // cached under.
+    let generation = verdict::generation();
+    let mut read = Some(read);
+    let cached = CACHED.try_with(|cached| {
+        let mut cached = cached.try_borrow_mut().ok()?;
+        if !matches!(&*cached, Some((at, _)) if *at == generation) {
+            *cached = Some((generation, load()));
+        }
+        let (_, config) = cached.as_ref()?;
+        Some(read.take()?(config))
+    });
+    if let Ok(Some(result)) = cached {
+        return result;
+    }
+    // Called again from inside `read`, or while the thread is exiting.
+    let read = read.take().expect("the snapshot was not read");
+    read(&load())
+}
+
+pub(crate) fn update<R>(change: impl FnOnce(&mut Config) -> R) -> R {
+    let mut current = CURRENT.write().unwrap_or_else(|e| e.into_inner());
+    let mut config = current.as_deref().cloned().unwrap_or_default();
+    let result = change(&mut config);
+    *current = Some(Arc::new(config));
+    verdict::invalidate();
+    result
+}
+
+pub fn set_audit_level(level: Level) {
+    update(|config| config.audit_level = level);
+}
+
+pub fn audit_level() -> Level {
+    with(|config| config.audit_level)
+}
+
+pub(crate) fn level_from_usize(value: usize) -> Level {
+    match value {
+        1 => Level::Error,
+        2 => Level::Warn,
+        3 => Level::Info,
+        4 => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdxFormat {
+    Decimal,
+    Hex,
+    Base36,
+}
+
+pub fn set_idx_format(format: IdxFormat) {
+    update(|config| config.idx_format = format);
+}
+
+pub fn idx_format() -> IdxFormat {
+    with(|config| config.idx_format)
+}
+
+// Only changes how an idx is shown; events and stats keep the raw value.
+pub(crate) struct Idx(pub(crate) u64);
+
+impl fmt::Display for Idx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match idx_format() {
+            IdxFormat::Decimal => write!(f, "{}", self.0),
+            IdxFormat::Hex => write!(f, "{:x}", self.0),
+            IdxFormat::Base36 => {
+                let mut digits = [0u8; 13];
+                let mut start = digits.len();
+                let mut value = self.0;
+                loop {
+                    start -= 1;
+                    digits[start] = b"0123456789abcdefghijklmnopqrstuvwxyz"[(value % 36) as usize];
+                    value /= 36;
+                    if value == 0 {
+                        break;
+                    }
+                }
+                f.write_str(std::str::from_utf8(&digits[start..]).unwrap_or_default())
+            }
+        }
+    }
+}
+
+pub fn set_strict_levels(strict: bool) {
+    update(|config| config.strict_levels = strict);
+}
+
+pub fn strict_levels() -> bool {
+    with(|config| config.strict_levels)
+}
+
+// Access violations panic instead of logging, for tests that pin down which
+// code may take a lock.
+pub fn set_strict_access(strict: bool) {
+    update(|config| config.strict_access = strict);
+}
+
+pub fn strict_access() -> bool {
+    with(|config| config.strict_access)
+}
+
+// Adds the protected type's name to every Acquire line, for telling apart
+// locks with similar names; it is always on the Created and First use lines.
+pub fn set_type_name_on_acquire(enabled: bool) {
+    update(|config| config.type_name_on_acquire = enabled);
+}
+
+pub fn type_name_on_acquire() -> bool {
+    with(|config| config.type_name_on_acquire)
+}
+
+// A release whose guard recorded at least this many bytes through
+// `record_work` is logged at warn at least. Zero, the default, turns it off.
+pub fn set_work_warn_threshold(bytes: usize) {
+    update(|config| config.work_warn = bytes);
+}
+
+pub fn work_warn_threshold() -> usize {
+    with(|config| config.work_warn)
+}
+
+// Silences every lock event, log line and sink until the guard is dropped,
+// which puts back whatever was in force before, so guards nest as long as
+// they drop in reverse order. Counters and the registries keep running, so
+// stats and dumps stay right across the window, though acquisitions taken in
+// it are not numbered and show idx 0.
+pub fn disable_all() -> DisableGuard {
+    let previous = update(|config| mem::replace(&mut config.logging, false));
+    DisableGuard { previous }
+}
+
+#[derive(Debug)]
+#[must_use = "logging comes back as soon as the guard is dropped"]
+pub struct DisableGuard {
+    previous: bool,
+}
+
+impl Drop for DisableGuard {
+    fn drop(&mut self) {
+        let previous = self.previous;
+        update(|config| config.logging = previous);
+    }
+}
+
+pub(crate) fn logging_enabled() -> bool {
+    with(|config| config.logging)
+}
+
+// Binary units with one decimal: `512B`, `4.2MiB`.
+pub(crate) struct Bytes(pub(crate) u64);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+        if self.0 < 1024 {
+            return write!(f, "{}B", self.0);
+        }
+        let mut value = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        write!(f, "{:.1}{}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_work_warn_threshold, with, work_warn_threshold};
+
+    use std::{sync::mpsc, thread};
+
+    #[test]
+    fn changes_reach_threads_holding_a_snapshot() {
+        let before = work_warn_threshold();
+        let (read, first_read) = mpsc::channel();
+        let (changed, wait_for_change) = mpsc::channel::<()>();
+        let reader = thread::spawn(move || {
+            read.send(work_warn_threshold()).unwrap();
+            wait_for_change.recv().unwrap();
+            work_warn_threshold()
+        });
+        assert_eq!(first_read.recv().unwrap(), before);
+        set_work_warn_threshold(before + 4096);
+        changed.send(()).unwrap();
+        let after = reader.join().unwrap();
+        set_work_warn_threshold(before);
+        assert_eq!(after, before + 4096);
+    }
+
+    #[test]
+    fn a_snapshot_can_be_read_from_inside_another() {
+        let nested = with(|outer| (outer.logging, with(|inner| inner.logging)));
+        assert_eq!(nested.0, nested.1);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{
+    registry::{self, HeldEntry},
+    task::TaskKey,
+};
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+static NEXT_CONTEXT: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Captured>>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug)]
+struct Captured {
+    id: u64,
+    // Registry entry ids, so a hold the parent has since released no longer
+    // counts.
+    held: Vec<u64>,
+}
+
+// The locks the current task holds, plus any it inherited itself, for a task
+// that runs on its behalf. See `with_context`.
+#[derive(Debug, Clone)]
+pub struct LockContext {
+    captured: Arc<Captured>,
+}
+
+impl LockContext {
+    pub fn id(&self) -> u64 {
+        self.captured.id
+    }
+}
+
+pub fn current_context() -> LockContext {
+    let task = TaskKey::current();
+    let mut held: Vec<u64> = registry::held_with_ids()
+        .into_iter()
+        .filter(|(_, entry)| entry.task == task && entry.handoff.is_none())
+        .map(|(id, _)| id)
+        .collect();
+    CURRENT.with(|current| {
+        if let Some(parent) = current.borrow().as_ref() {
+            held.extend(parent.held.iter().copied());
+        }
+    });
+    LockContext {
+        captured: Arc::new(Captured {
+            id: NEXT_CONTEXT.fetch_add(1, Ordering::Relaxed),
+            held,
+        }),
+    }
+}
+
+// While `future` is polled, acquisitions are checked for ordering and
+// deadlocks as if the context's locks were held here too, and carry
+// `ctx=<id>`. Those holds are reported as inherited, not actual.
+pub fn with_context<F: Future>(context: LockContext, future: F) -> WithContext<F> {
+    WithContext {
+        inner: future,
+        context,
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct WithContext<F> {
+    inner: F,
+    context: LockContext,
+}
+
+impl<F: Future> Future for WithContext<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Safety: `inner` is never moved out of the pinned wrapper.
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+        let captured = this.context.captured.clone();
+        let previous = CURRENT.with(|current| current.replace(Some(captured)));
+        let _restore = Restore(previous);
+        inner.poll(cx)
+    }
+}
+
+struct Restore(Option<Arc<Captured>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|current| current.replace(previous));
+    }
+}
+
+// Read for every event, so also from guards dropped while the thread's
+// locals are torn down, when there is no context left to report.
+pub(crate) fn current_id() -> Option<u64> {
+    CURRENT
+        .try_with(|current| current.borrow().as_ref().map(|captured| captured.id))
+        .ok()
+        .flatten()
+}
+
+// The context's holds that are still live, marked with the context id.
+pub(crate) fn inherited() -> Vec<HeldEntry> {
+    let captured = match CURRENT.with(|current| current.borrow().clone()) {
+        Some(captured) => captured,
+        None => return Vec::new(),
+    };
+    registry::held_with_ids()
+        .into_iter()
+        .filter(|(id, _)| captured.held.contains(id))
+        .map(|(_, mut entry)| {
+            entry.inherited = Some(captured.id);
+            entry
+        })
+        .collect()
+}
@@ -0,0 +1,7 @@
+// Quotes only when needed, doubling embedded quotes as RFC 4180 expects.
+pub(crate) fn csv_field(value: &str) -> String {
+    if !value.contains([',', '"', '\n', '\r']) {
+        return value.to_owned();
+    }
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
@@ -0,0 +1,189 @@
+use crate::{
+    clock::Instant,
+    fallback,
+    registry::{self, HeldEntry, LockMeta},
+    retention::{self, RetentionPolicy, Tracked},
+    task::TaskKey,
+};
+
+use log::Level;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Mutex,
+    },
+};
+
+static ACTION: AtomicU8 = AtomicU8::new(DeadlockAction::Log as u8);
+static GRAPH: Mutex<Option<Graph>> = Mutex::new(None);
+
+// Edges by the lock held, then by the lock acquired while holding it.
+type Graph = HashMap<u64, HashMap<u64, Tracked<Edge>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeadlockAction {
+    Log,
+    Panic,
+    Abort,
+}
+
+pub fn set_deadlock_action(action: DeadlockAction) {
+    ACTION.store(action as u8, Ordering::Relaxed);
+}
+
+pub fn deadlock_action() -> DeadlockAction {
+    match ACTION.load(Ordering::Relaxed) {
+        1 => DeadlockAction::Panic,
+        2 => DeadlockAction::Abort,
+        _ => DeadlockAction::Log,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Edge {
+    from: String,
+    to: String,
+    held_at: &'static Location<'static>,
+    acquired_at: &'static Location<'static>,
+    inherited: Option<u64>,
+    task: String,
+}
+
+// Records "acquired `meta` while holding X" edges. A new edge that closes a
+// cycle means two tasks can each hold one end and wait on the other.
+pub(crate) fn check(meta: &LockMeta, caller: &'static Location<'static>, held: &[HeldEntry]) {
+    if held.is_empty() {
+        return;
+    }
+
+    let mut graph = GRAPH.lock().unwrap_or_else(|e| e.into_inner());
+    let graph = graph.get_or_insert_with(HashMap::new);
+    let mut cycles = Vec::new();
+
+    for entry in held {
+        if entry.lock_id == meta.id {
+            continue;
+        }
+        let edges = graph.entry(entry.lock_id).or_default();
+        if let Some(edge) = edges.get_mut(&meta.id) {
+            edge.touch();
+            continue;
+        }
+        // An evicted edge that comes back can report a cycle over again.
+        let cap = if retention::active() {
+            retention::retention_policy().max_order_edges
+        } else {
+            None
+        };
+        retention::make_room(edges, &meta.id, cap);
+        edges.insert(
+            meta.id,
+            Tracked::new(Edge {
+                from: entry.name.clone(),
+                to: meta.name().to_string(),
+                held_at: entry.caller,
+                acquired_at: caller,
+                inherited: entry.inherited,
+                task: TaskKey::current().to_string(),
+            }),
+        );
+        if let Some(path) = find_path(graph, meta.id, entry.lock_id) {
+            let mut cycle = vec![entry.lock_id];
+            cycle.extend(path);
+            cycles.push(cycle);
+        }
+    }
+
+    for cycle in cycles {
+        report(graph, &cycle);
+    }
+}
+
+fn find_path(graph: &Graph, from: u64, to: u64) -> Option<Vec<u64>> {
+    let mut stack = vec![vec![from]];
+    let mut seen = HashSet::new();
+
+    while let Some(path) = stack.pop() {
+        let node = *path.last().unwrap();
+        if node == to {
+            return Some(path);
+        }
+        if !seen.insert(node) {
+            continue;
+        }
+        for next in graph.get(&node).into_iter().flat_map(HashMap::keys) {
+            let mut path = path.clone();
+            path.push(*next);
+            stack.push(path);
+        }
+    }
+
+    None
+}
+
+fn report(graph: &Graph, cycle: &[u64]) {
+    let edges: Vec<&Edge> = cycle
+        .windows(2)
+        .filter_map(|pair| graph.get(&pair[0]).and_then(|edges| edges.get(&pair[1])))
+        .map(|edge| &edge.value)
+        .collect();
+
+    let mut names: Vec<&str> = edges.iter().map(|edge| edge.from.as_str()).collect();
+    if let Some(last) = edges.last() {
+        names.push(&last.to);
+    }
+    let mut message = format!("[DEADLOCK] lock-order cycle: {}", names.join(" -> "));
+    for edge in edges {
+        let _ = write!(
+            message,
+            "\n    ({}) acquired at {}:{}:{} by {} while holding ({}) acquired at {}:{}:{}",
+            edge.to,
+            edge.acquired_at.file(),
+            edge.acquired_at.line(),
+            edge.acquired_at.column(),
+            edge.task,
+            edge.from,
+            edge.held_at.file(),
+            edge.held_at.line(),
+            edge.held_at.column(),
+        );
+        if let Some(context) = edge.inherited {
+            let _ = write!(message, " (inherited via ctx={})", context);
+        }
+    }
+
+    fallback::log!(Level::Error, "{}", message);
+    match deadlock_action() {
+        DeadlockAction::Log => {}
+        DeadlockAction::Panic => panic!("{}", message),
+        DeadlockAction::Abort => std::process::abort(),
+    }
+}
+
+// Edges from or to a dropped lock can never close a cycle again, so they go
+// whatever the policy; the rest are trimmed to it. Returns how many went.
+pub(crate) fn compact(policy: &RetentionPolicy, now: Instant) -> u64 {
+    let live: HashSet<u64> = registry::all_locks().iter().map(|meta| meta.id).collect();
+    let mut graph = GRAPH.lock().unwrap_or_else(|e| e.into_inner());
+    let graph = match graph.as_mut() {
+        Some(graph) => graph,
+        None => return 0,
+    };
+    let mut evicted = 0;
+    graph.retain(|from, edges| {
+        let before = edges.len();
+        if live.contains(from) {
+            edges.retain(|to, _| live.contains(to));
+        } else {
+            edges.clear();
+        }
+        evicted += (before - edges.len()) as u64;
+        evicted += retention::trim(edges, policy.max_order_edges, policy, now);
+        !edges.is_empty()
+    });
+    evicted
+}
@@ -0,0 +1,259 @@
+use crate::{
+    json::json_string,
+    redact::{self, Export, Redaction},
+    registry::{self, LockMeta},
+    snapshot::{self, RegistrySnapshot},
+    stats::LockStats,
+    summary,
+};
+
+use axum::{
+    extract::Path,
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use std::{cmp::Reverse, fmt::Write};
+
+// Past these a response is cut short and carries the `TRUNCATED` header.
+const MAX_LOCKS: usize = 1000;
+const MAX_ENTRIES: usize = 100;
+const MAX_EVENTS: usize = 100;
+const MAX_SITES: usize = 50;
+const MAX_TEXT: usize = 1 << 20;
+
+const TRUNCATED: &str = "x-rwlock-trace-truncated";
+
+// Read-only views of the crate's own registry and counters, to nest under a
+// service's admin router. Handlers take only the short tracking mutexes and
+// never an application lock, so they answer while the service is wedged.
+//
+// - `/locks`: `snapshot().to_json()`
+// - `/locks/{name}`: locks with that name or id, with their ring's recent
+//   events and their top call sites
+// - `/dump`: `dump_held_locks()`
+// - `/held`: `render_held_table()`
+// - `/summary`: `summary()`
+// - `/metrics`: the stats in the Prometheus text format
+//
+// Names go through the redactor as in the snapshot and metrics exports; event
+// messages are log lines and, as in the log, are not redacted.
+pub fn debug_router() -> Router {
+    Router::new()
+        .route("/locks", get(locks))
+        .route("/locks/{name}", get(lock))
+        .route("/dump", get(dump))
+        .route("/held", get(held))
+        .route("/summary", get(summary_table))
+        .route("/metrics", get(metrics))
+}
+
+async fn locks() -> Response {
+    let mut snapshot = snapshot::snapshot();
+    let truncated = bound(&mut snapshot);
+    respond("application/json", snapshot.to_json(), truncated)
+}
+
+// Names can repeat, so every match is listed; ids stay unredacted, so a lock
+// can still be looked up when its name is not shown.
+async fn lock(Path(key): Path<String>) -> Response {
+    let mut snapshot = snapshot::snapshot();
+    snapshot
+        .locks
+        .retain(|lock| lock.name == key || lock.id.to_string() == key);
+    if snapshot.locks.is_empty() {
+        return (StatusCode::NOT_FOUND, format!("no lock named {:?}\n", key)).into_response();
+    }
+    let truncated = bound(&mut snapshot);
+
+    let mut redaction = Redaction::new(Export::Snapshot);
+    let mut out = String::from("{\"locks\":[");
+    for (index, lock) in snapshot.locks.iter().enumerate() {
+        let _ = write!(
+            out,
+            "{}{{\"lock\":{},\"recent_events\":",
+            if index == 0 { "" } else { "," },
+            lock.to_json(&mut redaction)
+        );
+        let meta = registry::lock(lock.id);
+        match meta.as_deref().and_then(|meta| meta.event_ring.get()) {
+            Some(ring) => {
+                let events: Vec<String> = ring
+                    .recent(MAX_EVENTS)
+                    .iter()
+                    .map(|event| {
+                        format!(
+                            "{{\"seq\":{},\"mode\":{},\"phase\":{},\"level\":{},\"message\":{}}}",
+                            event.seq,
+                            json_string(event.mode.as_str()),
+                            json_string(event.phase.as_str()),
+                            json_string(event.level.as_str()),
+                            json_string(&event.message),
+                        )
+                    })
+                    .collect();
+                let _ = write!(out, "[{}]", events.join(","));
+            }
+            None => out.push_str("null"),
+        }
+        out.push_str(",\"sites\":[");
+        if let Some(meta) = &meta {
+            out.push_str(&sites(meta).join(","));
+        }
+        out.push_str("]}");
+    }
+    out.push_str("]}");
+    respond("application/json", out, truncated)
+}
+
+async fn dump() -> Response {
+    text(registry::dump_held_locks())
+}
+
+async fn held() -> Response {
+    text(registry::render_held_table())
+}
+
+async fn summary_table() -> Response {
+    text(summary::summary())
+}
+
+// Name, type and value of a metric with one sample per lock. Acquisitions are
+// split by mode, so they are written on their own.
+type Family = (&'static str, &'static str, fn(&Row) -> f64);
+
+struct Row {
+    labels: String,
+    stats: LockStats,
+    held: usize,
+    waiters: u64,
+}
+
+async fn metrics() -> Response {
+    let mut locks = registry::all_locks();
+    let truncated = locks.len() > MAX_LOCKS;
+    locks.truncate(MAX_LOCKS);
+    let rows: Vec<Row> = locks
+        .iter()
+        .map(|meta| Row {
+            labels: labels(meta),
+            stats: meta.stats.snapshot(),
+            held: meta.availability.holders(),
+            waiters: meta.stats.waiters(),
+        })
+        .collect();
+
+    let mut out = String::from("# TYPE rwlock_acquisitions_total counter\n");
+    for row in &rows {
+        for (mode, count) in [("read", row.stats.reads), ("write", row.stats.writes)] {
+            let _ = writeln!(
+                out,
+                "rwlock_acquisitions_total{{{},mode=\"{}\"}} {}",
+                row.labels, mode, count
+            );
+        }
+    }
+    let families: [Family; 6] = [
+        ("rwlock_contended_total", "counter", |row| {
+            row.stats.contended as f64
+        }),
+        ("rwlock_wait_seconds_total", "counter", |row| {
+            row.stats.total_wait.as_secs_f64()
+        }),
+        ("rwlock_hold_seconds_total", "counter", |row| {
+            row.stats.total_hold.as_secs_f64()
+        }),
+        ("rwlock_max_hold_seconds", "gauge", |row| {
+            row.stats.max_hold.as_secs_f64()
+        }),
+        ("rwlock_held", "gauge", |row| row.held as f64),
+        ("rwlock_waiters", "gauge", |row| row.waiters as f64),
+    ];
+    for (name, kind, value) in families {
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for row in &rows {
+            let _ = writeln!(out, "{}{{{}}} {}", name, row.labels, value(row));
+        }
+    }
+    respond("text/plain; version=0.0.4", out, truncated)
+}
+
+fn labels(meta: &LockMeta) -> String {
+    let group = meta
+        .group
+        .get()
+        .map(|group| redact::redact(Export::Metrics, group).into_owned())
+        .unwrap_or_default();
+    format!(
+        "lock=\"{}\",id=\"{}\",group=\"{}\"",
+        label_value(&redact::redact(Export::Metrics, &meta.name())),
+        meta.id,
+        label_value(&group)
+    )
+}
+
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Longest total hold first, as in `callsite_stats`.
+fn sites(meta: &LockMeta) -> Vec<String> {
+    let mut sites = meta.stats.sites();
+    sites.sort_by_key(|(_, site)| Reverse(site.total_hold));
+    sites
+        .iter()
+        .take(MAX_SITES)
+        .map(|(location, site)| {
+            format!(
+                "{{\"site\":{},\"count\":{},\"total_wait_ns\":{},\"total_hold_ns\":{},\
+                 \"unmutated_writes\":{}}}",
+                json_string(&format!("{}:{}", location.file(), location.line())),
+                site.count,
+                site.total_wait.as_nanos(),
+                site.total_hold.as_nanos(),
+                site.unmutated_writes,
+            )
+        })
+        .collect()
+}
+
+fn bound(snapshot: &mut RegistrySnapshot) -> bool {
+    let mut truncated = snapshot.locks.len() > MAX_LOCKS;
+    snapshot.locks.truncate(MAX_LOCKS);
+    for lock in &mut snapshot.locks {
+        truncated |= lock.holders.len() > MAX_ENTRIES || lock.waiters.len() > MAX_ENTRIES;
+        lock.holders.truncate(MAX_ENTRIES);
+        lock.waiters.truncate(MAX_ENTRIES);
+    }
+    truncated
+}
+
+// Cut at a line boundary, so a table loses rows rather than columns.
+fn text(mut body: String) -> Response {
+    let truncated = body.len() > MAX_TEXT;
+    if truncated {
+        let mut end = MAX_TEXT;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        let end = body[..end].rfind('\n').map_or(end, |newline| newline + 1);
+        body.truncate(end);
+        body.push_str("...\n");
+    }
+    respond("text/plain; charset=utf-8", body, truncated)
+}
+
+fn respond(content_type: &'static str, body: String, truncated: bool) -> Response {
+    let mut response = body.into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if truncated {
+        headers.insert(TRUNCATED, HeaderValue::from_static("true"));
+    }
+    response
+}
@@ -0,0 +1,209 @@
+use crate::{
+    clock::Instant,
+    json::json_string,
+    redact::{Export, Redaction},
+    registry,
+    stats::{StatsDiff, StatsSnapshot},
+};
+
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+// Entries are keyed by registry id rather than name: a name can be reused by
+// a lock created after the first one was dropped.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub locks: Vec<SnapshotEntry>,
+    taken_at: Instant,
+}
+
+#[derive(Debug, Clone)]
+pub struct SnapshotEntry {
+    pub id: u64,
+    pub name: String,
+    pub stats: StatsSnapshot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum LockChange {
+    Kept,
+    New,
+    Removed,
+}
+
+impl LockChange {
+    fn as_str(self) -> &'static str {
+        match self {
+            LockChange::Kept => "kept",
+            LockChange::New => "new",
+            LockChange::Removed => "removed",
+        }
+    }
+}
+
+// A new lock's delta counts from zero. A removed lock's counters went with
+// it, so its delta is empty; only its presence in the list says anything.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LockDelta {
+    pub id: u64,
+    pub name: String,
+    pub change: LockChange,
+    pub diff: StatsDiff,
+}
+
+// Sorted by the increase in total wait, largest first.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SnapshotDelta {
+    pub interval: Duration,
+    pub locks: Vec<LockDelta>,
+}
+
+pub fn all_stats() -> Snapshot {
+    let mut locks: Vec<SnapshotEntry> = registry::all_locks()
+        .iter()
+        .map(|meta| SnapshotEntry {
+            id: meta.id,
+            name: meta.name().to_string(),
+            stats: StatsSnapshot::take(&meta.stats),
+        })
+        .collect();
+    locks.sort_by_key(|entry| entry.id);
+    Snapshot {
+        locks,
+        taken_at: Instant::now(),
+    }
+}
+
+impl Snapshot {
+    pub fn diff(&self, earlier: &Snapshot) -> SnapshotDelta {
+        let interval = self.taken_at.saturating_duration_since(earlier.taken_at);
+        let mut before: BTreeMap<u64, &SnapshotEntry> = earlier
+            .locks
+            .iter()
+            .map(|entry| (entry.id, entry))
+            .collect();
+
+        let mut locks: Vec<LockDelta> = self
+            .locks
+            .iter()
+            .map(|entry| match before.remove(&entry.id) {
+                Some(then) => LockDelta {
+                    id: entry.id,
+                    name: entry.name.clone(),
+                    change: LockChange::Kept,
+                    diff: entry.stats.diff(&then.stats),
+                },
+                None => LockDelta {
+                    id: entry.id,
+                    name: entry.name.clone(),
+                    change: LockChange::New,
+                    diff: StatsDiff {
+                        interval,
+                        reads: entry.stats.stats.reads,
+                        writes: entry.stats.stats.writes,
+                        contended: entry.stats.stats.contended,
+                        releases: entry.stats.stats.releases,
+                        total_wait: entry.stats.stats.total_wait,
+                        total_hold: entry.stats.stats.total_hold,
+                    },
+                },
+            })
+            .collect();
+        locks.extend(before.into_values().map(|then| LockDelta {
+            id: then.id,
+            name: then.name.clone(),
+            change: LockChange::Removed,
+            diff: StatsDiff {
+                interval,
+                ..StatsDiff::default()
+            },
+        }));
+        locks.sort_by(|a, b| {
+            b.diff
+                .total_wait
+                .cmp(&a.diff.total_wait)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+
+        SnapshotDelta { interval, locks }
+    }
+}
+
+impl SnapshotDelta {
+    // Durations are integer `_ns` fields, as in the other JSON exports.
+    pub fn to_json(&self) -> String {
+        let mut redaction = Redaction::new(Export::Snapshot);
+        let locks: Vec<String> = self
+            .locks
+            .iter()
+            .map(|lock| {
+                format!(
+                    "{{\"id\":{},\"name\":{},\"change\":{},\"reads\":{},\"writes\":{},\
+                     \"contended\":{},\"releases\":{},\"total_wait_ns\":{},\"total_hold_ns\":{}}}",
+                    lock.id,
+                    json_string(&redaction.apply(&lock.name)),
+                    json_string(lock.change.as_str()),
+                    lock.diff.reads,
+                    lock.diff.writes,
+                    lock.diff.contended,
+                    lock.diff.releases,
+                    lock.diff.total_wait.as_nanos(),
+                    lock.diff.total_hold.as_nanos(),
+                )
+            })
+            .collect();
+        format!(
+            "{{\"interval_ns\":{},\"locks\":[{}]}}",
+            self.interval.as_nanos(),
+            locks.join(",")
+        )
+    }
+}
+
+impl fmt::Display for SnapshotDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = [
+            "id",
+            "name",
+            "change",
+            "acq (r/w)",
+            "contended",
+            "+wait",
+            "+hold",
+        ];
+        let mut table: Vec<[String; 7]> = vec![header.map(str::to_owned)];
+        let mut redaction = Redaction::new(Export::Snapshot);
+        for lock in &self.locks {
+            table.push([
+                lock.id.to_string(),
+                redaction.apply(&lock.name).into_owned(),
+                lock.change.as_str().to_owned(),
+                format!("+{}/+{}", lock.diff.reads, lock.diff.writes),
+                format!("+{}", lock.diff.contended),
+                format!("{:?}", lock.diff.total_wait),
+                format!("{:?}", lock.diff.total_hold),
+            ]);
+        }
+
+        let mut widths = [0; 7];
+        for line in &table {
+            for (width, cell) in widths.iter_mut().zip(line.iter()) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        writeln!(f, "over {:?}", self.interval)?;
+        for line in &table {
+            let cells: Vec<String> = line
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            writeln!(f, "{}", cells.join("  ").trim_end())?;
+        }
+        Ok(())
+    }
+}
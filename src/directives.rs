@@ -0,0 +1,319 @@
+use crate::{config, fallback};
+
+use log::{Level, LevelFilter};
+
+use std::{env, error::Error, fmt, sync::Once};
+
+const ENV_VAR: &str = "RWLOCK_TRACE_LOG";
+const ONLY_VAR: &str = "RWLOCK_TRACE_ONLY";
+const RUST_LOG: &str = "RUST_LOG";
+
+static ENV: Once = Once::new();
+static ONLY_ENV: Once = Once::new();
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectiveError {
+    pub directive: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for DirectiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid directive {:?}: {}", self.directive, self.reason)
+    }
+}
+
+impl Error for DirectiveError {}
+
+// `pattern` is None for the bare level that applies to every lock.
+#[derive(Debug, Clone)]
+pub(crate) struct Directive {
+    pattern: Option<String>,
+    level: LevelFilter,
+}
+
+impl Directive {
+    // Exact names beat globs, and among globs the one with more literal
+    // characters wins; a tie goes to the directive written last.
+    fn specificity(&self) -> Option<(bool, usize)> {
+        self.pattern.as_ref().map(|pattern| {
+            let literal = pattern.chars().filter(|c| *c != '*').count();
+            (!pattern.contains('*'), literal)
+        })
+    }
+}
+
+// The `RUST_LOG` shape, matched against lock names instead of module paths:
+// `info,sessions=trace,cache*=off`. Applied where events are emitted, so the
+// log, subscribers and the binary sink all see the same events. Locks that no
+// directive matches are not filtered. `rwlock_trace=LEVEL` is read as the bare
+// level, so a directive copied from `RUST_LOG` still works. An empty string
+// clears the directives; an invalid one leaves the current set in place.
+// Either way, this replaces whatever `RWLOCK_TRACE_LOG` or `RUST_LOG` gave.
+pub fn set_directives(spec: &str) -> Result<(), DirectiveError> {
+    let directives = parse(spec)?;
+    ENV.call_once(|| {});
+    store(directives);
+    Ok(())
+}
+
+fn store(directives: Vec<Directive>) {
+    config::update(|config| config.directives = directives);
+}
+
+// Until `set_directives` is called, the first event loads `RWLOCK_TRACE_LOG`,
+// or when that is unset the level `RUST_LOG` gives this crate.
+fn load_env() {
+    let (var, directives) = match (env::var(ENV_VAR), env::var(RUST_LOG)) {
+        (Ok(spec), _) => (ENV_VAR, parse(&spec)),
+        (Err(_), Ok(spec)) => (RUST_LOG, from_rust_log(&spec)),
+        (Err(_), Err(_)) => return,
+    };
+    match directives {
+        Ok(directives) => store(directives),
+        Err(error) => fallback::log!(Level::Warn, "[META] ignoring {}: {}", var, error),
+    }
+}
+
+// `RUST_LOG` names modules rather than locks, so only its `rwlock_trace=LEVEL`
+// is taken, or failing that its bare level, as the level for every lock. The
+// other directives and a trailing `/regex` are left to the logger.
+fn from_rust_log(spec: &str) -> Result<Vec<Directive>, DirectiveError> {
+    let spec = spec.split('/').next().unwrap_or_default();
+    let mut bare = None;
+    let mut own = None;
+    for part in spec.split(',').map(str::trim) {
+        match part.split_once('=') {
+            Some((target, level)) if target.trim() == "rwlock_trace" => {
+                let level = level
+                    .trim()
+                    .parse::<LevelFilter>()
+                    .map_err(|_| DirectiveError {
+                        directive: part.to_owned(),
+                        reason: "unknown level",
+                    })?;
+                own = Some(level);
+            }
+            Some(_) => {}
+            None => bare = part.parse::<LevelFilter>().ok().or(bare),
+        }
+    }
+    Ok(own
+        .or(bare)
+        .map(|level| Directive {
+            pattern: None,
+            level,
+        })
+        .into_iter()
+        .collect())
+}
+
+// Traces only the locks whose names match one of the comma-separated globs,
+// `cache-*,config`; the rest are silenced as under `disable_all`, their
+// counters still kept. An empty string traces every lock again. Until this is
+// called, the first verdict loads `RWLOCK_TRACE_ONLY`. The directives still
+// apply to the locks it lets through.
+pub fn set_trace_only(spec: &str) {
+    ONLY_ENV.call_once(|| {});
+    store_only(spec);
+}
+
+fn store_only(spec: &str) {
+    let globs: Vec<String> = spec
+        .split(',')
+        .map(str::trim)
+        .filter(|glob| !glob.is_empty())
+        .map(str::to_owned)
+        .collect();
+    let only = if globs.is_empty() { None } else { Some(globs) };
+    config::update(|config| config.trace_only = only);
+}
+
+fn load_only_env() {
+    if let Ok(spec) = env::var(ONLY_VAR) {
+        store_only(&spec);
+    }
+}
+
+pub(crate) fn traced(name: &str) -> bool {
+    ONLY_ENV.call_once(load_only_env);
+    config::with(|config| match &config.trace_only {
+        Some(globs) => globs.iter().any(|glob| glob_match(glob, name)),
+        None => true,
+    })
+}
+
+pub(crate) fn active() -> bool {
+    ENV.call_once(load_env);
+    config::with(|config| !config.directives.is_empty())
+}
+
+pub(crate) fn max_level(name: &str) -> LevelFilter {
+    ENV.call_once(load_env);
+    config::with(|config| level_for(&config.directives, name))
+}
+
+fn level_for(directives: &[Directive], name: &str) -> LevelFilter {
+    let mut best: Option<&Directive> = None;
+    for directive in directives.iter() {
+        let matches = match &directive.pattern {
+            Some(pattern) => glob_match(pattern, name),
+            None => true,
+        };
+        if matches && best.is_none_or(|best| directive.specificity() >= best.specificity()) {
+            best = Some(directive);
+        }
+    }
+    best.map_or(LevelFilter::Trace, |directive| directive.level)
+}
+
+fn parse(spec: &str) -> Result<Vec<Directive>, DirectiveError> {
+    let mut directives = Vec::new();
+    for part in spec
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let error = |reason| DirectiveError {
+            directive: part.to_owned(),
+            reason,
+        };
+        let (pattern, level) = match part.split_once('=') {
+            Some((pattern, level)) => {
+                let pattern = pattern.trim();
+                if pattern.is_empty() {
+                    return Err(error("missing lock name"));
+                }
+                (Some(pattern), level.trim())
+            }
+            None => (None, part),
+        };
+        let level = level
+            .parse::<LevelFilter>()
+            .map_err(|_| error("unknown level"))?;
+        let pattern = pattern
+            .filter(|pattern| *pattern != "rwlock_trace")
+            .map(str::to_owned);
+        directives.push(Directive { pattern, level });
+    }
+    Ok(directives)
+}
+
+// `*` matches any run of characters, including none.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match name.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts: Vec<&str> = parts.collect();
+    let (last, middle) = match parts.split_last() {
+        Some(split) => split,
+        None => return rest.is_empty(),
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_rust_log, glob_match, level_for, parse, DirectiveError};
+
+    use log::LevelFilter;
+
+    fn level(spec: &str, name: &str) -> LevelFilter {
+        level_for(&parse(spec).unwrap(), name)
+    }
+
+    #[test]
+    fn globs_match_runs_of_any_characters() {
+        assert!(glob_match("cache", "cache"));
+        assert!(!glob_match("cache", "cache-1"));
+        assert!(glob_match("cache*", "cache"));
+        assert!(glob_match("cache*", "cache-1"));
+        assert!(glob_match("*-1", "cache-1"));
+        assert!(glob_match("c*e-*", "cache-1"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*a", "aba"));
+        assert!(!glob_match("a*b*a", "ab"));
+        assert!(!glob_match("ab*ba", "aba"));
+        assert!(!glob_match("*-2", "cache-1"));
+    }
+
+    #[test]
+    fn the_most_specific_directive_wins() {
+        let spec = "warn,cache*=info,cache-hot*=debug,cache-hot-1=off,*-1=trace";
+        assert_eq!(level(spec, "sessions"), LevelFilter::Warn);
+        assert_eq!(level(spec, "cache-cold"), LevelFilter::Info);
+        assert_eq!(level(spec, "cache-hot-2"), LevelFilter::Debug);
+        assert_eq!(level(spec, "cache-hot-1"), LevelFilter::Off);
+        assert_eq!(level(spec, "config-1"), LevelFilter::Trace);
+        // Unmatched locks are not filtered.
+        assert_eq!(level("cache=off", "sessions"), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn ties_go_to_the_last_directive() {
+        assert_eq!(level("a*=info,*a=debug", "aa"), LevelFilter::Debug);
+        assert_eq!(level("*a=debug,a*=info", "aa"), LevelFilter::Info);
+        assert_eq!(level("cache=info,cache=error", "cache"), LevelFilter::Error);
+        assert_eq!(level("info,rwlock_trace=warn", "cache"), LevelFilter::Warn);
+    }
+
+    #[test]
+    fn malformed_directives_are_rejected() {
+        let error = |directive: &str, reason| {
+            Err(DirectiveError {
+                directive: directive.to_owned(),
+                reason,
+            })
+        };
+        assert_eq!(
+            parse("info,cache=loud").map(|_| ()),
+            error("cache=loud", "unknown level")
+        );
+        assert_eq!(
+            parse("=debug").map(|_| ()),
+            error("=debug", "missing lock name")
+        );
+        assert_eq!(
+            parse("verbose").map(|_| ()),
+            error("verbose", "unknown level")
+        );
+        assert!(parse(" , cache = DEBUG ,").is_ok());
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rust_log_gives_the_crate_level() {
+        let only = |spec| {
+            let directives = from_rust_log(spec).unwrap();
+            assert!(directives
+                .iter()
+                .all(|directive| directive.pattern.is_none()));
+            directives.first().map(|directive| directive.level)
+        };
+        assert_eq!(
+            only("info,myapp=trace,rwlock_trace=warn"),
+            Some(LevelFilter::Warn)
+        );
+        assert_eq!(only("rwlock_trace=warn,info"), Some(LevelFilter::Warn));
+        assert_eq!(only("debug,myapp::db=trace"), Some(LevelFilter::Debug));
+        assert_eq!(only("myapp=trace,hyper"), None);
+        assert_eq!(
+            only("info,rwlock_trace=error/lock"),
+            Some(LevelFilter::Error)
+        );
+        assert_eq!(
+            only("myapp[span{id=1}]=debug,warn"),
+            Some(LevelFilter::Warn)
+        );
+        assert!(from_rust_log("rwlock_trace=loud").is_err());
+    }
+}
@@ -0,0 +1,611 @@
+use crate::{
+    batch, binary, callsite, capture,
+    config::Idx,
+    context,
+    fallback::{self, Fields},
+    history, operation,
+    phase::{Mode, Phase},
+    recorder,
+    registry::{self, LockMeta},
+    severity,
+    task::TaskKey,
+    task_level,
+    verdict::Verdict,
+};
+
+use log::Level;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use std::{
+    collections::BTreeSet,
+    fmt::Write,
+    iter,
+    panic::Location,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    thread::{self, ThreadId},
+    time::{Duration, SystemTime},
+};
+
+const SUBSCRIBER_CAPACITY: usize = 1024;
+
+static SEQ: AtomicU64 = AtomicU64::new(0);
+static UNTRACED_SITES: Mutex<BTreeSet<(u64, &'static Location<'static>)>> =
+    Mutex::new(BTreeSet::new());
+static HAS_SUBSCRIBERS: AtomicBool = AtomicBool::new(false);
+static SUBSCRIBERS: Mutex<Vec<Subscriber>> = Mutex::new(Vec::new());
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone)]
+pub struct LockEvent {
+    pub seq: u64,
+    pub timestamp: SystemTime,
+    pub lock_id: u64,
+    pub name: String,
+    pub scope: String,
+    pub mode: Mode,
+    pub phase: Phase,
+    pub idx: u64,
+    pub location: &'static Location<'static>,
+    pub level: Level,
+    pub message: String,
+    // Set for events from inside `with_context`, to stitch them to the
+    // parent's trace.
+    pub context: Option<u64>,
+    // From `with_operation_id`; for an acquisition's events, the operation
+    // it started under.
+    pub operation: Option<String>,
+    // The thread the event was emitted on; for `Got`, the acquiring thread.
+    pub thread: ThreadId,
+    // The tokio task the event was emitted on; None outside a task.
+    pub task: Option<tokio::task::Id>,
+    // From `name_current_task`, for the task the event was emitted on.
+    pub task_name: Option<String>,
+    // The acquisition's wait, on events from Got on, and its hold, on its
+    // Release; None on events not about one acquisition.
+    pub waited: Option<Duration>,
+    pub held: Option<Duration>,
+}
+
+// The acquisition an event is about.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Subject {
+    pub(crate) idx: u64,
+    pub(crate) waited: Option<Duration>,
+    pub(crate) held: Option<Duration>,
+}
+
+impl Subject {
+    fn idx(idx: u64) -> Self {
+        Self {
+            idx,
+            ..Self::default()
+        }
+    }
+}
+
+// The call site goes out as `file`, `line` and `column` and the thread as its
+// debug form. Neither can be rebuilt on the way back in, so there is no
+// `Deserialize`; `Mode` and `Phase` have one for a consumer's own event type.
+#[cfg(feature = "serde")]
+impl serde::Serialize for LockEvent {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use crate::stats::duration_nanos;
+        use serde::ser::SerializeStruct;
+
+        let mut event = serializer.serialize_struct("LockEvent", 20)?;
+        event.serialize_field("seq", &self.seq)?;
+        event.serialize_field("timestamp", &self.timestamp)?;
+        event.serialize_field("lock_id", &self.lock_id)?;
+        event.serialize_field("name", &self.name)?;
+        event.serialize_field("scope", &self.scope)?;
+        event.serialize_field("mode", &self.mode)?;
+        event.serialize_field("phase", &self.phase)?;
+        event.serialize_field("idx", &self.idx)?;
+        event.serialize_field("file", self.location.file())?;
+        event.serialize_field("line", &self.location.line())?;
+        event.serialize_field("column", &self.location.column())?;
+        event.serialize_field("level", self.level.as_str())?;
+        event.serialize_field("message", &self.message)?;
+        event.serialize_field("context", &self.context)?;
+        event.serialize_field("operation", &self.operation)?;
+        event.serialize_field("thread", &format!("{:?}", self.thread))?;
+        event.serialize_field("task", &self.task.map(|id| id.to_string()))?;
+        event.serialize_field("task_name", &self.task_name)?;
+        event.serialize_field("waited_ns", &self.waited.map(duration_nanos))?;
+        event.serialize_field("held_ns", &self.held.map(duration_nanos))?;
+        event.end()
+    }
+}
+
+pub fn subscribe() -> mpsc::Receiver<LockEvent> {
+    subscribe_with_capacity(SUBSCRIBER_CAPACITY)
+}
+
+pub fn subscribe_with_capacity(capacity: usize) -> mpsc::Receiver<LockEvent> {
+    add_subscriber(capacity, None)
+}
+
+// Only events passing `filter` are queued, so a subscriber interested in, say,
+// `Mode::Write` and `Phase::Got` never fills its channel with the rest. The
+// filter runs on the emitting task and must not take a traced lock.
+pub fn subscribe_where<F>(filter: F) -> mpsc::Receiver<LockEvent>
+where
+    F: Fn(&LockEvent) -> bool + Send + 'static,
+{
+    add_subscriber(SUBSCRIBER_CAPACITY, Some(Box::new(filter)))
+}
+
+type EventFilter = Box<dyn Fn(&LockEvent) -> bool + Send>;
+
+struct Subscriber {
+    sender: mpsc::Sender<LockEvent>,
+    filter: Option<EventFilter>,
+    // Locks this subscriber has been sent a Created event for.
+    announced: BTreeSet<u64>,
+}
+
+fn add_subscriber(capacity: usize, filter: Option<EventFilter>) -> mpsc::Receiver<LockEvent> {
+    let (sender, receiver) = mpsc::channel(capacity);
+    let mut subscribers = SUBSCRIBERS.lock().unwrap_or_else(|e| e.into_inner());
+    subscribers.push(Subscriber {
+        sender,
+        filter,
+        announced: BTreeSet::new(),
+    });
+    HAS_SUBSCRIBERS.store(true, Ordering::Release);
+    receiver
+}
+
+pub(crate) fn has_subscribers() -> bool {
+    HAS_SUBSCRIBERS.load(Ordering::Acquire)
+}
+
+pub fn dropped_events() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+pub(crate) fn emit(
+    level: Level,
+    meta: &LockMeta,
+    mode: Mode,
+    phase: Phase,
+    subject: Subject,
+    caller: &'static Location<'static>,
+    details: &str,
+) {
+    let message = format_message(meta, mode, phase, subject.idx, details);
+    send(level, meta, mode, phase, subject, caller, message);
+}
+
+// For audited acquisitions, whose events go out whatever the directives,
+// scopes and sampling would drop.
+pub(crate) fn emit_audited(
+    level: Level,
+    meta: &LockMeta,
+    mode: Mode,
+    phase: Phase,
+    subject: Subject,
+    caller: &'static Location<'static>,
+    details: &str,
+) {
+    let message = format_message(meta, mode, phase, subject.idx, details);
+    deliver(level, meta, mode, phase, subject, caller, message);
+}
+
+pub(crate) fn format_message(
+    meta: &LockMeta,
+    mode: Mode,
+    phase: Phase,
+    idx: u64,
+    details: &str,
+) -> String {
+    format!("[{}] {} ({}:{}){}", mode, phase, meta.name(), Idx(idx), details)
+}
+
+pub(crate) fn emit_message(
+    level: Level,
+    meta: &LockMeta,
+    mode: Mode,
+    phase: Phase,
+    idx: u64,
+    caller: &'static Location<'static>,
+    message: String,
+) {
+    send(level, meta, mode, phase, Subject::idx(idx), caller, message);
+}
+
+fn send(
+    level: Level,
+    meta: &LockMeta,
+    mode: Mode,
+    phase: Phase,
+    subject: Subject,
+    caller: &'static Location<'static>,
+    message: String,
+) {
+    let verdict = Verdict::of(meta);
+    if !verdict.reaches(meta, level) {
+        return;
+    }
+    if !verdict.allows(level) {
+        if let Some(ring) = meta.event_ring.get() {
+            ring.push(build(level, meta, mode, phase, subject, caller, message));
+        }
+        return;
+    }
+    deliver(level, meta, mode, phase, subject, caller, message);
+}
+
+fn deliver(
+    level: Level,
+    meta: &LockMeta,
+    mode: Mode,
+    phase: Phase,
+    subject: Subject,
+    caller: &'static Location<'static>,
+    message: String,
+) {
+    let idx = subject.idx;
+    let ring = meta.event_ring.get();
+    let operation = operation::current();
+    let fields = Fields {
+        lock_id: meta.id,
+        name: &meta.name(),
+        idx,
+        kind: mode,
+        phase,
+        operation: operation.as_deref(),
+    };
+    let backtrace = meta.backtrace.get().copied().unwrap_or_else(capture::enabled);
+    if callsite::compact() {
+        log_compact(level, meta, fields, &message, caller, backtrace);
+    } else {
+        log_backtrace(level, fields, &message, caller, backtrace);
+    }
+    if binary::enabled() {
+        binary::record(level, meta, mode, phase, idx, caller);
+    }
+
+    let subscribed = HAS_SUBSCRIBERS.load(Ordering::Acquire);
+    let task = history::recording_task();
+    let recording = recorder::enabled();
+    if !subscribed && task.is_none() && !recording && ring.is_none() {
+        return;
+    }
+
+    let event = build(level, meta, mode, phase, subject, caller, message);
+    if let Some(ring) = ring {
+        ring.push(event.clone());
+    }
+    if let Some(task) = task {
+        history::record(task, event.clone());
+    }
+    if recording {
+        recorder::record(event.clone());
+    }
+    if subscribed {
+        publish(event);
+    }
+}
+
+pub(crate) fn build(
+    level: Level,
+    meta: &LockMeta,
+    mode: Mode,
+    phase: Phase,
+    subject: Subject,
+    caller: &'static Location<'static>,
+    message: String,
+) -> LockEvent {
+    let seq = SEQ.fetch_add(1, Ordering::Relaxed);
+    if seq == u64::MAX {
+        fallback::log!(Level::Warn, "[META] seq wrapped");
+    }
+    LockEvent {
+        seq,
+        timestamp: SystemTime::now(),
+        lock_id: meta.id,
+        name: meta.name().to_string(),
+        scope: meta.scope().path().to_owned(),
+        mode,
+        phase,
+        idx: subject.idx,
+        location: caller,
+        level,
+        message,
+        context: context::current_id(),
+        operation: operation::current().map(|id| id.to_string()),
+        thread: thread::current().id(),
+        task: tokio::task::try_id(),
+        task_name: TaskKey::current().name(),
+        waited: subject.waited,
+        held: subject.held,
+    }
+}
+
+// Counters wrap silently; the META line marks the discontinuity so offline
+// tools can stitch the sequence back together.
+pub(crate) fn idx_wrapped(meta: &LockMeta, caller: &'static Location<'static>) {
+    let message = format!("[META] idx wrapped ({})", meta.name());
+    let level = severity::severity_policy().anomaly;
+    emit_message(level, meta, Mode::Meta, Phase::Wrap, 0, caller, message);
+}
+
+pub(crate) fn untraced(meta: &LockMeta, caller: &'static Location<'static>) {
+    let first = UNTRACED_SITES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert((meta.id, caller));
+    if first {
+        let message = format!("[META] untraced access handed out ({})", meta.name());
+        let level = severity::severity_policy().anomaly;
+        emit_message(level, meta, Mode::Meta, Phase::Untraced, 0, caller, message);
+    }
+}
+
+pub(crate) fn counter_reset(meta: &LockMeta, previous: u64, caller: &'static Location<'static>) {
+    let message = format!(
+        "[LOCK] Counter reset ({}) after idx {}",
+        meta.name(),
+        Idx(previous)
+    );
+    let level = severity::severity_policy().anomaly;
+    emit_message(level, meta, Mode::Lock, Phase::Reset, 0, caller, message);
+}
+
+pub(crate) fn created(meta: &LockMeta, caller: &'static Location<'static>) {
+    let _ = meta.created_at.set(caller);
+    announce(meta);
+}
+
+// Replays Created for every registered lock, so a subscriber or logger attached
+// late can build its lock table. Sinks that already saw a lock are skipped.
+pub fn announce_all() {
+    for meta in registry::all_locks() {
+        announce(&meta);
+    }
+}
+
+// The log counts as told once the line got past its level, a subscriber once
+// the event was queued on its channel, and the binary sink once recorded.
+fn announce(meta: &LockMeta) {
+    let caller = match meta.created_at.get() {
+        Some(caller) => *caller,
+        None => return,
+    };
+    if meta.scope().is_muted() {
+        return;
+    }
+    let level = task_level::level_override(meta)
+        .unwrap_or_else(|| severity::severity_policy().acquire);
+    let verdict = Verdict::of(meta);
+    if !verdict.logging() || !verdict.allows(level) {
+        return;
+    }
+    let logged =
+        !meta.announced.load(Ordering::Relaxed) && fallback::enabled(module_path!(), level);
+    let subscribed = HAS_SUBSCRIBERS.load(Ordering::Acquire);
+    if !logged && !subscribed && !binary::enabled() {
+        return;
+    }
+
+    let mut message = format!("[META] Created ({}) type={}", meta.name(), meta.type_name);
+    if !meta.scope().path().is_empty() {
+        let _ = write!(message, " scope={}", meta.scope().path());
+    }
+    if let Some(level) = meta.level {
+        let _ = write!(message, " order_level={}", level);
+    }
+    if let Some(metadata) = meta.shown_metadata() {
+        let _ = write!(message, " metadata={}", metadata);
+    }
+    if let Some(threshold) = meta.slow_wait.get() {
+        let _ = write!(message, " slow_wait={:?}", threshold);
+    }
+    if let Some(threshold) = meta.slow_hold.get() {
+        let _ = write!(message, " slow_hold={:?}", threshold);
+    }
+    if let Some(enabled) = meta.backtrace.get() {
+        let _ = write!(message, " backtrace={}", enabled);
+    }
+    let delay = meta.acquire_delay.load(Ordering::Relaxed);
+    if delay != 0 {
+        let _ = write!(message, " acquire_delay={:?}", Duration::from_nanos(delay));
+    }
+    if meta.unmutated_writes_ok.load(Ordering::Relaxed) {
+        message.push_str(" allow_unmutated_writes=true");
+    }
+
+    if binary::enabled() {
+        binary::record(level, meta, Mode::Meta, Phase::Created, 0, caller);
+    }
+    if logged && !meta.announced.swap(true, Ordering::Relaxed) {
+        let fields = Fields {
+            lock_id: meta.id,
+            name: &meta.name(),
+            idx: 0,
+            kind: Mode::Meta,
+            phase: Phase::Created,
+            operation: None,
+        };
+        log_backtrace(level, fields, &message, caller, false);
+    }
+    if !subscribed {
+        return;
+    }
+    let event = build(
+        level,
+        meta,
+        Mode::Meta,
+        Phase::Created,
+        Subject::default(),
+        caller,
+        message,
+    );
+    batch::flush_current();
+    let mut subscribers = SUBSCRIBERS.lock().unwrap_or_else(|e| e.into_inner());
+    for subscriber in subscribers.iter_mut() {
+        if subscriber.announced.contains(&meta.id)
+            || subscriber.filter.as_ref().is_some_and(|filter| !filter(&event))
+        {
+            continue;
+        }
+        match subscriber.sender.try_send(event.clone()) {
+            Ok(()) => {
+                subscriber.announced.insert(meta.id);
+            }
+            Err(TrySendError::Full(_)) => {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+}
+
+pub(crate) fn first_use(meta: &LockMeta, caller: &'static Location<'static>) {
+    if meta.scope().is_muted() {
+        return;
+    }
+    let message = format!("[LOCK] First use ({}) type={}", meta.name(), meta.type_name);
+    let level = task_level::level_override(meta)
+        .unwrap_or_else(|| severity::severity_policy().acquire);
+    emit_message(level, meta, Mode::Lock, Phase::FirstUse, 0, caller, message);
+}
+
+pub(crate) fn destroyed(meta: &LockMeta, idx: u64, caller: &'static Location<'static>) {
+    if meta.scope().is_muted() {
+        return;
+    }
+    let stats = meta.stats.snapshot();
+    let message = format!(
+        "[LOCK] Destroyed ({}) after {} acquisitions, max hold {:?}",
+        meta.name(),
+        stats.acquisitions(),
+        stats.max_hold
+    );
+    let level = task_level::level_override(meta)
+        .unwrap_or_else(|| severity::severity_policy().release);
+    emit_message(level, meta, Mode::Lock, Phase::Destroy, idx, caller, message);
+}
+
+pub(crate) fn dropped(meta: &LockMeta, caller: &'static Location<'static>) {
+    if meta.scope().is_muted() {
+        return;
+    }
+    let stats = meta.stats.snapshot();
+    let message = format!(
+        "[META] Lock dropped ({}) lifetime={:?} acquisitions={} contended={} max_hold={:?} \
+         max_wait={:?} peak_readers={}",
+        meta.name(),
+        meta.created.elapsed(),
+        stats.acquisitions(),
+        stats.contended,
+        stats.max_hold,
+        stats.max_wait,
+        meta.availability.peak_readers()
+    );
+    let level = task_level::level_override(meta)
+        .unwrap_or_else(|| severity::severity_policy().release);
+    emit_message(level, meta, Mode::Meta, Phase::Dropped, 0, caller, message);
+}
+
+fn publish(event: LockEvent) {
+    if let Some(event) = batch::push(event) {
+        publish_batch(iter::once(event));
+    }
+}
+
+pub(crate) fn publish_batch(events: impl IntoIterator<Item = LockEvent>) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap_or_else(|e| e.into_inner());
+    for event in events {
+        subscribers.retain(|subscriber| {
+            if subscriber.filter.as_ref().is_some_and(|filter| !filter(&event)) {
+                return !subscriber.sender.is_closed();
+            }
+            match subscriber.sender.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    DROPPED.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                Err(TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+    HAS_SUBSCRIBERS.store(!subscribers.is_empty(), Ordering::Release);
+}
+
+// See `set_compact_callsites`.
+fn log_compact(
+    level: Level,
+    meta: &LockMeta,
+    fields: Fields<'_>,
+    message: &str,
+    caller: &'static Location<'static>,
+    backtrace: bool,
+) {
+    let target = module_path!();
+    if !fallback::enabled(target, level) {
+        return;
+    }
+    let got = fields.phase == Phase::Got;
+    let (id, first) = callsite::reference(target, level, meta.id, caller, got);
+    if !first {
+        fallback::write_fields(target, level, fields, format_args!("{}: #{}", message, id));
+        return;
+    }
+    let mut detail = format!(" type={}", meta.type_name);
+    if let Some(threshold) = meta.slow_wait.get() {
+        let _ = write!(detail, " slow_wait={:?}", threshold);
+    }
+    if let Some(threshold) = meta.slow_hold.get() {
+        let _ = write!(detail, " slow_hold={:?}", threshold);
+    }
+    let _ = write!(
+        detail,
+        ": {}:{}:{} #{}",
+        caller.file(),
+        caller.line(),
+        caller.column(),
+        id
+    );
+    if backtrace {
+        detail.push_str(&capture::capture());
+    }
+    fallback::write_fields(target, level, fields, format_args!("{}{}", message, detail));
+}
+
+fn log_backtrace(
+    level: Level,
+    fields: Fields<'_>,
+    message: &str,
+    caller: &'static Location<'static>,
+    backtrace: bool,
+) {
+    let target = module_path!();
+    if backtrace && fallback::enabled(target, level) {
+        fallback::write_fields(
+            target,
+            level,
+            fields,
+            format_args!(
+                "{}: {}:{}:{}{}",
+                message,
+                caller.file(),
+                caller.line(),
+                caller.column(),
+                capture::capture()
+            ),
+        );
+    } else {
+        fallback::write_fields(
+            target,
+            level,
+            fields,
+            format_args!("{}: {}:{}:{}", message, caller.file(), caller.line(), caller.column()),
+        );
+    }
+}
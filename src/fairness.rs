@@ -0,0 +1,119 @@
+use crate::{
+    clock::Instant,
+    event,
+    guard::Acquisition,
+    phase::{Mode, Phase},
+    registry::{self, LockMeta},
+    stats, READ,
+};
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+static AUDIT: AtomicBool = AtomicBool::new(false);
+
+// Off by default. When on, every contended grant is checked against the
+// waiters on the same lock that started before it; each one still queued was
+// overtaken and is reported as an inversion.
+pub fn set_fairness_audit(enabled: bool) {
+    AUDIT.store(enabled, Ordering::Relaxed);
+}
+
+pub(crate) fn enabled() -> bool {
+    AUDIT.load(Ordering::Relaxed)
+}
+
+// Keyed by (mode of the overtaken waiter, mode of the one granted first).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FairnessReport {
+    pub inversions: u64,
+    pub by_modes: Vec<(Mode, Mode, u64)>,
+    pub max_delta: Duration,
+}
+
+impl FairnessReport {
+    pub fn count(&self, overtaken: Mode, granted: Mode) -> u64 {
+        self.by_modes
+            .iter()
+            .find(|(a, b, _)| *a == overtaken && *b == granted)
+            .map_or(0, |(_, _, count)| *count)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Inversions {
+    by_modes: Mutex<BTreeMap<(Mode, Mode), u64>>,
+    max_delta: AtomicU64,
+}
+
+impl Inversions {
+    pub(crate) fn report(&self) -> FairnessReport {
+        let by_modes = self.by_modes.lock().unwrap_or_else(|e| e.into_inner());
+        FairnessReport {
+            inversions: by_modes.values().sum(),
+            by_modes: by_modes.iter().map(|(&(a, b), &n)| (a, b, n)).collect(),
+            max_delta: Duration::from_nanos(self.max_delta.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn record(&self, overtaken: Mode, granted: Mode, delta: Duration) {
+        *self
+            .by_modes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry((overtaken, granted))
+            .or_insert(0) += 1;
+        self.max_delta
+            .fetch_max(stats::duration_nanos(delta), Ordering::Relaxed);
+    }
+}
+
+// Called once the grant is timed, after this acquisition's own pending entry
+// is gone. Entry ids are handed out in order, so every entry below ours on the
+// same lock started waiting first. Readers queued together are woken together
+// and race to be polled, so a read overtaking a read is not counted.
+pub(crate) fn granted(
+    meta: &LockMeta,
+    acquisition: &Acquisition,
+    mode: Mode,
+    entry: u64,
+    since: Instant,
+) {
+    for earlier in registry::pending_before(meta.id, entry) {
+        if earlier.mode == READ && mode == READ {
+            continue;
+        }
+        let delta = since.duration_since(earlier.since);
+        meta.inversions.record(earlier.mode, mode, delta);
+        if !acquisition.sampled {
+            continue;
+        }
+        event::emit_message(
+            acquisition.level(meta, Phase::Inversion),
+            meta,
+            mode,
+            Phase::Inversion,
+            acquisition.idx,
+            acquisition.caller,
+            format!(
+                "[FAIRNESS] Inversion ({}) {} granted before {} by {} at {}:{}:{}, \
+                 which started waiting {:?} earlier",
+                meta.name(),
+                mode,
+                earlier.mode,
+                earlier.task,
+                earlier.caller.file(),
+                earlier.caller.line(),
+                earlier.caller.column(),
+                delta,
+            ),
+        );
+    }
+}
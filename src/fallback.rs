@@ -0,0 +1,161 @@
+use crate::{
+    config,
+    phase::{Mode, Phase},
+};
+
+use log::{Level, LevelFilter, Metadata};
+
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+static FALLBACK: AtomicUsize = AtomicUsize::new(Fallback::Stderr as usize);
+static LOGGER_SEEN: AtomicBool = AtomicBool::new(false);
+static NOTICED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fallback {
+    Stderr,
+    Silent,
+}
+
+pub fn set_fallback(fallback: Fallback) {
+    FALLBACK.store(fallback as usize, Ordering::Relaxed);
+}
+
+pub fn fallback() -> Fallback {
+    match FALLBACK.load(Ordering::Relaxed) {
+        1 => Fallback::Silent,
+        _ => Fallback::Stderr,
+    }
+}
+
+// Keeps the caller's module as the log target, so filters written against
+// the crate's modules still apply.
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {
+        $crate::fallback::write(module_path!(), $level, format_args!($($arg)+))
+    };
+}
+
+pub(crate) use log;
+
+// A logger cannot be uninstalled, so once one is seen the probe is skipped and
+// the only cost left is this load. Until then it is re-run on every record, so
+// a logger installed after the first event still takes over.
+fn logger_installed() -> bool {
+    if LOGGER_SEEN.load(Ordering::Relaxed) {
+        return true;
+    }
+    let metadata = Metadata::builder().level(Level::Error).build();
+    let installed = log::max_level() != LevelFilter::Off || log::logger().enabled(&metadata);
+    if installed {
+        LOGGER_SEEN.store(true, Ordering::Relaxed);
+    }
+    installed
+}
+
+pub(crate) fn enabled(target: &str, level: Level) -> bool {
+    if logger_installed() {
+        log::log_enabled!(target: target, level)
+    } else {
+        fallback() == Fallback::Stderr
+    }
+}
+
+// The most verbose level that could still be written, for skipping events
+// before they are built; the logger's own filter may be stricter.
+pub(crate) fn max_level() -> LevelFilter {
+    if logger_installed() {
+        log::max_level()
+    } else if fallback() == Fallback::Stderr {
+        LevelFilter::Trace
+    } else {
+        LevelFilter::Off
+    }
+}
+
+pub(crate) fn write(target: &str, level: Level, args: fmt::Arguments<'_>) {
+    if !config::logging_enabled() {
+        return;
+    }
+    if logger_installed() {
+        log::log!(target: target, level, "{}", args);
+        return;
+    }
+    write_stderr(target, level, args);
+}
+
+// Per-event fields attached as `log` key-values with the `kv` feature, so
+// structured loggers get them without parsing the message.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(feature = "kv"), allow(dead_code))]
+pub(crate) struct Fields<'a> {
+    pub(crate) lock_id: u64,
+    pub(crate) name: &'a str,
+    pub(crate) idx: u64,
+    pub(crate) kind: Mode,
+    pub(crate) phase: Phase,
+    pub(crate) operation: Option<&'a str>,
+}
+
+pub(crate) fn write_fields(
+    target: &str,
+    level: Level,
+    fields: Fields<'_>,
+    args: fmt::Arguments<'_>,
+) {
+    if !logger_installed() {
+        write_stderr(target, level, args);
+        return;
+    }
+    #[cfg(feature = "kv")]
+    log::log!(
+        target: target,
+        level,
+        lock_id = fields.lock_id,
+        name = fields.name,
+        idx = fields.idx,
+        kind = fields.kind.as_str(),
+        phase = fields.phase.as_str(),
+        operation = fields.operation;
+        "{}",
+        args
+    );
+    #[cfg(not(feature = "kv"))]
+    {
+        let _ = fields;
+        log::log!(target: target, level, "{}", args);
+    }
+}
+
+fn write_stderr(target: &str, level: Level, args: fmt::Arguments<'_>) {
+    if fallback() == Fallback::Silent {
+        return;
+    }
+    let notice = !NOTICED.swap(true, Ordering::Relaxed);
+    write_record(&mut io::stderr().lock(), notice, target, level, args);
+}
+
+// `eprintln!` panics when stderr is closed, and this runs in guard drops, so
+// write errors are dropped instead.
+pub(crate) fn write_record(
+    out: &mut impl Write,
+    notice: bool,
+    target: &str,
+    level: Level,
+    args: fmt::Arguments<'_>,
+) {
+    if notice {
+        let _ = writeln!(
+            out,
+            "{:<5} rwlock_trace: [META] no logger is installed, so lock events go to stderr; \
+             install a `log` logger to redirect them or call \
+             rwlock_trace::set_fallback(Fallback::Silent) to drop them",
+            Level::Info,
+        );
+    }
+    let _ = writeln!(out, "{:<5} {}: {}", level, target, args);
+}
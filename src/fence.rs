@@ -0,0 +1,80 @@
+use crate::{
+    clock::Instant,
+    guard::Acquisition,
+    phase::{Mode, Phase},
+    registry::LockMeta,
+};
+
+use tokio::sync::Barrier;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+};
+
+static ARMED: AtomicBool = AtomicBool::new(false);
+static BARRIERS: Mutex<Option<HashMap<String, Arc<Barrier>>>> = Mutex::new(None);
+
+fn barriers() -> MutexGuard<'static, Option<HashMap<String, Arc<Barrier>>>> {
+    BARRIERS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// For building interleavings in tests: every acquisition of the lock named
+// `name` waits on `barrier` right after its Got, still holding the lock, until
+// it is cleared. The name is the scoped one the lock logs with. Acquires that
+// cannot await, `try_*` and the blocking backend, pass straight through.
+pub fn set_acquire_barrier(name: impl Into<String>, barrier: Arc<Barrier>) {
+    barriers()
+        .get_or_insert_with(HashMap::new)
+        .insert(name.into(), barrier);
+    ARMED.store(true, Ordering::Relaxed);
+}
+
+pub fn clear_acquire_barrier(name: &str) -> bool {
+    let mut barriers = barriers();
+    let removed = barriers
+        .as_mut()
+        .is_some_and(|barriers| barriers.remove(name).is_some());
+    if barriers.as_ref().is_some_and(HashMap::is_empty) {
+        ARMED.store(false, Ordering::Relaxed);
+    }
+    removed
+}
+
+pub fn clear_acquire_barriers() {
+    *barriers() = None;
+    ARMED.store(false, Ordering::Relaxed);
+}
+
+pub(crate) async fn wait(meta: &LockMeta, acquisition: &Acquisition, mode: Mode) {
+    if !ARMED.load(Ordering::Relaxed) {
+        return;
+    }
+    let barrier = barriers()
+        .as_ref()
+        .and_then(|barriers| barriers.get(&*meta.name()).cloned());
+    let barrier = match barrier {
+        Some(barrier) => barrier,
+        None => return,
+    };
+
+    let caller = acquisition.caller;
+    acquisition.emit(
+        meta,
+        mode,
+        Phase::Fence,
+        caller,
+        " parked on acquire barrier",
+    );
+    let since = Instant::now();
+    let leader = barrier.wait().await.is_leader();
+    let details = format!(
+        " passed acquire barrier after {:?} leader={}",
+        since.elapsed(),
+        leader
+    );
+    acquisition.emit(meta, mode, Phase::Fence, caller, &details);
+}
@@ -0,0 +1,36 @@
+use crate::phase::Mode;
+
+use std::{fmt, panic::Location, time::Duration};
+
+#[derive(Debug, Clone)]
+pub struct HoldInfo<'a> {
+    pub lock_id: u64,
+    pub name: &'a str,
+    pub mode: Mode,
+    pub idx: u64,
+    pub held: Duration,
+    pub waited: Duration,
+    pub mutated: bool,
+    pub acquired_at: &'static Location<'static>,
+}
+
+pub(crate) struct ReleaseFilter(Box<dyn Fn(&HoldInfo<'_>) -> bool + Send + Sync>);
+
+impl ReleaseFilter {
+    pub(crate) fn new<F>(filter: F) -> Self
+    where
+        F: Fn(&HoldInfo<'_>) -> bool + Send + Sync + 'static,
+    {
+        Self(Box::new(filter))
+    }
+
+    pub(crate) fn should_log(&self, info: &HoldInfo<'_>) -> bool {
+        (self.0)(info)
+    }
+}
+
+impl fmt::Debug for ReleaseFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ReleaseFilter")
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{
+    capture,
+    phase::Mode,
+    redact::{Export, Redaction},
+    registry::LockMeta,
+    stats::duration_nanos,
+    verdict,
+};
+
+use std::{
+    collections::HashMap,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static HOLDS: Mutex<Option<HashMap<Stack, u64>>> = Mutex::new(None);
+
+pub fn set_folded_stacks(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    verdict::invalidate();
+}
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// Frames stay unsymbolized until export, so capturing on every acquire only
+// costs a stack walk.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Stack {
+    ips: Vec<usize>,
+    mode: Mode,
+    lock: String,
+}
+
+pub(crate) fn capture(meta: &LockMeta, mode: Mode) -> Stack {
+    Stack {
+        ips: capture::capture_ips(),
+        mode,
+        lock: meta.name().to_string(),
+    }
+}
+
+pub(crate) fn record(stack: Stack, held: Duration) {
+    let mut holds = HOLDS.lock().unwrap_or_else(|e| e.into_inner());
+    *holds
+        .get_or_insert_with(HashMap::new)
+        .entry(stack)
+        .or_default() += duration_nanos(held);
+}
+
+// Where the lock shows up in each stack. `Combined` only adds it as the leaf;
+// the others also add a root frame so the graph splits at the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoldedSplit {
+    Combined,
+    PerLock,
+    PerMode,
+}
+
+// One `frame;frame;frame nanos` line per distinct stack, in the format
+// inferno-flamegraph and flamegraph.pl read.
+pub fn export_folded<W: io::Write>(writer: W) -> io::Result<()> {
+    export_folded_split(writer, FoldedSplit::Combined)
+}
+
+pub fn export_folded_split<W: io::Write>(mut writer: W, split: FoldedSplit) -> io::Result<()> {
+    let holds: Vec<(Stack, u64)> = HOLDS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .flatten()
+        .map(|(stack, nanos)| (stack.clone(), *nanos))
+        .collect();
+
+    let mut names = HashMap::new();
+    let mut redaction = Redaction::new(Export::Folded);
+    let mut lines: HashMap<String, u64> = HashMap::new();
+    for (stack, nanos) in holds {
+        let lock = redaction.apply(&stack.lock);
+        let mut frames = Vec::new();
+        match split {
+            FoldedSplit::Combined => {}
+            FoldedSplit::PerLock => frames.push(format!("[{}]", lock)),
+            FoldedSplit::PerMode => frames.push(format!("[{}]", stack.mode)),
+        }
+        frames.extend(capture::symbolize(&stack.ips, &mut names));
+        frames.push(format!("[{} {}]", stack.mode, lock));
+
+        let frames: Vec<String> = frames.iter().map(|frame| frame.replace(';', ":")).collect();
+        *lines.entry(frames.join(";")).or_default() += nanos;
+    }
+
+    let mut lines: Vec<(String, u64)> = lines.into_iter().collect();
+    lines.sort();
+    for (stack, nanos) in lines {
+        writeln!(writer, "{} {}", stack, nanos)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,251 @@
+use crate::{
+    config::Idx, fallback, phase::Mode, registry, task::TaskKey, RwLock, RwLockWriteGuard, WRITE,
+};
+
+use log::Level;
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+static NEXT_GROUP: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    // The order a group was checked in, until its first acquisition, which
+    // starts on the same thread before anything else runs there, logs it.
+    static ORDER: RefCell<Option<(u64, String)>> = const { RefCell::new(None) };
+}
+
+pub fn next_group() -> u64 {
+    NEXT_GROUP.fetch_add(1, Ordering::Relaxed)
+}
+
+#[track_caller]
+pub fn check_group(group: u64, locks: &[(u64, Arc<str>, Mode)]) -> Vec<usize> {
+    check_group_at(group, locks, Location::caller())
+}
+
+// Returns the positions of `locks` in the order they must be acquired.
+pub(crate) fn check_group_at(
+    group: u64,
+    locks: &[(u64, Arc<str>, Mode)],
+    caller: &'static Location<'static>,
+) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..locks.len()).collect();
+    order.sort_by_key(|position| locks[*position].0);
+
+    for pair in order.windows(2) {
+        let (first, second) = (&locks[pair[0]], &locks[pair[1]]);
+        if first.0 == second.0 && (first.2 == WRITE || second.2 == WRITE) {
+            panic!(
+                "[GROUP] group={} requests ({}) twice with a write, which would deadlock: {}:{}:{}",
+                group,
+                first.1,
+                caller.file(),
+                caller.line(),
+                caller.column(),
+            );
+        }
+    }
+
+    let names: Vec<String> = order
+        .iter()
+        .map(|position| format!("{} {}", locks[*position].2, locks[*position].1))
+        .collect();
+    let names = format!(" order=[{}]", names.join(", "));
+    ORDER.with(|pending| *pending.borrow_mut() = Some((group, names)));
+
+    if let Some(lowest) = order.first().map(|position| locks[*position].0) {
+        for entry in registry::held_by(TaskKey::current()) {
+            if entry.lock_id > lowest {
+                fallback::log!(
+                    Level::Warn,
+                    "[GROUP] group={} acquired while holding ({}:{}), which orders after \
+                     locks in the group: {}:{}:{} (held since {}:{}:{})",
+                    group,
+                    entry.name,
+                    Idx(entry.idx),
+                    caller.file(),
+                    caller.line(),
+                    caller.column(),
+                    entry.caller.file(),
+                    entry.caller.line(),
+                    entry.caller.column(),
+                );
+            }
+        }
+    }
+
+    order
+}
+
+// For the group's first Acquire, which carries its order.
+pub(crate) fn take_order(group: u64) -> Option<String> {
+    ORDER.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        match pending.take() {
+            Some((pending_group, names)) if pending_group == group => Some(names),
+            other => {
+                *pending = other;
+                None
+            }
+        }
+    })
+}
+
+#[track_caller]
+pub fn acquire_write_pair<'a, A, B>(
+    a: &'a RwLock<A>,
+    b: &'a RwLock<B>,
+) -> impl Future<Output = (RwLockWriteGuard<'a, A>, RwLockWriteGuard<'a, B>)> {
+    let caller = Location::caller();
+
+    async move {
+        let group = next_group();
+        let order = check_group_at(
+            group,
+            &[
+                (a.ordering_key(), a.name(), WRITE),
+                (b.ordering_key(), b.name(), WRITE),
+            ],
+            caller,
+        );
+
+        if order[0] == 0 {
+            let a = a.write_grouped(group, caller).await;
+            let b = b.write_grouped(group, caller).await;
+            (a, b)
+        } else {
+            let b = b.write_grouped(group, caller).await;
+            let a = a.write_grouped(group, caller).await;
+            (a, b)
+        }
+    }
+}
+
+// `acquire_write_pair` for any number of same-typed locks: each is
+// write-locked in the group's canonical order and the guards come back in
+// input order, one per lock listed. Listing a lock twice panics, as any group
+// asking for a lock twice with a write does.
+#[track_caller]
+pub fn write_all<'a, T>(
+    locks: &[&'a RwLock<T>],
+) -> impl Future<Output = Vec<RwLockWriteGuard<'a, T>>> {
+    let caller = Location::caller();
+    let locks = locks.to_vec();
+
+    async move {
+        let group = next_group();
+        let entries: Vec<(u64, Arc<str>, Mode)> = locks
+            .iter()
+            .map(|lock| (lock.ordering_key(), lock.name(), WRITE))
+            .collect();
+        let mut guards: Vec<Option<RwLockWriteGuard<'a, T>>> = locks.iter().map(|_| None).collect();
+        for position in check_group_at(group, &entries, caller) {
+            guards[position] = Some(locks[position].write_grouped(group, caller).await);
+        }
+        guards.into_iter().flatten().collect()
+    }
+}
+
+#[macro_export]
+macro_rules! acquire_ordered {
+    ($($mode:ident $lock:expr),+ $(,)?) => {
+        $crate::__acquire_ordered!(@bind [] $($mode ($lock))+)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __acquire_ordered {
+    (@bind [$($done:tt)*] $mode:ident ($lock:expr) $($rest:tt)*) => {
+        $crate::__acquire_ordered!(@bind [$($done)* ($mode lock slot position ($lock))] $($rest)*)
+    };
+    (@bind [$(($mode:ident $lock_ref:ident $slot:ident $position:ident ($lock:expr)))+]) => {
+        async {
+            let group = $crate::__private::next_group();
+            let mut locks = ::std::vec::Vec::new();
+            $(
+                let $lock_ref = &$lock;
+                let $position = locks.len();
+                locks.push((
+                    $lock_ref.ordering_key(),
+                    $lock_ref.name(),
+                    $crate::__acquire_ordered!(@mode $mode),
+                ));
+                let mut $slot = ::std::option::Option::None;
+            )+
+            for position in $crate::__private::check_group(group, &locks) {
+                $(
+                    if position == $position {
+                        $slot = ::std::option::Option::Some(
+                            $crate::__acquire_ordered!(@acquire $mode $lock_ref group),
+                        );
+                    }
+                )+
+            }
+            ($($slot.unwrap(),)+)
+        }
+    };
+    (@mode read) => { $crate::Mode::Read };
+    (@mode write) => { $crate::Mode::Write };
+    (@acquire read $lock:ident $group:ident) => { $lock.read_in_group($group).await };
+    (@acquire write $lock:ident $group:ident) => { $lock.write_in_group($group).await };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{acquire_write_pair, write_all};
+    use crate::{scope, Phase, RwLock};
+
+    #[tokio::test]
+    async fn the_group_order_rides_on_the_first_acquire() {
+        let scope = scope("group-order");
+        let a = scope.rwlock_named(0u8, "group-order-a");
+        let b = scope.rwlock_named(0u8, "group-order-b");
+        let ids = [a.id(), b.id()];
+        let mut events = crate::subscribe_where(move |event| {
+            ids.contains(&event.lock_id) && event.phase == Phase::Acquire
+        });
+
+        drop(a.write().await);
+        let plain = events.try_recv().unwrap();
+        drop(acquire_write_pair(&b, &a).await);
+        let first = events.try_recv().unwrap();
+        let second = events.try_recv().unwrap();
+        assert_eq!(first.lock_id, a.id());
+        assert_eq!(first.level, plain.level);
+        assert!(first.message.ends_with(
+            " order=[WRITE group-order/group-order-a, WRITE group-order/group-order-b]"
+        ));
+        assert_eq!(second.lock_id, b.id());
+        assert!(!second.message.contains("order="));
+
+        scope.set_muted(true);
+        drop(acquire_write_pair(&a, &b).await);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn write_all_returns_guards_in_input_order() {
+        let a = RwLock::new_named('a', "group-write-all-a");
+        let b = RwLock::new_named('b', "group-write-all-b");
+        let c = RwLock::new_named('c', "group-write-all-c");
+        let guards = write_all(&[&c, &a, &b]).await;
+        let values: Vec<char> = guards.iter().map(|guard| ***guard).collect();
+        assert_eq!(values, ['c', 'a', 'b']);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "twice with a write")]
+    async fn write_all_rejects_a_lock_listed_twice() {
+        let a = RwLock::new_named(0u8, "group-write-all-twice");
+        drop(write_all(&[&a, &a]).await);
+    }
+}
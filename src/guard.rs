@@ -0,0 +1,2032 @@
+use crate::{
+    access, background, breadcrumb,
+    clock::Instant,
+    config::{self, Bytes, Idx},
+    context, event,
+    event::{LockEvent, Subject},
+    fairness, fallback,
+    filter::HoldInfo,
+    folded, group, metrics_facade, operation, order,
+    otel::{self, HoldSpan},
+    phase::{Mode, Phase},
+    polls::{self, PollEpoch, Suspension},
+    registry::{self, HeldEntry, LockMeta, PendingEntry},
+    severity, stall,
+    stats::Waiting,
+    task::{Origin, TaskKey},
+    task_level,
+    traced::RawAsyncRwLock,
+    transaction,
+    value::ValueHooks,
+    verdict::Verdict,
+    READ, WRITE,
+};
+
+use log::Level;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use std::{
+    any,
+    fmt::{self, Write},
+    future::Future,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    panic::Location,
+    ptr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+#[derive(Debug)]
+pub(crate) struct Acquisition {
+    pub(crate) idx: u64,
+    pub(crate) caller: &'static Location<'static>,
+    pub(crate) waited: Duration,
+    pub(crate) contended: bool,
+    pub(crate) reason: Option<String>,
+    pub(crate) group: Option<u64>,
+    pub(crate) transaction: Option<u64>,
+    pub(crate) context: Option<u64>,
+    pub(crate) operation: Option<Arc<str>>,
+    // The thread that completed the acquisition; until then, the one that
+    // started it.
+    pub(crate) thread: ThreadId,
+    pub(crate) scoped: bool,
+    pub(crate) yields: Option<u32>,
+    pub(crate) queued_behind: Option<u64>,
+    pub(crate) pending: Option<(u64, Instant)>,
+    pub(crate) held: Option<Duration>,
+    pub(crate) stack: Option<folded::Stack>,
+    pub(crate) previously_poisoned: bool,
+    // Taken by the test utilities rather than the code under test.
+    pub(crate) simulated: bool,
+    pub(crate) sampled: bool,
+    pub(crate) permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Acquisition {
+    pub(crate) fn new(caller: &'static Location<'static>) -> Self {
+        Self {
+            idx: 0,
+            caller,
+            waited: Duration::ZERO,
+            contended: false,
+            reason: None,
+            group: None,
+            transaction: transaction::current(),
+            context: context::current_id(),
+            operation: operation::current(),
+            thread: thread::current().id(),
+            scoped: false,
+            yields: None,
+            queued_behind: None,
+            pending: None,
+            held: None,
+            stack: None,
+            previously_poisoned: false,
+            simulated: false,
+            sampled: true,
+            permit: None,
+        }
+    }
+
+    pub(crate) fn begin(mut self, meta: &LockMeta, idx: &AtomicU64, mode: Mode) -> Self {
+        let verdict = Verdict::of(meta);
+        // Every acquisition of a lock bumps the one counter, so with logging
+        // off, when no idx can be seen, it is left alone and the acquisition
+        // keeps 0, as do its registry entries. Audited ones are always seen.
+        if verdict.logging() || self.audited() {
+            self.idx = idx.fetch_add(1, Ordering::SeqCst).wrapping_add(1);
+            if self.idx == 0 {
+                event::idx_wrapped(meta, self.caller);
+            }
+        }
+        // Read first, so only a lock's first acquisitions write the flag.
+        if !meta.used.load(Ordering::Relaxed) && !meta.used.swap(true, Ordering::Relaxed) {
+            event::first_use(meta, self.caller);
+        }
+        self.sampled = self.audited() || verdict.sample(meta);
+        self.previously_poisoned = meta.poisoned.load(Ordering::Relaxed);
+        if verdict.folded() {
+            self.stack = Some(folded::capture(meta, mode));
+        }
+        let mut details = String::new();
+        if verdict.type_name() {
+            let _ = write!(details, " type={}", meta.type_name);
+        }
+        if let Some(breadcrumb) = breadcrumb::take() {
+            details.push_str(&breadcrumb);
+        }
+        if let Some(order) = self.group.and_then(group::take_order) {
+            details.push_str(&order);
+        }
+        self.emit(meta, mode, Phase::Acquire, self.caller, &details);
+        order::check(meta, mode, self.idx, self.caller);
+        access::check(meta, mode, self.idx, self.caller);
+        // Its hint only goes to the log.
+        if verdict.logging() {
+            meta.loops.record(|| meta.name(), self.caller);
+        }
+        self
+    }
+
+    pub(crate) fn record_wait(
+        &mut self,
+        meta: &LockMeta,
+        mode: Mode,
+        start: Instant,
+        contended: bool,
+    ) {
+        self.waited = start.elapsed();
+        self.contended = contended;
+        meta.stats.record_got(mode == WRITE, contended, self.waited);
+        if Verdict::of(meta).logging() {
+            meta.writer_waits
+                .record(|| meta.name(), mode == WRITE, self.waited);
+        }
+        metrics_facade::on_got(meta, mode, self.waited);
+        if let Some((entry, since)) = self.pending {
+            if fairness::enabled() {
+                fairness::granted(meta, self, mode, entry, since);
+            }
+        }
+    }
+
+    pub(crate) fn start_waiting<'a>(&mut self, meta: &'a LockMeta, mode: Mode) -> Waiting<'a> {
+        let since = Instant::now();
+        let entry = registry::register_pending(PendingEntry {
+            lock_id: meta.id,
+            mode,
+            idx: self.idx,
+            caller: self.caller,
+            since,
+            task: TaskKey::current(),
+            operation: self.operation.clone(),
+        });
+        self.pending = Some((entry, since));
+        let waiting = meta.stats.start_waiting(entry);
+        self.queued_behind = Some(waiting.ahead());
+        if mode == WRITE {
+            let holders = registry::held_on(meta.id);
+            meta.stats
+                .record_contention(self.caller, holders.iter().map(|entry| entry.caller));
+            if self.sampled {
+                self.queued(meta, waiting.ahead());
+            }
+        }
+        waiting
+    }
+
+    // The backends' wait queues are opaque, so this counts the waiters that
+    // registered first. Ones that gave up or slipped in between make it an
+    // estimate; the holders it must also outlast are reported beside it.
+    fn queued(&self, meta: &LockMeta, ahead: u64) {
+        event::emit_message(
+            self.level(meta, Phase::Acquire),
+            meta,
+            WRITE,
+            Phase::Waiting,
+            self.idx,
+            self.caller,
+            format!(
+                "[WRITE] Acquire ({}:{}) queued behind ~{} holders={}",
+                meta.name(),
+                Idx(self.idx),
+                ahead,
+                meta.availability.holders()
+            ),
+        );
+    }
+
+    // Runs before the wait is timed, so the delay never counts as contention.
+    pub(crate) async fn throttle(&self, meta: &LockMeta, mode: Mode) {
+        let delay = Duration::from_nanos(meta.acquire_delay.load(Ordering::Relaxed));
+        if delay.is_zero() {
+            return;
+        }
+        if self.sampled {
+            event::emit_message(
+                self.level(meta, Phase::Throttle),
+                meta,
+                mode,
+                Phase::Throttle,
+                self.idx,
+                self.caller,
+                format!("[THROTTLE] ({}) delayed {:?}", meta.name(), delay),
+            );
+        }
+        background::sleep(delay).await;
+    }
+
+    // Taken with `write_audited`: its events skip muting, sampling and the
+    // directives, so the record of the write is never lost to them.
+    pub(crate) fn audited(&self) -> bool {
+        self.reason.is_some()
+    }
+
+    pub(crate) fn level(&self, meta: &LockMeta, phase: Phase) -> Level {
+        let mut policy = severity::severity_policy();
+        if let Some(threshold) = meta.slow_wait.get() {
+            policy.wait_warn = Some(*threshold);
+        }
+        if let Some(threshold) = meta.slow_hold.get() {
+            policy.hold_warn = Some(*threshold);
+        }
+        let base = if self.reason.is_some() {
+            config::audit_level()
+        } else {
+            task_level::level_override(meta).unwrap_or_else(|| policy.base(phase))
+        };
+        policy.escalate(base, phase, self.waited, self.held)
+    }
+
+    pub(crate) fn annotations(&self) -> String {
+        let mut out = String::new();
+        if let Some(reason) = &self.reason {
+            let _ = write!(out, " reason={:?}", reason);
+        }
+        if let Some(group) = self.group {
+            let _ = write!(out, " group={}", group);
+        }
+        if let Some(transaction) = self.transaction {
+            let _ = write!(out, " txn={}", transaction);
+        }
+        if let Some(context) = self.context {
+            let _ = write!(out, " ctx={}", context);
+        }
+        if let Some(operation) = &self.operation {
+            let _ = write!(out, " op={}", operation);
+        }
+        let _ = write!(out, " thread={:?}", self.thread);
+        // `held` is only set once a scoped section ends, so its Release
+        // reports the section as a single timed unit.
+        if self.scoped {
+            out.push_str(" scoped=true");
+            if let Some(held) = self.held {
+                let _ = write!(out, " held={:?}", held);
+            }
+        }
+        if let Some(yields) = self.yields {
+            let _ = write!(out, " yields={}", yields);
+        }
+        if let Some(queued_behind) = self.queued_behind {
+            let _ = write!(out, " queued_behind={}", queued_behind);
+        }
+        if self.previously_poisoned {
+            out.push_str(" previously_poisoned=true");
+        }
+        if self.simulated {
+            out.push_str(" simulated=true");
+        }
+        out
+    }
+
+    pub(crate) fn emit(
+        &self,
+        meta: &LockMeta,
+        mode: Mode,
+        phase: Phase,
+        caller: &'static Location<'static>,
+        details: &str,
+    ) {
+        if !self.audited() && (!self.sampled || Verdict::of(meta).silent(meta)) {
+            return;
+        }
+        self.emit_at(self.level(meta, phase), meta, mode, phase, caller, details);
+    }
+
+    // Like `emit` at the acquiring call site, at a level the caller raised.
+    fn emit_at(
+        &self,
+        level: Level,
+        meta: &LockMeta,
+        mode: Mode,
+        phase: Phase,
+        caller: &'static Location<'static>,
+        details: &str,
+    ) {
+        let audited = self.audited();
+        if !audited && (!self.sampled || !Verdict::of(meta).reaches(meta, level)) {
+            return;
+        }
+        let _operation = operation::enter(self.operation.clone());
+        let details = format!("{}{}", self.annotations(), details);
+        let subject = self.subject(phase);
+        if audited {
+            event::emit_audited(level, meta, mode, phase, subject, caller, &details);
+        } else {
+            event::emit(level, meta, mode, phase, subject, caller, &details);
+        }
+    }
+
+    // Acquire is raised before the wait is timed.
+    fn subject(&self, phase: Phase) -> Subject {
+        Subject {
+            idx: self.idx,
+            waited: (phase != Phase::Acquire).then_some(self.waited),
+            held: self.held,
+        }
+    }
+
+    fn should_log_release(
+        &self,
+        meta: &LockMeta,
+        mode: Mode,
+        held: Duration,
+        mutated: bool,
+    ) -> bool {
+        if self.audited() {
+            return true;
+        }
+        match meta.release_filter.get() {
+            Some(filter) => filter.should_log(&HoldInfo {
+                lock_id: meta.id,
+                name: &meta.name(),
+                mode,
+                idx: self.idx,
+                held,
+                waited: self.waited,
+                mutated,
+                acquired_at: self.caller,
+            }),
+            None => true,
+        }
+    }
+
+    fn register(&self, meta: &LockMeta, mode: Mode, since: Instant) -> u64 {
+        registry::register(HeldEntry {
+            lock_id: meta.id,
+            name: meta.name().to_string(),
+            mode,
+            idx: self.idx,
+            caller: self.caller,
+            since,
+            task: TaskKey::current(),
+            level: meta.level,
+            reason: self.reason.clone(),
+            operation: self.operation.clone(),
+            handoff: None,
+            inherited: None,
+            thread: self.thread,
+        })
+    }
+}
+
+// What a read guard needs to lock its source again. Borrowed piecewise rather
+// than as `&TracedLock<T, L>` so guards keep working for unsized `T`.
+pub(crate) struct Source<'a, T: ?Sized, L: ?Sized> {
+    pub(crate) lock: &'a L,
+    pub(crate) meta: &'a Arc<LockMeta>,
+    pub(crate) idx: &'a AtomicU64,
+    pub(crate) hooks: &'a ValueHooks<T>,
+    pub(crate) readers: Option<&'a Arc<Semaphore>>,
+}
+
+impl<T: ?Sized, L: ?Sized> Clone for Source<'_, T, L> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized, L: ?Sized> Copy for Source<'_, T, L> {}
+
+impl<T: ?Sized, L: ?Sized> fmt::Debug for Source<'_, T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Source")
+            .field("name", &self.meta.name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> Source<'a, T, L> {
+    pub(crate) fn begin(&self, mode: Mode, acquisition: Acquisition) -> Acquisition {
+        acquisition.begin(self.meta, self.idx, mode)
+    }
+
+    // Writers never take a permit, so a full reader cap does not block them.
+    pub(crate) async fn reader_permit(&self, acquisition: &mut Acquisition) -> bool {
+        let readers = match self.readers {
+            Some(readers) => readers,
+            None => return false,
+        };
+        let (permit, contended) = match readers.clone().try_acquire_owned() {
+            Ok(permit) => (permit, false),
+            Err(_) => {
+                if acquisition.sampled {
+                    event::emit_message(
+                        acquisition.level(self.meta, Phase::Acquire),
+                        self.meta,
+                        READ,
+                        Phase::Waiting,
+                        acquisition.idx,
+                        acquisition.caller,
+                        format!(
+                            "[READ] Waiting for permit ({}:{})",
+                            self.meta.name(),
+                            Idx(acquisition.idx)
+                        ),
+                    );
+                }
+                let _waiting = acquisition.start_waiting(self.meta, READ);
+                let permit = readers
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("reader semaphore is never closed");
+                (permit, true)
+            }
+        };
+        acquisition.permit = Some(permit);
+        contended
+    }
+
+    pub(crate) async fn acquire_read(
+        self,
+        mut acquisition: Acquisition,
+    ) -> RwLockReadGuard<'a, T, L> {
+        acquisition.throttle(self.meta, READ).await;
+        let start = Instant::now();
+        let permit_contended = self.reader_permit(&mut acquisition).await;
+        let (guard, contended) = match self.lock.try_raw_read() {
+            Some(guard) => (guard, permit_contended),
+            None => {
+                let _waiting = acquisition.start_waiting(self.meta, READ);
+                (self.lock.raw_read().await, true)
+            }
+        };
+        acquisition.record_wait(self.meta, READ, start, contended);
+
+        let guard = RwLockReadGuard::acquired(guard, self.meta.clone(), acquisition, Some(self));
+        guard.state.fence(READ).await;
+        guard
+    }
+
+    pub(crate) async fn acquire_write(
+        self,
+        mut acquisition: Acquisition,
+    ) -> RwLockWriteGuard<'a, T, L> {
+        acquisition.throttle(self.meta, WRITE).await;
+        let start = Instant::now();
+        let (guard, contended) = match self.lock.try_raw_write() {
+            Some(guard) => (guard, false),
+            None => {
+                let _waiting = acquisition.start_waiting(self.meta, WRITE);
+                (self.lock.raw_write().await, true)
+            }
+        };
+        acquisition.record_wait(self.meta, WRITE, start, contended);
+
+        let guard =
+            RwLockWriteGuard::acquired(guard, self.meta.clone(), acquisition, Some(self.hooks));
+        guard.state.fence(WRITE).await;
+        guard
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AcquireTiming {
+    pub waited: Duration,
+}
+
+// `queued_behind` is the number of waiters already queued when this one
+// enqueued; None when the lock was taken without waiting.
+#[derive(Debug, Clone, Copy)]
+pub struct AcquireReport {
+    pub idx: u64,
+    pub waited: Duration,
+    pub contended: bool,
+    pub queued_behind: Option<u64>,
+}
+
+#[derive(Debug)]
+pub(crate) struct GuardState {
+    pub(crate) meta: Arc<LockMeta>,
+    pub(crate) acquisition: Acquisition,
+    held: u64,
+    // Cleared when the registry entry turns out to have been purged, so a
+    // resync's counts are not decremented a second time.
+    counted: bool,
+    got_at: Instant,
+    epoch: Option<PollEpoch>,
+    reader: bool,
+    check_mutation: bool,
+    span: HoldSpan,
+    release_event: Option<LockEvent>,
+    origin: Origin,
+    handoff: Option<String>,
+    blocking_watch: Option<Instant>,
+    // Only taken for write guards polled inside `track_polls` with the hold
+    // breakdown on.
+    suspension: Option<Suspension>,
+    work: Option<Work>,
+    // What a `with_read_async` section spent suspended under the guard.
+    awaited: Option<Suspension>,
+    // Set by the first map: the guard's place under its acquisition, and the
+    // call site of the narrowest map.
+    path: Option<String>,
+    narrowed_at: Option<&'static Location<'static>>,
+    // Set by `release_with_reason`, with where it was called.
+    reason: Option<(String, &'static Location<'static>)>,
+    // Where `hold_during` was called, for a guard held across an await on
+    // purpose.
+    held_during: Option<&'static Location<'static>>,
+}
+
+// What `record_work` reported for one hold; a note repeated across calls is
+// kept once.
+#[derive(Debug, Default)]
+struct Work {
+    bytes: u64,
+    notes: Vec<String>,
+}
+
+impl GuardState {
+    pub(crate) fn timing(&self) -> AcquireTiming {
+        AcquireTiming {
+            waited: self.acquisition.waited,
+        }
+    }
+
+    pub(crate) fn acquire_report(&self) -> AcquireReport {
+        AcquireReport {
+            idx: self.acquisition.idx,
+            waited: self.acquisition.waited,
+            contended: self.acquisition.contended,
+            queued_behind: self.acquisition.queued_behind,
+        }
+    }
+
+    // Shared by every guard's Debug impl; the payload is never printed, so a
+    // guard is Debug whatever it protects.
+    pub(crate) fn fmt_guard(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        guard: &str,
+        mode: Mode,
+    ) -> fmt::Result {
+        let caller = self.acquisition.caller;
+        f.debug_struct(guard)
+            .field("name", &self.meta.name())
+            .field("mode", &mode)
+            .field("idx", &format_args!("{}", Idx(self.acquisition.idx)))
+            .field(
+                "acquired_at",
+                &format_args!("{}:{}:{}", caller.file(), caller.line(), caller.column()),
+            )
+            .field("held_for", &self.got_at.elapsed())
+            .finish()
+    }
+
+    // For guards that cannot see mutable access, and for call sites that have
+    // opted out of the check.
+    pub(crate) fn skip_mutation_check(&mut self) {
+        self.check_mutation = false;
+    }
+
+    // The mapped guard keeps this state, so its release still carries the
+    // original idx. Each map narrows the path by a level, `17` to `17.1` to
+    // `17.1.1`: a map consumes its guard, so a level has one child, and a
+    // failed `try_map` hands the guard back at the level it was.
+    pub(crate) fn mapped<U: ?Sized>(
+        &mut self,
+        mode: Mode,
+        phase: Phase,
+        caller: &'static Location<'static>,
+    ) {
+        let details = if phase == Phase::Map {
+            let path = format!("{}.1", self.parent_path());
+            let details = format!(" -> {} path={}", any::type_name::<U>(), path);
+            self.path = Some(path);
+            self.narrowed_at = Some(caller);
+            details
+        } else {
+            format!(" -> {} path={}", any::type_name::<U>(), self.parent_path())
+        };
+        self.acquisition
+            .emit(&self.meta, mode, phase, caller, &details);
+    }
+
+    // None until the guard is mapped.
+    pub(crate) fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    fn parent_path(&self) -> String {
+        match &self.path {
+            Some(path) => path.clone(),
+            None => Idx(self.acquisition.idx).to_string(),
+        }
+    }
+
+    // With `test-util`, waits on the acquire barrier set for this lock, if
+    // any, before the guard reaches the caller.
+    pub(crate) async fn fence(&self, mode: Mode) {
+        #[cfg(feature = "test-util")]
+        crate::fence::wait(&self.meta, &self.acquisition, mode).await;
+        #[cfg(not(feature = "test-util"))]
+        let _ = mode;
+    }
+
+    pub(crate) fn record_work(&mut self, bytes: usize, note: &str) {
+        let work = self.work.get_or_insert_with(Work::default);
+        work.bytes = work.bytes.saturating_add(bytes as u64);
+        if !note.is_empty() && !work.notes.iter().any(|known| known == note) {
+            work.notes.push(note.to_owned());
+        }
+    }
+
+    // From here to release, the hold is checked against the stalls the
+    // stall detector records.
+    pub(crate) fn watch_blocking(&mut self) {
+        self.blocking_watch = Some(Instant::now());
+    }
+
+    // Called just before the guard is dropped, so the release reports it. A
+    // section ended early with a reason is not also reported as unmutated.
+    pub(crate) fn give_reason(&mut self, reason: &str, caller: &'static Location<'static>) {
+        self.reason = Some((reason.to_owned(), caller));
+        self.check_mutation = false;
+    }
+
+    // For `hold_during`, once its future is done and just before the guard is
+    // dropped: the release is logged at `caller`, with what the future spent
+    // suspended, and not reported as held across an await.
+    pub(crate) fn held_during(&mut self, awaited: Suspension, caller: &'static Location<'static>) {
+        self.awaited = Some(awaited);
+        self.held_during = Some(caller);
+    }
+
+    pub(crate) fn got(meta: Arc<LockMeta>, mut acquisition: Acquisition, mode: Mode) -> Self {
+        let got_at = Instant::now();
+        acquisition.thread = thread::current().id();
+        let held = acquisition.register(&meta, mode, got_at);
+        meta.availability.acquired();
+        let reader = mode == READ;
+        let readers = if reader {
+            Some(meta.availability.reader_acquired())
+        } else {
+            None
+        };
+        // A writer let in by the last of a run of read releases paid for the
+        // run: what it waited on was readers draining, not one holder.
+        let drained = if reader {
+            None
+        } else {
+            meta.availability
+                .writer_acquired(got_at)
+                .filter(|_| acquisition.contended)
+        };
+        // A waiter was let in by some release; an uncontended acquire found
+        // the lock free.
+        let handoff = if acquisition.contended {
+            " handoff=true"
+        } else {
+            ""
+        };
+        let threshold = meta.reader_warn.load(Ordering::Relaxed);
+        match readers {
+            Some(readers) if threshold != 0 && readers > threshold => {
+                let details = format!(
+                    " active_readers={}{} WARNING {} concurrent readers",
+                    readers, handoff, readers
+                );
+                let level = acquisition.level(&meta, Phase::Got).min(Level::Warn);
+                let caller = acquisition.caller;
+                acquisition.emit_at(level, &meta, mode, Phase::Got, caller, &details);
+            }
+            Some(readers) => {
+                let details = format!(" active_readers={}{}", readers, handoff);
+                acquisition.emit(&meta, mode, Phase::Got, acquisition.caller, &details);
+            }
+            None => {
+                let mut details = String::new();
+                if let Some((readers, took)) = drained {
+                    let _ = write!(details, " after draining {} readers in {:?}", readers, took);
+                }
+                details.push_str(handoff);
+                acquisition.emit(&meta, mode, Phase::Got, acquisition.caller, &details);
+            }
+        }
+        let span = otel::on_got(
+            &meta,
+            mode,
+            acquisition.idx,
+            acquisition.waited,
+            acquisition.operation.as_deref(),
+        );
+        Self {
+            meta,
+            acquisition,
+            held,
+            counted: true,
+            got_at,
+            epoch: PollEpoch::current(),
+            reader,
+            check_mutation: true,
+            span,
+            release_event: None,
+            origin: Origin::current(),
+            handoff: None,
+            blocking_watch: None,
+            suspension: if reader { None } else { Suspension::current() },
+            work: None,
+            awaited: None,
+            path: None,
+            narrowed_at: None,
+            reason: None,
+            held_during: None,
+        }
+    }
+
+    // The guard stays registered while in transit, but no task counts as
+    // holding it until `resume`.
+    pub(crate) fn hand_off(&mut self, mode: Mode, label: &str, caller: &'static Location<'static>) {
+        registry::mark_handoff(self.held, label);
+        self.meta.stats.record_handoff();
+        let details = format!(" label={:?} from {}", label, self.origin.task());
+        self.acquisition
+            .emit(&self.meta, mode, Phase::Handoff, caller, &details);
+        self.handoff = Some(label.to_owned());
+    }
+
+    pub(crate) fn resume(
+        &mut self,
+        mode: Mode,
+        after: Duration,
+        caller: &'static Location<'static>,
+    ) {
+        let now = Origin::current();
+        registry::resume(self.held, now.task());
+        let details = format!(
+            " label={:?} from {} to {} after {:?}",
+            self.handoff.as_deref().unwrap_or_default(),
+            self.origin.task(),
+            now.task(),
+            after,
+        );
+        self.acquisition
+            .emit(&self.meta, mode, Phase::Resume, caller, &details);
+        // Released from here on, so the handoff is not also a migration.
+        self.origin = now;
+    }
+
+    pub(crate) fn release<F>(
+        &mut self,
+        mode: Mode,
+        caller: &'static Location<'static>,
+        mutated: bool,
+        details: F,
+    ) where
+        F: FnOnce() -> String,
+    {
+        let _operation = operation::enter(self.acquisition.operation.clone());
+        // A scoped section drops its guard inside the helper, so the section's
+        // own call site says more than the drop location.
+        let caller = if self.acquisition.scoped {
+            self.acquisition.caller
+        } else {
+            caller
+        };
+        let caller = self.held_during.unwrap_or(caller);
+        // The drop runs inside `release_with_reason`, so its caller is kept.
+        let (caller, reason) = match self.reason.take() {
+            Some((reason, at)) => (at, Some(reason)),
+            None => (caller, None),
+        };
+        match registry::unregister(self.held) {
+            Some(Some(newer)) => self.report_out_of_order(mode, caller, &newer),
+            Some(None) => {}
+            None => self.counted = false,
+        }
+        breadcrumb::released(&self.meta, self.acquisition.idx);
+        let remaining_readers = self.leave_readers();
+        // Waiters registered now, so racy; a read release wakes none while
+        // other readers still hold the lock.
+        let woke = if mode == WRITE || remaining_readers == Some(0) {
+            registry::woken_by(self.meta.id, mode)
+        } else {
+            0
+        };
+        self.meta.stats.record_woken(woke);
+        let migration = self.origin.migration(&Origin::current());
+        if migration.is_some() {
+            self.meta.stats.record_migration();
+        }
+        let held = self.got_at.elapsed();
+        self.acquisition.held = Some(held);
+        if let Some(stack) = self.acquisition.stack.take() {
+            folded::record(stack, held);
+        }
+        // Tokio does not poison, so a writer unwinding mid-mutation is flagged
+        // here for later acquirers; locking itself is unaffected.
+        let panicked = mode == WRITE && thread::panicking();
+        if panicked {
+            self.meta.poisoned.store(true, Ordering::Relaxed);
+        }
+
+        let unmutated = mode == WRITE
+            && !mutated
+            && !panicked
+            && self.check_mutation
+            && !self.meta.unmutated_writes_ok.load(Ordering::Relaxed);
+        self.meta.stats.record_release(
+            self.acquisition.caller,
+            self.acquisition.waited,
+            held,
+            unmutated,
+        );
+        let suspended = self
+            .suspension
+            .zip(Suspension::current())
+            .map(|(start, now)| now.since(&start));
+        if let Some(suspended) = suspended {
+            self.meta.stats.record_suspension(
+                self.acquisition.caller,
+                suspended.suspended,
+                suspended.yields,
+            );
+        }
+        let work = self.work.take();
+        if let Some(work) = &work {
+            self.meta
+                .stats
+                .record_work(self.acquisition.caller, work.bytes);
+        }
+        let threshold = config::work_warn_threshold() as u64;
+        let over_budget =
+            threshold != 0 && work.as_ref().is_some_and(|work| work.bytes >= threshold);
+        metrics_facade::on_release(&self.meta, mode, held);
+        otel::on_release(&mut self.span, held);
+        if self.held_during.is_none() && self.epoch.is_some_and(|epoch| epoch.yielded_since()) {
+            self.report_across_await(mode, caller, held);
+        }
+        if unmutated {
+            self.report_unmutated(caller);
+        }
+        if let Some(since) = self.blocking_watch {
+            self.report_blocking(mode, caller, held, since);
+        }
+
+        let should_log = panicked
+            || over_budget
+            || reason.is_some()
+            || self.held_during.is_some()
+            || self
+                .acquisition
+                .should_log_release(&self.meta, mode, held, mutated);
+        let notify = self.meta.availability.has_callbacks();
+        let silent = !self.acquisition.audited() && Verdict::of(&self.meta).silent(&self.meta);
+        if !notify && (!should_log || silent) {
+            return;
+        }
+
+        let mut details = details();
+        if let Some(remaining) = remaining_readers {
+            let _ = write!(details, " remaining_readers={}", remaining);
+        }
+        if woke != 0 {
+            let _ = write!(details, " woke={}", woke);
+        }
+        if let (Some(path), Some(at)) = (&self.path, self.narrowed_at) {
+            let _ = write!(
+                details,
+                " path={} narrowed_at={}:{}",
+                path,
+                at.file(),
+                at.line()
+            );
+        }
+        if let Some((from, to)) = migration {
+            let _ = write!(details, " migrated_from {} -> {}", from, to);
+        }
+        if let Some(label) = &self.handoff {
+            let _ = write!(details, " handoff={:?}", label);
+        }
+        if let Some(suspended) = suspended {
+            let _ = write!(
+                details,
+                " held={:?} (cpu≈{:?}, suspended≈{:?}, yields={})",
+                held,
+                held.saturating_sub(suspended.suspended),
+                suspended.suspended,
+                suspended.yields,
+            );
+        }
+        if let Some(awaited) = self.awaited {
+            let _ = write!(
+                details,
+                " awaited={:?} yields={}",
+                awaited.suspended, awaited.yields
+            );
+        }
+        if self.held_during.is_some() {
+            details.push_str(" held_during_await");
+        }
+        if let Some(work) = &work {
+            let _ = write!(details, " work={}", Bytes(work.bytes));
+            if !work.notes.is_empty() {
+                let _ = write!(details, " {:?}", work.notes.join("; "));
+            }
+            if over_budget {
+                let _ = write!(details, " WARNING over {} under lock", Bytes(threshold));
+            }
+        }
+        if let Some(reason) = &reason {
+            let _ = write!(details, " reason={:?}", reason);
+        }
+        if panicked {
+            self.report_panicked(mode, caller, &details);
+        } else if over_budget {
+            let level = self
+                .acquisition
+                .level(&self.meta, Phase::Release)
+                .min(Level::Warn);
+            self.acquisition
+                .emit_at(level, &self.meta, mode, Phase::Release, caller, &details);
+        } else if should_log {
+            self.acquisition
+                .emit(&self.meta, mode, Phase::Release, caller, &details);
+        }
+        if notify {
+            let details = format!("{}{}", self.acquisition.annotations(), details);
+            let message = event::format_message(
+                &self.meta,
+                mode,
+                Phase::Release,
+                self.acquisition.idx,
+                &details,
+            );
+            self.release_event = Some(event::build(
+                self.acquisition.level(&self.meta, Phase::Release),
+                &self.meta,
+                mode,
+                Phase::Release,
+                self.acquisition.subject(Phase::Release),
+                caller,
+                message,
+            ));
+        }
+    }
+}
+
+impl GuardState {
+    // Runs at most once per guard, from release or else from drop, so the
+    // reader count stays balanced however the guard is mapped or handed over.
+    fn leave_readers(&mut self) -> Option<usize> {
+        if !self.reader || !self.counted {
+            return None;
+        }
+        self.reader = false;
+        let remaining = self.meta.availability.reader_released();
+        if remaining.is_none() {
+            self.report_underflow("reader");
+            // This hold is already unregistered, so the resync left it out.
+            self.counted = false;
+        }
+        remaining
+    }
+
+    // A count about to go below zero means some release was counted twice or
+    // some acquire not at all; the counts are rebuilt from the registry rather
+    // than wrapping and staying wrong.
+    fn report_underflow(&self, count: &str) {
+        fallback::log!(
+            Level::Error,
+            "[META] {} count for ({}) would go negative releasing ({}:{}); resyncing from the \
+             registry",
+            count,
+            self.meta.name(),
+            self.meta.name(),
+            Idx(self.acquisition.idx),
+        );
+        registry::resync(&self.meta, false);
+    }
+
+    fn report_unmutated(&self, caller: &'static Location<'static>) {
+        if !self.acquisition.sampled {
+            return;
+        }
+        let acquired_at = self.acquisition.caller;
+        let message = format!(
+            "[{}] Released without mutation ({}:{}) acquired at {}:{}:{}",
+            WRITE,
+            self.meta.name(),
+            Idx(self.acquisition.idx),
+            acquired_at.file(),
+            acquired_at.line(),
+            acquired_at.column(),
+        );
+        event::emit_message(
+            severity::severity_policy().anomaly,
+            &self.meta,
+            WRITE,
+            Phase::Unmutated,
+            self.acquisition.idx,
+            caller,
+            message,
+        );
+    }
+
+    fn report_out_of_order(
+        &self,
+        mode: Mode,
+        caller: &'static Location<'static>,
+        newer: &HeldEntry,
+    ) {
+        if !self.acquisition.sampled {
+            return;
+        }
+        let message = format!(
+            "[{}] ({}:{}) released out of order (expected {}:{})",
+            mode,
+            self.meta.name(),
+            Idx(self.acquisition.idx),
+            newer.name,
+            Idx(newer.idx),
+        );
+        event::emit_message(
+            severity::severity_policy().anomaly,
+            &self.meta,
+            mode,
+            Phase::Order,
+            self.acquisition.idx,
+            caller,
+            message,
+        );
+    }
+
+    // The guard sat idle for as long as the section was suspended, so past the
+    // hold warn threshold that gets a warning of its own, whatever the level
+    // of the release that follows.
+    pub(crate) fn record_awaited(&mut self, mode: Mode, awaited: Suspension) {
+        self.awaited = Some(awaited);
+        let threshold = self
+            .meta
+            .slow_hold
+            .get()
+            .copied()
+            .or(severity::severity_policy().hold_warn);
+        if !self.acquisition.sampled || threshold.is_none_or(|warn| awaited.suspended < warn) {
+            return;
+        }
+        let message = format!(
+            "[{}] Awaited under guard ({}:{}) for {:?} over {} yields",
+            mode,
+            self.meta.name(),
+            Idx(self.acquisition.idx),
+            awaited.suspended,
+            awaited.yields,
+        );
+        event::emit_message(
+            severity::severity_policy().anomaly.min(Level::Warn),
+            &self.meta,
+            mode,
+            Phase::Await,
+            self.acquisition.idx,
+            self.acquisition.caller,
+            message,
+        );
+    }
+
+    fn report_across_await(&self, mode: Mode, caller: &'static Location<'static>, held: Duration) {
+        if !self.acquisition.sampled {
+            return;
+        }
+        let acquired_at = self.acquisition.caller;
+        let message = format!(
+            "[{}] Held across await ({}:{}) held={:?} acquired_at={}:{}:{}",
+            mode,
+            self.meta.name(),
+            Idx(self.acquisition.idx),
+            held,
+            acquired_at.file(),
+            acquired_at.line(),
+            acquired_at.column(),
+        );
+        event::emit_message(
+            severity::severity_policy().anomaly,
+            &self.meta,
+            mode,
+            Phase::Await,
+            self.acquisition.idx,
+            caller,
+            message,
+        );
+    }
+
+    fn report_blocking(
+        &self,
+        mode: Mode,
+        caller: &'static Location<'static>,
+        held: Duration,
+        since: Instant,
+    ) {
+        if !self.acquisition.sampled {
+            return;
+        }
+        let (stalled, stalls) = stall::overlap(since, Instant::now());
+        if stalls == 0 {
+            return;
+        }
+        stall::report(
+            &self.meta,
+            mode,
+            self.acquisition.idx,
+            self.acquisition.caller,
+            caller,
+            format!("held={:?} stalled={:?} stalls={}", held, stalled, stalls),
+        );
+    }
+
+    fn report_panicked(&self, mode: Mode, caller: &'static Location<'static>, details: &str) {
+        let acquired_at = self.acquisition.caller;
+        let message = format!(
+            "[{}] Release (PANICKED) ({}:{}){}{} acquired_at={}:{}:{}",
+            mode,
+            self.meta.name(),
+            Idx(self.acquisition.idx),
+            self.acquisition.annotations(),
+            details,
+            acquired_at.file(),
+            acquired_at.line(),
+            acquired_at.column(),
+        );
+        event::emit_message(
+            Level::Error,
+            &self.meta,
+            mode,
+            Phase::Release,
+            self.acquisition.idx,
+            caller,
+            message,
+        );
+    }
+}
+
+// Declared after the inner guard in every guard type, so this runs once the
+// lock itself has been given back.
+impl Drop for GuardState {
+    fn drop(&mut self) {
+        let _operation = operation::enter(self.acquisition.operation.clone());
+        self.leave_readers();
+        let event = self.release_event.take();
+        if !self.meta.availability.released(event, self.counted) {
+            self.report_underflow("holder");
+        }
+    }
+}
+
+// Generic over the backend `L`, whose own guard this derefs to.
+pub struct RwLockReadGuard<
+    'a,
+    T: ?Sized,
+    L: RawAsyncRwLock<T> + ?Sized + 'a = tokio::sync::RwLock<T>,
+> {
+    guard: L::ReadGuard<'a>,
+    pub(crate) state: GuardState,
+    pub(crate) source: Option<Source<'a, T, L>>,
+}
+
+impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+    #[track_caller]
+    pub fn new(inner: tokio::sync::RwLockReadGuard<'a, T>, name: &'a str, idx: u64) -> Self {
+        let meta = Arc::new(LockMeta::detached(name.to_owned(), any::type_name::<T>()));
+        let acquisition = Acquisition {
+            idx,
+            ..Acquisition::new(Location::caller())
+        };
+        Self::acquired(inner, meta, acquisition, None)
+    }
+}
+
+impl<'a, T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> RwLockReadGuard<'a, T, L> {
+    pub(crate) fn acquired(
+        inner: L::ReadGuard<'a>,
+        meta: Arc<LockMeta>,
+        acquisition: Acquisition,
+        source: Option<Source<'a, T, L>>,
+    ) -> Self {
+        Self {
+            guard: inner,
+            state: GuardState::got(meta, acquisition, READ),
+            source,
+        }
+    }
+
+    pub fn acquired_at(&self) -> &'static Location<'static> {
+        self.state.acquisition.caller
+    }
+
+    // Cooperative accounting for what the critical section does: `bytes` is
+    // added to this hold and to its call site's stats, and shows at Release
+    // as `work=4.2MiB "note"`. Past `set_work_warn_threshold`, the Release is
+    // logged at warn at least.
+    pub fn record_work(&mut self, bytes: usize, note: &str) {
+        self.state.record_work(bytes, note);
+    }
+
+    pub fn debug_value(&self) -> &T
+    where
+        T: fmt::Debug,
+    {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> fmt::Debug for RwLockReadGuard<'_, T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.state.fmt_guard(f, "RwLockReadGuard", READ)
+    }
+}
+
+impl<'a, T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> Drop for RwLockReadGuard<'a, T, L> {
+    #[track_caller]
+    fn drop(&mut self) {
+        self.state
+            .release(READ, Location::caller(), false, String::new);
+    }
+}
+
+// Deref only: `&mut` to the backend's guard would let it be swapped for
+// another lock's, leaving this state to release a hold it no longer has.
+impl<'a, T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> Deref for RwLockReadGuard<'a, T, L> {
+    type Target = L::ReadGuard<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+pub struct RwLockWriteGuard<
+    'a,
+    T: ?Sized,
+    L: RawAsyncRwLock<T> + ?Sized + 'a = tokio::sync::RwLock<T>,
+> {
+    guard: L::WriteGuard<'a>,
+    pub(crate) state: GuardState,
+    mutated: bool,
+    hooks: Option<&'a ValueHooks<T>>,
+    fingerprint: Option<u64>,
+}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+    #[track_caller]
+    pub fn new(inner: tokio::sync::RwLockWriteGuard<'a, T>, name: &'a str, idx: u64) -> Self {
+        let meta = Arc::new(LockMeta::detached(name.to_owned(), any::type_name::<T>()));
+        let acquisition = Acquisition {
+            idx,
+            ..Acquisition::new(Location::caller())
+        };
+        Self::acquired(inner, meta, acquisition, None)
+    }
+}
+
+impl<'a, T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> RwLockWriteGuard<'a, T, L> {
+    pub(crate) fn acquired(
+        inner: L::WriteGuard<'a>,
+        meta: Arc<LockMeta>,
+        acquisition: Acquisition,
+        hooks: Option<&'a ValueHooks<T>>,
+    ) -> Self {
+        let fingerprint = hooks.and_then(|hooks| hooks.fingerprint(&inner));
+        Self {
+            guard: inner,
+            state: GuardState::got(meta, acquisition, WRITE),
+            mutated: false,
+            hooks,
+            fingerprint,
+        }
+    }
+
+    pub fn acquired_at(&self) -> &'static Location<'static> {
+        self.state.acquisition.caller
+    }
+
+    pub fn record_work(&mut self, bytes: usize, note: &str) {
+        self.state.record_work(bytes, note);
+    }
+
+    pub fn debug_value(&self) -> &T
+    where
+        T: fmt::Debug,
+    {
+        &self.guard
+    }
+
+    // Opts this guard out of the release-without-mutation report.
+    pub fn allow_no_mutation(&mut self) {
+        self.state.skip_mutation_check();
+    }
+
+    // Call before blocking work done under the lock. If the runtime stalls
+    // between here and release, the release reports how long it was stalled;
+    // stalls are only seen while `spawn_stall_detector` is running.
+    pub fn warn_if_held_through_blocking(&mut self) {
+        self.state.watch_blocking();
+    }
+
+    // Releases the lock now, with `reason="..."` on its Release, which is
+    // logged whatever the release thresholds: for an early return, where a
+    // drop alone would not say why the section ended. The write is not then
+    // reported as unmutated.
+    #[track_caller]
+    pub fn release_with_reason(mut self, reason: &str) {
+        self.state.give_reason(reason, Location::caller());
+    }
+
+    // Awaits `future` with the lock held throughout, then releases it: a hold
+    // across an await made on purpose, which is logged as `held_during_await`
+    // at the call site, with what the future spent suspended, instead of
+    // being reported as held across an await.
+    #[track_caller]
+    pub fn hold_during<F>(mut self, future: F) -> impl Future<Output = F::Output> + 'a
+    where
+        F: Future + 'a,
+    {
+        let caller = Location::caller();
+
+        async move {
+            let (output, awaited) = polls::timed(future).await;
+            self.state.held_during(awaited, caller);
+            output
+        }
+    }
+
+    #[track_caller]
+    pub fn map<F, U: ?Sized>(mut this: Self, f: F) -> RwLockMappedWriteGuard<'a, T, U, L>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let data = f(&mut *this.guard) as *mut U;
+        this.state
+            .mapped::<U>(WRITE, Phase::Map, Location::caller());
+        this.into_mapped(data)
+    }
+
+    #[track_caller]
+    #[allow(clippy::result_large_err)]
+    pub fn try_map<F, U: ?Sized>(
+        mut this: Self,
+        f: F,
+    ) -> Result<RwLockMappedWriteGuard<'a, T, U, L>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        let data = match f(&mut *this.guard) {
+            Some(data) => data as *mut U,
+            None => {
+                this.state
+                    .mapped::<U>(WRITE, Phase::MapFailed, Location::caller());
+                return Err(this);
+            }
+        };
+        this.state
+            .mapped::<U>(WRITE, Phase::Map, Location::caller());
+        Ok(this.into_mapped(data))
+    }
+
+    fn into_mapped<U: ?Sized>(self, data: *mut U) -> RwLockMappedWriteGuard<'a, T, U, L> {
+        let this = ManuallyDrop::new(self);
+        // Safety: `this` is never dropped, so each field is moved out exactly once.
+        unsafe {
+            RwLockMappedWriteGuard {
+                guard: ptr::read(&this.guard),
+                state: ptr::read(&this.state),
+                mutated: this.mutated,
+                hooks: this.hooks,
+                fingerprint: this.fingerprint,
+                data,
+                marker: PhantomData,
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> fmt::Debug for RwLockWriteGuard<'_, T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.state.fmt_guard(f, "RwLockWriteGuard", WRITE)
+    }
+}
+
+impl<'a, T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> Drop for RwLockWriteGuard<'a, T, L> {
+    #[track_caller]
+    fn drop(&mut self) {
+        let (guard, hooks, fingerprint) = (&self.guard, self.hooks, self.fingerprint);
+        self.state
+            .release(WRITE, Location::caller(), self.mutated, || match hooks {
+                Some(hooks) => hooks.release_annotations(guard, fingerprint),
+                None => String::new(),
+            });
+    }
+}
+
+impl<'a, T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> Deref for RwLockWriteGuard<'a, T, L> {
+    type Target = L::WriteGuard<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<'a, T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> DerefMut for RwLockWriteGuard<'a, T, L> {
+    fn deref_mut(&mut self) -> &mut L::WriteGuard<'a> {
+        self.mutated = true;
+        &mut self.guard
+    }
+}
+
+// Like the owned variant, the full write guard is kept so the whole value is
+// still reachable for change tracking when the lock is released.
+pub struct RwLockMappedWriteGuard<
+    'a,
+    T: ?Sized,
+    U: ?Sized,
+    L: RawAsyncRwLock<T> + ?Sized + 'a = tokio::sync::RwLock<T>,
+> {
+    guard: L::WriteGuard<'a>,
+    state: GuardState,
+    mutated: bool,
+    hooks: Option<&'a ValueHooks<T>>,
+    fingerprint: Option<u64>,
+    data: *mut U,
+    marker: PhantomData<&'a mut U>,
+}
+
+impl<'a, T: ?Sized, U: ?Sized, L: RawAsyncRwLock<T> + ?Sized> RwLockMappedWriteGuard<'a, T, U, L> {
+    pub fn acquired_at(&self) -> &'static Location<'static> {
+        self.state.acquisition.caller
+    }
+
+    // Where the guard sits under its acquisition, as in its Map events.
+    pub fn path(&self) -> &str {
+        self.state.path().unwrap_or_default()
+    }
+
+    pub fn record_work(&mut self, bytes: usize, note: &str) {
+        self.state.record_work(bytes, note);
+    }
+
+    pub fn debug_value(&self) -> &U
+    where
+        U: fmt::Debug,
+    {
+        self
+    }
+
+    #[track_caller]
+    pub fn map<F, V: ?Sized>(mut this: Self, f: F) -> RwLockMappedWriteGuard<'a, T, V, L>
+    where
+        F: FnOnce(&mut U) -> &mut V,
+    {
+        // Safety: as in `deref_mut`, without marking the value mutated.
+        let data = f(unsafe { &mut *this.data }) as *mut V;
+        this.state
+            .mapped::<V>(WRITE, Phase::Map, Location::caller());
+        this.remap(data)
+    }
+
+    #[track_caller]
+    #[allow(clippy::result_large_err)]
+    pub fn try_map<F, V: ?Sized>(
+        mut this: Self,
+        f: F,
+    ) -> Result<RwLockMappedWriteGuard<'a, T, V, L>, Self>
+    where
+        F: FnOnce(&mut U) -> Option<&mut V>,
+    {
+        // Safety: as in `deref_mut`, without marking the value mutated.
+        match f(unsafe { &mut *this.data }) {
+            Some(data) => {
+                let data = data as *mut V;
+                this.state
+                    .mapped::<V>(WRITE, Phase::Map, Location::caller());
+                Ok(this.remap(data))
+            }
+            None => {
+                this.state
+                    .mapped::<V>(WRITE, Phase::MapFailed, Location::caller());
+                Err(this)
+            }
+        }
+    }
+
+    fn remap<V: ?Sized>(self, data: *mut V) -> RwLockMappedWriteGuard<'a, T, V, L> {
+        let this = ManuallyDrop::new(self);
+        // Safety: `this` is never dropped, so each field is moved out exactly once.
+        unsafe {
+            RwLockMappedWriteGuard {
+                guard: ptr::read(&this.guard),
+                state: ptr::read(&this.state),
+                mutated: this.mutated,
+                hooks: this.hooks,
+                fingerprint: this.fingerprint,
+                data,
+                marker: PhantomData,
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, L: RawAsyncRwLock<T> + ?Sized> fmt::Debug
+    for RwLockMappedWriteGuard<'_, T, U, L>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.state.fmt_guard(f, "RwLockMappedWriteGuard", WRITE)
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, L: RawAsyncRwLock<T> + ?Sized> Drop
+    for RwLockMappedWriteGuard<'_, T, U, L>
+{
+    #[track_caller]
+    fn drop(&mut self) {
+        let (guard, hooks, fingerprint) = (&self.guard, self.hooks, self.fingerprint);
+        self.state
+            .release(WRITE, Location::caller(), self.mutated, || match hooks {
+                Some(hooks) => hooks.release_annotations(guard, fingerprint),
+                None => String::new(),
+            });
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, L: RawAsyncRwLock<T> + ?Sized> Deref
+    for RwLockMappedWriteGuard<'_, T, U, L>
+{
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        // Safety: `data` points into the value behind `guard`, which is held
+        // exclusively for as long as this guard is alive.
+        unsafe { &*self.data }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, L: RawAsyncRwLock<T> + ?Sized> DerefMut
+    for RwLockMappedWriteGuard<'_, T, U, L>
+{
+    fn deref_mut(&mut self) -> &mut U {
+        self.mutated = true;
+        unsafe { &mut *self.data }
+    }
+}
+
+unsafe impl<'a, T, L> Send for RwLockWriteGuard<'a, T, L>
+where
+    T: ?Sized + Send + Sync,
+    L: RawAsyncRwLock<T> + ?Sized,
+    L::WriteGuard<'a>: Send,
+{
+}
+unsafe impl<'a, T, U, L> Send for RwLockMappedWriteGuard<'a, T, U, L>
+where
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Send + Sync,
+    L: RawAsyncRwLock<T> + ?Sized,
+    L::WriteGuard<'a>: Send,
+{
+}
+unsafe impl<'a, T, L> Sync for RwLockWriteGuard<'a, T, L>
+where
+    T: ?Sized + Send + Sync,
+    L: RawAsyncRwLock<T> + ?Sized,
+    L::WriteGuard<'a>: Sync,
+{
+}
+unsafe impl<'a, T, U, L> Sync for RwLockMappedWriteGuard<'a, T, U, L>
+where
+    T: ?Sized + Send + Sync,
+    U: ?Sized + Send + Sync,
+    L: RawAsyncRwLock<T> + ?Sized,
+    L::WriteGuard<'a>: Sync,
+{
+}
+
+// Compile checks on the guards, run as doctests so a regression fails
+// `cargo test`. Each case that must not build sits beside one that does, so
+// it fails for the reason given and not a typo.
+#[cfg(doctest)]
+/// A read guard hands out no `&mut`, not even to the tokio guard inside it,
+/// so two read guards cannot have their holds swapped:
+///
+/// ```compile_fail,E0596
+/// async fn swap(a: &rwlock_trace::RwLock<u8>, b: &rwlock_trace::RwLock<u8>) {
+///     let (mut first, mut second) = (a.read().await, b.read().await);
+///     std::mem::swap(&mut *first, &mut *second);
+/// }
+/// ```
+///
+/// ```
+/// async fn read(lock: &rwlock_trace::RwLock<u8>) -> u8 {
+///     **lock.read().await
+/// }
+/// ```
+///
+/// A borrowed guard cannot outlive its lock by moving into a task, while an
+/// owned one is `Send` and `'static`:
+///
+/// ```compile_fail,E0597
+/// async fn hold(lock: rwlock_trace::RwLock<u8>) {
+///     let guard = lock.write().await;
+///     tokio::spawn(async move { drop(guard) });
+/// }
+/// ```
+///
+/// ```
+/// async fn hold(lock: std::sync::Arc<rwlock_trace::RwLock<u8>>) {
+///     let read = lock.clone().read_owned().await;
+///     tokio::spawn(async move { drop(read) });
+///     let write = lock.write_owned().await;
+///     tokio::spawn(async move { drop(write) });
+/// }
+/// ```
+mod compile_checks {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Mode, OwnedRwLockWriteGuard, Phase, RwLock, RwLockMappedWriteGuard, RwLockWriteGuard,
+    };
+
+    use std::{fmt::Debug, sync::Arc, time::Duration};
+
+    // Deliberately not Debug.
+    struct Secret(&'static str);
+
+    fn printed(guard: &impl Debug) -> String {
+        let printed = format!("{:?}", guard);
+        assert!(
+            !printed.contains("hunter2"),
+            "{} shows the payload",
+            printed
+        );
+        printed
+    }
+
+    #[tokio::test]
+    async fn guards_over_non_debug_values_print_only_metadata() {
+        let lock = Arc::new(RwLock::new_named(Secret("hunter2"), "guard-debug"));
+        let read = printed(&lock.read().await);
+        assert!(read.starts_with("RwLockReadGuard { name: \"guard-debug\", mode: Read, idx: 1,"));
+        assert!(read.contains("held_for: "));
+        printed(&lock.clone().read_owned().await);
+        printed(&lock.upgradable_read().await);
+
+        let write = printed(&lock.write().await);
+        assert!(write.starts_with("RwLockWriteGuard { name: \"guard-debug\", mode: Write,"));
+        printed(&RwLockWriteGuard::map(lock.write().await, |secret| {
+            &mut secret.0
+        }));
+        let owned = lock.clone().write_owned().await;
+        printed(&owned);
+        printed(&OwnedRwLockWriteGuard::map(owned, |secret| &mut secret.0));
+
+        let visible = RwLock::new_named("shown", "guard-debug-value");
+        assert_eq!(
+            format!("{:?}", visible.read().await.debug_value()),
+            "\"shown\""
+        );
+    }
+
+    #[tokio::test]
+    async fn mapped_write_guards_release_the_lock() {
+        let lock = RwLock::new_named((1u8, Some(2u8)), "guard-mapped-release");
+        let id = lock.id();
+        let mut releases = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.phase == Phase::Release
+        });
+
+        let mapped = RwLockWriteGuard::map(lock.write().await, |pair| &mut pair.1);
+        let mut mapped = RwLockMappedWriteGuard::try_map(mapped, Option::as_mut).unwrap();
+        *mapped += 1;
+        drop(mapped);
+        let release = releases
+            .try_recv()
+            .expect("the mapped guard logged no release");
+        assert_eq!(release.mode, Mode::Write);
+        assert!(release
+            .message
+            .starts_with("[WRITE] Release (guard-mapped-release:1)"));
+
+        let failed = RwLockWriteGuard::try_map(lock.write().await, |pair| {
+            pair.1 = None;
+            pair.1.as_mut()
+        });
+        drop(failed.expect_err("nothing to map to"));
+        assert!(releases.try_recv().is_ok());
+
+        let read = tokio::time::timeout(Duration::from_secs(1), lock.read())
+            .await
+            .expect("the write lock was never released");
+        assert_eq!(**read, (1, None));
+    }
+
+    // Mapping reaches the value through the inner guard, so it alone does not
+    // count as a write.
+    #[tokio::test]
+    async fn maps_that_are_only_read_are_reported_unmutated() {
+        let lock = RwLock::new_named((1u8, Some(2u8)), "guard-map-unmutated");
+        let id = lock.id();
+        let mut unmutated = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.phase == Phase::Unmutated
+        });
+
+        let mapped = RwLockWriteGuard::map(lock.write().await, |pair| &mut pair.1);
+        let field = RwLockMappedWriteGuard::try_map(mapped, Option::as_mut).unwrap();
+        assert_eq!(*field, 2);
+        drop(field);
+        let mapped = RwLockWriteGuard::try_map(lock.write().await, |pair| pair.1.as_mut());
+        let field = RwLockMappedWriteGuard::map(mapped.unwrap(), |value| value);
+        assert_eq!(*field, 2);
+        drop(field);
+        let idxs: Vec<u64> = std::iter::from_fn(|| unmutated.try_recv().ok())
+            .map(|event| event.idx)
+            .collect();
+        assert_eq!(idxs, [1, 2]);
+
+        let mut field = RwLockWriteGuard::map(lock.write().await, |pair| &mut pair.0);
+        *field += 1;
+        drop(field);
+        assert!(unmutated.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn a_failed_try_map_hands_back_a_traced_guard() {
+        let lock = RwLock::new_named((1u8, None::<u8>), "guard-map-failed");
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| {
+            event.lock_id == id
+                && matches!(event.phase, Phase::Map | Phase::MapFailed | Phase::Release)
+        });
+
+        let guard = RwLockWriteGuard::try_map(lock.write().await, |pair| pair.1.as_mut())
+            .expect_err("nothing to map to");
+        let failed = events.try_recv().unwrap();
+        assert_eq!((failed.phase, failed.idx), (Phase::MapFailed, 1));
+        assert!(failed
+            .message
+            .starts_with("[WRITE] MapFailed (guard-map-failed:1)"));
+        assert!(failed.message.ends_with(" -> u8 path=1"));
+
+        let mut field = RwLockWriteGuard::map(guard, |pair| &mut pair.0);
+        *field += 1;
+        let map = events.try_recv().unwrap();
+        assert_eq!((map.phase, map.idx), (Phase::Map, 1));
+        assert!(map.message.ends_with(" -> u8 path=1.1"));
+
+        drop(field);
+        let release = events.try_recv().unwrap();
+        assert_eq!((release.phase, release.idx), (Phase::Release, 1));
+        assert!(events.try_recv().is_err());
+        assert_eq!(lock.read().await.0, 2);
+    }
+
+    #[tokio::test]
+    async fn each_map_narrows_the_path_and_a_failed_one_keeps_it() {
+        let lock = RwLock::new_named(vec![(0u8, None::<u8>)], "guard-map-path");
+        drop(lock.read().await);
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| {
+            event.lock_id == id
+                && matches!(event.phase, Phase::Map | Phase::MapFailed | Phase::Release)
+        });
+
+        let entries = RwLockWriteGuard::map(lock.write().await, |entries| &mut entries[0]);
+        assert_eq!(entries.path(), "2.1");
+        let entries = RwLockMappedWriteGuard::try_map(entries, |entry| entry.1.as_mut())
+            .expect_err("nothing to map to");
+        assert_eq!(entries.path(), "2.1");
+        let mut field = RwLockMappedWriteGuard::map(entries, |entry| &mut entry.0);
+        assert_eq!(field.path(), "2.1.1");
+        *field = 7;
+        drop(field);
+
+        let paths: Vec<(Phase, String)> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| {
+                let path = event.message.split(" path=").nth(1).unwrap_or_default();
+                let path = path.split(' ').next().unwrap_or_default().to_owned();
+                (event.phase, path)
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                (Phase::Map, "2.1".to_owned()),
+                (Phase::MapFailed, "2.1".to_owned()),
+                (Phase::Map, "2.1.1".to_owned()),
+                (Phase::Release, "2.1.1".to_owned()),
+            ]
+        );
+    }
+
+    #[cfg(not(feature = "test-clock"))]
+    #[tokio::test(start_paused = true)]
+    async fn verbose_acquires_report_the_wait() {
+        let lock = Arc::new(RwLock::new_named(0u8, "guard-verbose"));
+        let (read, report) = lock.read_verbose().await;
+        assert_eq!(report.idx, 1);
+        assert_eq!(report.waited, Duration::ZERO);
+        assert!(!report.contended && report.queued_behind.is_none());
+        drop(read);
+
+        let write = lock.write().await;
+        let reader = tokio::spawn({
+            let lock = lock.clone();
+            async move { lock.read_verbose().await.1 }
+        });
+        while lock.waiter_count() < 1 {
+            tokio::task::yield_now().await;
+        }
+        let writer = tokio::spawn({
+            let lock = lock.clone();
+            async move { lock.write_verbose().await.1 }
+        });
+        while lock.waiter_count() < 2 {
+            tokio::task::yield_now().await;
+        }
+        tokio::time::sleep(Duration::from_millis(15)).await;
+        drop(write);
+
+        let read = reader.await.unwrap();
+        assert_eq!(read.idx, 3);
+        assert_eq!(read.waited, Duration::from_millis(15));
+        assert!(read.contended);
+        assert_eq!(read.queued_behind, Some(0));
+        let write = writer.await.unwrap();
+        assert_eq!(write.idx, 4);
+        assert!(write.contended && write.waited >= Duration::from_millis(15));
+        assert_eq!(write.queued_behind, Some(1));
+    }
+
+    #[tokio::test]
+    async fn releases_note_woken_waiters_and_gots_the_handoff() {
+        let lock = Arc::new(RwLock::new_named(0u8, "guard-handoff"));
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| {
+            event.lock_id == id && matches!(event.phase, Phase::Got | Phase::Release)
+        });
+        let mut messages = move || -> Vec<String> {
+            std::iter::from_fn(|| events.try_recv().ok())
+                .map(|event| event.message)
+                .collect()
+        };
+
+        drop(lock.write().await);
+        drop(lock.read().await);
+        for message in messages() {
+            assert!(!message.contains(" woke=") && !message.contains(" handoff=true"));
+        }
+
+        let write = lock.write().await;
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let lock = lock.clone();
+                tokio::spawn(async move { drop(lock.read().await) })
+            })
+            .collect();
+        while lock.waiter_count() < 3 {
+            tokio::task::yield_now().await;
+        }
+        drop(write);
+        for reader in readers {
+            reader.await.unwrap();
+        }
+        let convoy = messages();
+        let release = convoy
+            .iter()
+            .find(|message| message.starts_with("[WRITE] Release"))
+            .unwrap();
+        assert!(release.contains(" woke=3"), "{}", release);
+        let handoffs = convoy
+            .iter()
+            .filter(|message| message.starts_with("[READ] Got"))
+            .filter(|message| message.contains(" handoff=true"))
+            .count();
+        assert_eq!(handoffs, 3);
+        assert_eq!(lock.stats().release_handoffs, 1);
+
+        // A writer at the head of the queue goes in alone.
+        let write = lock.write().await;
+        let queued: Vec<_> = (0..2)
+            .map(|n| {
+                let lock = lock.clone();
+                let task = tokio::spawn(async move {
+                    match n {
+                        0 => drop(lock.write().await),
+                        _ => drop(lock.read().await),
+                    }
+                });
+                (task, n + 1)
+            })
+            .collect();
+        for (_, waiters) in &queued {
+            while lock.waiter_count() < *waiters {
+                tokio::task::yield_now().await;
+            }
+        }
+        drop(write);
+        for (task, _) in queued {
+            task.await.unwrap();
+        }
+        let woke: Vec<String> = messages()
+            .into_iter()
+            .filter(|message| message.contains(" Release "))
+            .map(|message| message.split(" woke=").nth(1).unwrap_or("-").to_owned())
+            .collect();
+        assert_eq!(woke, ["1", "1", "-"]);
+    }
+
+    #[tokio::test]
+    async fn acquires_and_releases_pair_up_across_an_idx_wrap() {
+        let lock = RwLock::new_named(0u8, "guard-idx-wrap").with_next_idx(u64::MAX - 1);
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| event.lock_id == id);
+
+        for _ in 0..4 {
+            drop(lock.write().await);
+        }
+        let mut pairs = Vec::new();
+        let mut wraps = 0;
+        while let Ok(event) = events.try_recv() {
+            match event.phase {
+                Phase::Acquire => pairs.push((event.idx, false)),
+                Phase::Release => {
+                    let open = pairs
+                        .iter_mut()
+                        .find(|(idx, released)| *idx == event.idx && !released);
+                    open.expect("a release with no acquire").1 = true;
+                }
+                Phase::Wrap => {
+                    assert_eq!(event.message, "[META] idx wrapped (guard-idx-wrap)");
+                    wraps += 1;
+                }
+                _ => {}
+            }
+        }
+        let idxs: Vec<u64> = pairs.iter().map(|(idx, _)| *idx).collect();
+        assert_eq!(idxs, [u64::MAX - 1, u64::MAX, 0, 1]);
+        assert!(pairs.iter().all(|(_, released)| *released));
+        assert_eq!(wraps, 1);
+    }
+
+    #[tokio::test]
+    async fn audited_writes_get_past_muting_sampling_and_directives() {
+        let lock = RwLock::builder()
+            .name("guard-audit-bypass")
+            .muted(true)
+            .sample_rate(1000)
+            .build(0u8);
+        crate::set_directives("guard-audit-bypass=off").unwrap();
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| event.lock_id == id);
+
+        drop(lock.write().await);
+        **lock.write_audited("rotate keys").await = 1;
+        crate::set_directives("").unwrap();
+
+        let mut audited = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            assert!(
+                event.message.contains(" reason=\"rotate keys\""),
+                "{}",
+                event.message
+            );
+            audited.push(event.phase);
+        }
+        assert_eq!(audited, [Phase::Acquire, Phase::Got, Phase::Release]);
+    }
+
+    #[tokio::test]
+    async fn audited_writes_are_logged_at_the_audit_level() {
+        let lock = RwLock::new_named(0u8, "guard-audit-level");
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.message.contains(" reason=")
+        });
+
+        crate::set_audit_level(log::Level::Error);
+        **lock.write_audited("migrate").await = 1;
+        crate::set_audit_level(log::Level::Warn);
+        **lock.write_audited("migrate").await = 2;
+
+        let mut levels = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            levels.push((event.phase, event.level));
+        }
+        assert_eq!(
+            levels,
+            [
+                (Phase::Acquire, log::Level::Error),
+                (Phase::Got, log::Level::Error),
+                (Phase::Release, log::Level::Error),
+                (Phase::Acquire, log::Level::Warn),
+                (Phase::Got, log::Level::Warn),
+                (Phase::Release, log::Level::Warn),
+            ]
+        );
+    }
+}
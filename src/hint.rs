@@ -0,0 +1,149 @@
+use crate::{clock::Instant, fallback, stats};
+
+use log::Level;
+
+use std::{
+    collections::HashMap,
+    mem,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+static HOIST_THRESHOLD: AtomicU64 = AtomicU64::new(500);
+
+// Acquisitions per second from a single call site before a hoisting hint is
+// logged; 0 turns the hint off.
+pub fn set_hoist_hint_threshold(per_second: u64) {
+    HOIST_THRESHOLD.store(per_second, Ordering::Relaxed);
+}
+
+#[derive(Debug)]
+struct Window {
+    start: Instant,
+    count: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct LoopDetector {
+    windows: Mutex<HashMap<&'static Location<'static>, Window>>,
+}
+
+impl LoopDetector {
+    // The name is only read for a hint, since `relabel` makes reading it cost
+    // a lock.
+    pub(crate) fn record<N>(&self, name: N, caller: &'static Location<'static>)
+    where
+        N: FnOnce() -> Arc<str>,
+    {
+        let threshold = HOIST_THRESHOLD.load(Ordering::Relaxed);
+        if threshold == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let window = windows.entry(caller).or_insert(Window {
+            start: now,
+            count: 0,
+        });
+        let elapsed = now.duration_since(window.start);
+        if elapsed >= WINDOW {
+            *window = Window {
+                start: now,
+                count: 0,
+            };
+        }
+        window.count += 1;
+
+        // Only the acquisition that crosses the threshold logs, so each call
+        // site produces at most one hint per window.
+        if window.count == threshold {
+            let rate = threshold as f64 / elapsed.as_secs_f64().max(1e-9);
+            fallback::log!(
+                Level::Warn,
+                "[HINT] ({}) acquired {:.0}x/s from {}:{}; consider hoisting",
+                name(),
+                rate,
+                caller.file(),
+                caller.line(),
+            );
+        }
+    }
+}
+
+// Mean writer wait over a window before the write-preference hint is logged,
+// in nanoseconds; 0 turns it off.
+static WRITER_WAIT_THRESHOLD: AtomicU64 = AtomicU64::new(10_000_000);
+
+// The share of a window's acquisitions that must be reads, and the writes it
+// must have, before its writers' waits say anything about the readers.
+const READ_HEAVY_PCT: u64 = 90;
+const MIN_WRITES: u64 = 2;
+
+// Tokio's RwLock, like the other backends, queues new readers behind a
+// waiting writer, so a writer waits only for the reads already granted to be
+// released. Writers that still wait long on a read-heavy lock point at long
+// read holds. Duration::ZERO turns the hint off.
+pub fn set_writer_wait_hint_threshold(threshold: Duration) {
+    WRITER_WAIT_THRESHOLD.store(stats::duration_nanos(threshold), Ordering::Relaxed);
+}
+
+#[derive(Debug, Default)]
+struct Tally {
+    start: Option<Instant>,
+    reads: u64,
+    writes: u64,
+    writer_wait: Duration,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct WriterWaitDetector {
+    tally: Mutex<Tally>,
+}
+
+impl WriterWaitDetector {
+    // Each window is judged by the first acquisition after it closes, so a
+    // lock logs at most one hint per window.
+    pub(crate) fn record<N>(&self, name: N, write: bool, waited: Duration)
+    where
+        N: FnOnce() -> Arc<str>,
+    {
+        let threshold = WRITER_WAIT_THRESHOLD.load(Ordering::Relaxed);
+        if threshold == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut tally = self.tally.lock().unwrap_or_else(|e| e.into_inner());
+        let start = *tally.start.get_or_insert(now);
+        if now.duration_since(start) >= WINDOW {
+            let closed = mem::take(&mut *tally);
+            tally.start = Some(now);
+            let total = closed.reads + closed.writes;
+            if closed.writes >= MIN_WRITES && closed.reads * 100 >= total * READ_HEAVY_PCT {
+                let mean = closed.writer_wait / closed.writes as u32;
+                if stats::duration_nanos(mean) >= threshold {
+                    fallback::log!(
+                        Level::Warn,
+                        "[HINT] ({}) writers waiting avg {:?}; RwLock is write-preferring, check \
+                         for long read holds",
+                        name(),
+                        mean,
+                    );
+                }
+            }
+        }
+        if write {
+            tally.writes += 1;
+            tally.writer_wait += waited;
+        } else {
+            tally.reads += 1;
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::event::LockEvent;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+// Histories are kept for the most recent tasks only, so finished tasks do not
+// accumulate without bound.
+const MAX_TASKS: usize = 4096;
+
+static CAPACITY: AtomicUsize = AtomicUsize::new(32);
+static HISTORIES: Mutex<Option<Histories>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Histories {
+    by_task: HashMap<tokio::task::Id, VecDeque<LockEvent>>,
+    order: VecDeque<tokio::task::Id>,
+}
+
+pub fn set_task_history_capacity(capacity: usize) {
+    CAPACITY.store(capacity, Ordering::Relaxed);
+    if capacity == 0 {
+        *HISTORIES.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+// Empty outside a tokio task.
+pub fn current_task_lock_history() -> Vec<LockEvent> {
+    let task = match tokio::task::try_id() {
+        Some(task) => task,
+        None => return Vec::new(),
+    };
+    let histories = HISTORIES.lock().unwrap_or_else(|e| e.into_inner());
+    histories
+        .as_ref()
+        .and_then(|histories| histories.by_task.get(&task))
+        .map(|events| events.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+pub(crate) fn recording_task() -> Option<tokio::task::Id> {
+    if CAPACITY.load(Ordering::Relaxed) == 0 {
+        return None;
+    }
+    tokio::task::try_id()
+}
+
+pub(crate) fn record(task: tokio::task::Id, event: LockEvent) {
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    let mut histories = HISTORIES.lock().unwrap_or_else(|e| e.into_inner());
+    let histories = histories.get_or_insert_with(Histories::default);
+
+    if !histories.by_task.contains_key(&task) {
+        if histories.order.len() >= MAX_TASKS {
+            if let Some(oldest) = histories.order.pop_front() {
+                histories.by_task.remove(&oldest);
+            }
+        }
+        histories.order.push_back(task);
+    }
+
+    let events = histories.by_task.entry(task).or_default();
+    while events.len() >= capacity.max(1) {
+        events.pop_front();
+    }
+    events.push_back(event);
+}
@@ -0,0 +1,72 @@
+use crate::{LockConfig, RwLock};
+
+use std::{fmt, ops::Deref, panic::Location, sync::OnceLock};
+
+// Registration allocates, so a traced lock cannot be built in a const
+// context; this defers construction to first use while keeping the name fixed.
+pub struct StaticRwLock<T> {
+    name: &'static str,
+    init: fn() -> T,
+    lock: OnceLock<RwLock<T>>,
+}
+
+impl<T> StaticRwLock<T> {
+    pub const fn new(name: &'static str, init: fn() -> T) -> Self {
+        Self {
+            name,
+            init,
+            lock: OnceLock::new(),
+        }
+    }
+
+    // The first caller to touch the lock is reported as its creation site.
+    #[track_caller]
+    pub fn get(&self) -> &RwLock<T> {
+        let caller = Location::caller();
+        self.lock.get_or_init(|| {
+            LockConfig::builder()
+                .name(self.name)
+                .rwlock_at((self.init)(), caller)
+        })
+    }
+}
+
+impl<T> Deref for StaticRwLock<T> {
+    type Target = RwLock<T>;
+
+    #[track_caller]
+    fn deref(&self) -> &RwLock<T> {
+        self.get()
+    }
+}
+
+impl<T> fmt::Debug for StaticRwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticRwLock")
+            .field("name", &self.name)
+            .field("lock", &self.lock.get())
+            .finish()
+    }
+}
+
+#[macro_export]
+macro_rules! rwlock {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr;) => {
+        $(#[$attr])*
+        $vis static $name: $crate::StaticRwLock<$ty> =
+            $crate::StaticRwLock::new(::std::stringify!($name), || $init);
+    };
+}
+
+// `traced_rwlock!(sessions)` wraps the variable `sessions`, and
+// `traced_rwlock!(sessions = HashMap::new())` any expression, in a lock named
+// `sessions`; the macro's call site is the creation site.
+#[macro_export]
+macro_rules! traced_rwlock {
+    ($name:ident) => {
+        $crate::RwLock::new_named($name, ::std::stringify!($name))
+    };
+    ($name:ident = $init:expr) => {
+        $crate::RwLock::new_named($init, ::std::stringify!($name))
+    };
+}
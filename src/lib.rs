@@ -1,190 +1,946 @@
-use backtrace::{Backtrace, BacktraceFrame};
-use names::Generator;
+#[cfg(feature = "async-lock")]
+pub mod async_lock;
+mod access;
+mod advisory;
+mod availability;
+mod background;
+mod barrier;
+mod batch;
+mod binary;
+mod breadcrumb;
+mod builder;
+mod callsite;
+mod cancel;
+mod capture;
+mod clock;
+mod config;
+mod context;
+mod csv;
+mod deadlock;
+#[cfg(feature = "axum")]
+mod debug_router;
+mod delta;
+mod directives;
+mod event;
+mod fairness;
+mod fallback;
+#[cfg(feature = "test-util")]
+mod fence;
+mod filter;
+mod folded;
+mod group;
+mod guard;
+mod hint;
+mod history;
+mod json;
+mod lazy;
+mod lock_group;
+mod metrics_facade;
+#[cfg(feature = "derive")]
+mod named;
+mod once;
+mod operation;
+mod order;
+mod otel;
+mod overhead;
+mod owned;
+mod periodic;
+mod phase;
+mod polls;
+mod quiesce;
+#[cfg(feature = "parking-lot")]
+pub mod raw;
+mod recorder;
+mod redact;
+mod relabel;
+mod retention;
+mod registry;
+mod ring;
+mod scope;
+mod severity;
+mod shared;
+#[cfg(feature = "test-util")]
+mod simulate;
+mod snapshot;
+mod stall;
+mod stats;
+mod summary;
+mod sweeper;
+#[cfg(feature = "parking-lot")]
+pub mod sync;
+mod task;
+mod task_level;
+mod testing;
+mod timeline;
+mod traced;
+mod transaction;
+mod upgrade;
+mod value;
+mod verdict;
+
+pub use advisory::{advisories, set_advisory_thresholds, Advisory, AdvisoryThresholds, Finding};
+pub use barrier::Barrier;
+pub use batch::{batch_events, flush_events, EventBatchHandle};
+pub use binary::{
+    disable_binary_sink, enable_binary_sink, flush_binary_sink, replay_binary, BinaryEvent,
+};
+pub use breadcrumb::set_release_breadcrumbs;
+pub use builder::LockConfig;
+pub use callsite::{set_compact_callsites, CallSiteDecoder};
+pub use cancel::Cancelled;
+pub use capture::{hide_internal_frames, set_backtrace_depth, set_backtraces};
+#[cfg(feature = "test-clock")]
+pub use clock::test_clock;
+pub use config::{
+    audit_level, disable_all, idx_format, set_audit_level, set_idx_format, set_strict_access,
+    set_strict_levels, set_type_name_on_acquire, set_work_warn_threshold, strict_access,
+    strict_levels, type_name_on_acquire, work_warn_threshold, DisableGuard, IdxFormat,
+};
+pub use context::{current_context, with_context, LockContext, WithContext};
+#[cfg(feature = "axum")]
+pub use debug_router::debug_router;
+pub use deadlock::{deadlock_action, set_deadlock_action, DeadlockAction};
+pub use delta::{all_stats, LockChange, LockDelta, Snapshot, SnapshotDelta, SnapshotEntry};
+pub use directives::{set_directives, set_trace_only, DirectiveError};
+pub use event::{
+    announce_all, dropped_events, subscribe, subscribe_where, subscribe_with_capacity, LockEvent,
+};
+pub use fairness::{set_fairness_audit, FairnessReport};
+pub use fallback::{fallback, set_fallback, Fallback};
+#[cfg(feature = "test-util")]
+pub use fence::{clear_acquire_barrier, clear_acquire_barriers, set_acquire_barrier};
+pub use filter::HoldInfo;
+pub use folded::{export_folded, export_folded_split, set_folded_stacks, FoldedSplit};
+pub use group::{acquire_write_pair, write_all};
+pub use guard::{
+    AcquireReport, AcquireTiming, RwLockMappedWriteGuard, RwLockReadGuard, RwLockWriteGuard,
+};
+pub use hint::{set_hoist_hint_threshold, set_writer_wait_hint_threshold};
+pub use history::{current_task_lock_history, set_task_history_capacity};
+pub use lazy::StaticRwLock;
+pub use lock_group::LockGroup;
+#[cfg(feature = "metrics")]
+pub use metrics_facade::{set_metrics_labels, MetricsLabels};
+pub use once::OnceCell;
+pub use operation::{current_operation_id, with_operation_id, WithOperation};
+pub use order::declare_lock_order;
+pub use overhead::{overhead_report, OverheadConfig, OverheadCost, OverheadReport};
+pub use owned::{
+    OwnedRwLockMappedWriteGuard, OwnedRwLockReadGuard, OwnedRwLockWriteGuard, TransferToken,
+};
+pub use periodic::{enable_periodic_stats, PeriodicStatsHandle};
+pub use phase::{Mode, Phase};
+pub use polls::{set_hold_breakdown, track_polls, TrackPolls};
+pub use quiesce::{quiesce, LockHandle, QuiesceLock, QuiescePoint, QuiesceTimeout};
+pub use recorder::{
+    drain_recorded_events, recording_overflowed, set_recording_capacity, set_recording_overflow,
+    RecordingOverflow,
+};
+pub use redact::{clear_redactor, set_export_trusted, set_redactor, Export};
+pub use relabel::{relabel, LockInfo};
+pub use retention::{
+    compact, retention_policy, set_retention_policy, Compaction, RetentionPolicy,
+};
+pub use registry::{
+    dump_held_locks, render_held_table, set_drop_summaries, set_max_concurrent_guards,
+    CounterResync,
+};
+#[cfg(feature = "derive")]
+pub use rwlock_trace_derive::TracedLocks;
+pub use scope::{scope, Scope};
+pub use severity::{set_severity_policy, severity_policy, SeverityPolicy};
+pub use shared::Shared;
+pub use snapshot::{
+    snapshot, wait_for_graph, HolderSnapshot, LockSnapshot, LockState, RegistrySnapshot, WaitEdge,
+    WaiterSnapshot,
+};
+#[cfg(not(target_family = "wasm"))]
+pub use stall::block_in_place;
+pub use stall::{spawn_stall_detector, StallDetectorHandle};
+pub use stats::{
+    callsite_stats, contention_pairs, Fairness, LockStats, Site, SiteStats, StatsDiff,
+    StatsSnapshot,
+};
+pub use summary::{
+    export_call_sites_csv, export_stats_csv, print_summary_on_exit, summary, summary_json,
+    SummaryGuard,
+};
+pub use sweeper::{set_escalation_ladder, spawn_sweeper, SweeperHandle};
+pub use task::name_current_task;
+pub use task_level::{scoped_level, ScopedLevel};
+pub use transaction::{begin_transaction, TransactionGuard};
+pub use testing::{assert_acquired_before, assert_no_locks_held, with_lock_timeout};
+pub use timeline::{timeline, EventSource, Interval, SourceEvent, Timeline};
+pub use traced::{RawAsyncRwLock, RawOwnedRwLock};
+pub use upgrade::RwLockUpgradableReadGuard;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::group::{check_group, next_group};
+    #[cfg(feature = "derive")]
+    pub use crate::named::NamedLock;
+    pub use crate::testing::check_write_held;
+}
+
+use access::AccessRule;
+use clock::Instant;
+use filter::ReleaseFilter;
+use guard::{Acquisition, Source};
+use registry::LockMeta;
+use ring::EventRing;
+use value::ValueHooks;
+
+use tokio::sync::Semaphore;
 
 use std::{
-    marker::PhantomData, 
-    ops::{Deref, DerefMut},
+    any::{self, Any},
+    fmt::{self, Debug},
+    future::Future,
+    hash::Hash,
     panic::Location,
-    sync::atomic::{AtomicU64, Ordering},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
-const FRAME_OFFSET: usize = 3;
+const READ: Mode = Mode::Read;
+const WRITE: Mode = Mode::Write;
 
-#[derive(Debug)]
-pub struct RwLock<T> {
-    lock: tokio::sync::RwLock<T>,
-    name: String,
+// Tracing over any `RawAsyncRwLock`, for plugging in a lock other than tokio's,
+// as `async_lock` does with `async_lock::RwLock`. The events, stats, registry
+// entries and checks are the same whatever the backend. Owned guards need a
+// `RawOwnedRwLock`; cancellable acquires and simulated holds need tokio's
+// lock, so only `RwLock` has them.
+pub struct TracedLock<T, L: RawAsyncRwLock<T> = tokio::sync::RwLock<T>> {
+    lock: Arc<L>,
+    meta: Arc<LockMeta>,
     idx: AtomicU64,
+    hooks: ValueHooks<T>,
+    readers: Option<Arc<Semaphore>>,
+    upgrader: tokio::sync::Mutex<()>,
+}
+
+// Tokio's RwLock is write-preferring: once a writer queues, later readers
+// queue behind it, so readers cannot starve a writer but a long read hold
+// stalls every reader that comes after the writer. See
+// `set_writer_wait_hint_threshold`.
+pub type RwLock<T> = TracedLock<T>;
+
+// Not generic so `RwLock::builder()` needs no type annotation; the value's
+// type is fixed by `build`.
+impl RwLock<()> {
+    pub fn builder() -> LockConfig {
+        LockConfig::builder()
+    }
 }
 
-impl<'a, T: ?Sized> RwLock<T>
-where
-    T: Sized,
-{
-    pub fn new(inner: T) -> RwLock<T> {
-        let mut generator = Generator::default();
+impl<T, L: RawAsyncRwLock<T>> TracedLock<T, L> {
+    #[track_caller]
+    pub fn new(inner: T) -> Self {
+        LockConfig::builder().traced_lock(inner)
+    }
+
+    #[track_caller]
+    pub fn new_debug(inner: T) -> Self
+    where
+        T: Debug,
+    {
+        Self::new(inner).with_value_debug(value::DEFAULT_DEBUG_LEN)
+    }
+
+    #[track_caller]
+    pub fn new_named(inner: T, name: impl Into<String>) -> Self {
+        LockConfig::builder().name(name).traced_lock(inner)
+    }
 
+    // Named after the construction site instead of a random word.
+    #[track_caller]
+    pub fn new_here(inner: T) -> Self {
+        let name = LockConfig::site_name(Location::caller());
+        LockConfig::builder().name(name).traced_lock(inner)
+    }
+
+    #[track_caller]
+    pub fn named_const(name: &'static str, inner: T) -> Self {
+        LockConfig::builder().name(name).traced_lock(inner)
+    }
+
+    #[track_caller]
+    pub fn new_named_with_level(inner: T, name: impl Into<String>, level: u32) -> Self {
+        LockConfig::builder()
+            .name(name)
+            .order_level(level)
+            .traced_lock(inner)
+    }
+
+    pub(crate) fn with_scope(
+        inner: T,
+        scope: Scope,
+        name: String,
+        level: Option<u32>,
+    ) -> Self {
         Self {
-            lock: tokio::sync::RwLock::new(inner),
-            name: generator.next().unwrap(),
+            lock: Arc::new(L::new(inner)),
+            meta: LockMeta::registered(scope, name, any::type_name::<T>(), level),
             idx: AtomicU64::new(0),
+            hooks: ValueHooks::new(),
+            readers: None,
+            upgrader: tokio::sync::Mutex::new(()),
         }
     }
 
     #[track_caller]
-    pub async fn read(&'a self) -> RwLockReadGuard<'a, T> {
-        self.idx.fetch_add(1, Ordering::SeqCst);
-        let idx = self.idx.load(Ordering::SeqCst);
-        log_backtrace(
-            &format!("[READ] Acquire ({}:{})", self.name, idx),
-            Location::caller(),
-        );
-        RwLockReadGuard::new(self.lock.read().await, &self.name, idx)
+    pub fn with_max_readers(inner: T, max_readers: usize) -> Self {
+        LockConfig::builder().max_readers(max_readers).traced_lock(inner)
     }
 
+    // See `LockConfig::metadata` and `displayed_metadata`.
     #[track_caller]
-    pub async fn write(&'a self) -> RwLockWriteGuard<'a, T> {
-        self.idx.fetch_add(1, Ordering::SeqCst);
-        let idx = self.idx.load(Ordering::SeqCst);
-        log_backtrace(
-            &format!("[WRITE] Acquire ({}:{})", self.name, idx),
-            Location::caller(),
-        );
-        RwLockWriteGuard::new(self.lock.write().await, &self.name, idx)
+    pub fn with_metadata<M>(inner: T, metadata: M) -> Self
+    where
+        M: Any + Send + Sync,
+    {
+        LockConfig::builder().metadata(metadata).traced_lock(inner)
     }
-}
 
-#[derive(Debug)]
-pub struct RwLockReadGuard<'a, T: ?Sized> {
-    guard: tokio::sync::RwLockReadGuard<'a, T>,
-    name: &'a str,
-    idx: u64,
-}
+    #[track_caller]
+    pub fn with_displayed_metadata<M>(inner: T, metadata: M) -> Self
+    where
+        M: Any + fmt::Display + Send + Sync,
+    {
+        LockConfig::builder().displayed_metadata(metadata).traced_lock(inner)
+    }
+
+    pub fn with_release_filter<F>(self, filter: F) -> Self
+    where
+        F: Fn(&HoldInfo<'_>) -> bool + Send + Sync + 'static,
+    {
+        let _ = self.meta.release_filter.set(ReleaseFilter::new(filter));
+        self
+    }
+
+    // Writes from outside these modules, upgrades included, are logged at
+    // error with their call site, or panic under `set_strict_access`.
+    // `myapp::maintenance` covers `src/maintenance.rs` and
+    // `src/maintenance/` of `myapp`; the first restriction set sticks.
+    pub fn restrict_writes_to_modules(self, modules: &[&str]) -> Self {
+        let _ = self.meta.write_access.set(AccessRule::modules(modules));
+        self
+    }
+
+    pub fn restrict_reads_to_modules(self, modules: &[&str]) -> Self {
+        let _ = self.meta.read_access.set(AccessRule::modules(modules));
+        self
+    }
+
+    // For modules a file path cannot name, such as inline ones.
+    pub fn restrict_writes<F>(self, allowed: F) -> Self
+    where
+        F: Fn(&Location<'_>) -> bool + Send + Sync + 'static,
+    {
+        let _ = self.meta.write_access.set(AccessRule::new(allowed));
+        self
+    }
+
+    pub fn restrict_reads<F>(self, allowed: F) -> Self
+    where
+        F: Fn(&Location<'_>) -> bool + Send + Sync + 'static,
+    {
+        let _ = self.meta.read_access.set(AccessRule::new(allowed));
+        self
+    }
+
+    // Write guards that only read are reported on release. Mutation through
+    // interior mutability behind `&T` is not seen and shows up as a false
+    // positive; this turns the report off for the whole lock.
+    pub fn allow_unmutated_writes(self) -> Self {
+        self.meta.unmutated_writes_ok.store(true, Ordering::Relaxed);
+        self
+    }
+
+    // A read Got that leaves more than `max` readers active is logged at warn
+    // at least, to surface read storms. Zero, the default, turns it off.
+    pub fn set_reader_warn_threshold(&self, max: usize) {
+        self.meta.reader_warn.store(max, Ordering::Relaxed);
+    }
+
+    // Rebuilds the holder, reader and waiter counts from the registered holds
+    // and waits. If nothing holds the lock at that moment, a hold still
+    // registered has no guard behind it and is purged and reported. A guard
+    // leaked with `mem::forget` keeps the inner lock too, so it still counts.
+    pub fn resync_counters(&self) -> CounterResync {
+        let probe = self.lock.try_raw_write();
+        registry::resync(&self.meta, probe.is_some())
+    }
+
+    // Keeps this lock's last `capacity` events for `recent_events`, whatever
+    // directives and sinks are set. Off unless set; the memory bound is
+    // `capacity` events for this lock.
+    pub fn with_event_ring(self, capacity: usize) -> Self {
+        let _ = self.meta.event_ring.set(EventRing::new(capacity));
+        self
+    }
+
+    // Debugging aid: every async acquire sleeps this long first, widening race
+    // windows on a lock suspected of contention. Zero turns it back off.
+    pub fn set_acquire_delay(&self, delay: Duration) {
+        self.meta
+            .acquire_delay
+            .store(stats::duration_nanos(delay), Ordering::Relaxed);
+    }
+
+    pub fn with_change_tracking(mut self) -> Self
+    where
+        T: Hash,
+    {
+        self.hooks.set_hash_fingerprint();
+        self
+    }
+
+    pub fn with_fingerprint<F>(mut self, fingerprint: F) -> Self
+    where
+        F: Fn(&T) -> u64 + Send + Sync + 'static,
+    {
+        self.hooks.set_fingerprint(fingerprint);
+        self
+    }
+
+    pub fn with_value_debug(mut self, max_len: usize) -> Self
+    where
+        T: Debug,
+    {
+        self.hooks.set_debug(max_len);
+        self
+    }
+
+    #[doc(hidden)]
+    pub fn with_next_idx(self, idx: u64) -> Self {
+        self.idx.store(idx.wrapping_sub(1), Ordering::SeqCst);
+        self
+    }
+
+    // Unique for the life of the process and never reused, unlike names; the
+    // `lock_id` on events, snapshots and exports.
+    pub fn id(&self) -> u64 {
+        self.meta.id
+    }
+
+    // Can change under `relabel`, so it is handed out rather than borrowed.
+    pub fn name(&self) -> Arc<str> {
+        self.meta.name()
+    }
+
+    pub fn level(&self) -> Option<u32> {
+        self.meta.level
+    }
+
+    // None without metadata or when it is not an `M`.
+    pub fn metadata<M: Any>(&self) -> Option<&M> {
+        self.meta.metadata()
+    }
+
+    pub fn scope(&self) -> Scope {
+        self.meta.scope()
+    }
+
+    pub fn stats(&self) -> LockStats {
+        self.meta.stats.snapshot()
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot::take(&self.meta.stats)
+    }
+
+    // The last `n` events held by the event ring, oldest first; empty when it
+    // is off.
+    pub fn recent_events(&self, n: usize) -> Vec<LockEvent> {
+        self.meta
+            .event_ring
+            .get()
+            .map(|ring| ring.recent(n))
+            .unwrap_or_default()
+    }
+
+    pub fn fairness(&self) -> Fairness {
+        self.meta.stats.fairness()
+    }
+
+    // Empty unless `set_fairness_audit` was on while the lock was contended.
+    pub fn fairness_report(&self) -> FairnessReport {
+        self.meta.inversions.report()
+    }
+
+    pub fn waiter_count(&self) -> u64 {
+        self.meta.stats.waiters()
+    }
+
+    pub fn was_poisoned(&self) -> bool {
+        self.meta.poisoned.load(Ordering::Relaxed)
+    }
+
+    pub fn clear_poison(&self) {
+        self.meta.poisoned.store(false, Ordering::Relaxed);
+    }
 
-impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+    // For APIs that need the backend's lock itself. Nothing acquired through it is
+    // traced: holder counts, `waiter_count`, `dump_held_locks`, lock-order and
+    // deadlock checks all miss those holds. The first call from each call site
+    // logs a notice saying so.
     #[track_caller]
-    pub fn new(inner: tokio::sync::RwLockReadGuard<'a, T>, name: &'a str, idx: u64) -> Self {
-        let new = Self { guard: inner, name, idx };
-        log_backtrace(
-            &format!("[READ] Got ({}:{})", name, idx),
-            Location::caller(),
-        );
-        new
+    pub fn as_inner(&self) -> &L {
+        event::untraced(&self.meta, Location::caller());
+        &self.lock
+    }
+
+    // No tracing and no notice; see `as_inner` for what this bypasses.
+    pub async fn acquire_untraced_read(&self) -> L::ReadGuard<'_> {
+        self.lock.raw_read().await
+    }
+
+    // As `acquire_untraced_read`; change tracking and poisoning are skipped
+    // as well.
+    pub async fn acquire_untraced_write(&self) -> L::WriteGuard<'_> {
+        self.lock.raw_write().await
+    }
+
+    pub fn into_inner(self) -> T {
+        // Owned guards hold an `Arc` of this lock, so none can outlive `self`.
+        match Arc::try_unwrap(self.lock) {
+            Ok(lock) => lock.into_inner(),
+            Err(_) => unreachable!("inner lock shared after the traced lock was consumed"),
+        }
     }
-}
 
-impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
     #[track_caller]
-    fn drop(&mut self) {
-        log_backtrace(
-            &format!("[READ] Release ({}:{})", self.name, self.idx),
+    pub fn into_inner_logged(self) -> T {
+        event::destroyed(
+            &self.meta,
+            self.idx.load(Ordering::Relaxed),
             Location::caller(),
         );
+        self.into_inner()
     }
-}
 
-impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
-    type Target = tokio::sync::RwLockReadGuard<'a, T>;
+    // Restarts idx numbering, but only while nothing holds or waits on the lock
+    // so no in-flight acquisition straddles the reset; returns whether it
+    // happened. A `[LOCK] Counter reset` marker makes the jump explicit.
+    #[track_caller]
+    pub fn reset_counter(&self) -> bool {
+        if !self.meta.availability.is_unlocked() || self.meta.stats.waiters() > 0 {
+            return false;
+        }
+        let previous = self.idx.swap(0, Ordering::SeqCst);
+        event::counter_reset(&self.meta, previous, Location::caller());
+        true
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.guard
+    #[track_caller]
+    pub fn assert_unlocked(&self) {
+        testing::assert_unlocked(&self.meta);
     }
-}
 
-impl<'a, T: ?Sized> DerefMut for RwLockReadGuard<'a, T> {
-    fn deref_mut(&mut self) -> &mut tokio::sync::RwLockReadGuard<'a, T> {
-        &mut self.guard
+    // Resolves once no guard from this lock is outstanding, without queueing
+    // on the lock. Wakeups can be spurious: another task may take the lock
+    // before the caller gets to act, so treat this as a hint to retry.
+    pub async fn unlocked(&self) {
+        self.meta.availability.wait_unlocked().await
     }
-}
 
-#[derive(Debug)]
-pub struct RwLockMappedWriteGuard<'a, T: ?Sized> {
-    data: *mut T,
-    marker: PhantomData<&'a mut T>,
-}
+    pub fn on_release<F>(&self, callback: F)
+    where
+        F: Fn(&LockEvent) + Send + Sync + 'static,
+    {
+        self.meta.availability.add_callback(Arc::new(callback));
+    }
 
-impl<T: ?Sized> Deref for RwLockMappedWriteGuard<'_, T> {
-    type Target = T;
+    #[track_caller]
+    pub fn read(&self) -> impl Future<Output = RwLockReadGuard<'_, T, L>> {
+        let caller = Location::caller();
 
-    fn deref(&self) -> &T {
-        unsafe { &*self.data }
+        async move {
+            let acquisition = self.begin(READ, Acquisition::new(caller));
+            self.acquire_read(acquisition).await
+        }
     }
-}
 
-impl<T: ?Sized> DerefMut for RwLockMappedWriteGuard<'_, T> {
-    fn deref_mut(&mut self) -> &mut T {
-        unsafe { &mut *self.data }
+    #[track_caller]
+    pub fn read_timed(
+        &self,
+    ) -> impl Future<Output = (RwLockReadGuard<'_, T, L>, AcquireTiming)> {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(READ, Acquisition::new(caller));
+            let guard = self.acquire_read(acquisition).await;
+            let timing = guard.state.timing();
+            (guard, timing)
+        }
     }
-}
 
-#[derive(Debug)]
-pub struct RwLockWriteGuard<'a, T: ?Sized> {
-    guard: tokio::sync::RwLockWriteGuard<'a, T>,
-    name: &'a str,
-    idx: u64,
-}
+    // The same measurements the events carry, for callers that act on them,
+    // e.g. shedding a request when `report.waited` is over its budget.
+    #[track_caller]
+    pub fn read_verbose(
+        &self,
+    ) -> impl Future<Output = (RwLockReadGuard<'_, T, L>, AcquireReport)> {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(READ, Acquisition::new(caller));
+            let guard = self.acquire_read(acquisition).await;
+            let report = guard.state.acquire_report();
+            (guard, report)
+        }
+    }
 
-impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
     #[track_caller]
-    pub fn new(inner: tokio::sync::RwLockWriteGuard<'a, T>, name: &'a str, idx: u64) -> Self {
-        let new = Self { guard: inner, name, idx };
-        log_backtrace(
-            &format!("[WRITE] Got ({}:{})", name, idx),
-            Location::caller(),
-        );
-        new
+    pub fn try_read_for(
+        &self,
+        attempts: u32,
+    ) -> impl Future<Output = Option<RwLockReadGuard<'_, T, L>>> {
+        let caller = Location::caller();
+
+        async move {
+            let mut acquisition = self.begin(READ, Acquisition::new(caller));
+            acquisition.throttle(&self.meta, READ).await;
+            let start = Instant::now();
+            let mut yields = 0;
+            let guard = loop {
+                match self.lock.try_raw_read() {
+                    Some(guard) => break Some(guard),
+                    None if yields + 1 < attempts => {
+                        tokio::task::yield_now().await;
+                        yields += 1;
+                    }
+                    None => break None,
+                }
+            };
+            acquisition.yields = Some(yields);
+
+            let guard = match guard {
+                Some(guard) => guard,
+                None => {
+                    acquisition.waited = start.elapsed();
+                    acquisition.emit(&self.meta, READ, Phase::GiveUp, caller, "");
+                    return None;
+                }
+            };
+            acquisition.record_wait(&self.meta, READ, start, yields > 0);
+
+            Some(RwLockReadGuard::acquired(
+                guard,
+                self.meta.clone(),
+                acquisition,
+                Some(self.source()),
+            ))
+        }
+    }
+
+    #[track_caller]
+    pub fn write(&self) -> impl Future<Output = RwLockWriteGuard<'_, T, L>> {
+        self.write_at(Location::caller())
+    }
+
+    #[track_caller]
+    pub fn write_timed(
+        &self,
+    ) -> impl Future<Output = (RwLockWriteGuard<'_, T, L>, AcquireTiming)> {
+        let caller = Location::caller();
+
+        async move {
+            let guard = self.write_at(caller).await;
+            let timing = guard.state.timing();
+            (guard, timing)
+        }
+    }
+
+    #[track_caller]
+    pub fn write_verbose(
+        &self,
+    ) -> impl Future<Output = (RwLockWriteGuard<'_, T, L>, AcquireReport)> {
+        let caller = Location::caller();
+
+        async move {
+            let guard = self.write_at(caller).await;
+            let report = guard.state.acquire_report();
+            (guard, report)
+        }
+    }
+
+    pub(crate) async fn write_at(
+        &self,
+        caller: &'static Location<'static>,
+    ) -> RwLockWriteGuard<'_, T, L> {
+        let acquisition = self.begin(WRITE, Acquisition::new(caller));
+        self.acquire_write(acquisition).await
+    }
+
+    // Only one upgradable reader exists at a time; plain readers are not
+    // held up by it. See `RwLockUpgradableReadGuard::upgrade`.
+    #[track_caller]
+    pub fn upgradable_read(
+        &self,
+    ) -> impl Future<Output = RwLockUpgradableReadGuard<'_, T, L>> {
+        let caller = Location::caller();
+
+        async move {
+            // Taken before the read, so a second upgrader queues here holding
+            // nothing rather than holding a read the first one must outlast.
+            let upgrader = self.upgrader.lock().await;
+            let acquisition = self.begin(READ, Acquisition::new(caller));
+            RwLockUpgradableReadGuard::new(self, self.acquire_read(acquisition).await, upgrader)
+        }
     }
 
-    pub fn try_map<F, U: ?Sized>(
-        mut this: Self,
-        f: F,
-    ) -> Result<RwLockMappedWriteGuard<'a, U>, Self>
+    #[track_caller]
+    pub fn read_upgradable(
+        &self,
+    ) -> impl Future<Output = RwLockUpgradableReadGuard<'_, T, L>> {
+        self.upgradable_read()
+    }
+
+    #[track_caller]
+    pub fn write_audited(
+        &self,
+        reason: &str,
+    ) -> impl Future<Output = RwLockWriteGuard<'_, T, L>> {
+        let caller = Location::caller();
+        let reason = reason.to_owned();
+
+        async move {
+            let acquisition = self.begin(
+                WRITE,
+                Acquisition {
+                    reason: Some(reason),
+                    ..Acquisition::new(caller)
+                },
+            );
+            self.acquire_write(acquisition).await
+        }
+    }
+
+    #[track_caller]
+    pub fn read_with<'a, R, F>(&'a self, f: F) -> impl Future<Output = R> + 'a
     where
-        F: FnOnce(&mut T) -> Option<&mut U>,
+        F: FnOnce(&T) -> R + 'a,
     {
-        let data = match f(&mut *this) {
-            Some(data) => data as *mut U,
-            None => return Err(this),
-        };
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(
+                READ,
+                Acquisition {
+                    scoped: true,
+                    ..Acquisition::new(caller)
+                },
+            );
+            let guard = self.acquire_read(acquisition).await;
+            f(&guard)
+        }
+    }
 
-        std::mem::forget(this);
+    // `read_with` for async work: `f`'s future runs under the read guard,
+    // which is released once it completes. Everything the future awaits keeps
+    // writers out, so the release reports the time it spent suspended, and a
+    // suspension past the hold warn threshold is warned about on its own.
+    #[track_caller]
+    pub fn with_read_async<'a, R, F>(&'a self, f: F) -> impl Future<Output = R> + 'a
+    where
+        F: AsyncFnOnce(&T) -> R + 'a,
+    {
+        let caller = Location::caller();
 
-        Ok(RwLockMappedWriteGuard {
-            data,
-            marker: PhantomData,
-        })
+        async move {
+            let acquisition = self.begin(
+                READ,
+                Acquisition {
+                    scoped: true,
+                    ..Acquisition::new(caller)
+                },
+            );
+            let mut guard = self.acquire_read(acquisition).await;
+            let (output, awaited) = polls::timed(f(&guard)).await;
+            guard.state.record_awaited(READ, awaited);
+            output
+        }
     }
-}
 
-impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
     #[track_caller]
-    fn drop(&mut self) {
-        log_backtrace(
-            &format!("[WRITE] Release ({}:{})", self.name, self.idx),
-            Location::caller(),
+    pub fn write_with<'a, R, F>(&'a self, f: F) -> impl Future<Output = R> + 'a
+    where
+        F: FnOnce(&mut T) -> R + 'a,
+    {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(
+                WRITE,
+                Acquisition {
+                    scoped: true,
+                    ..Acquisition::new(caller)
+                },
+            );
+            let mut guard = self.acquire_write(acquisition).await;
+            f(&mut guard)
+        }
+    }
+
+    // A scoped read, so the snapshot shows up as one timed section and the
+    // guard never outlives the clone.
+    #[track_caller]
+    pub fn clone_inner(&self) -> impl Future<Output = T> + '_
+    where
+        T: Clone,
+    {
+        self.read_with(T::clone)
+    }
+
+    #[doc(hidden)]
+    #[track_caller]
+    pub fn read_in_group(&self, group: u64) -> impl Future<Output = RwLockReadGuard<'_, T, L>> {
+        self.read_grouped(group, Location::caller())
+    }
+
+    pub(crate) async fn read_grouped(
+        &self,
+        group: u64,
+        caller: &'static Location<'static>,
+    ) -> RwLockReadGuard<'_, T, L> {
+        let acquisition = self.begin(
+            READ,
+            Acquisition {
+                group: Some(group),
+                ..Acquisition::new(caller)
+            },
         );
+        self.acquire_read(acquisition).await
     }
-}
 
-impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
-    type Target = tokio::sync::RwLockWriteGuard<'a, T>;
+    #[doc(hidden)]
+    #[track_caller]
+    pub fn write_in_group(
+        &self,
+        group: u64,
+    ) -> impl Future<Output = RwLockWriteGuard<'_, T, L>> {
+        self.write_grouped(group, Location::caller())
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.guard
+    pub(crate) async fn write_grouped(
+        &self,
+        group: u64,
+        caller: &'static Location<'static>,
+    ) -> RwLockWriteGuard<'_, T, L> {
+        let acquisition = self.begin(
+            WRITE,
+            Acquisition {
+                group: Some(group),
+                ..Acquisition::new(caller)
+            },
+        );
+        self.acquire_write(acquisition).await
+    }
+
+    #[doc(hidden)]
+    pub fn ordering_key(&self) -> u64 {
+        self.meta.id
+    }
+
+    fn begin(&self, mode: Mode, acquisition: Acquisition) -> Acquisition {
+        acquisition.begin(&self.meta, &self.idx, mode)
+    }
+
+    fn source(&self) -> Source<'_, T, L> {
+        Source {
+            lock: &self.lock,
+            meta: &self.meta,
+            idx: &self.idx,
+            hooks: &self.hooks,
+            readers: self.readers.as_ref(),
+        }
+    }
+
+    async fn reader_permit(&self, acquisition: &mut Acquisition) -> bool {
+        self.source().reader_permit(acquisition).await
+    }
+
+    async fn acquire_read(&self, acquisition: Acquisition) -> RwLockReadGuard<'_, T, L> {
+        self.source().acquire_read(acquisition).await
+    }
+
+    async fn acquire_write(&self, acquisition: Acquisition) -> RwLockWriteGuard<'_, T, L> {
+        self.source().acquire_write(acquisition).await
     }
 }
 
-impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
-    fn deref_mut(&mut self) -> &mut tokio::sync::RwLockWriteGuard<'a, T> {
-        &mut self.guard
+// Double-checked initialization for lazily filled state. A read returns the
+// value when it is already there; only an empty lock is written, and the check
+// is repeated under the write so a task that lost the race takes the winner's
+// value instead of running `f` again.
+impl<V, L: RawAsyncRwLock<Option<V>>> TracedLock<Option<V>, L> {
+    #[track_caller]
+    pub fn get_or_init_with<'a, F>(&'a self, f: F) -> impl Future<Output = V> + 'a
+    where
+        V: Clone,
+        F: FnOnce() -> V + 'a,
+    {
+        let caller = Location::caller();
+
+        async move {
+            let guard = self.acquire_read(self.begin(READ, Acquisition::new(caller))).await;
+            let current: &Option<V> = &guard;
+            if let Some(value) = current {
+                return value.clone();
+            }
+            guard
+                .state
+                .acquisition
+                .emit(&self.meta, READ, Phase::Init, caller, " empty, taking write");
+            drop(guard);
+
+            let mut guard = self.acquire_write(self.begin(WRITE, Acquisition::new(caller))).await;
+            let current: &Option<V> = &guard;
+            if let Some(value) = current {
+                let value = value.clone();
+                guard.allow_no_mutation();
+                guard.state.acquisition.emit(
+                    &self.meta,
+                    WRITE,
+                    Phase::Init,
+                    caller,
+                    " already initialized by another task",
+                );
+                return value;
+            }
+            let started = Instant::now();
+            let value = f();
+            let slot: &mut Option<V> = &mut guard;
+            *slot = Some(value.clone());
+            let details = format!(" initialized in {:?}", started.elapsed());
+            guard
+                .state
+                .acquisition
+                .emit(&self.meta, WRITE, Phase::Init, caller, &details);
+            value
+        }
     }
 }
 
-fn log_backtrace(message: &str, caller: &'static Location<'static>) {
-    log::warn!("{}: {}:{}:{}", message, caller.file(), caller.line(), caller.column());
+// Never formats `T`: tokio's Debug would read the value, and locks often guard
+// secrets.
+impl<T, L: RawAsyncRwLock<T>> Debug for TracedLock<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RwLock")
+            .field("name", &self.meta.name())
+            .field("idx", &self.idx.load(Ordering::Relaxed))
+            .field("kind", &L::KIND)
+            .finish()
+    }
 }
 
-unsafe impl<T> Send for RwLockWriteGuard<'_, T> where T: ?Sized + Send + Sync {}
-unsafe impl<T> Send for RwLockMappedWriteGuard<'_, T> where T: ?Sized + Send + Sync {}
-unsafe impl<T> Sync for RwLockWriteGuard<'_, T> where T: ?Sized + Send + Sync {}
-unsafe impl<T> Sync for RwLockMappedWriteGuard<'_, T> where T: ?Sized + Send + Sync {}
+impl<T, L: RawAsyncRwLock<T>> fmt::Display for TracedLock<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.meta.name())
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{
+    registry::LockMeta,
+    scope::Scope,
+    stats::{LockStats, Stats},
+    LockConfig, RwLock,
+};
+
+use std::{
+    iter,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+};
+
+#[derive(Debug, Clone)]
+pub struct LockGroup {
+    inner: Arc<LockGroupInner>,
+}
+
+#[derive(Debug)]
+struct LockGroupInner {
+    name: String,
+    scope: Scope,
+    members: Mutex<Vec<(usize, Weak<LockMeta>)>>,
+    retired: Arc<Stats>,
+}
+
+impl LockGroup {
+    pub fn new(name: impl Into<String>) -> LockGroup {
+        Scope::root().lock_group(name)
+    }
+
+    pub(crate) fn with_scope(scope: &Scope, name: String) -> LockGroup {
+        LockGroup {
+            inner: Arc::new(LockGroupInner {
+                name,
+                scope: scope.detached_child(),
+                members: Mutex::new(Vec::new()),
+                retired: Arc::new(Stats::new()),
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    // Level, sampling and muting set here apply to every member, on top of
+    // whatever the enclosing scope configures.
+    pub fn scope(&self) -> &Scope {
+        &self.inner.scope
+    }
+
+    #[track_caller]
+    pub fn rwlock<T>(&self, index: usize, inner: T) -> RwLock<T> {
+        let name = format!("{}[{}]", self.inner.name, index);
+        let lock = LockConfig::builder()
+            .scope(&self.inner.scope)
+            .name(name)
+            .rwlock(inner);
+        let _ = lock.meta.group.set(self.inner.name.clone());
+        let _ = lock.meta.group_totals.set(self.inner.retired.clone());
+        self.members().push((index, Arc::downgrade(&lock.meta)));
+        lock
+    }
+
+    // Includes members already dropped.
+    pub fn stats(&self) -> LockStats {
+        let members = self.live_members();
+        Stats::rollup(
+            members
+                .iter()
+                .map(|(_, meta)| &meta.stats)
+                .chain(iter::once(&*self.inner.retired)),
+        )
+    }
+
+    pub fn member_stats(&self) -> Vec<(usize, LockStats)> {
+        self.live_members()
+            .into_iter()
+            .map(|(index, meta)| (index, meta.stats.snapshot()))
+            .collect()
+    }
+
+    fn members(&self) -> MutexGuard<'_, Vec<(usize, Weak<LockMeta>)>> {
+        self.inner.members.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn live_members(&self) -> Vec<(usize, Arc<LockMeta>)> {
+        let mut members = self.members();
+        members.retain(|(_, meta)| meta.strong_count() > 0);
+        let mut live: Vec<_> = members
+            .iter()
+            .filter_map(|(index, meta)| Some((*index, meta.upgrade()?)))
+            .collect();
+        live.sort_by_key(|(index, _)| *index);
+        live
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{phase::Mode, registry::LockMeta};
+
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+pub use enabled::{set_metrics_labels, MetricsLabels};
+
+#[cfg(feature = "metrics")]
+mod enabled {
+    use crate::{
+        phase::Mode,
+        redact::{self, Export},
+        registry::LockMeta,
+        READ,
+    };
+
+    use metrics::{counter, gauge, histogram};
+
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    static BY_TYPE: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum MetricsLabels {
+        LockName,
+        TypeName,
+    }
+
+    pub fn set_metrics_labels(labels: MetricsLabels) {
+        BY_TYPE.store(labels == MetricsLabels::TypeName, Ordering::Relaxed);
+    }
+
+    fn label(meta: &LockMeta) -> String {
+        if BY_TYPE.load(Ordering::Relaxed) {
+            meta.type_name.to_owned()
+        } else {
+            redact::redact(Export::Metrics, &meta.name()).into_owned()
+        }
+    }
+
+    fn group_label(meta: &LockMeta) -> String {
+        meta.group
+            .get()
+            .map(|group| redact::redact(Export::Metrics, group).into_owned())
+            .unwrap_or_default()
+    }
+
+    fn mode_label(mode: Mode) -> &'static str {
+        if mode == READ {
+            "read"
+        } else {
+            "write"
+        }
+    }
+
+    pub(crate) fn on_got(meta: &LockMeta, mode: Mode, waited: Duration) {
+        let lock = label(meta);
+        let group = group_label(meta);
+        let mode = mode_label(mode);
+        counter!(
+            "rwlock.acquisitions",
+            "lock" => lock.clone(),
+            "group" => group.clone(),
+            "mode" => mode
+        )
+        .increment(1);
+        histogram!(
+            "rwlock.wait_seconds",
+            "lock" => lock.clone(),
+            "group" => group.clone(),
+            "mode" => mode
+        )
+        .record(waited.as_secs_f64());
+        gauge!("rwlock.held", "lock" => lock, "group" => group).increment(1.0);
+    }
+
+    pub(crate) fn on_release(meta: &LockMeta, mode: Mode, held: Duration) {
+        let lock = label(meta);
+        let group = group_label(meta);
+        let mode = mode_label(mode);
+        histogram!(
+            "rwlock.hold_seconds",
+            "lock" => lock.clone(),
+            "group" => group.clone(),
+            "mode" => mode
+        )
+        .record(held.as_secs_f64());
+        gauge!("rwlock.held", "lock" => lock, "group" => group).decrement(1.0);
+    }
+
+    pub(crate) fn on_stuck(meta: &LockMeta, stuck: bool) {
+        let gauge = gauge!("rwlock.stuck", "lock" => label(meta), "group" => group_label(meta));
+        if stuck {
+            gauge.increment(1.0);
+        } else {
+            gauge.decrement(1.0);
+        }
+    }
+}
+
+#[inline(always)]
+pub(crate) fn on_got(meta: &LockMeta, mode: Mode, waited: Duration) {
+    #[cfg(feature = "metrics")]
+    enabled::on_got(meta, mode, waited);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (meta, mode, waited);
+}
+
+#[inline(always)]
+pub(crate) fn on_release(meta: &LockMeta, mode: Mode, held: Duration) {
+    #[cfg(feature = "metrics")]
+    enabled::on_release(meta, mode, held);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (meta, mode, held);
+}
+
+#[inline(always)]
+pub(crate) fn on_stuck(meta: &LockMeta, stuck: bool) {
+    #[cfg(feature = "metrics")]
+    enabled::on_stuck(meta, stuck);
+    #[cfg(not(feature = "metrics"))]
+    let _ = (meta, stuck);
+}
@@ -0,0 +1,28 @@
+use crate::{traced::RawAsyncRwLock, TracedLock};
+
+// Lets the TracedLocks derive build a lock from the field's declared type, so
+// aliases and generic parameters work without the macro knowing the backend.
+pub trait NamedLock {
+    type Inner;
+
+    fn new_named(inner: Self::Inner, name: &str) -> Self;
+}
+
+#[cfg(feature = "parking-lot")]
+impl<T> NamedLock for crate::sync::RwLock<T> {
+    type Inner = T;
+
+    #[track_caller]
+    fn new_named(inner: T, name: &str) -> Self {
+        crate::sync::RwLock::new_named(inner, name)
+    }
+}
+
+impl<T, L: RawAsyncRwLock<T>> NamedLock for TracedLock<T, L> {
+    type Inner = T;
+
+    #[track_caller]
+    fn new_named(inner: T, name: &str) -> Self {
+        TracedLock::new_named(inner, name)
+    }
+}
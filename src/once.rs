@@ -0,0 +1,265 @@
+use crate::{clock::Instant, fallback, severity, task::TaskKey, LockConfig};
+
+use log::Level;
+use tokio::sync::SetError;
+
+use std::{
+    fmt,
+    future::{self, Future},
+    panic::Location,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    task::Poll,
+};
+
+// Only initialization is traced: `get`, `set` and calls on a cell that is
+// already set go straight to tokio's. Tokio does not say who is queued behind
+// an init, so a caller still pending without having run its own initializer
+// is counted here as waiting on someone else's.
+pub struct OnceCell<T> {
+    inner: tokio::sync::OnceCell<T>,
+    name: String,
+    in_flight: AtomicBool,
+    waiters: AtomicUsize,
+    waits: AtomicU64,
+}
+
+impl<T> OnceCell<T> {
+    pub fn new() -> OnceCell<T> {
+        Self::new_named(LockConfig::generated_name("once"))
+    }
+
+    pub fn new_named(name: impl Into<String>) -> OnceCell<T> {
+        OnceCell {
+            inner: tokio::sync::OnceCell::new(),
+            name: name.into(),
+            in_flight: AtomicBool::new(false),
+            waiters: AtomicUsize::new(0),
+            waits: AtomicU64::new(0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.inner.get()
+    }
+
+    pub fn set(&self, value: T) -> Result<(), SetError<T>> {
+        self.inner.set(value)
+    }
+
+    pub fn initialized(&self) -> bool {
+        self.inner.initialized()
+    }
+
+    // Callers currently parked behind another caller's initializer.
+    pub fn waiters(&self) -> usize {
+        self.waiters.load(Ordering::Relaxed)
+    }
+
+    // Every caller that has had to wait, over the cell's lifetime.
+    pub fn total_waits(&self) -> u64 {
+        self.waits.load(Ordering::Relaxed)
+    }
+
+    #[track_caller]
+    pub fn get_or_init<'a, F, Fut>(&'a self, f: F) -> impl Future<Output = &'a T> + 'a
+    where
+        F: FnOnce() -> Fut + 'a,
+        Fut: Future<Output = T> + 'a,
+    {
+        let caller = Location::caller();
+
+        async move {
+            if let Some(value) = self.inner.get() {
+                return value;
+            }
+            let ran = AtomicBool::new(false);
+            let init = self.inner.get_or_init(|| {
+                let init = self.start_init(&ran, caller);
+                async move {
+                    let value = f().await;
+                    init.finish(None);
+                    value
+                }
+            });
+            self.watch(init, &ran, caller).await
+        }
+    }
+
+    #[track_caller]
+    pub fn get_or_try_init<'a, E, F, Fut>(
+        &'a self,
+        f: F,
+    ) -> impl Future<Output = Result<&'a T, E>> + 'a
+    where
+        F: FnOnce() -> Fut + 'a,
+        Fut: Future<Output = Result<T, E>> + 'a,
+    {
+        let caller = Location::caller();
+
+        async move {
+            if let Some(value) = self.inner.get() {
+                return Ok(value);
+            }
+            let ran = AtomicBool::new(false);
+            let init = self.inner.get_or_try_init(|| {
+                let init = self.start_init(&ran, caller);
+                async move {
+                    let result = f().await;
+                    init.finish(Some(result.is_ok()));
+                    result
+                }
+            });
+            self.watch(init, &ran, caller).await
+        }
+    }
+
+    fn start_init(&self, ran: &AtomicBool, caller: &'static Location<'static>) -> Init<'_, T> {
+        ran.store(true, Ordering::Relaxed);
+        self.in_flight.store(true, Ordering::Relaxed);
+        self.log(
+            severity::severity_policy().acquire,
+            "Init-Start",
+            "",
+            caller,
+        );
+        Init {
+            cell: self,
+            caller,
+            started: Instant::now(),
+            finished: false,
+        }
+    }
+
+    async fn watch<R>(
+        &self,
+        init: impl Future<Output = R>,
+        ran: &AtomicBool,
+        caller: &'static Location<'static>,
+    ) -> R {
+        tokio::pin!(init);
+        let mut waiting: Option<Waiter<'_, T>> = None;
+
+        let result = future::poll_fn(|cx| {
+            if let Poll::Ready(result) = init.as_mut().poll(cx) {
+                return Poll::Ready(result);
+            }
+            if waiting.is_none() && !ran.load(Ordering::Relaxed) {
+                waiting = Some(self.start_waiting(caller));
+            }
+            Poll::Pending
+        })
+        .await;
+
+        if let Some(waiter) = waiting {
+            let details = format!(" after {:?}", waiter.since.elapsed());
+            self.log(
+                severity::severity_policy().release,
+                "Wait-Done",
+                &details,
+                caller,
+            );
+        }
+        result
+    }
+
+    fn start_waiting(&self, caller: &'static Location<'static>) -> Waiter<'_, T> {
+        let waiters = self.waiters.fetch_add(1, Ordering::Relaxed) + 1;
+        self.waits.fetch_add(1, Ordering::Relaxed);
+        let details = if self.in_flight.load(Ordering::Relaxed) {
+            format!(" behind in-flight init waiters={}", waiters)
+        } else {
+            format!(" waiters={}", waiters)
+        };
+        self.log(
+            severity::severity_policy().acquire,
+            "Wait",
+            &details,
+            caller,
+        );
+        Waiter {
+            cell: self,
+            since: Instant::now(),
+        }
+    }
+
+    fn log(&self, level: Level, phase: &str, details: &str, caller: &'static Location<'static>) {
+        fallback::log!(
+            level,
+            "[ONCE] {} ({}){} {}: {}:{}:{}",
+            phase,
+            self.name,
+            details,
+            TaskKey::current(),
+            caller.file(),
+            caller.line(),
+            caller.column(),
+        );
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// An initializer that is dropped before it finishes is reported as
+// cancelled; tokio lets the next caller run its own.
+struct Init<'a, T> {
+    cell: &'a OnceCell<T>,
+    caller: &'static Location<'static>,
+    started: Instant,
+    finished: bool,
+}
+
+impl<T> Init<'_, T> {
+    // `ok` is None for an initializer that cannot fail.
+    fn finish(mut self, ok: Option<bool>) {
+        self.finished = true;
+        let policy = severity::severity_policy();
+        let (level, phase) = match ok {
+            Some(false) => (policy.anomaly, "Init-Failed"),
+            _ => (policy.release, "Init-Done"),
+        };
+        let details = format!(" after {:?}", self.started.elapsed());
+        self.cell.log(level, phase, &details, self.caller);
+    }
+}
+
+impl<T> Drop for Init<'_, T> {
+    fn drop(&mut self) {
+        self.cell.in_flight.store(false, Ordering::Relaxed);
+        if !self.finished {
+            let details = format!(" after {:?}", self.started.elapsed());
+            let level = severity::severity_policy().anomaly;
+            self.cell
+                .log(level, "Init-Cancelled", &details, self.caller);
+        }
+    }
+}
+
+struct Waiter<'a, T> {
+    cell: &'a OnceCell<T>,
+    since: Instant,
+}
+
+impl<T> Drop for Waiter<'_, T> {
+    fn drop(&mut self) {
+        self.cell.waiters.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Never formats `T`, as with the locks.
+impl<T> fmt::Debug for OnceCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnceCell")
+            .field("name", &self.name)
+            .field("initialized", &self.inner.initialized())
+            .field("waiters", &self.waiters())
+            .finish()
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<str>>> = const { RefCell::new(None) };
+}
+
+// While `future` is polled, lock events, held and pending entries and dumps
+// carry `op=<id>`: the caller's unit of work, such as a request, rather than
+// anything about one acquisition. The innermost operation wins and the outer
+// one is back once it completes. Acquisitions keep the id they started under,
+// so a guard released elsewhere still reports it.
+pub fn with_operation_id<F: Future>(id: impl Into<String>, future: F) -> WithOperation<F> {
+    WithOperation {
+        inner: future,
+        id: Arc::from(id.into()),
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct WithOperation<F> {
+    inner: F,
+    id: Arc<str>,
+}
+
+impl<F: Future> Future for WithOperation<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Safety: `inner` is never moved out of the pinned wrapper.
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+        let id = this.id.clone();
+        let previous = CURRENT.with(|current| current.replace(Some(id)));
+        let _restore = Restore(previous);
+        inner.poll(cx)
+    }
+}
+
+pub(crate) struct Restore(Option<Arc<str>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        let _ = CURRENT.try_with(|current| current.replace(previous));
+    }
+}
+
+pub fn current_operation_id() -> Option<String> {
+    current().map(|id| id.to_string())
+}
+
+// Events for an acquisition report the operation it started under, wherever
+// its guard ends up. Without one, whatever is current stays, as it does for
+// a guard dropped while the thread's locals are torn down.
+pub(crate) fn enter(id: Option<Arc<str>>) -> Option<Restore> {
+    let id = id?;
+    let previous = CURRENT.try_with(|current| current.replace(Some(id))).ok()?;
+    Some(Restore(previous))
+}
+
+// As with the context, None once the thread's locals are torn down.
+pub(crate) fn current() -> Option<Arc<str>> {
+    CURRENT
+        .try_with(|current| current.borrow().clone())
+        .ok()
+        .flatten()
+}
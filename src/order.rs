@@ -0,0 +1,153 @@
+use crate::{
+    config::{self, Idx},
+    context, deadlock, fallback,
+    phase::Mode,
+    registry::{self, HeldEntry, LockMeta},
+    task::TaskKey,
+};
+
+use log::Level;
+
+use std::{
+    collections::HashMap,
+    panic::Location,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+static HAS_DECLARED_ORDER: AtomicBool = AtomicBool::new(false);
+static DECLARED_ORDER: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+pub fn declare_lock_order(names: &[&str]) {
+    let ranks: HashMap<String, usize> = names
+        .iter()
+        .enumerate()
+        .map(|(rank, name)| ((*name).to_owned(), rank))
+        .collect();
+
+    HAS_DECLARED_ORDER.store(!ranks.is_empty(), Ordering::Relaxed);
+    *DECLARED_ORDER.lock().unwrap_or_else(|e| e.into_inner()) = Some(ranks);
+}
+
+// Holds inherited through `with_context` count as held here, so a wait in a
+// spawned helper is checked against what its parent holds.
+pub(crate) fn check(meta: &LockMeta, mode: Mode, idx: u64, caller: &'static Location<'static>) {
+    let mut held = registry::held_by(TaskKey::current());
+    held.extend(context::inherited());
+    check_level(meta, mode, idx, caller, &held);
+    check_declared_order(meta, mode, idx, caller, &held);
+    deadlock::check(meta, caller, &held);
+}
+
+fn check_declared_order(
+    meta: &LockMeta,
+    mode: Mode,
+    idx: u64,
+    caller: &'static Location<'static>,
+    held: &[HeldEntry],
+) {
+    if !HAS_DECLARED_ORDER.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let order = DECLARED_ORDER.lock().unwrap_or_else(|e| e.into_inner());
+    let ranks = match order.as_ref() {
+        Some(ranks) => ranks,
+        None => return,
+    };
+    let rank = match ranks.get(&*meta.name()) {
+        Some(rank) => *rank,
+        None => return,
+    };
+
+    for entry in held {
+        match ranks.get(&entry.name) {
+            Some(held_rank) if *held_rank > rank => fallback::log!(
+                Level::Error,
+                "[ORDER VIOLATION] acquired {} while holding {}: [{}] ({}:{}) at {}:{}:{}, \
+                 ({}:{}) held since {}:{}:{}{}",
+                meta.name(),
+                entry.name,
+                mode,
+                meta.name(),
+                Idx(idx),
+                caller.file(),
+                caller.line(),
+                caller.column(),
+                entry.name,
+                Idx(entry.idx),
+                entry.caller.file(),
+                entry.caller.line(),
+                entry.caller.column(),
+                match entry.inherited {
+                    Some(context) => format!(" (inherited via ctx={})", context),
+                    None => String::new(),
+                },
+            ),
+            _ => {}
+        }
+    }
+}
+
+fn check_level(
+    meta: &LockMeta,
+    mode: Mode,
+    idx: u64,
+    caller: &'static Location<'static>,
+    held: &[HeldEntry],
+) {
+    let level = match meta.level {
+        Some(level) => level,
+        None => return,
+    };
+
+    let highest = held
+        .iter()
+        .filter_map(|entry| entry.level.map(|held_level| (held_level, entry)))
+        .max_by_key(|(held_level, _)| *held_level);
+
+    if let Some((held_level, entry)) = highest {
+        if level <= held_level {
+            report_level_violation(meta, level, mode, idx, caller, entry, held);
+        }
+    }
+}
+
+fn report_level_violation(
+    meta: &LockMeta,
+    level: u32,
+    mode: Mode,
+    idx: u64,
+    caller: &'static Location<'static>,
+    highest: &HeldEntry,
+    held: &[HeldEntry],
+) {
+    let mut message = format!(
+        "[LEVEL VIOLATION] [{}] Acquire ({}:{}) level={} while holding ({}:{}) level={}: {}:{}:{} \
+         (held since {}:{}:{})",
+        mode,
+        meta.name(),
+        Idx(idx),
+        level,
+        highest.name,
+        Idx(highest.idx),
+        highest.level.unwrap_or_default(),
+        caller.file(),
+        caller.line(),
+        caller.column(),
+        highest.caller.file(),
+        highest.caller.line(),
+        highest.caller.column(),
+    );
+    for entry in held {
+        message.push_str("\n    ");
+        message.push_str(&entry.describe());
+    }
+
+    if config::strict_levels() {
+        panic!("{}", message);
+    }
+    fallback::log!(Level::Error, "{}", message);
+}
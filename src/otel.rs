@@ -0,0 +1,96 @@
+use crate::{phase::Mode, registry::LockMeta};
+
+use std::time::Duration;
+
+#[cfg(feature = "opentelemetry")]
+mod enabled {
+    use crate::{
+        phase::Mode,
+        redact::{self, Export},
+        registry::LockMeta,
+        READ,
+    };
+
+    use opentelemetry::{
+        global::{self, BoxedSpan},
+        trace::{Span, Tracer},
+        Context, KeyValue,
+    };
+
+    use std::{fmt, time::Duration};
+
+    // Parented on whatever context is current when the lock is taken.
+    pub(crate) struct HoldSpan(Option<BoxedSpan>);
+
+    impl fmt::Debug for HoldSpan {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("HoldSpan")
+                .field("open", &self.0.is_some())
+                .finish()
+        }
+    }
+
+    pub(crate) fn on_got(
+        meta: &LockMeta,
+        mode: Mode,
+        idx: u64,
+        waited: Duration,
+        operation: Option<&str>,
+    ) -> HoldSpan {
+        let tracer = global::tracer("rwlock-trace");
+        let mut span = tracer.start_with_context("rwlock.hold", &Context::current());
+        span.set_attributes([
+            KeyValue::new("lock.id", meta.id as i64),
+            KeyValue::new(
+                "lock.name",
+                redact::redact(Export::OpenTelemetry, &meta.name()).into_owned(),
+            ),
+            KeyValue::new("lock.kind", if mode == READ { "read" } else { "write" }),
+            KeyValue::new("lock.idx", idx as i64),
+            KeyValue::new("lock.wait_ms", waited.as_secs_f64() * 1000.0),
+        ]);
+        if let Some(operation) = operation {
+            span.set_attribute(KeyValue::new("operation.id", operation.to_owned()));
+        }
+        HoldSpan(Some(span))
+    }
+
+    pub(crate) fn on_release(span: &mut HoldSpan, held: Duration) {
+        if let Some(mut span) = span.0.take() {
+            span.set_attribute(KeyValue::new("lock.hold_ms", held.as_secs_f64() * 1000.0));
+            span.end();
+        }
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
+pub(crate) use enabled::HoldSpan;
+
+#[cfg(not(feature = "opentelemetry"))]
+#[derive(Debug)]
+pub(crate) struct HoldSpan;
+
+#[inline(always)]
+pub(crate) fn on_got(
+    meta: &LockMeta,
+    mode: Mode,
+    idx: u64,
+    waited: Duration,
+    operation: Option<&str>,
+) -> HoldSpan {
+    #[cfg(feature = "opentelemetry")]
+    return enabled::on_got(meta, mode, idx, waited, operation);
+    #[cfg(not(feature = "opentelemetry"))]
+    {
+        let _ = (meta, mode, idx, waited, operation);
+        HoldSpan
+    }
+}
+
+#[inline(always)]
+pub(crate) fn on_release(span: &mut HoldSpan, held: Duration) {
+    #[cfg(feature = "opentelemetry")]
+    enabled::on_release(span, held);
+    #[cfg(not(feature = "opentelemetry"))]
+    let _ = (span, held);
+}
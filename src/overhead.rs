@@ -0,0 +1,203 @@
+use crate::{
+    batch, binary, breadcrumb, capture,
+    config::{self, IdxFormat},
+    directives, event, fairness, folded, recorder, stats, LockConfig,
+};
+
+use log::{Level, LevelFilter};
+
+use std::{
+    fmt,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+// A batch is timed once it runs this long, and the fastest of a few runs of
+// it counts, so a preemption does not skew the result.
+const CALIBRATE: Duration = Duration::from_millis(2);
+const MAX_BATCH: u64 = 1 << 16;
+const SAMPLES: usize = 3;
+
+// One uncontended acquire and release, in nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverheadCost {
+    pub read_nanos: u64,
+    pub write_nanos: u64,
+}
+
+// The global settings that change what an acquisition costs, as they were
+// when the report was taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverheadConfig {
+    pub logging: bool,
+    pub max_level: LevelFilter,
+    pub directives: bool,
+    pub backtraces: bool,
+    pub binary_sink: bool,
+    pub recording: bool,
+    pub subscribers: bool,
+    pub event_batching: bool,
+    pub folded_stacks: bool,
+    pub fairness_audit: bool,
+    pub release_breadcrumbs: bool,
+    pub type_name_on_acquire: bool,
+    pub idx_format: IdxFormat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverheadReport {
+    // A plain tokio lock, the floor everything else is measured against.
+    pub untraced: OverheadCost,
+    // A muted lock: registry and stats only. Stats have no switch of their
+    // own, so this is also what they cost.
+    pub muted: OverheadCost,
+    pub traced: OverheadCost,
+    // None without the `backtrace` feature.
+    pub backtraces: Option<OverheadCost>,
+    pub config: OverheadConfig,
+}
+
+// Times uncontended acquire-release pairs on locks of its own, on the calling
+// task, in well under a second. Nothing runs it implicitly. The traced locks
+// log at trace, so a service logging at debug or above pays for the events
+// without its log filling up; their events still reach subscribers, the
+// recorder and the binary sink like any lock's, under the names
+// `rwlock_trace.overhead.*`. Timing uses the real clock even under
+// `test-clock`.
+pub async fn overhead_report() -> OverheadReport {
+    tokio::task::unconstrained(measure()).await
+}
+
+async fn measure() -> OverheadReport {
+    let untraced = tokio::sync::RwLock::new(0u64);
+    let untraced = OverheadCost {
+        read_nanos: per_op(|| async { drop(untraced.read().await) }).await,
+        write_nanos: per_op(|| async { *untraced.write().await += 1 }).await,
+    };
+
+    let muted = LockConfig::builder()
+        .name("rwlock_trace.overhead.muted")
+        .muted(true);
+    let muted = cost(muted).await;
+    let traced = LockConfig::builder()
+        .name("rwlock_trace.overhead.traced")
+        .level(Level::Trace);
+    let traced = cost(traced).await;
+    let backtraces = if cfg!(feature = "backtrace") {
+        let config = LockConfig::builder()
+            .name("rwlock_trace.overhead.backtraces")
+            .level(Level::Trace)
+            .backtrace(true);
+        Some(cost(config).await)
+    } else {
+        None
+    };
+
+    OverheadReport {
+        untraced,
+        muted,
+        traced,
+        backtraces,
+        config: OverheadConfig::current(),
+    }
+}
+
+async fn cost(config: LockConfig) -> OverheadCost {
+    let lock = config.build(0u64);
+    OverheadCost {
+        read_nanos: per_op(|| async { drop(lock.read().await) }).await,
+        write_nanos: per_op(|| async { **lock.write().await += 1 }).await,
+    }
+}
+
+async fn per_op<F, Fut>(mut op: F) -> u64
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut batch = 1;
+    while batch < MAX_BATCH && run(&mut op, batch).await < CALIBRATE {
+        batch *= 2;
+    }
+    let mut best = Duration::MAX;
+    for _ in 0..SAMPLES {
+        best = best.min(run(&mut op, batch).await);
+    }
+    stats::duration_nanos(best) / batch
+}
+
+async fn run<F, Fut>(op: &mut F, batch: u64) -> Duration
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let start = Instant::now();
+    for _ in 0..batch {
+        op().await;
+    }
+    start.elapsed()
+}
+
+impl OverheadConfig {
+    fn current() -> Self {
+        Self {
+            logging: config::logging_enabled(),
+            max_level: log::max_level(),
+            directives: directives::active(),
+            backtraces: capture::enabled(),
+            binary_sink: binary::enabled(),
+            recording: recorder::enabled(),
+            subscribers: event::has_subscribers(),
+            event_batching: batch::enabled(),
+            folded_stacks: folded::enabled(),
+            fairness_audit: fairness::enabled(),
+            release_breadcrumbs: breadcrumb::enabled(),
+            type_name_on_acquire: config::type_name_on_acquire(),
+            idx_format: config::idx_format(),
+        }
+    }
+}
+
+impl fmt::Display for OverheadCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}ns", self.read_nanos, self.write_nanos)
+    }
+}
+
+impl fmt::Display for OverheadConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            ("logging", self.logging),
+            ("directives", self.directives),
+            ("backtraces", self.backtraces),
+            ("binary", self.binary_sink),
+            ("recording", self.recording),
+            ("subscribers", self.subscribers),
+            ("batching", self.event_batching),
+            ("folded", self.folded_stacks),
+            ("fairness", self.fairness_audit),
+            ("breadcrumbs", self.release_breadcrumbs),
+            ("type_names", self.type_name_on_acquire),
+        ];
+        write!(f, "max_level={}", self.max_level)?;
+        for (name, on) in flags {
+            write!(f, " {}={}", name, if on { "on" } else { "off" })?;
+        }
+        write!(f, " idx={:?}", self.idx_format)
+    }
+}
+
+// One line, for logging at startup.
+impl fmt::Display for OverheadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[META] overhead per acquire+release (read/write): untraced {}, muted {}, traced {}",
+            self.untraced, self.muted, self.traced
+        )?;
+        if let Some(backtraces) = self.backtraces {
+            write!(f, ", backtraces {}", backtraces)?;
+        }
+        write!(f, "; {}", self.config)
+    }
+}
@@ -0,0 +1,703 @@
+use crate::{
+    clock::Instant,
+    guard::{Acquisition, GuardState},
+    phase::Phase,
+    polls,
+    registry::LockMeta,
+    traced::RawOwnedRwLock,
+    TracedLock, READ, WRITE,
+};
+
+use std::{
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    panic::Location,
+    ptr,
+    sync::Arc,
+};
+
+impl<T, L: RawOwnedRwLock<T>> TracedLock<T, L> {
+    #[track_caller]
+    pub fn read_owned(self: Arc<Self>) -> impl Future<Output = OwnedRwLockReadGuard<T, T, L>> {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(READ, Acquisition::new(caller));
+            self.acquire_read_owned(acquisition).await
+        }
+    }
+
+    #[track_caller]
+    pub fn write_owned(self: Arc<Self>) -> impl Future<Output = OwnedRwLockWriteGuard<T, L>> {
+        let caller = Location::caller();
+
+        async move {
+            let acquisition = self.begin(WRITE, Acquisition::new(caller));
+            self.acquire_write_owned(acquisition).await
+        }
+    }
+
+    pub(crate) async fn acquire_read_owned(
+        self: Arc<Self>,
+        mut acquisition: Acquisition,
+    ) -> OwnedRwLockReadGuard<T, T, L> {
+        acquisition.throttle(&self.meta, READ).await;
+        let start = Instant::now();
+        let permit_contended = self.reader_permit(&mut acquisition).await;
+        let (guard, contended) = match self.lock.clone().try_raw_read_owned() {
+            Some(guard) => (guard, permit_contended),
+            None => {
+                let _waiting = acquisition.start_waiting(&self.meta, READ);
+                (self.lock.clone().raw_read_owned().await, true)
+            }
+        };
+        acquisition.record_wait(&self.meta, READ, start, contended);
+
+        let guard = OwnedRwLockReadGuard::acquired(guard, self.meta.clone(), acquisition, self);
+        guard.state.fence(READ).await;
+        guard
+    }
+
+    pub(crate) async fn acquire_write_owned(
+        self: Arc<Self>,
+        mut acquisition: Acquisition,
+    ) -> OwnedRwLockWriteGuard<T, L> {
+        acquisition.throttle(&self.meta, WRITE).await;
+        let start = Instant::now();
+        let (guard, contended) = match self.lock.clone().try_raw_write_owned() {
+            Some(guard) => (guard, false),
+            None => {
+                let _waiting = acquisition.start_waiting(&self.meta, WRITE);
+                (self.lock.clone().raw_write_owned().await, true)
+            }
+        };
+        acquisition.record_wait(&self.meta, WRITE, start, contended);
+
+        let fingerprint = self.hooks.fingerprint(&guard);
+        let guard = OwnedRwLockWriteGuard {
+            state: GuardState::got(self.meta.clone(), acquisition, WRITE),
+            guard,
+            lock: self,
+            mutated: false,
+            fingerprint,
+        };
+        guard.state.fence(WRITE).await;
+        guard
+    }
+
+    #[track_caller]
+    pub fn try_read_owned(self: Arc<Self>) -> Option<OwnedRwLockReadGuard<T, T, L>> {
+        let caller = Location::caller();
+        let mut acquisition = self.begin(READ, Acquisition::new(caller));
+        let start = Instant::now();
+
+        let permit = match &self.readers {
+            Some(readers) => match readers.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    acquisition.emit(&self.meta, READ, Phase::GiveUp, caller, "");
+                    return None;
+                }
+            },
+            None => None,
+        };
+        let guard = match self.lock.clone().try_raw_read_owned() {
+            Some(guard) => guard,
+            None => {
+                acquisition.emit(&self.meta, READ, Phase::GiveUp, caller, "");
+                return None;
+            }
+        };
+        acquisition.permit = permit;
+        acquisition.record_wait(&self.meta, READ, start, false);
+
+        let meta = self.meta.clone();
+        Some(OwnedRwLockReadGuard::acquired(
+            guard,
+            meta,
+            acquisition,
+            self,
+        ))
+    }
+
+    #[track_caller]
+    pub fn try_write_owned(self: Arc<Self>) -> Option<OwnedRwLockWriteGuard<T, L>> {
+        let caller = Location::caller();
+        let mut acquisition = self.begin(WRITE, Acquisition::new(caller));
+        let start = Instant::now();
+
+        let guard = match self.lock.clone().try_raw_write_owned() {
+            Some(guard) => guard,
+            None => {
+                acquisition.emit(&self.meta, WRITE, Phase::GiveUp, caller, "");
+                return None;
+            }
+        };
+        acquisition.record_wait(&self.meta, WRITE, start, false);
+
+        let fingerprint = self.hooks.fingerprint(&guard);
+        Some(OwnedRwLockWriteGuard {
+            state: GuardState::got(self.meta.clone(), acquisition, WRITE),
+            guard,
+            lock: self,
+            mutated: false,
+            fingerprint,
+        })
+    }
+}
+
+// Mapping only moves `data`, so one guard type serves every backend.
+pub struct OwnedRwLockReadGuard<T, U: ?Sized = T, L: RawOwnedRwLock<T> = tokio::sync::RwLock<T>> {
+    guard: L::OwnedReadGuard,
+    pub(crate) state: GuardState,
+    // Keeps `RwLock::into_inner` from seeing the lock unshared while this is
+    // held, as the write guards' `lock` does.
+    _lock: Arc<TracedLock<T, L>>,
+    data: *const U,
+}
+
+impl<T, L: RawOwnedRwLock<T>> OwnedRwLockReadGuard<T, T, L> {
+    fn acquired(
+        guard: L::OwnedReadGuard,
+        meta: Arc<LockMeta>,
+        acquisition: Acquisition,
+        lock: Arc<TracedLock<T, L>>,
+    ) -> Self {
+        let data: *const T = &*guard;
+        OwnedRwLockReadGuard {
+            guard,
+            state: GuardState::got(meta, acquisition, READ),
+            _lock: lock,
+            data,
+        }
+    }
+}
+
+impl<T, U: ?Sized, L: RawOwnedRwLock<T>> OwnedRwLockReadGuard<T, U, L> {
+    pub fn acquired_at(&self) -> &'static Location<'static> {
+        self.state.acquisition.caller
+    }
+
+    // Where the guard sits under its acquisition once mapped, as in its Map
+    // events.
+    pub fn path(&self) -> Option<&str> {
+        self.state.path()
+    }
+
+    pub fn record_work(&mut self, bytes: usize, note: &str) {
+        self.state.record_work(bytes, note);
+    }
+
+    pub fn debug_value(&self) -> &U
+    where
+        U: fmt::Debug,
+    {
+        self
+    }
+
+    #[track_caller]
+    pub fn map<F, V: ?Sized>(mut this: Self, f: F) -> OwnedRwLockReadGuard<T, V, L>
+    where
+        F: FnOnce(&U) -> &V,
+    {
+        let data = f(&this) as *const V;
+        this.state.mapped::<V>(READ, Phase::Map, Location::caller());
+        this.remap(data)
+    }
+
+    #[track_caller]
+    #[allow(clippy::result_large_err)]
+    pub fn try_map<F, V: ?Sized>(
+        mut this: Self,
+        f: F,
+    ) -> Result<OwnedRwLockReadGuard<T, V, L>, Self>
+    where
+        F: FnOnce(&U) -> Option<&V>,
+    {
+        match f(&this) {
+            Some(data) => {
+                let data = data as *const V;
+                this.state.mapped::<V>(READ, Phase::Map, Location::caller());
+                Ok(this.remap(data))
+            }
+            None => {
+                this.state
+                    .mapped::<V>(READ, Phase::MapFailed, Location::caller());
+                Err(this)
+            }
+        }
+    }
+
+    fn remap<V: ?Sized>(self, data: *const V) -> OwnedRwLockReadGuard<T, V, L> {
+        let this = ManuallyDrop::new(self);
+        // Safety: `this` is never dropped, so each field is moved out exactly once.
+        unsafe {
+            OwnedRwLockReadGuard {
+                guard: ptr::read(&this.guard),
+                state: ptr::read(&this.state),
+                _lock: ptr::read(&this._lock),
+                data,
+            }
+        }
+    }
+}
+
+impl<T, U: ?Sized, L: RawOwnedRwLock<T>> Drop for OwnedRwLockReadGuard<T, U, L> {
+    #[track_caller]
+    fn drop(&mut self) {
+        self.state
+            .release(READ, Location::caller(), false, String::new);
+    }
+}
+
+impl<T, U: ?Sized, L: RawOwnedRwLock<T>> Deref for OwnedRwLockReadGuard<T, U, L> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        // Safety: `data` points into the value behind `guard`, which keeps it
+        // shared for as long as this guard is alive.
+        unsafe { &*self.data }
+    }
+}
+
+impl<T, U: ?Sized, L: RawOwnedRwLock<T>> fmt::Debug for OwnedRwLockReadGuard<T, U, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.state.fmt_guard(f, "OwnedRwLockReadGuard", READ)
+    }
+}
+
+pub struct OwnedRwLockWriteGuard<T, L: RawOwnedRwLock<T> = tokio::sync::RwLock<T>> {
+    guard: L::OwnedWriteGuard,
+    state: GuardState,
+    lock: Arc<TracedLock<T, L>>,
+    mutated: bool,
+    fingerprint: Option<u64>,
+}
+
+impl<T, L: RawOwnedRwLock<T>> OwnedRwLockWriteGuard<T, L> {
+    pub fn acquired_at(&self) -> &'static Location<'static> {
+        self.state.acquisition.caller
+    }
+
+    pub fn record_work(&mut self, bytes: usize, note: &str) {
+        self.state.record_work(bytes, note);
+    }
+
+    pub fn debug_value(&self) -> &T
+    where
+        T: fmt::Debug,
+    {
+        &self.guard
+    }
+
+    // Opts this guard out of the release-without-mutation report.
+    pub fn allow_no_mutation(&mut self) {
+        self.state.skip_mutation_check();
+    }
+
+    // See `RwLockWriteGuard::warn_if_held_through_blocking`.
+    pub fn warn_if_held_through_blocking(&mut self) {
+        self.state.watch_blocking();
+    }
+
+    // See `RwLockWriteGuard::release_with_reason`.
+    #[track_caller]
+    pub fn release_with_reason(mut self, reason: &str) {
+        self.state.give_reason(reason, Location::caller());
+    }
+
+    // See `RwLockWriteGuard::hold_during`.
+    #[track_caller]
+    pub fn hold_during<F: Future>(mut self, future: F) -> impl Future<Output = F::Output> {
+        let caller = Location::caller();
+
+        async move {
+            let (output, awaited) = polls::timed(future).await;
+            self.state.held_during(awaited, caller);
+            output
+        }
+    }
+
+    // Marks the guard as deliberately passed to another task, which finishes
+    // and releases it after `redeem`. The release is then reported with the
+    // label rather than as a migration.
+    #[track_caller]
+    pub fn transfer(mut self, label: &str) -> TransferToken<T, L> {
+        self.state.hand_off(WRITE, label, Location::caller());
+        TransferToken {
+            guard: self,
+            since: Instant::now(),
+        }
+    }
+
+    #[track_caller]
+    pub fn map<F, U: ?Sized>(mut this: Self, f: F) -> OwnedRwLockMappedWriteGuard<T, U, L>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let data = f(&mut *this.guard) as *mut U;
+        this.state
+            .mapped::<U>(WRITE, Phase::Map, Location::caller());
+        this.into_mapped(data)
+    }
+
+    #[track_caller]
+    #[allow(clippy::result_large_err)]
+    pub fn try_map<F, U: ?Sized>(
+        mut this: Self,
+        f: F,
+    ) -> Result<OwnedRwLockMappedWriteGuard<T, U, L>, Self>
+    where
+        F: FnOnce(&mut T) -> Option<&mut U>,
+    {
+        let data = match f(&mut *this.guard) {
+            Some(data) => data as *mut U,
+            None => {
+                this.state
+                    .mapped::<U>(WRITE, Phase::MapFailed, Location::caller());
+                return Err(this);
+            }
+        };
+        this.state
+            .mapped::<U>(WRITE, Phase::Map, Location::caller());
+        Ok(this.into_mapped(data))
+    }
+
+    fn into_mapped<U: ?Sized>(self, data: *mut U) -> OwnedRwLockMappedWriteGuard<T, U, L> {
+        let this = ManuallyDrop::new(self);
+        // Safety: `this` is never dropped, so each field is moved out exactly once.
+        unsafe {
+            OwnedRwLockMappedWriteGuard {
+                guard: ptr::read(&this.guard),
+                state: ptr::read(&this.state),
+                lock: ptr::read(&this.lock),
+                mutated: this.mutated,
+                fingerprint: this.fingerprint,
+                data,
+                marker: PhantomData,
+            }
+        }
+    }
+}
+
+impl<T, L: RawOwnedRwLock<T>> Drop for OwnedRwLockWriteGuard<T, L> {
+    #[track_caller]
+    fn drop(&mut self) {
+        let (guard, hooks, fingerprint) = (&self.guard, &self.lock.hooks, self.fingerprint);
+        self.state
+            .release(WRITE, Location::caller(), self.mutated, || {
+                hooks.release_annotations(guard, fingerprint)
+            });
+    }
+}
+
+impl<T, L: RawOwnedRwLock<T>> Deref for OwnedRwLockWriteGuard<T, L> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T, L: RawOwnedRwLock<T>> DerefMut for OwnedRwLockWriteGuard<T, L> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.mutated = true;
+        &mut self.guard
+    }
+}
+
+impl<T, L: RawOwnedRwLock<T>> fmt::Debug for OwnedRwLockWriteGuard<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.state.fmt_guard(f, "OwnedRwLockWriteGuard", WRITE)
+    }
+}
+
+// Dropping a token without redeeming it releases the lock where it is dropped.
+#[must_use = "the lock is released when the token is dropped"]
+pub struct TransferToken<T, L: RawOwnedRwLock<T> = tokio::sync::RwLock<T>> {
+    guard: OwnedRwLockWriteGuard<T, L>,
+    since: Instant,
+}
+
+impl<T, L: RawOwnedRwLock<T>> TransferToken<T, L> {
+    #[track_caller]
+    pub fn redeem(mut self) -> OwnedRwLockWriteGuard<T, L> {
+        let after = self.since.elapsed();
+        self.guard.state.resume(WRITE, after, Location::caller());
+        self.guard
+    }
+}
+
+impl<T, L: RawOwnedRwLock<T>> fmt::Debug for TransferToken<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.guard.state.fmt_guard(f, "TransferToken", WRITE)
+    }
+}
+
+// The full write guard is kept rather than a mapped one so the whole value is
+// still reachable for change tracking when the lock is released.
+pub struct OwnedRwLockMappedWriteGuard<T, U: ?Sized, L = tokio::sync::RwLock<T>>
+where
+    L: RawOwnedRwLock<T>,
+{
+    guard: L::OwnedWriteGuard,
+    state: GuardState,
+    lock: Arc<TracedLock<T, L>>,
+    mutated: bool,
+    fingerprint: Option<u64>,
+    data: *mut U,
+    marker: PhantomData<U>,
+}
+
+impl<T, U: ?Sized, L: RawOwnedRwLock<T>> OwnedRwLockMappedWriteGuard<T, U, L> {
+    pub fn acquired_at(&self) -> &'static Location<'static> {
+        self.state.acquisition.caller
+    }
+
+    // Where the guard sits under its acquisition, as in its Map events.
+    pub fn path(&self) -> &str {
+        self.state.path().unwrap_or_default()
+    }
+
+    pub fn record_work(&mut self, bytes: usize, note: &str) {
+        self.state.record_work(bytes, note);
+    }
+
+    pub fn debug_value(&self) -> &U
+    where
+        U: fmt::Debug,
+    {
+        self
+    }
+
+    #[track_caller]
+    pub fn map<F, V: ?Sized>(mut this: Self, f: F) -> OwnedRwLockMappedWriteGuard<T, V, L>
+    where
+        F: FnOnce(&mut U) -> &mut V,
+    {
+        // Safety: as in `deref_mut`, without marking the value mutated.
+        let data = f(unsafe { &mut *this.data }) as *mut V;
+        this.state
+            .mapped::<V>(WRITE, Phase::Map, Location::caller());
+        this.remap(data)
+    }
+
+    #[track_caller]
+    #[allow(clippy::result_large_err)]
+    pub fn try_map<F, V: ?Sized>(
+        mut this: Self,
+        f: F,
+    ) -> Result<OwnedRwLockMappedWriteGuard<T, V, L>, Self>
+    where
+        F: FnOnce(&mut U) -> Option<&mut V>,
+    {
+        // Safety: as in `deref_mut`, without marking the value mutated.
+        match f(unsafe { &mut *this.data }) {
+            Some(data) => {
+                let data = data as *mut V;
+                this.state
+                    .mapped::<V>(WRITE, Phase::Map, Location::caller());
+                Ok(this.remap(data))
+            }
+            None => {
+                this.state
+                    .mapped::<V>(WRITE, Phase::MapFailed, Location::caller());
+                Err(this)
+            }
+        }
+    }
+
+    fn remap<V: ?Sized>(self, data: *mut V) -> OwnedRwLockMappedWriteGuard<T, V, L> {
+        let this = ManuallyDrop::new(self);
+        // Safety: `this` is never dropped, so each field is moved out exactly once.
+        unsafe {
+            OwnedRwLockMappedWriteGuard {
+                guard: ptr::read(&this.guard),
+                state: ptr::read(&this.state),
+                lock: ptr::read(&this.lock),
+                mutated: this.mutated,
+                fingerprint: this.fingerprint,
+                data,
+                marker: PhantomData,
+            }
+        }
+    }
+}
+
+impl<T, U: ?Sized, L: RawOwnedRwLock<T>> Drop for OwnedRwLockMappedWriteGuard<T, U, L> {
+    #[track_caller]
+    fn drop(&mut self) {
+        let (guard, hooks, fingerprint) = (&self.guard, &self.lock.hooks, self.fingerprint);
+        self.state
+            .release(WRITE, Location::caller(), self.mutated, || {
+                hooks.release_annotations(guard, fingerprint)
+            });
+    }
+}
+
+impl<T, U: ?Sized, L: RawOwnedRwLock<T>> Deref for OwnedRwLockMappedWriteGuard<T, U, L> {
+    type Target = U;
+
+    fn deref(&self) -> &U {
+        // Safety: `data` points into the value behind `guard`, which is held
+        // exclusively for as long as this guard is alive.
+        unsafe { &*self.data }
+    }
+}
+
+impl<T, U: ?Sized, L: RawOwnedRwLock<T>> DerefMut for OwnedRwLockMappedWriteGuard<T, U, L> {
+    fn deref_mut(&mut self) -> &mut U {
+        self.mutated = true;
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<T, U: ?Sized, L: RawOwnedRwLock<T>> fmt::Debug for OwnedRwLockMappedWriteGuard<T, U, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.state
+            .fmt_guard(f, "OwnedRwLockMappedWriteGuard", WRITE)
+    }
+}
+
+unsafe impl<T, U, L> Send for OwnedRwLockReadGuard<T, U, L>
+where
+    T: Send + Sync,
+    U: ?Sized + Sync,
+    L: RawOwnedRwLock<T> + Send + Sync,
+    L::OwnedReadGuard: Send,
+{
+}
+unsafe impl<T, U, L> Sync for OwnedRwLockReadGuard<T, U, L>
+where
+    T: Send + Sync,
+    U: ?Sized + Sync,
+    L: RawOwnedRwLock<T> + Send + Sync,
+    L::OwnedReadGuard: Sync,
+{
+}
+unsafe impl<T, U, L> Send for OwnedRwLockMappedWriteGuard<T, U, L>
+where
+    T: Send + Sync,
+    U: ?Sized + Send + Sync,
+    L: RawOwnedRwLock<T> + Send + Sync,
+    L::OwnedWriteGuard: Send,
+{
+}
+unsafe impl<T, U, L> Sync for OwnedRwLockMappedWriteGuard<T, U, L>
+where
+    T: Send + Sync,
+    U: ?Sized + Send + Sync,
+    L: RawOwnedRwLock<T> + Send + Sync,
+    L::OwnedWriteGuard: Sync,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        OwnedRwLockMappedWriteGuard, OwnedRwLockReadGuard, OwnedRwLockWriteGuard, Phase, RwLock,
+    };
+
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn releases_elsewhere_are_marked_as_migrations() {
+        let lock = Arc::new(RwLock::new_named(0u8, "owned-migration"));
+        let id = lock.id();
+        let mut releases = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.phase == Phase::Release
+        });
+
+        drop(lock.clone().write_owned().await);
+        let write = lock.clone().write_owned().await;
+        tokio::spawn(async move { drop(write) }).await.unwrap();
+        let read = lock.clone().read_owned().await;
+        std::thread::spawn(move || drop(read)).join().unwrap();
+
+        let details: Vec<String> = std::iter::from_fn(|| releases.try_recv().ok())
+            .map(|event| event.message)
+            .collect();
+        assert_eq!(details.len(), 3);
+        assert!(!details[0].contains("migrated_from"), "{}", details[0]);
+        let to_task = details[1].split(" migrated_from ").nth(1).unwrap();
+        assert!(to_task.starts_with("thread=") && to_task.contains(" -> task="));
+        let to_thread = details[2].split(" migrated_from ").nth(1).unwrap();
+        assert!(to_thread.starts_with("thread=") && to_thread.contains(" -> thread="));
+        assert_eq!(lock.stats().migrations, 2);
+    }
+
+    #[tokio::test]
+    async fn owned_maps_that_are_only_read_are_reported_unmutated() {
+        let lock = Arc::new(RwLock::new_named((1u8, Some(2u8)), "owned-map-unmutated"));
+        let id = lock.id();
+        let mut unmutated = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.phase == Phase::Unmutated
+        });
+
+        let write = lock.clone().write_owned().await;
+        let mapped = OwnedRwLockWriteGuard::map(write, |pair| &mut pair.1);
+        let field = OwnedRwLockMappedWriteGuard::try_map(mapped, Option::as_mut).unwrap();
+        assert_eq!(*field, 2);
+        drop(field);
+        let write = lock.clone().write_owned().await;
+        let mapped = OwnedRwLockWriteGuard::try_map(write, |pair| pair.1.as_mut());
+        let field = OwnedRwLockMappedWriteGuard::map(mapped.unwrap(), |value| value);
+        assert_eq!(*field, 2);
+        drop(field);
+        let idxs: Vec<u64> = std::iter::from_fn(|| unmutated.try_recv().ok())
+            .map(|event| event.idx)
+            .collect();
+        assert_eq!(idxs, [1, 2]);
+
+        let write = lock.clone().write_owned().await;
+        let mut field = OwnedRwLockWriteGuard::map(write, |pair| &mut pair.0);
+        *field += 1;
+        drop(field);
+        assert!(unmutated.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn a_failed_owned_try_map_keeps_the_read_traced() {
+        let lock = Arc::new(RwLock::new_named(vec![1u8], "owned-map-failed"));
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| {
+            event.lock_id == id && matches!(event.phase, Phase::MapFailed | Phase::Release)
+        });
+
+        let read = lock.clone().read_owned().await;
+        let read = OwnedRwLockReadGuard::try_map(read, |value| value.get(1))
+            .expect_err("nothing to map to");
+        assert_eq!(read[0], 1);
+        let failed = events.try_recv().unwrap();
+        assert_eq!((failed.phase, failed.idx), (Phase::MapFailed, 1));
+        assert!(failed
+            .message
+            .starts_with("[READ] MapFailed (owned-map-failed:1)"));
+
+        drop(read);
+        let release = events.try_recv().unwrap();
+        assert_eq!((release.phase, release.idx), (Phase::Release, 1));
+        assert!(lock.clone().try_write_owned().is_some());
+    }
+
+    #[tokio::test]
+    async fn owned_read_guards_keep_the_lock_shared() {
+        let lock = Arc::new(RwLock::new_named((1u8, 2u8), "owned-into-inner"));
+        let guard = lock.clone().read_owned().await;
+        let lock = Arc::try_unwrap(lock).expect_err("an owned read guard is alive");
+        let mapped = OwnedRwLockReadGuard::map(guard, |pair| &pair.1);
+        let lock = Arc::try_unwrap(lock).expect_err("a mapped read guard is alive");
+        assert_eq!(*mapped, 2);
+        drop(mapped);
+
+        let guard = lock.clone().try_read_owned().unwrap();
+        let lock = Arc::try_unwrap(lock).expect_err("an owned read guard is alive");
+        drop(guard);
+
+        let lock = Arc::try_unwrap(lock).expect("no guard is alive");
+        assert_eq!(lock.into_inner(), (1, 2));
+    }
+}
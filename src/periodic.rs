@@ -0,0 +1,150 @@
+use crate::{
+    background::Ticker,
+    delta::{self, LockChange, Snapshot},
+    fallback,
+    redact::{Export, Redaction},
+    registry,
+    summary::aligned,
+};
+
+use log::Level;
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+static CURRENT: AtomicU64 = AtomicU64::new(0);
+
+// Every `interval`, logs at info one `[STATS]` table of what each lock did
+// since the last one: acquisitions, mean wait and hold, and the most readers
+// active at once. Locks with no acquisitions in the interval are left out,
+// and an interval with none at all logs nothing, so a quiet service stays
+// quiet. The cumulative stats are not reset; each table is a diff against
+// the previous one, the first against the moment this was called. Started
+// outside a runtime, a thread does the dumps. Dropping the handle stops them.
+// One handle reports at a time: a second call takes over, and the earlier
+// handle logs nothing more. A zero interval panics here.
+#[track_caller]
+pub fn enable_periodic_stats(interval: Duration) -> PeriodicStatsHandle {
+    assert!(
+        !interval.is_zero(),
+        "enable_periodic_stats needs a non-zero interval"
+    );
+    let mut dumper = Dumper::start();
+    PeriodicStatsHandle {
+        _ticker: Ticker::every(interval, move || dumper.dump()),
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "periodic stats stop when this handle is dropped"]
+pub struct PeriodicStatsHandle {
+    _ticker: Ticker,
+}
+
+struct Dumper {
+    last: Snapshot,
+    generation: u64,
+}
+
+impl Dumper {
+    fn start() -> Self {
+        let generation = CURRENT.fetch_add(1, Ordering::Relaxed) + 1;
+        let last = delta::all_stats();
+        peak_readers();
+        Self { last, generation }
+    }
+
+    fn dump(&mut self) {
+        if let Some((interval, table)) = self.table() {
+            fallback::log!(Level::Info, "[STATS] over {:?}", interval);
+            for line in table.lines() {
+                fallback::log!(Level::Info, "[STATS] {}", line);
+            }
+        }
+    }
+
+    // The peaks are taken, so only the current handle may read them.
+    fn table(&mut self) -> Option<(Duration, String)> {
+        if CURRENT.load(Ordering::Relaxed) != self.generation {
+            return None;
+        }
+        let now = delta::all_stats();
+        let peaks = peak_readers();
+        let delta = now.diff(&self.last);
+        self.last = now;
+
+        let header = [
+            "name",
+            "acq (r/w)",
+            "contended",
+            "avg wait",
+            "avg hold",
+            "peak readers",
+        ];
+        let mut table: Vec<[String; 6]> = vec![header.map(str::to_owned)];
+        let mut redaction = Redaction::new(Export::Summary);
+        for lock in &delta.locks {
+            if lock.change == LockChange::Removed || lock.diff.acquisitions() == 0 {
+                continue;
+            }
+            table.push([
+                redaction.apply(&lock.name).into_owned(),
+                format!("{}/{}", lock.diff.reads, lock.diff.writes),
+                format!("{:.1}%", lock.diff.contended_pct()),
+                format!("{:?}", lock.diff.mean_wait()),
+                format!("{:?}", lock.diff.mean_hold()),
+                peaks.get(&lock.id).copied().unwrap_or(0).to_string(),
+            ]);
+        }
+        if table.len() == 1 {
+            return None;
+        }
+        Some((delta.interval, aligned(&table)))
+    }
+}
+
+// Takes every live lock's peak readers for the interval ending now.
+fn peak_readers() -> HashMap<u64, usize> {
+    registry::all_locks()
+        .iter()
+        .map(|meta| (meta.id, meta.availability.take_interval_peak_readers()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{enable_periodic_stats, Dumper};
+    use crate::RwLock;
+
+    use std::time::Duration;
+
+    fn row(dumper: &mut Dumper, name: &str) -> Option<String> {
+        let (_, table) = dumper.table()?;
+        let row = table.lines().find(|line| line.starts_with(name))?;
+        let columns: Vec<&str> = row.split_whitespace().collect();
+        // The wait and hold columns depend on timing.
+        Some(format!("{} {} {}", columns[1], columns[2], columns[5]))
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero interval")]
+    fn a_zero_interval_is_rejected() {
+        let _stats = enable_periodic_stats(Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn a_second_dumper_takes_over() {
+        let lock = RwLock::new_named(0u8, "periodic-takeover");
+        let mut first = Dumper::start();
+        let mut second = Dumper::start();
+        let a = lock.read().await;
+        let b = lock.read().await;
+        drop((a, b));
+        assert_eq!(row(&mut first, "periodic-takeover"), None);
+        assert_eq!(row(&mut second, "periodic-takeover").unwrap(), "2/0 0.0% 2");
+        assert_eq!(row(&mut second, "periodic-takeover"), None);
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+
+// Display gives the exact token used in log lines, so formatting through these
+// enums never changes the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Mode {
+    Read,
+    Write,
+    // Events about the lock itself rather than one acquisition.
+    Lock,
+    Meta,
+}
+
+impl Mode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mode::Read => "READ",
+            Mode::Write => "WRITE",
+            Mode::Lock => "LOCK",
+            Mode::Meta => "META",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Phase {
+    Acquire,
+    Waiting,
+    Got,
+    Release,
+    GiveUp,
+    Cancelled,
+    Upgrade,
+    Throttle,
+    Handoff,
+    Resume,
+    Map,
+    MapFailed,
+    Fence,
+    Init,
+    Heartbeat,
+    Quiesce,
+    // Anomalies reported alongside an acquisition.
+    Await,
+    Order,
+    Unmutated,
+    Inversion,
+    Stuck,
+    Blocking,
+    Access,
+    // Lifecycle of the lock.
+    Created,
+    FirstUse,
+    Reset,
+    Wrap,
+    Untraced,
+    Destroy,
+    Dropped,
+    Rename,
+}
+
+impl Phase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Acquire => "Acquire",
+            Phase::Waiting => "Waiting",
+            Phase::Got => "Got",
+            Phase::Release => "Release",
+            Phase::GiveUp => "GiveUp",
+            Phase::Cancelled => "Cancelled",
+            Phase::Upgrade => "Upgrade",
+            Phase::Throttle => "Throttle",
+            Phase::Handoff => "Handoff",
+            Phase::Resume => "Resume",
+            Phase::Map => "Map",
+            Phase::MapFailed => "MapFailed",
+            Phase::Fence => "Fence",
+            Phase::Init => "Init",
+            Phase::Heartbeat => "Heartbeat",
+            Phase::Quiesce => "Quiesce",
+            Phase::Await => "Await",
+            Phase::Order => "Order",
+            Phase::Unmutated => "Unmutated",
+            Phase::Inversion => "Inversion",
+            Phase::Stuck => "Stuck",
+            Phase::Blocking => "Blocking",
+            Phase::Access => "Access",
+            Phase::Created => "Created",
+            Phase::FirstUse => "FirstUse",
+            Phase::Reset => "Reset",
+            Phase::Wrap => "Wrap",
+            Phase::Untraced => "Untraced",
+            Phase::Destroy => "Destroy",
+            Phase::Dropped => "Dropped",
+            Phase::Rename => "Rename",
+        }
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Serialized as the same tokens, so JSON output is unchanged too.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Mode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Phase {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+// Accepts the tokens `Serialize` writes, so consumers can derive
+// `Deserialize` for their own event types around these.
+#[cfg(feature = "serde")]
+fn from_token<'de, D, T>(deserializer: D, all: &[T], what: &str) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Copy + fmt::Display,
+{
+    let token = <std::borrow::Cow<'de, str> as serde::Deserialize>::deserialize(deserializer)?;
+    all.iter()
+        .copied()
+        .find(|value| value.to_string() == token)
+        .ok_or_else(|| serde::de::Error::custom(format!("unknown {} {:?}", what, token)))
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Mode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        from_token(deserializer, &crate::binary::MODES, "mode")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Phase {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        from_token(deserializer, &crate::binary::PHASES, "phase")
+    }
+}
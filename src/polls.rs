@@ -0,0 +1,171 @@
+use crate::clock::Instant;
+
+use std::{
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+static BREAKDOWN: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static EPOCH: Cell<u64> = const { Cell::new(0) };
+    static DEPTH: Cell<u32> = const { Cell::new(0) };
+    static SUSPENSION: Cell<Option<Suspension>> = const { Cell::new(None) };
+}
+
+// Off by default. When on, each `track_polls` future times how long its task
+// spends suspended, and write guards released inside it split their hold into
+// time suspended and time running (`cpu≈`, which includes time spent waiting
+// for a worker thread).
+pub fn set_hold_breakdown(enabled: bool) {
+    BREAKDOWN.store(enabled, Ordering::Relaxed);
+}
+
+// Each outermost poll of a tracked future starts a new epoch on its thread. A
+// guard released in a different epoch than it was acquired in was held while
+// the task yielded, e.g. across an `.await` inside a `select!` branch.
+pub fn track_polls<F: Future>(future: F) -> TrackPolls<F> {
+    TrackPolls {
+        inner: future,
+        suspension: Suspension::default(),
+        pending_since: None,
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct TrackPolls<F> {
+    inner: F,
+    suspension: Suspension,
+    pending_since: Option<Instant>,
+}
+
+impl<F: Future> Future for TrackPolls<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Safety: `inner` is never moved out of the pinned wrapper, and the
+        // other fields are plain data.
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+        let depth = DEPTH.with(|depth| depth.replace(depth.get() + 1));
+        let outermost = depth == 0;
+        let timed = outermost && BREAKDOWN.load(Ordering::Relaxed);
+        if outermost {
+            EPOCH.with(|epoch| epoch.set(epoch.get().wrapping_add(1)));
+        }
+        if timed {
+            if let Some(since) = this.pending_since.take() {
+                this.suspension.yields += 1;
+                this.suspension.suspended += since.elapsed();
+            }
+            let suspension = this.suspension;
+            SUSPENSION.with(|current| current.set(Some(suspension)));
+        }
+        let _exit = Exit;
+        let poll = inner.poll(cx);
+        if timed && poll.is_pending() {
+            this.pending_since = Some(Instant::now());
+        }
+        poll
+    }
+}
+
+struct Exit;
+
+impl Drop for Exit {
+    fn drop(&mut self) {
+        let depth = DEPTH.with(|depth| {
+            depth.set(depth.get() - 1);
+            depth.get()
+        });
+        if depth == 0 {
+            SUSPENSION.with(|current| current.set(None));
+        }
+    }
+}
+
+// Running totals for the task being polled on this thread. Two readings taken
+// inside the same `track_polls` future give the suspensions between them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Suspension {
+    pub(crate) yields: u64,
+    pub(crate) suspended: Duration,
+}
+
+impl Suspension {
+    pub(crate) fn current() -> Option<Self> {
+        SUSPENSION.with(Cell::get)
+    }
+
+    pub(crate) fn since(&self, earlier: &Suspension) -> Suspension {
+        Suspension {
+            yields: self.yields.saturating_sub(earlier.yields),
+            suspended: self.suspended.saturating_sub(earlier.suspended),
+        }
+    }
+}
+
+// Times one future's own suspensions, whether or not it runs inside
+// `track_polls`, for a section that awaits under a guard.
+pub(crate) fn timed<F: Future>(future: F) -> Timed<F> {
+    Timed {
+        inner: future,
+        pending_since: None,
+        suspension: Suspension::default(),
+    }
+}
+
+pub(crate) struct Timed<F> {
+    inner: F,
+    pending_since: Option<Instant>,
+    suspension: Suspension,
+}
+
+impl<F: Future> Future for Timed<F> {
+    type Output = (F::Output, Suspension);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: as in `TrackPolls`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+        if let Some(since) = this.pending_since.take() {
+            this.suspension.yields += 1;
+            this.suspension.suspended += since.elapsed();
+        }
+        match inner.poll(cx) {
+            Poll::Ready(output) => Poll::Ready((output, this.suspension)),
+            Poll::Pending => {
+                this.pending_since = Some(Instant::now());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PollEpoch {
+    thread: ThreadId,
+    epoch: u64,
+}
+
+impl PollEpoch {
+    pub(crate) fn current() -> Option<Self> {
+        if DEPTH.with(Cell::get) == 0 {
+            return None;
+        }
+        Some(Self {
+            thread: thread::current().id(),
+            epoch: EPOCH.with(Cell::get),
+        })
+    }
+
+    pub(crate) fn yielded_since(&self) -> bool {
+        Self::current().is_some_and(|now| now != *self)
+    }
+}
@@ -0,0 +1,271 @@
+use crate::{
+    background,
+    clock::Instant,
+    event,
+    phase::{Mode, Phase},
+    registry::{self, LockMeta},
+    severity,
+    traced::RawAsyncRwLock,
+    TracedLock,
+};
+
+use log::Level;
+use tokio::sync::futures::Notified;
+
+use std::{
+    fmt,
+    future::{self, Future},
+    panic::Location,
+    pin::Pin,
+    sync::Arc,
+    task::Poll,
+    time::Duration,
+};
+
+mod private {
+    pub trait Sealed {}
+}
+
+// A lock of any backend, for helpers that watch several at once.
+pub trait LockHandle: private::Sealed {
+    fn id(&self) -> u64;
+}
+
+#[cfg(feature = "parking-lot")]
+impl<T> private::Sealed for crate::sync::RwLock<T> {}
+
+#[cfg(feature = "parking-lot")]
+impl<T> LockHandle for crate::sync::RwLock<T> {
+    fn id(&self) -> u64 {
+        self.id()
+    }
+}
+
+impl<T, L: RawAsyncRwLock<T>> private::Sealed for TracedLock<T, L> {}
+
+impl<T, L: RawAsyncRwLock<T>> LockHandle for TracedLock<T, L> {
+    fn id(&self) -> u64 {
+        self.id()
+    }
+}
+
+// How one lock behaved while `quiesce` watched it. `straggler` is the time it
+// was the only one still held, what kept the set from going quiet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuiesceLock {
+    pub lock_id: u64,
+    pub name: String,
+    pub busy: Duration,
+    pub straggler: Duration,
+    // Times it was seen free while another lock was not.
+    pub idle_moments: u64,
+}
+
+// The moment every lock was seen free. Nothing keeps them free: the guards
+// are not taken, so check `age` or `still_quiet` before relying on it.
+#[derive(Debug)]
+pub struct QuiescePoint {
+    at: Instant,
+    waited: Duration,
+    locks: Vec<QuiesceLock>,
+    metas: Vec<Arc<LockMeta>>,
+}
+
+impl QuiescePoint {
+    pub fn age(&self) -> Duration {
+        self.at.elapsed()
+    }
+
+    pub fn waited(&self) -> Duration {
+        self.waited
+    }
+
+    pub fn locks(&self) -> &[QuiesceLock] {
+        &self.locks
+    }
+
+    // Whether the locks are all free right now, not just when observed.
+    pub fn still_quiet(&self) -> bool {
+        self.metas
+            .iter()
+            .all(|meta| meta.availability.is_unlocked())
+    }
+}
+
+// The set never went quiet within the timeout. The lock with the largest
+// `straggler` is the one that held it up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuiesceTimeout {
+    pub waited: Duration,
+    pub locks: Vec<QuiesceLock>,
+}
+
+impl fmt::Display for QuiesceTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "locks did not quiesce within {:?}", self.waited)?;
+        let straggler = self.locks.iter().max_by_key(|lock| lock.straggler);
+        if let Some(lock) = straggler {
+            write!(
+                f,
+                "; ({}) was the last one held, for {:?}",
+                lock.name, lock.straggler
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for QuiesceTimeout {}
+
+// Resolves at a moment when none of `locks` has a guard outstanding, without
+// queueing on any of them, so traffic is never held back while it waits. It
+// re-checks every lock whenever one of them is released; under constant churn
+// that can still miss every gap, and after `timeout` it gives up with what it
+// saw of each lock.
+#[track_caller]
+pub fn quiesce<'a>(
+    locks: &'a [&'a dyn LockHandle],
+    timeout: Duration,
+) -> impl Future<Output = Result<QuiescePoint, QuiesceTimeout>> + 'a {
+    let caller = Location::caller();
+    // The borrows keep every lock registered for the lookup.
+    let metas: Vec<Arc<LockMeta>> = locks
+        .iter()
+        .filter_map(|lock| registry::lock(lock.id()))
+        .collect();
+
+    async move {
+        let mut watch = Watch::new(metas.clone());
+        let deadline = background::sleep(timeout);
+        tokio::pin!(deadline);
+        loop {
+            let mut released: Vec<Pin<Box<Notified<'_>>>> = Vec::new();
+            for meta in &metas {
+                let mut notified = Box::pin(meta.availability.notified());
+                notified.as_mut().enable();
+                released.push(notified);
+            }
+            if watch.observe() {
+                return Ok(watch.quiesced(caller));
+            }
+            // Resolves true at the deadline, false on a release before it.
+            let timed_out = future::poll_fn(|cx| {
+                if released
+                    .iter_mut()
+                    .any(|notified| notified.as_mut().poll(cx).is_ready())
+                {
+                    Poll::Ready(false)
+                } else if deadline.as_mut().poll(cx).is_ready() {
+                    Poll::Ready(true)
+                } else {
+                    Poll::Pending
+                }
+            });
+            if timed_out.await {
+                watch.observe();
+                return Err(watch.timed_out(caller));
+            }
+        }
+    }
+}
+
+struct Watch {
+    metas: Vec<Arc<LockMeta>>,
+    locks: Vec<QuiesceLock>,
+    started: Instant,
+    last: Instant,
+    // Which locks were held at the last observation.
+    busy: Vec<bool>,
+}
+
+impl Watch {
+    fn new(metas: Vec<Arc<LockMeta>>) -> Self {
+        let now = Instant::now();
+        let locks = metas
+            .iter()
+            .map(|meta| QuiesceLock {
+                lock_id: meta.id,
+                name: meta.name().to_string(),
+                busy: Duration::ZERO,
+                straggler: Duration::ZERO,
+                idle_moments: 0,
+            })
+            .collect();
+        let busy = vec![false; metas.len()];
+        Self {
+            metas,
+            locks,
+            started: now,
+            last: now,
+            busy,
+        }
+    }
+
+    // Charges the time since the last look to what was held then, and
+    // returns whether every lock is free now.
+    fn observe(&mut self) -> bool {
+        let now = Instant::now();
+        let since = now.saturating_duration_since(self.last);
+        let held = self.busy.iter().filter(|&&busy| busy).count();
+        for (lock, &busy) in self.locks.iter_mut().zip(&self.busy) {
+            if busy {
+                lock.busy += since;
+                if held == 1 {
+                    lock.straggler += since;
+                }
+            }
+        }
+        self.last = now;
+
+        for (index, meta) in self.metas.iter().enumerate() {
+            self.busy[index] = !meta.availability.is_unlocked();
+        }
+        let quiet = !self.busy.contains(&true);
+        if !quiet {
+            for (lock, &busy) in self.locks.iter_mut().zip(&self.busy) {
+                if !busy {
+                    lock.idle_moments += 1;
+                }
+            }
+        }
+        quiet
+    }
+
+    fn quiesced(self, caller: &'static Location<'static>) -> QuiescePoint {
+        let waited = self.last.saturating_duration_since(self.started);
+        self.report(Level::Info, caller, &format!("after {:?}", waited));
+        QuiescePoint {
+            at: self.last,
+            waited,
+            locks: self.locks,
+            metas: self.metas,
+        }
+    }
+
+    fn timed_out(self, caller: &'static Location<'static>) -> QuiesceTimeout {
+        let waited = self.last.saturating_duration_since(self.started);
+        let level = severity::severity_policy().anomaly;
+        self.report(level, caller, &format!("timed out after {:?}", waited));
+        QuiesceTimeout {
+            waited,
+            locks: self.locks,
+        }
+    }
+
+    // One event per lock, so each lock's own history shows the pass.
+    fn report(&self, level: Level, caller: &'static Location<'static>, outcome: &str) {
+        let names: Vec<&str> = self.locks.iter().map(|lock| lock.name.as_str()).collect();
+        for (meta, lock) in self.metas.iter().zip(&self.locks) {
+            let message = format!(
+                "[META] Quiesce ({}) of [{}] {}: busy {:?}, last straggler for {:?}, idle {} times",
+                meta.name(),
+                names.join(", "),
+                outcome,
+                lock.busy,
+                lock.straggler,
+                lock.idle_moments,
+            );
+            event::emit_message(level, meta, Mode::Meta, Phase::Quiesce, 0, caller, message);
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use crate::{
+    clock::Instant,
+    event,
+    guard::{Acquisition, GuardState},
+    phase::{Mode, Phase},
+    registry::LockMeta,
+    scope::Scope,
+    READ, WRITE,
+};
+
+use lock_api::{RawRwLock, RawRwLockDowngrade, RawRwLockTimed};
+
+use std::{
+    any, fmt,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, OnceLock,
+    },
+    thread::{self, ThreadId},
+};
+
+pub type TracedParkingLotRwLock<T> = lock_api::RwLock<TracedRaw<parking_lot::RawRwLock>, T>;
+
+// Raw locks are built from `INIT`, so there is nowhere to pass a name: one is
+// derived from the address, `raw@0x...`, on first use unless `set_name` ran
+// before that. Such a name changes from run to run, and a lock made where a
+// dropped one lived gets the same name, so give any lock worth finding in a
+// log one with `set_name`. Unlocks carry no guard, so a shared unlock is
+// matched to a hold taken on the same thread, falling back to the newest one.
+// lock_api's acquiring methods pass their caller down, which is why 0.4.14 is
+// required, so acquire locations and the creation site are the user's. A
+// guard's drop cannot, so release locations point into lock_api's guard drop
+// rather than the user's code.
+pub struct TracedRaw<R> {
+    inner: R,
+    meta: OnceLock<Arc<LockMeta>>,
+    idx: AtomicU64,
+    exclusive: Mutex<Option<GuardState>>,
+    shared: Mutex<Vec<(ThreadId, GuardState)>>,
+}
+
+#[track_caller]
+pub fn set_name<R, T>(lock: &lock_api::RwLock<TracedRaw<R>, T>, name: impl Into<String>) -> bool
+where
+    R: RawRwLock,
+    T: ?Sized,
+{
+    // Safety: only the tracing state is touched, never the inner raw lock.
+    let raw = unsafe { lock.raw() };
+    let meta = LockMeta::registered(Scope::root(), name.into(), any::type_name::<T>(), None);
+    if raw.meta.set(meta).is_err() {
+        return false;
+    }
+    let caller = Location::caller();
+    event::created(raw.meta(caller), caller);
+    true
+}
+
+impl<R: RawRwLock> TracedRaw<R> {
+    // Raw locks are built in const contexts, so the first lock call is
+    // reported as the creation site.
+    fn meta(&self, caller: &'static Location<'static>) -> &Arc<LockMeta> {
+        self.meta.get_or_init(|| {
+            let meta = LockMeta::registered(
+                Scope::root(),
+                format!("raw@{:p}", self),
+                any::type_name::<R>(),
+                None,
+            );
+            event::created(&meta, caller);
+            meta
+        })
+    }
+
+    fn exclusive(&self) -> MutexGuard<'_, Option<GuardState>> {
+        self.exclusive.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn shared(&self) -> MutexGuard<'_, Vec<(ThreadId, GuardState)>> {
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn acquire<W>(
+        &self,
+        mode: Mode,
+        caller: &'static Location<'static>,
+        try_lock: fn(&R) -> bool,
+        wait: Option<W>,
+    ) -> bool
+    where
+        W: FnOnce(&R) -> bool,
+    {
+        let meta = self.meta(caller);
+        let mut acquisition = Acquisition::new(caller).begin(meta, &self.idx, mode);
+        let start = Instant::now();
+
+        let contended = !try_lock(&self.inner);
+        let locked = !contended
+            || match wait {
+                Some(wait) => {
+                    let _waiting = acquisition.start_waiting(meta, mode);
+                    wait(&self.inner)
+                }
+                None => false,
+            };
+        if !locked {
+            acquisition.waited = start.elapsed();
+            acquisition.emit(meta, mode, Phase::GiveUp, caller, "");
+            return false;
+        }
+        acquisition.record_wait(meta, mode, start, contended);
+
+        let mut state = GuardState::got(meta.clone(), acquisition, mode);
+        state.skip_mutation_check();
+        self.hold(mode, state);
+        true
+    }
+
+    fn hold(&self, mode: Mode, state: GuardState) {
+        if mode == WRITE {
+            *self.exclusive() = Some(state);
+        } else {
+            self.shared().push((thread::current().id(), state));
+        }
+    }
+
+    fn take_shared(&self) -> Option<GuardState> {
+        let mut shared = self.shared();
+        let current = thread::current().id();
+        let position = shared
+            .iter()
+            .rposition(|(thread, _)| *thread == current)
+            .or_else(|| shared.len().checked_sub(1))?;
+        Some(shared.remove(position).1)
+    }
+}
+
+unsafe impl<R: RawRwLock> RawRwLock for TracedRaw<R> {
+    const INIT: Self = TracedRaw {
+        inner: R::INIT,
+        meta: OnceLock::new(),
+        idx: AtomicU64::new(0),
+        exclusive: Mutex::new(None),
+        shared: Mutex::new(Vec::new()),
+    };
+
+    type GuardMarker = R::GuardMarker;
+
+    #[track_caller]
+    fn lock_shared(&self) {
+        self.acquire(
+            READ,
+            Location::caller(),
+            R::try_lock_shared,
+            Some(|inner: &R| {
+                inner.lock_shared();
+                true
+            }),
+        );
+    }
+
+    #[track_caller]
+    fn try_lock_shared(&self) -> bool {
+        self.acquire(
+            READ,
+            Location::caller(),
+            R::try_lock_shared,
+            None::<fn(&R) -> bool>,
+        )
+    }
+
+    #[track_caller]
+    unsafe fn unlock_shared(&self) {
+        let mut state = self.take_shared();
+        if let Some(state) = &mut state {
+            state.release(READ, Location::caller(), false, String::new);
+        }
+        self.inner.unlock_shared();
+    }
+
+    #[track_caller]
+    fn lock_exclusive(&self) {
+        self.acquire(
+            WRITE,
+            Location::caller(),
+            R::try_lock_exclusive,
+            Some(|inner: &R| {
+                inner.lock_exclusive();
+                true
+            }),
+        );
+    }
+
+    #[track_caller]
+    fn try_lock_exclusive(&self) -> bool {
+        self.acquire(
+            WRITE,
+            Location::caller(),
+            R::try_lock_exclusive,
+            None::<fn(&R) -> bool>,
+        )
+    }
+
+    #[track_caller]
+    unsafe fn unlock_exclusive(&self) {
+        let mut state = self.exclusive().take();
+        if let Some(state) = &mut state {
+            state.release(WRITE, Location::caller(), false, String::new);
+        }
+        self.inner.unlock_exclusive();
+    }
+
+    fn is_locked(&self) -> bool {
+        self.inner.is_locked()
+    }
+
+    fn is_locked_exclusive(&self) -> bool {
+        self.inner.is_locked_exclusive()
+    }
+}
+
+unsafe impl<R: RawRwLockDowngrade> RawRwLockDowngrade for TracedRaw<R> {
+    #[track_caller]
+    unsafe fn downgrade(&self) {
+        let caller = Location::caller();
+        let mut state = self.exclusive().take();
+        if let Some(state) = &mut state {
+            state.release(WRITE, caller, false, String::new);
+        }
+        self.inner.downgrade();
+
+        let meta = self.meta(caller);
+        let mut acquisition = Acquisition::new(caller).begin(meta, &self.idx, READ);
+        acquisition.record_wait(meta, READ, Instant::now(), false);
+        self.hold(READ, GuardState::got(meta.clone(), acquisition, READ));
+    }
+}
+
+unsafe impl<R: RawRwLockTimed> RawRwLockTimed for TracedRaw<R> {
+    type Duration = R::Duration;
+    type Instant = R::Instant;
+
+    #[track_caller]
+    fn try_lock_shared_for(&self, timeout: R::Duration) -> bool {
+        let wait = |inner: &R| inner.try_lock_shared_for(timeout);
+        self.acquire(READ, Location::caller(), R::try_lock_shared, Some(wait))
+    }
+
+    #[track_caller]
+    fn try_lock_shared_until(&self, timeout: R::Instant) -> bool {
+        let wait = |inner: &R| inner.try_lock_shared_until(timeout);
+        self.acquire(READ, Location::caller(), R::try_lock_shared, Some(wait))
+    }
+
+    #[track_caller]
+    fn try_lock_exclusive_for(&self, timeout: R::Duration) -> bool {
+        let wait = |inner: &R| inner.try_lock_exclusive_for(timeout);
+        self.acquire(WRITE, Location::caller(), R::try_lock_exclusive, Some(wait))
+    }
+
+    #[track_caller]
+    fn try_lock_exclusive_until(&self, timeout: R::Instant) -> bool {
+        let wait = |inner: &R| inner.try_lock_exclusive_until(timeout);
+        self.acquire(WRITE, Location::caller(), R::try_lock_exclusive, Some(wait))
+    }
+}
+
+impl<R> fmt::Debug for TracedRaw<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TracedRaw")
+            .field("name", &self.meta.get().map(|meta| meta.name()))
+            .field("idx", &self.idx.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_name, TracedParkingLotRwLock};
+    use crate::{Mode, Phase};
+
+    use lock_api::RwLockWriteGuard;
+
+    #[test]
+    fn only_releases_point_into_lock_api() {
+        let lock = TracedParkingLotRwLock::new(0u8);
+        assert!(set_name(&lock, "raw-locations"));
+        let mut events = crate::subscribe_where(|event| event.name == "raw-locations");
+        drop(lock.write());
+        let mut phases = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            let in_lock_api = event.location.file().contains("lock_api");
+            assert_eq!(in_lock_api, event.phase == Phase::Release, "{:?}", event);
+            phases.push(event.phase);
+        }
+        assert!(phases.contains(&Phase::Acquire) && phases.contains(&Phase::Release));
+    }
+
+    #[test]
+    fn a_downgrade_counts_its_reader_once() {
+        let lock = TracedParkingLotRwLock::new(0u8);
+        assert!(set_name(&lock, "raw-downgrade"));
+        let mut events = crate::subscribe_where(|event| {
+            event.name == "raw-downgrade" && event.mode == Mode::Read
+        });
+        let read = RwLockWriteGuard::downgrade(lock.write());
+        let other = lock.read();
+        drop(read);
+        drop(other);
+        let details: Vec<String> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| matches!(event.phase, Phase::Got | Phase::Release))
+            .map(|event| event.message)
+            .collect();
+        assert_eq!(details.len(), 4, "{:?}", details);
+        assert!(details[0].contains("active_readers=1"), "{:?}", details);
+        assert!(details[1].contains("active_readers=2"), "{:?}", details);
+        assert!(details[2].contains("remaining_readers=1"), "{:?}", details);
+        assert!(details[3].contains("remaining_readers=0"), "{:?}", details);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::event::LockEvent;
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    mem,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+// Zero, the default, leaves recording off.
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+static OVERFLOWED: AtomicU64 = AtomicU64::new(0);
+static RECORDED: Mutex<Recorded> = Mutex::new(Recorded {
+    events: VecDeque::new(),
+    ranked: BTreeMap::new(),
+    overflow: RecordingOverflow::DropOldest,
+});
+
+// An event's score, then its seq, so equal scores evict the oldest first.
+type Rank = (Duration, u64);
+
+// Events are in `ranked` under `DropLeastSignificant` and in `events`
+// otherwise.
+struct Recorded {
+    events: VecDeque<LockEvent>,
+    ranked: BTreeMap<Rank, LockEvent>,
+    overflow: RecordingOverflow,
+}
+
+// `DropLeastSignificant` scores each event by the longer of its wait and its
+// hold and evicts the lowest score, so a full buffer keeps the worst holds
+// and waits seen rather than the latest ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingOverflow {
+    DropOldest,
+    DropNewest,
+    DropLeastSignificant,
+}
+
+// One buffer for the events of every lock and task, meant to be drained to
+// disk periodically. Lowering the capacity discards what the overflow policy
+// would have; zero turns recording off and clears the buffer uncounted. Lock
+// creation is not recorded; `announce_all` replays it to subscribers instead.
+pub fn set_recording_capacity(capacity: usize) {
+    let mut recorded = recorded();
+    CAPACITY.store(capacity, Ordering::Relaxed);
+    if capacity == 0 {
+        recorded.events = VecDeque::new();
+        recorded.ranked = BTreeMap::new();
+        return;
+    }
+    let excess = recorded.len().saturating_sub(capacity);
+    if excess == 0 {
+        return;
+    }
+    match recorded.overflow {
+        RecordingOverflow::DropOldest => drop(recorded.events.drain(..excess)),
+        RecordingOverflow::DropNewest => recorded.events.truncate(capacity),
+        RecordingOverflow::DropLeastSignificant => {
+            for _ in 0..excess {
+                recorded.ranked.pop_first();
+            }
+        }
+    }
+    OVERFLOWED.fetch_add(excess as u64, Ordering::Relaxed);
+}
+
+// Switching to or from `DropLeastSignificant` keeps what is buffered.
+pub fn set_recording_overflow(overflow: RecordingOverflow) {
+    let mut recorded = recorded();
+    let ranked = RecordingOverflow::DropLeastSignificant;
+    if (recorded.overflow == ranked) != (overflow == ranked) {
+        let events = recorded.take();
+        if overflow == ranked {
+            recorded.ranked = events
+                .into_iter()
+                .map(|event| (rank(&event), event))
+                .collect();
+        } else {
+            recorded.events = events.into();
+        }
+    }
+    recorded.overflow = overflow;
+}
+
+// Takes everything recorded so far in one step, so an event is drained
+// exactly once however often this runs. Events come out in seq order under
+// every overflow policy.
+pub fn drain_recorded_events() -> Vec<LockEvent> {
+    recorded().take()
+}
+
+// Events discarded because the buffer was full, whichever end they came from.
+pub fn recording_overflowed() -> u64 {
+    OVERFLOWED.load(Ordering::Relaxed)
+}
+
+// What `drain_recorded_events` would return, left in the buffer.
+pub(crate) fn recorded_events() -> Vec<LockEvent> {
+    let recorded = recorded();
+    let mut events: Vec<LockEvent> = recorded.events.iter().cloned().collect();
+    events.extend(recorded.ranked.values().cloned());
+    events.sort_by_key(|event| event.seq);
+    events
+}
+
+pub(crate) fn enabled() -> bool {
+    CAPACITY.load(Ordering::Relaxed) != 0
+}
+
+pub(crate) fn record(event: LockEvent) {
+    let mut recorded = recorded();
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+    if recorded.overflow == RecordingOverflow::DropLeastSignificant {
+        recorded.ranked.insert(rank(&event), event);
+        if recorded.ranked.len() > capacity {
+            OVERFLOWED.fetch_add(1, Ordering::Relaxed);
+            recorded.ranked.pop_first();
+        }
+        return;
+    }
+    if recorded.events.len() >= capacity {
+        OVERFLOWED.fetch_add(1, Ordering::Relaxed);
+        match recorded.overflow {
+            RecordingOverflow::DropNewest => return,
+            _ => drop(recorded.events.pop_front()),
+        }
+    }
+    recorded.events.push_back(event);
+}
+
+impl Recorded {
+    fn len(&self) -> usize {
+        self.events.len() + self.ranked.len()
+    }
+
+    fn take(&mut self) -> Vec<LockEvent> {
+        if self.ranked.is_empty() {
+            return mem::take(&mut self.events).into();
+        }
+        let mut events: Vec<LockEvent> = mem::take(&mut self.ranked).into_values().collect();
+        events.sort_by_key(|event| event.seq);
+        events
+    }
+}
+
+fn rank(event: &LockEvent) -> Rank {
+    let score = event.waited.max(event.held).unwrap_or_default();
+    (score, event.seq)
+}
+
+fn recorded() -> MutexGuard<'static, Recorded> {
+    RECORDED.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,164 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, RwLock,
+    },
+};
+
+type Redactor = Arc<dyn Fn(&str) -> Cow<'_, str> + Send + Sync>;
+
+static REDACTOR: RwLock<Option<Redactor>> = RwLock::new(None);
+static TRUSTED: AtomicU8 = AtomicU8::new(0);
+
+// Where names and labels leave the process. Log lines are not covered: they
+// are formatted in many places and reach the logger as finished text, so a
+// logger-side filter is the place to scrub them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Export {
+    // Label values for the `metrics` facade.
+    Metrics,
+    // Span attributes.
+    OpenTelemetry,
+    // Name records in the binary event sink.
+    Binary,
+    // `summary`, `summary_json` and the CSV exports.
+    Summary,
+    // `RegistrySnapshot::to_json`, `SnapshotDelta`'s JSON and table, and
+    // `render_held_table`.
+    Snapshot,
+    // Lock frames in folded stacks.
+    Folded,
+}
+
+impl Export {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+// Applied to non-empty lock names, scopes, groups, task names and handoff labels as
+// they are exported; the registry keeps the originals, so in-process debugging
+// and `LockEvent`s are unaffected. Ids are exported unredacted alongside, so
+// redacted entries still correlate across exports.
+pub fn set_redactor<F>(redactor: F)
+where
+    F: Fn(&str) -> Cow<'_, str> + Send + Sync + 'static,
+{
+    *REDACTOR.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(redactor));
+}
+
+pub fn clear_redactor() {
+    *REDACTOR.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+// A trusted export skips the redactor, e.g. a binary sink kept on local disk.
+pub fn set_export_trusted(export: Export, trusted: bool) {
+    if trusted {
+        TRUSTED.fetch_or(export.bit(), Ordering::Relaxed);
+    } else {
+        TRUSTED.fetch_and(!export.bit(), Ordering::Relaxed);
+    }
+}
+
+fn redactor(export: Export) -> Option<Redactor> {
+    if TRUSTED.load(Ordering::Relaxed) & export.bit() != 0 {
+        return None;
+    }
+    REDACTOR.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// For exports that handle one string at a time.
+pub(crate) fn redact(export: Export, value: &str) -> Cow<'_, str> {
+    match redactor(export) {
+        Some(redactor) if !value.is_empty() => Cow::Owned(redactor(value).into_owned()),
+        _ => Cow::Borrowed(value),
+    }
+}
+
+// One per export run: the redactor is looked up once, and each distinct
+// string goes through it once however often it appears.
+pub(crate) struct Redaction {
+    redactor: Option<Redactor>,
+    seen: HashMap<String, String>,
+}
+
+impl Redaction {
+    pub(crate) fn new(export: Export) -> Self {
+        Self {
+            redactor: redactor(export),
+            seen: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn apply<'a>(&mut self, value: &'a str) -> Cow<'a, str> {
+        let redactor = match &self.redactor {
+            Some(redactor) if !value.is_empty() => redactor,
+            _ => return Cow::Borrowed(value),
+        };
+        if let Some(redacted) = self.seen.get(value) {
+            return Cow::Owned(redacted.clone());
+        }
+        let redacted = redactor(value).into_owned();
+        self.seen.insert(value.to_owned(), redacted.clone());
+        Cow::Owned(redacted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clear_redactor, set_export_trusted, set_redactor, Export};
+    use crate::{snapshot, RwLock};
+
+    use std::{borrow::Cow, cell::RefCell};
+
+    thread_local! {
+        static CALLS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    // Other tests export from other threads and never name a tenant, so they
+    // see their own strings back and add no calls here.
+    fn redact_tenant(value: &str) -> Cow<'_, str> {
+        match value.strip_prefix("tenant-42/") {
+            Some(rest) => {
+                CALLS.with(|calls| calls.borrow_mut().push(value.to_owned()));
+                Cow::Owned(format!("tenant-*/{}", rest))
+            }
+            None => Cow::Borrowed(value),
+        }
+    }
+
+    fn calls() -> Vec<String> {
+        CALLS.with(|calls| calls.borrow_mut().drain(..).collect())
+    }
+
+    #[test]
+    fn each_export_redacts_a_name_once_and_keeps_ids() {
+        let a = RwLock::new_named(0u8, "tenant-42/cache");
+        let b = RwLock::new_named(0u8, "tenant-42/cache");
+        set_redactor(redact_tenant);
+        assert_eq!(&*a.name(), "tenant-42/cache");
+
+        let mut exports = Vec::new();
+        for _ in 0..2 {
+            exports.push(snapshot().to_json());
+            assert_eq!(calls(), ["tenant-42/cache"]);
+        }
+        for json in &exports {
+            assert!(!json.contains("tenant-42"));
+            for id in [a.id(), b.id()] {
+                let entry = format!("{{\"id\":{},\"name\":\"tenant-*/cache\"", id);
+                assert!(json.contains(&entry), "{}", json);
+            }
+        }
+
+        set_export_trusted(Export::Snapshot, true);
+        assert!(snapshot()
+            .to_json()
+            .contains("\"name\":\"tenant-42/cache\""));
+        assert!(calls().is_empty());
+        set_export_trusted(Export::Snapshot, false);
+        clear_redactor();
+    }
+}
@@ -0,0 +1,694 @@
+use crate::{
+    access::AccessRule,
+    availability::Availability,
+    clock::Instant,
+    config::Idx,
+    event,
+    fairness::Inversions,
+    fallback,
+    filter::ReleaseFilter,
+    hint::{LoopDetector, WriterWaitDetector},
+    phase::Mode,
+    redact::{Export, Redaction},
+    ring::EventRing,
+    scope::Scope,
+    stats::Stats,
+    summary,
+    task::TaskKey,
+    READ, WRITE,
+};
+
+use log::Level;
+
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    fmt::Write,
+    mem,
+    panic::Location,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, OnceLock, RwLock, RwLockReadGuard, Weak,
+    },
+    thread::ThreadId,
+    time::Duration,
+};
+
+static NEXT_LOCK: AtomicU64 = AtomicU64::new(1);
+static NEXT_ENTRY: AtomicU64 = AtomicU64::new(1);
+static LOCKS: Mutex<BTreeMap<u64, Weak<LockMeta>>> = Mutex::new(BTreeMap::new());
+static HELD: Mutex<BTreeMap<u64, HeldEntry>> = Mutex::new(BTreeMap::new());
+static PENDING: Mutex<BTreeMap<u64, PendingEntry>> = Mutex::new(BTreeMap::new());
+static MAX_GUARDS: AtomicUsize = AtomicUsize::new(0);
+static OVER_MAX: AtomicBool = AtomicBool::new(false);
+static DROP_SUMMARIES: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug)]
+struct Label {
+    name: Arc<str>,
+    scope: Scope,
+}
+
+#[derive(Debug)]
+pub(crate) struct LockMeta {
+    pub(crate) id: u64,
+    // Swapped whole by `relabel`, so a reader never sees half a rename or a
+    // name without its scope.
+    label: RwLock<Label>,
+    pub(crate) type_name: &'static str,
+    pub(crate) level: Option<u32>,
+    pub(crate) group: OnceLock<String>,
+    pub(crate) group_totals: OnceLock<Arc<Stats>>,
+    pub(crate) release_filter: OnceLock<ReleaseFilter>,
+    pub(crate) read_access: OnceLock<AccessRule>,
+    pub(crate) write_access: OnceLock<AccessRule>,
+    pub(crate) stats: Stats,
+    pub(crate) loops: LoopDetector,
+    pub(crate) writer_waits: WriterWaitDetector,
+    pub(crate) inversions: Inversions,
+    pub(crate) availability: Availability,
+    pub(crate) poisoned: AtomicBool,
+    pub(crate) used: AtomicBool,
+    pub(crate) unmutated_writes_ok: AtomicBool,
+    pub(crate) acquire_delay: AtomicU64,
+    pub(crate) reader_warn: AtomicUsize,
+    pub(crate) slow_wait: OnceLock<Duration>,
+    pub(crate) slow_hold: OnceLock<Duration>,
+    // After how long a hold starts logging `Still holding`, and how often.
+    pub(crate) heartbeat: OnceLock<(Duration, Duration)>,
+    pub(crate) backtrace: OnceLock<bool>,
+    pub(crate) event_ring: OnceLock<EventRing>,
+    pub(crate) created: Instant,
+    pub(crate) created_at: OnceLock<&'static Location<'static>>,
+    pub(crate) announced: AtomicBool,
+    // The settings' verdict on this lock, tagged with their generation.
+    pub(crate) verdict: AtomicU64,
+    pub(crate) metadata: OnceLock<Metadata>,
+}
+
+// What `LockConfig::metadata` attached, shared by every lock the config
+// builds, and its Display when attached through `displayed_metadata`.
+#[derive(Debug, Clone)]
+pub(crate) struct Metadata {
+    pub(crate) value: Arc<dyn Any + Send + Sync>,
+    pub(crate) shown: Option<String>,
+}
+
+impl LockMeta {
+    pub(crate) fn registered(
+        scope: Scope,
+        name: String,
+        type_name: &'static str,
+        level: Option<u32>,
+    ) -> Arc<Self> {
+        let mut meta = Self::detached(scope.qualify(name), type_name);
+        meta.label
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .scope = scope;
+        meta.level = level;
+        let meta = Arc::new(meta);
+        locks().insert(meta.id, Arc::downgrade(&meta));
+        meta
+    }
+
+    pub(crate) fn detached(name: String, type_name: &'static str) -> Self {
+        Self {
+            id: NEXT_LOCK.fetch_add(1, Ordering::Relaxed),
+            label: RwLock::new(Label {
+                name: name.into(),
+                scope: Scope::root(),
+            }),
+            type_name,
+            level: None,
+            group: OnceLock::new(),
+            group_totals: OnceLock::new(),
+            release_filter: OnceLock::new(),
+            read_access: OnceLock::new(),
+            write_access: OnceLock::new(),
+            stats: Stats::new(),
+            loops: LoopDetector::default(),
+            writer_waits: WriterWaitDetector::default(),
+            inversions: Inversions::default(),
+            availability: Availability::default(),
+            poisoned: AtomicBool::new(false),
+            used: AtomicBool::new(false),
+            unmutated_writes_ok: AtomicBool::new(false),
+            acquire_delay: AtomicU64::new(0),
+            reader_warn: AtomicUsize::new(0),
+            slow_wait: OnceLock::new(),
+            slow_hold: OnceLock::new(),
+            heartbeat: OnceLock::new(),
+            backtrace: OnceLock::new(),
+            event_ring: OnceLock::new(),
+            created: Instant::now(),
+            created_at: OnceLock::new(),
+            announced: AtomicBool::new(false),
+            verdict: AtomicU64::new(0),
+            metadata: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn name(&self) -> Arc<str> {
+        self.label().name.clone()
+    }
+
+    pub(crate) fn scope(&self) -> Scope {
+        self.label().scope.clone()
+    }
+
+    pub(crate) fn metadata<M: Any>(&self) -> Option<&M> {
+        self.metadata.get()?.value.downcast_ref()
+    }
+
+    pub(crate) fn shown_metadata(&self) -> Option<&str> {
+        self.metadata.get()?.shown.as_deref()
+    }
+
+    fn label(&self) -> RwLockReadGuard<'_, Label> {
+        self.label.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Returns the old name. Guards already registered are renamed with it,
+    // so dumps agree with the events that follow.
+    pub(crate) fn relabel(&self, name: String, scope: Scope) -> Arc<str> {
+        let name: Arc<str> = name.into();
+        let old = {
+            let mut label = self.label.write().unwrap_or_else(|e| e.into_inner());
+            label.scope = scope;
+            mem::replace(&mut label.name, name.clone())
+        };
+        for entry in held().values_mut() {
+            if entry.lock_id == self.id {
+                entry.name = name.to_string();
+            }
+        }
+        old
+    }
+
+    // Short-lived locks would otherwise take their numbers with them.
+    fn retire(&self) {
+        self.scope().retire(&self.stats);
+        if let Some(totals) = self.group_totals.get() {
+            totals.absorb(&self.stats);
+        }
+    }
+
+    fn report_dropped(&self) {
+        let outstanding = held_on(self.id);
+        let holders = self.availability.holders();
+        if holders > 0 || !outstanding.is_empty() {
+            let mut message = format!(
+                "[META] Lock dropped ({}) with {} holders counted and {} guards registered; \
+                 rwlock-trace lost track of a release:",
+                self.name(),
+                holders,
+                outstanding.len(),
+            );
+            for entry in &outstanding {
+                let _ = write!(message, "\n    {}", entry.describe());
+            }
+            fallback::log!(Level::Error, "{}", message);
+        }
+
+        event::dropped(self, Location::caller());
+    }
+}
+
+impl Drop for LockMeta {
+    fn drop(&mut self) {
+        if locks().remove(&self.id).is_some() {
+            summary::retire(self);
+            self.retire();
+            if DROP_SUMMARIES.load(Ordering::Relaxed) {
+                self.report_dropped();
+            }
+        }
+    }
+}
+
+fn locks() -> MutexGuard<'static, BTreeMap<u64, Weak<LockMeta>>> {
+    LOCKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) fn all_locks() -> Vec<Arc<LockMeta>> {
+    locks().values().filter_map(Weak::upgrade).collect()
+}
+
+pub(crate) fn lock(id: u64) -> Option<Arc<LockMeta>> {
+    locks().get(&id).and_then(Weak::upgrade)
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct HeldEntry {
+    pub(crate) lock_id: u64,
+    pub(crate) name: String,
+    pub(crate) mode: Mode,
+    pub(crate) idx: u64,
+    pub(crate) caller: &'static Location<'static>,
+    pub(crate) since: Instant,
+    pub(crate) task: TaskKey,
+    pub(crate) level: Option<u32>,
+    pub(crate) reason: Option<String>,
+    pub(crate) operation: Option<Arc<str>>,
+    // Set while the guard is between tasks after `transfer`.
+    pub(crate) handoff: Option<String>,
+    // Set on copies handed to a task through `with_context`; the id is the
+    // context's.
+    pub(crate) inherited: Option<u64>,
+    pub(crate) thread: ThreadId,
+}
+
+impl HeldEntry {
+    pub(crate) fn describe(&self) -> String {
+        let mut out = format!(
+            "[{}] Held ({}:{}) for {:?} by {}: {}:{}:{}",
+            self.mode,
+            self.name,
+            Idx(self.idx),
+            self.since.elapsed(),
+            self.task,
+            self.caller.file(),
+            self.caller.line(),
+            self.caller.column(),
+        );
+        // Outside a task the holder is already named by its thread.
+        if let TaskKey::Task(_) = self.task {
+            let _ = write!(out, " thread={:?}", self.thread);
+        }
+        if let Some(level) = self.level {
+            let _ = write!(out, " level={}", level);
+        }
+        if let Some(reason) = &self.reason {
+            let _ = write!(out, " reason={:?}", reason);
+        }
+        if let Some(operation) = &self.operation {
+            let _ = write!(out, " op={}", operation);
+        }
+        if let Some(label) = &self.handoff {
+            let _ = write!(out, " handoff={:?} (in transit)", label);
+        }
+        if let Some(context) = self.inherited {
+            let _ = write!(out, " (inherited via ctx={})", context);
+        }
+        out
+    }
+}
+
+// The holds `dump_held_locks` lists, one per row and longest first, in the
+// shape of `tokio-console`'s task list. Names are redacted as in snapshots.
+pub fn render_held_table() -> String {
+    let mut entries: Vec<HeldEntry> = held().values().cloned().collect();
+    entries.sort_by_key(|entry| entry.since);
+
+    let header = ["name", "kind", "idx", "task", "held for", "acquired at"];
+    let mut table: Vec<[String; 6]> = vec![header.map(str::to_owned)];
+    let mut redaction = Redaction::new(Export::Snapshot);
+    for entry in entries {
+        let mut task = entry.task.to_string();
+        if entry.handoff.is_some() {
+            task.push_str(" (in transit)");
+        }
+        table.push([
+            redaction.apply(&entry.name).into_owned(),
+            entry.mode.to_string(),
+            Idx(entry.idx).to_string(),
+            task,
+            format!("{:?}", entry.since.elapsed()),
+            format!(
+                "{}:{}:{}",
+                entry.caller.file(),
+                entry.caller.line(),
+                entry.caller.column()
+            ),
+        ]);
+    }
+    summary::aligned(&table)
+}
+
+fn held() -> MutexGuard<'static, BTreeMap<u64, HeldEntry>> {
+    HELD.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Guards held at once across every traced lock before the holders are dumped
+// at error level; 0 turns the check off. With the `test-util` feature the
+// dump panics instead, so a soak test fails at the acquire that overshot.
+pub fn set_max_concurrent_guards(max: usize) {
+    MAX_GUARDS.store(max, Ordering::Relaxed);
+    OVER_MAX.store(false, Ordering::Relaxed);
+}
+
+pub(crate) fn register(entry: HeldEntry) -> u64 {
+    let id = NEXT_ENTRY.fetch_add(1, Ordering::Relaxed);
+    let mut held = held();
+    held.insert(id, entry);
+
+    let max = MAX_GUARDS.load(Ordering::Relaxed);
+    if max == 0 || held.len() <= max {
+        return id;
+    }
+    // Reported once each time the count climbs past the limit, not on every
+    // acquire while it stays there.
+    if OVER_MAX.swap(true, Ordering::Relaxed) && !cfg!(feature = "test-util") {
+        return id;
+    }
+    let mut message = format!(
+        "[LEAK] {} guards held at once, over the limit of {}:",
+        held.len(),
+        max
+    );
+    for entry in held.values() {
+        let _ = write!(message, "\n    {}", entry.describe());
+    }
+    if cfg!(feature = "test-util") {
+        // The panicking guard is never built, so nothing would unregister it.
+        held.remove(&id);
+        drop(held);
+        panic!("{}", message);
+    }
+    drop(held);
+    fallback::log!(Level::Error, "{}", message);
+    id
+}
+
+// Ids only grow, so anything the same task registered later and still holds
+// was acquired after this entry; the newest of those is returned. None means
+// the entry was already gone, purged by a resync that took it out of the
+// counts too.
+pub(crate) fn unregister(id: u64) -> Option<Option<HeldEntry>> {
+    let mut held = held();
+    let entry = held.remove(&id)?;
+    if held.len() <= MAX_GUARDS.load(Ordering::Relaxed) {
+        OVER_MAX.store(false, Ordering::Relaxed);
+    }
+    Some(
+        held.range(id..)
+            .rev()
+            .map(|(_, other)| other)
+            .find(|other| other.task == entry.task)
+            .cloned(),
+    )
+}
+
+pub(crate) fn mark_handoff(id: u64, label: &str) {
+    if let Some(entry) = held().get_mut(&id) {
+        entry.handoff = Some(label.to_owned());
+    }
+}
+
+pub(crate) fn resume(id: u64, task: TaskKey) {
+    if let Some(entry) = held().get_mut(&id) {
+        entry.handoff = None;
+        entry.task = task;
+    }
+}
+
+pub(crate) fn held_with_ids() -> Vec<(u64, HeldEntry)> {
+    held()
+        .iter()
+        .map(|(&id, entry)| (id, entry.clone()))
+        .collect()
+}
+
+pub(crate) fn held_all() -> Vec<HeldEntry> {
+    held().values().cloned().collect()
+}
+
+pub(crate) fn held_on(lock_id: u64) -> Vec<HeldEntry> {
+    held()
+        .values()
+        .filter(|entry| entry.lock_id == lock_id)
+        .cloned()
+        .collect()
+}
+
+// A guard in transit belongs to neither task until it is redeemed.
+pub(crate) fn held_by(task: TaskKey) -> Vec<HeldEntry> {
+    held()
+        .values()
+        .filter(|entry| entry.task == task && entry.handoff.is_none())
+        .cloned()
+        .collect()
+}
+
+// What `resync_counters` found: each count before and after, and the holds
+// it purged because no guard was behind them.
+#[derive(Debug, Clone, Default)]
+pub struct CounterResync {
+    pub holders_before: usize,
+    pub holders: usize,
+    pub readers_before: usize,
+    pub readers: usize,
+    pub waiters_before: u64,
+    pub waiters: u64,
+    pub leaked: Vec<String>,
+}
+
+impl CounterResync {
+    pub fn changed(&self) -> bool {
+        self.holders_before != self.holders
+            || self.readers_before != self.readers
+            || self.waiters_before != self.waiters
+            || !self.leaked.is_empty()
+    }
+}
+
+// Rebuilds a lock's counts from its registered holds and waits. `idle` means
+// the caller holds the inner lock exclusively, so any hold still registered
+// against it has no guard left behind it and is purged as leaked.
+pub(crate) fn resync(meta: &LockMeta, idle: bool) -> CounterResync {
+    let leaked: Vec<HeldEntry> = if idle {
+        let mut held = held();
+        let ids: Vec<u64> = held
+            .iter()
+            .filter(|(_, entry)| entry.lock_id == meta.id)
+            .map(|(&id, _)| id)
+            .collect();
+        ids.iter().filter_map(|id| held.remove(id)).collect()
+    } else {
+        Vec::new()
+    };
+    let holds = held_on(meta.id);
+    let readers = holds.iter().filter(|entry| entry.mode == READ).count();
+    let waiters = pending()
+        .values()
+        .filter(|entry| entry.lock_id == meta.id)
+        .count() as u64;
+
+    let (holders_before, readers_before) = meta.availability.resync(holds.len(), readers);
+    let report = CounterResync {
+        holders_before,
+        holders: holds.len(),
+        readers_before,
+        readers,
+        waiters_before: meta.stats.resync_waiters(waiters),
+        waiters,
+        leaked: leaked.iter().map(HeldEntry::describe).collect(),
+    };
+    if report.changed() {
+        let mut message = format!(
+            "[META] Counters resynced ({}) holders {} -> {} readers {} -> {} waiters {} -> {}",
+            meta.name(),
+            report.holders_before,
+            report.holders,
+            report.readers_before,
+            report.readers,
+            report.waiters_before,
+            report.waiters,
+        );
+        for entry in &report.leaked {
+            let _ = write!(message, "\n    [LEAK] {}", entry);
+        }
+        fallback::log!(Level::Error, "{}", message);
+    }
+    report
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PendingEntry {
+    pub(crate) lock_id: u64,
+    pub(crate) mode: Mode,
+    pub(crate) idx: u64,
+    pub(crate) caller: &'static Location<'static>,
+    pub(crate) since: Instant,
+    pub(crate) task: TaskKey,
+    pub(crate) operation: Option<Arc<str>>,
+}
+
+fn pending() -> MutexGuard<'static, BTreeMap<u64, PendingEntry>> {
+    PENDING.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Shares the id sequence with held entries; the two maps never mix.
+pub(crate) fn register_pending(entry: PendingEntry) -> u64 {
+    let id = NEXT_ENTRY.fetch_add(1, Ordering::Relaxed);
+    pending().insert(id, entry);
+    id
+}
+
+pub(crate) fn unregister_pending(id: u64) {
+    pending().remove(&id);
+}
+
+pub(crate) fn pending_before(lock_id: u64, entry: u64) -> Vec<PendingEntry> {
+    pending()
+        .range(..entry)
+        .map(|(_, pending)| pending)
+        .filter(|pending| pending.lock_id == lock_id)
+        .cloned()
+        .collect()
+}
+
+// Who a release lets in, judging by the waiters registered now in the order
+// they queued: a writer at the head alone, or the readers up to the first
+// writer. A read release frees the lock only as the last reader leaves, and
+// any reader still registered then was let in before it, so only a queued
+// writer counts.
+pub(crate) fn woken_by(lock_id: u64, released: Mode) -> u64 {
+    let pending = pending();
+    let mut queued = pending
+        .values()
+        .filter(|pending| pending.lock_id == lock_id)
+        .map(|pending| pending.mode);
+    if released == READ {
+        return queued.any(|mode| mode == WRITE) as u64;
+    }
+    match queued.next() {
+        Some(mode) if mode == READ => 1 + queued.take_while(|&mode| mode == READ).count() as u64,
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+pub(crate) fn pending_with_ids() -> Vec<(u64, PendingEntry)> {
+    pending()
+        .iter()
+        .map(|(&id, entry)| (id, entry.clone()))
+        .collect()
+}
+
+pub(crate) fn pending_all() -> Vec<PendingEntry> {
+    pending().values().cloned().collect()
+}
+
+// Off by default; when on, every registered lock reports a summary as it is
+// dropped. Scope and group rollups keep a dropped lock's stats either way.
+pub fn set_drop_summaries(enabled: bool) {
+    DROP_SUMMARIES.store(enabled, Ordering::Relaxed);
+}
+
+// Locks with an event ring also get a section listing its contents.
+pub fn dump_held_locks() -> String {
+    let mut out = String::new();
+
+    for entry in held().values() {
+        out.push_str(&entry.describe());
+        out.push('\n');
+    }
+
+    for meta in all_locks() {
+        let events = match meta.event_ring.get() {
+            Some(ring) => ring.recent(usize::MAX),
+            None => continue,
+        };
+        let _ = writeln!(out, "recent events ({}):", meta.name());
+        for event in events {
+            let _ = writeln!(out, "    {}", event.message);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{scope, LockGroup, RwLock, RwLockWriteGuard};
+
+    use std::{mem, sync::Arc};
+
+    // A forgotten guard keeps the inner lock, so its hold is still real: the
+    // counts it leaves are kept, not purged or wrapped.
+    #[tokio::test]
+    async fn forgotten_guards_stay_counted() {
+        let reads = Arc::new(RwLock::new_named(0u8, "registry-forget-read"));
+        mem::forget(reads.read().await);
+        mem::forget(reads.clone().read_owned().await);
+        let report = reads.resync_counters();
+        assert!(!report.changed());
+        assert_eq!((report.holders, report.readers), (2, 2));
+        assert!(reads.clone().try_write_owned().is_none());
+
+        let write = Arc::new(RwLock::new_named(0u8, "registry-forget-write"));
+        mem::forget(write.write().await);
+        let owned = Arc::new(RwLock::new_named(0u8, "registry-forget-owned"));
+        mem::forget(owned.clone().write_owned().await);
+        let mapped = Arc::new(RwLock::new_named(0u8, "registry-forget-mapped"));
+        mem::forget(RwLockWriteGuard::map(mapped.write().await, |value| value));
+        for lock in [&write, &owned, &mapped] {
+            let report = lock.resync_counters();
+            assert!(!report.changed());
+            assert_eq!((report.holders, report.readers), (1, 0));
+            assert!(lock.clone().try_read_owned().is_none());
+        }
+    }
+
+    // Counts lost behind a guard's back, as a double-counted release would
+    // leave them, must not wrap when that guard is dropped.
+    #[tokio::test]
+    async fn a_release_past_zero_resyncs_instead_of_wrapping() {
+        let lock = RwLock::new_named(0u8, "registry-underflow");
+        let read = lock.read().await;
+        assert_eq!(lock.meta.availability.resync(0, 0), (1, 1));
+        drop(read);
+        let report = lock.resync_counters();
+        assert_eq!((report.holders_before, report.readers_before), (0, 0));
+        assert!(!report.changed());
+        drop(lock.write().await);
+        assert_eq!(lock.resync_counters().holders_before, 0);
+    }
+
+    // A count inflated with no hold behind it is rebuilt from the registry.
+    #[tokio::test]
+    async fn inflated_counts_are_rebuilt() {
+        let lock = RwLock::new_named(0u8, "registry-inflated");
+        let _read = lock.read().await;
+        lock.meta.availability.resync(3, 2);
+        let report = lock.resync_counters();
+        assert!(report.changed());
+        assert_eq!((report.holders_before, report.holders), (3, 1));
+        assert_eq!((report.readers_before, report.readers), (2, 1));
+        assert!(report.leaked.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dropped_locks_stay_in_scope_and_group_rollups() {
+        let scope = scope("registry-rollup");
+        let lock = scope.rwlock_named(0u8, "registry-rollup-lock");
+        drop(lock.read().await);
+        drop(lock.write().await);
+        drop(lock);
+        let stats = scope.stats();
+        assert_eq!((stats.reads, stats.writes), (1, 1));
+
+        let group = LockGroup::new("registry-rollup-group");
+        let member = group.rwlock(0, 0u8);
+        drop(member.write().await);
+        drop(member);
+        assert_eq!(group.stats().writes, 1);
+        assert!(group.member_stats().is_empty());
+    }
+
+    #[tokio::test]
+    async fn held_audited_writes_show_their_reason() {
+        let lock = RwLock::new_named(0u8, "registry-audit-dump");
+        let guard = lock.write_audited("reindex").await;
+        let dump = crate::dump_held_locks();
+        let held = dump
+            .lines()
+            .find(|line| line.contains("registry-audit-dump"))
+            .expect("the audited write is not in the dump");
+        assert!(held.contains(" reason=\"reindex\""), "{}", held);
+        drop(guard);
+        assert!(!crate::dump_held_locks().contains("reason=\"reindex\""));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{
+    event, fallback,
+    phase::{Mode, Phase},
+    registry,
+    scope::Scope,
+    severity, task_level, verdict,
+};
+
+use std::{fmt::Write, panic::Location};
+
+// What `relabel` is shown of each lock to decide its new name.
+#[derive(Debug, Clone)]
+pub struct LockInfo {
+    pub id: u64,
+    pub name: String,
+    pub type_name: &'static str,
+    pub scope: String,
+    pub group: Option<String>,
+    pub created_at: Option<&'static Location<'static>>,
+}
+
+// Renames every lock `rename` returns a new name for, say once the config
+// that says what lock #12 is has loaded, and returns how many changed. Each
+// gets a Rename event naming the old and new names, for tools to join its
+// earlier events by, then one `[META] Relabeled N locks` line covers the
+// pass. Events from here on, in-flight guards' included, carry the new name.
+//
+// A lock whose name was its scope's path plus a last segment moves to the
+// scope the new name's prefix implies; one named apart from its scope keeps
+// it. Groups are set explicitly and are left as they are.
+#[track_caller]
+pub fn relabel<F>(mut rename: F) -> usize
+where
+    F: FnMut(&LockInfo) -> Option<String>,
+{
+    let caller = Location::caller();
+    let mut renamed = 0;
+    for meta in registry::all_locks() {
+        let scope = meta.scope();
+        let info = LockInfo {
+            id: meta.id,
+            name: meta.name().to_string(),
+            type_name: meta.type_name,
+            scope: scope.path().to_owned(),
+            group: meta.group.get().cloned(),
+            created_at: meta.created_at.get().copied(),
+        };
+        let name = match rename(&info) {
+            Some(name) if name != info.name => name,
+            _ => continue,
+        };
+        let moved = implied_scope(&scope, &info.name, &name);
+        let mut message = format!("[META] Renamed ({}) -> ({})", info.name, name);
+        if moved.path() != scope.path() {
+            let _ = write!(message, " scope={:?} -> {:?}", scope.path(), moved.path());
+        }
+        meta.relabel(name, moved);
+        // The directives match on names.
+        verdict::invalidate();
+        let level = task_level::level_override(&meta)
+            .unwrap_or_else(|| severity::severity_policy().acquire);
+        event::emit_message(level, &meta, Mode::Meta, Phase::Rename, 0, caller, message);
+        renamed += 1;
+    }
+    if renamed != 0 {
+        fallback::log!(
+            severity::severity_policy().acquire,
+            "[META] Relabeled {} locks: {}:{}:{}",
+            renamed,
+            caller.file(),
+            caller.line(),
+            caller.column(),
+        );
+    }
+    renamed
+}
+
+fn implied_scope(scope: &Scope, old: &str, new: &str) -> Scope {
+    if prefix(old) != scope.path() || prefix(new) == scope.path() {
+        return scope.clone();
+    }
+    Scope::root().child(prefix(new))
+}
+
+fn prefix(name: &str) -> &str {
+    name.rsplit_once('/').map_or("", |(prefix, _)| prefix)
+}
@@ -0,0 +1,293 @@
+use crate::{clock::Instant, deadlock, fallback, registry};
+
+use log::Level;
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+static POLICY: RwLock<RetentionPolicy> = RwLock::new(RetentionPolicy::new());
+// Set while any limit is, so the default of keeping everything costs the
+// recording paths one load and no timestamps.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static LAST_SWEEP: Mutex<Option<Instant>> = Mutex::new(None);
+static SET_AT: Mutex<Option<Instant>> = Mutex::new(None);
+
+// Bounds on the tables that otherwise grow with every distinct call site a
+// lock ever sees: its per-site stats, its contention pairs, and the
+// acquired-after edges the deadlock check keeps from it to other locks. The
+// caps are per lock; past one, the entry touched longest ago makes room.
+// `max_idle` is applied by `compact`, and by a running sweeper every half of
+// it. None everywhere, the default, keeps everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub max_sites: Option<usize>,
+    pub max_pairs: Option<usize>,
+    pub max_order_edges: Option<usize>,
+    pub max_idle: Option<Duration>,
+}
+
+impl RetentionPolicy {
+    pub const fn new() -> Self {
+        Self {
+            max_sites: None,
+            max_pairs: None,
+            max_order_edges: None,
+            max_idle: None,
+        }
+    }
+
+    fn is_unbounded(&self) -> bool {
+        *self == Self::new()
+    }
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Entries last touched before the policy was set count as touched then, and
+// as older than any touched since.
+pub fn set_retention_policy(policy: RetentionPolicy) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+    *SET_AT.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    ACTIVE.store(!policy.is_unbounded(), Ordering::Release);
+}
+
+pub fn retention_policy() -> RetentionPolicy {
+    *POLICY.read().unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) fn active() -> bool {
+    ACTIVE.load(Ordering::Acquire)
+}
+
+// What one `compact` dropped. Sites and pairs also count toward their lock's
+// `LockStats::evictions`, as do the ones the caps evict as they go.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Compaction {
+    pub sites: u64,
+    pub pairs: u64,
+    // Including every edge from or to a lock that has since been dropped.
+    pub order_edges: u64,
+}
+
+impl Compaction {
+    pub fn total(&self) -> u64 {
+        self.sites + self.pairs + self.order_edges
+    }
+}
+
+// Trims every table to the current policy now: entries idle past `max_idle`
+// first, then the least recently touched down to the caps. With the default
+// policy only the edges of dropped locks go. Aggregation carries on; a site
+// evicted and seen again restarts its counts from zero.
+pub fn compact() -> Compaction {
+    let policy = retention_policy();
+    let now = Instant::now();
+    let mut compaction = Compaction::default();
+    for meta in registry::all_locks() {
+        let (sites, pairs) = meta.stats.compact(&policy, now);
+        compaction.sites += sites;
+        compaction.pairs += pairs;
+    }
+    compaction.order_edges = deadlock::compact(&policy, now);
+    compaction
+}
+
+// From the sweeper's tick: compacts once every half of `max_idle`, and logs
+// what went.
+pub(crate) fn sweep() {
+    let max_idle = match retention_policy().max_idle {
+        Some(max_idle) if active() => max_idle,
+        _ => return,
+    };
+    let now = Instant::now();
+    {
+        let mut last = LAST_SWEEP.lock().unwrap_or_else(|e| e.into_inner());
+        match *last {
+            Some(at) if now.saturating_duration_since(at) < max_idle / 2 => return,
+            _ => *last = Some(now),
+        }
+    }
+    let compaction = compact();
+    if compaction.total() != 0 {
+        fallback::log!(
+            Level::Debug,
+            "[META] Compacted {} call sites, {} contention pairs and {} order edges",
+            compaction.sites,
+            compaction.pairs,
+            compaction.order_edges
+        );
+    }
+}
+
+// A table entry and, while a policy is set, when it was last recorded to.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Tracked<V> {
+    pub(crate) value: V,
+    touched: Option<Instant>,
+}
+
+impl<V> Tracked<V> {
+    pub(crate) fn new(value: V) -> Self {
+        Self {
+            value,
+            touched: active().then(Instant::now),
+        }
+    }
+
+    pub(crate) fn touch(&mut self) {
+        if active() {
+            self.touched = Some(Instant::now());
+        }
+    }
+}
+
+// `key`'s entry, made if missing; see `make_room`.
+pub(crate) fn entry<'a, K, V>(
+    table: &'a mut HashMap<K, Tracked<V>>,
+    key: K,
+    cap: Option<usize>,
+    evictions: &AtomicU64,
+) -> &'a mut V
+where
+    K: Hash + Eq + Copy,
+    V: Default,
+{
+    if !active() {
+        return &mut table.entry(key).or_default().value;
+    }
+    evictions.fetch_add(make_room(table, &key, cap), Ordering::Relaxed);
+    let entry = table
+        .entry(key)
+        .or_insert_with(|| Tracked::new(V::default()));
+    entry.touch();
+    &mut entry.value
+}
+
+// Before `key` is added to a table at its cap, evicts the least recently
+// touched entries to leave it room. Returns how many went.
+pub(crate) fn make_room<K, V>(
+    table: &mut HashMap<K, Tracked<V>>,
+    key: &K,
+    cap: Option<usize>,
+) -> u64
+where
+    K: Hash + Eq + Copy,
+{
+    match cap {
+        Some(cap) if active() && !table.contains_key(key) && table.len() >= cap.max(1) => {
+            evict(table, table.len() + 1 - cap.max(1))
+        }
+        _ => 0,
+    }
+}
+
+// Drops entries idle past the policy's `max_idle`, then the oldest down to
+// `cap`. Returns how many went.
+pub(crate) fn trim<K, V>(
+    table: &mut HashMap<K, Tracked<V>>,
+    cap: Option<usize>,
+    policy: &RetentionPolicy,
+    now: Instant,
+) -> u64
+where
+    K: Hash + Eq + Copy,
+{
+    let before = table.len();
+    if let Some(max_idle) = policy.max_idle {
+        let set_at = SET_AT
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .unwrap_or(now);
+        table.retain(|_, entry| {
+            let touched = entry.touched.unwrap_or(set_at);
+            now.saturating_duration_since(touched) < max_idle
+        });
+    }
+    let mut evicted = (before - table.len()) as u64;
+    if let Some(cap) = cap {
+        evicted += evict(table, table.len().saturating_sub(cap));
+    }
+    evicted
+}
+
+fn evict<K, V>(table: &mut HashMap<K, Tracked<V>>, count: usize) -> u64
+where
+    K: Hash + Eq + Copy,
+{
+    if count == 0 {
+        return 0;
+    }
+    let mut oldest: Vec<(Option<Instant>, K)> = table
+        .iter()
+        .map(|(key, entry)| (entry.touched, *key))
+        .collect();
+    oldest.sort_unstable_by_key(|(touched, _)| *touched);
+    for (_, key) in oldest.iter().take(count) {
+        table.remove(key);
+    }
+    count.min(oldest.len()) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compact, entry, set_retention_policy, RetentionPolicy, Tracked};
+    use crate::RwLock;
+
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    fn cap_sites(max_sites: usize) {
+        set_retention_policy(RetentionPolicy {
+            max_sites: Some(max_sites),
+            ..RetentionPolicy::new()
+        });
+    }
+
+    // One test, as the policy is global; it is put back to keeping everything
+    // at the end.
+    #[tokio::test]
+    async fn tables_stay_within_the_policy() {
+        cap_sites(2);
+
+        // Thousands of synthetic call sites through the path every table takes.
+        let mut table: HashMap<u64, Tracked<u64>> = HashMap::new();
+        let evictions = AtomicU64::new(0);
+        for site in 0..5000 {
+            *entry(&mut table, site, Some(100), &evictions) += 1;
+            assert!(table.len() <= 100);
+        }
+        assert_eq!(evictions.load(Ordering::Relaxed), 4900);
+        let returning = (0..5000).find(|site| !table.contains_key(site)).unwrap();
+        *entry(&mut table, returning, Some(100), &evictions) += 1;
+        assert_eq!(table[&returning].value, 1);
+        assert_eq!(table.len(), 100);
+
+        let lock = RwLock::new_named(0u8, "retention-soak");
+        drop(lock.write().await);
+        drop(lock.write().await);
+        drop(lock.write().await);
+        assert_eq!(lock.meta.stats.sites().len(), 2);
+        assert_eq!(lock.stats().evictions, 1);
+
+        cap_sites(1);
+        assert!(compact().sites >= 1);
+        assert_eq!(lock.meta.stats.sites().len(), 1);
+        assert_eq!(lock.stats().evictions, 2);
+
+        set_retention_policy(RetentionPolicy::new());
+    }
+}
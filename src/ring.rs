@@ -0,0 +1,45 @@
+use crate::event::LockEvent;
+
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard},
+};
+
+// A lock's own last `capacity` events. Filled before directives and sinks are
+// consulted, so it sees every phase the lock emits whatever else is enabled.
+#[derive(Debug)]
+pub(crate) struct EventRing {
+    capacity: usize,
+    events: Mutex<VecDeque<LockEvent>>,
+}
+
+impl EventRing {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    fn events(&self) -> MutexGuard<'_, VecDeque<LockEvent>> {
+        self.events.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn push(&self, event: LockEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = self.events();
+        if events.len() >= self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    // Oldest first.
+    pub(crate) fn recent(&self, n: usize) -> Vec<LockEvent> {
+        let events = self.events();
+        let skip = events.len().saturating_sub(n);
+        events.iter().skip(skip).cloned().collect()
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    config, registry,
+    stats::{LockStats, Stats},
+    verdict, LockConfig, LockGroup, RwLock,
+};
+
+use log::Level;
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+static ROOT: OnceLock<Scope> = OnceLock::new();
+static SCOPES: Mutex<BTreeMap<String, Scope>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    inner: Arc<ScopeInner>,
+}
+
+#[derive(Debug)]
+struct ScopeInner {
+    path: String,
+    parent: Option<Scope>,
+    muted: AtomicBool,
+    sample_every: AtomicU64,
+    seen: AtomicU64,
+    level: AtomicUsize,
+    // Totals of locks dropped under this scope or any below it, kept only
+    // while drop summaries are on.
+    retired: Stats,
+}
+
+pub fn scope(path: &str) -> Scope {
+    Scope::root().child(path)
+}
+
+impl Scope {
+    pub fn root() -> Scope {
+        ROOT.get_or_init(|| Scope::create(String::new(), None))
+            .clone()
+    }
+
+    fn create(path: String, parent: Option<Scope>) -> Scope {
+        Scope {
+            inner: Arc::new(ScopeInner {
+                path,
+                parent,
+                muted: AtomicBool::new(false),
+                sample_every: AtomicU64::new(0),
+                seen: AtomicU64::new(0),
+                level: AtomicUsize::new(0),
+                retired: Stats::new(),
+            }),
+        }
+    }
+
+    // The same path always resolves to the same scope, so settings applied
+    // through one handle affect every lock created under that path.
+    pub fn child(&self, name: &str) -> Scope {
+        let mut scopes = SCOPES.lock().unwrap_or_else(|e| e.into_inner());
+        let mut current = self.clone();
+        for segment in name.split('/').filter(|segment| !segment.is_empty()) {
+            let path = match current.path() {
+                "" => segment.to_owned(),
+                parent => format!("{}/{}", parent, segment),
+            };
+            current = scopes
+                .entry(path.clone())
+                .or_insert_with(|| Scope::create(path, Some(current.clone())))
+                .clone();
+        }
+        current
+    }
+
+    pub fn path(&self) -> &str {
+        &self.inner.path
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        self.inner.muted.store(muted, Ordering::Relaxed);
+        verdict::invalidate();
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.ancestors()
+            .any(|scope| scope.inner.muted.load(Ordering::Relaxed))
+    }
+
+    // Keeps one acquisition in every `every`; 0 inherits from the parent.
+    pub fn set_sample_rate(&self, every: u64) {
+        self.inner.sample_every.store(every, Ordering::Relaxed);
+        verdict::invalidate();
+    }
+
+    pub fn sample_rate(&self) -> u64 {
+        self.ancestors()
+            .map(|scope| scope.inner.sample_every.load(Ordering::Relaxed))
+            .find(|&every| every != 0)
+            .unwrap_or(1)
+    }
+
+    pub fn set_level(&self, level: Level) {
+        self.inner.level.store(level as usize, Ordering::Relaxed);
+    }
+
+    pub fn level(&self) -> Level {
+        self.level_override().unwrap_or(Level::Warn)
+    }
+
+    pub(crate) fn level_override(&self) -> Option<Level> {
+        self.ancestors()
+            .map(|scope| scope.inner.level.load(Ordering::Relaxed))
+            .find(|&level| level != 0)
+            .map(config::level_from_usize)
+    }
+
+    #[track_caller]
+    pub fn rwlock<T>(&self, inner: T) -> RwLock<T> {
+        LockConfig::builder().scope(self).rwlock(inner)
+    }
+
+    #[track_caller]
+    pub fn rwlock_named<T>(&self, inner: T, name: impl Into<String>) -> RwLock<T> {
+        LockConfig::builder().scope(self).name(name).rwlock(inner)
+    }
+
+    #[track_caller]
+    pub fn rwlock_named_with_level<T>(
+        &self,
+        inner: T,
+        name: impl Into<String>,
+        level: u32,
+    ) -> RwLock<T> {
+        LockConfig::builder()
+            .scope(self)
+            .name(name)
+            .order_level(level)
+            .rwlock(inner)
+    }
+
+    #[cfg(feature = "async-lock")]
+    #[track_caller]
+    pub fn async_lock_rwlock_named<T>(
+        &self,
+        inner: T,
+        name: impl Into<String>,
+    ) -> crate::async_lock::RwLock<T> {
+        LockConfig::builder()
+            .scope(self)
+            .name(name)
+            .async_lock_rwlock(inner)
+    }
+
+    #[cfg(feature = "parking-lot")]
+    #[track_caller]
+    pub fn sync_rwlock_named<T>(
+        &self,
+        inner: T,
+        name: impl Into<String>,
+    ) -> crate::sync::RwLock<T> {
+        LockConfig::builder()
+            .scope(self)
+            .name(name)
+            .sync_rwlock(inner)
+    }
+
+    // Shares this scope's path, so lock names are qualified the same way, but
+    // holds its own settings on top of the ones it inherits.
+    pub(crate) fn detached_child(&self) -> Scope {
+        Scope::create(self.path().to_owned(), Some(self.clone()))
+    }
+
+    pub fn lock_group(&self, name: impl Into<String>) -> LockGroup {
+        LockGroup::with_scope(self, name.into())
+    }
+
+    // Live locks created in this scope or below it, plus the ones already
+    // dropped.
+    pub fn stats(&self) -> LockStats {
+        let locks: Vec<_> = registry::all_locks()
+            .into_iter()
+            .filter(|meta| meta.scope().ancestors().any(|scope| scope.is(self)))
+            .collect();
+        Stats::rollup(
+            locks
+                .iter()
+                .map(|meta| &meta.stats)
+                .chain(std::iter::once(&self.inner.retired)),
+        )
+    }
+
+    pub(crate) fn retire(&self, stats: &Stats) {
+        for scope in self.ancestors() {
+            scope.inner.retired.absorb(stats);
+        }
+    }
+
+    fn is(&self, other: &Scope) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    pub(crate) fn qualify(&self, name: String) -> String {
+        match self.path() {
+            "" => name,
+            path => format!("{}/{}", path, name),
+        }
+    }
+
+    pub(crate) fn sample(&self) -> bool {
+        if self.is_muted() {
+            return false;
+        }
+        match self.sample_rate() {
+            1 => true,
+            every => self
+                .inner
+                .seen
+                .fetch_add(1, Ordering::Relaxed)
+                .is_multiple_of(every),
+        }
+    }
+
+    fn ancestors(&self) -> impl Iterator<Item = &Scope> {
+        std::iter::successors(Some(self), |scope| scope.inner.parent.as_ref())
+    }
+}
@@ -0,0 +1,123 @@
+use crate::phase::Phase;
+
+use log::Level;
+
+use std::{sync::RwLock, time::Duration};
+
+static POLICY: RwLock<SeverityPolicy> = RwLock::new(SeverityPolicy::uniform(Level::Warn));
+
+// Base levels per phase, raised to warn/error once a wait or hold crosses the
+// matching threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeverityPolicy {
+    pub acquire: Level,
+    pub got: Level,
+    pub release: Level,
+    pub anomaly: Level,
+    pub wait_warn: Option<Duration>,
+    pub wait_error: Option<Duration>,
+    pub hold_warn: Option<Duration>,
+    pub hold_error: Option<Duration>,
+}
+
+impl SeverityPolicy {
+    pub const fn uniform(level: Level) -> Self {
+        Self {
+            acquire: level,
+            got: level,
+            release: level,
+            anomaly: level,
+            wait_warn: None,
+            wait_error: None,
+            hold_warn: None,
+            hold_error: None,
+        }
+    }
+
+    pub const fn production() -> Self {
+        Self {
+            acquire: Level::Trace,
+            got: Level::Trace,
+            release: Level::Debug,
+            anomaly: Level::Warn,
+            wait_warn: Some(Duration::from_millis(10)),
+            wait_error: Some(Duration::from_secs(1)),
+            hold_warn: Some(Duration::from_millis(100)),
+            hold_error: Some(Duration::from_secs(1)),
+        }
+    }
+
+    pub(crate) fn base(&self, phase: Phase) -> Level {
+        match phase {
+            Phase::Acquire => self.acquire,
+            Phase::Got
+            | Phase::Handoff
+            | Phase::Resume
+            | Phase::Map
+            | Phase::MapFailed
+            | Phase::Init => self.got,
+            Phase::Release => self.release,
+            _ => self.anomaly,
+        }
+    }
+
+    // Anything outside the acquire, got and release family, and those once
+    // escalated past their base level.
+    pub(crate) fn is_anomaly(&self, phase: Phase, level: Level) -> bool {
+        match phase {
+            Phase::Acquire
+            | Phase::Got
+            | Phase::Handoff
+            | Phase::Resume
+            | Phase::Map
+            | Phase::MapFailed
+            | Phase::Init
+            | Phase::Release => level < self.base(phase),
+            _ => true,
+        }
+    }
+
+    pub(crate) fn escalate(
+        &self,
+        level: Level,
+        phase: Phase,
+        waited: Duration,
+        held: Option<Duration>,
+    ) -> Level {
+        match (phase, held) {
+            (Phase::Got, _) => raise(level, waited, self.wait_warn, self.wait_error),
+            (Phase::Release, Some(held)) => raise(level, held, self.hold_warn, self.hold_error),
+            _ => level,
+        }
+    }
+}
+
+impl Default for SeverityPolicy {
+    fn default() -> Self {
+        Self::uniform(Level::Warn)
+    }
+}
+
+fn raise(
+    level: Level,
+    duration: Duration,
+    warn: Option<Duration>,
+    error: Option<Duration>,
+) -> Level {
+    let target = if error.is_some_and(|error| duration >= error) {
+        Level::Error
+    } else if warn.is_some_and(|warn| duration >= warn) {
+        Level::Warn
+    } else {
+        return level;
+    };
+    level.min(target)
+}
+
+pub fn set_severity_policy(policy: SeverityPolicy) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+}
+
+pub fn severity_policy() -> SeverityPolicy {
+    *POLICY.read().unwrap_or_else(|e| e.into_inner())
+}
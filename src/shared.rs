@@ -0,0 +1,88 @@
+use crate::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+
+use std::{fmt, future::Future, ops::Deref, sync::Arc};
+
+// A cloneable handle whose acquires return owned guards, so it can be moved
+// into spawned tasks without an `Arc` at every call site. Everything else
+// derefs to the shared lock.
+pub struct Shared<T> {
+    lock: Arc<RwLock<T>>,
+}
+
+impl<T> Shared<T> {
+    #[track_caller]
+    pub fn new(inner: T) -> Shared<T> {
+        RwLock::new(inner).into()
+    }
+
+    #[track_caller]
+    pub fn new_named(inner: T, name: impl Into<String>) -> Shared<T> {
+        RwLock::new_named(inner, name).into()
+    }
+
+    pub fn as_arc(&self) -> &Arc<RwLock<T>> {
+        &self.lock
+    }
+
+    #[track_caller]
+    pub fn read(&self) -> impl Future<Output = OwnedRwLockReadGuard<T>> {
+        self.lock.clone().read_owned()
+    }
+
+    #[track_caller]
+    pub fn write(&self) -> impl Future<Output = OwnedRwLockWriteGuard<T>> {
+        self.lock.clone().write_owned()
+    }
+
+    #[track_caller]
+    pub fn try_read(&self) -> Option<OwnedRwLockReadGuard<T>> {
+        self.lock.clone().try_read_owned()
+    }
+
+    #[track_caller]
+    pub fn try_write(&self) -> Option<OwnedRwLockWriteGuard<T>> {
+        self.lock.clone().try_write_owned()
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self {
+            lock: self.lock.clone(),
+        }
+    }
+}
+
+impl<T> From<RwLock<T>> for Shared<T> {
+    fn from(lock: RwLock<T>) -> Self {
+        Self {
+            lock: Arc::new(lock),
+        }
+    }
+}
+
+impl<T> From<Arc<RwLock<T>>> for Shared<T> {
+    fn from(lock: Arc<RwLock<T>>) -> Self {
+        Self { lock }
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = RwLock<T>;
+
+    fn deref(&self) -> &RwLock<T> {
+        &self.lock
+    }
+}
+
+impl<T> fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.lock, f)
+    }
+}
+
+impl<T> fmt::Display for Shared<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.lock, f)
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{guard::Acquisition, phase::Phase, RwLock, READ};
+
+use tokio::{
+    sync::{oneshot, Notify},
+    task::JoinHandle,
+};
+
+use std::{future::Future, panic::Location, sync::Arc, time::Duration};
+
+// Contention on demand for detector tests: a background task takes a read on
+// the lock, so writers block while readers still get through, and the
+// returned future resolves once it is held. Its own events carry
+// `simulated=true` and the site of the call that set it up, so a drained
+// recording tells them apart from the code under test. The task needs a
+// tokio runtime, and these panic outside one as `tokio::spawn` does.
+impl<T: Send + Sync + 'static> RwLock<T> {
+    #[track_caller]
+    pub fn block_writes_for(
+        self: Arc<Self>,
+        duration: Duration,
+    ) -> impl Future<Output = JoinHandle<()>> {
+        let caller = Location::caller();
+        let details = format!(" holding for {:?}", duration);
+        self.hold_read(caller, details, tokio::time::sleep(duration))
+    }
+
+    // `notify_one` releases it even if sent before the hold was taken;
+    // `notify_waiters` only does once it waits.
+    #[track_caller]
+    pub fn hold_read_until(
+        self: Arc<Self>,
+        notify: Arc<Notify>,
+    ) -> impl Future<Output = JoinHandle<()>> {
+        let caller = Location::caller();
+        let details = " holding until notified".to_owned();
+        self.hold_read(caller, details, async move { notify.notified().await })
+    }
+
+    async fn hold_read(
+        self: Arc<Self>,
+        caller: &'static Location<'static>,
+        details: String,
+        until: impl Future<Output = ()> + Send + 'static,
+    ) -> JoinHandle<()> {
+        let (held, ready) = oneshot::channel();
+        // Acquired on the task that holds it, so the registry and the
+        // held-across-await checks see the hold where it lives.
+        let task = tokio::spawn(async move {
+            let acquisition = self.begin(
+                READ,
+                Acquisition {
+                    simulated: true,
+                    ..Acquisition::new(caller)
+                },
+            );
+            let guard = self.acquire_read_owned(acquisition).await;
+            let state = &guard.state;
+            state
+                .acquisition
+                .emit(&state.meta, READ, Phase::Fence, caller, &details);
+            let _ = held.send(());
+            until.await;
+            drop(guard);
+        });
+        let _ = ready.await;
+        task
+    }
+}
@@ -0,0 +1,298 @@
+use crate::{
+    clock::Instant,
+    json::json_string,
+    phase::Mode,
+    redact::{Export, Redaction},
+    registry::{self, HeldEntry, PendingEntry},
+    stats::LockStats,
+    READ, WRITE,
+};
+
+use std::{collections::HashMap, fmt::Write, panic::Location, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum LockState {
+    Unlocked,
+    Read,
+    Write,
+}
+
+impl LockState {
+    fn as_str(self) -> &'static str {
+        match self {
+            LockState::Unlocked => "unlocked",
+            LockState::Read => "read",
+            LockState::Write => "write",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HolderSnapshot {
+    pub mode: Mode,
+    pub task: String,
+    pub site: String,
+    pub held_for: Duration,
+    // The transfer label while the guard is between tasks.
+    pub handoff: Option<String>,
+    pub operation: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WaiterSnapshot {
+    pub mode: Mode,
+    pub task: String,
+    pub site: String,
+    pub waiting_for: Duration,
+    pub operation: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LockSnapshot {
+    // Stable for the lock's lifetime, unlike the name, which can repeat.
+    pub id: u64,
+    pub name: String,
+    pub type_name: &'static str,
+    pub scope: String,
+    pub group: Option<String>,
+    // The Display of `LockConfig::displayed_metadata`.
+    pub metadata: Option<String>,
+    pub state: LockState,
+    pub holders: Vec<HolderSnapshot>,
+    pub waiters: Vec<WaiterSnapshot>,
+    pub stats: LockStats,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RegistrySnapshot {
+    pub locks: Vec<LockSnapshot>,
+}
+
+fn site(caller: &'static Location<'static>) -> String {
+    format!("{}:{}:{}", caller.file(), caller.line(), caller.column())
+}
+
+// Reads only the crate's own registry and counters, taking each tracking
+// mutex once, so it never queues behind a held lock.
+pub fn snapshot() -> RegistrySnapshot {
+    let now = Instant::now();
+
+    let mut held: HashMap<u64, Vec<HeldEntry>> = HashMap::new();
+    for entry in registry::held_all() {
+        held.entry(entry.lock_id).or_default().push(entry);
+    }
+    let mut pending: HashMap<u64, Vec<PendingEntry>> = HashMap::new();
+    for entry in registry::pending_all() {
+        pending.entry(entry.lock_id).or_default().push(entry);
+    }
+
+    let mut locks: Vec<LockSnapshot> = registry::all_locks()
+        .iter()
+        .map(|meta| {
+            let holders: Vec<HolderSnapshot> = held
+                .remove(&meta.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|entry| HolderSnapshot {
+                    mode: entry.mode,
+                    task: entry.task.to_string(),
+                    site: site(entry.caller),
+                    held_for: now.saturating_duration_since(entry.since),
+                    handoff: entry.handoff,
+                    operation: entry.operation.map(|id| id.to_string()),
+                })
+                .collect();
+            let waiters = pending
+                .remove(&meta.id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|entry| WaiterSnapshot {
+                    mode: entry.mode,
+                    task: entry.task.to_string(),
+                    site: site(entry.caller),
+                    waiting_for: now.saturating_duration_since(entry.since),
+                    operation: entry.operation.map(|id| id.to_string()),
+                })
+                .collect();
+            let state = if holders.iter().any(|holder| holder.mode == WRITE) {
+                LockState::Write
+            } else if holders.iter().any(|holder| holder.mode == READ) {
+                LockState::Read
+            } else {
+                LockState::Unlocked
+            };
+
+            LockSnapshot {
+                id: meta.id,
+                name: meta.name().to_string(),
+                type_name: meta.type_name,
+                scope: meta.scope().path().to_owned(),
+                group: meta.group.get().cloned(),
+                metadata: meta.shown_metadata().map(str::to_owned),
+                state,
+                holders,
+                waiters,
+                stats: meta.stats.snapshot(),
+            }
+        })
+        .collect();
+    locks.sort_by(|a, b| a.name.cmp(&b.name));
+
+    RegistrySnapshot { locks }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct WaitEdge {
+    pub lock_id: u64,
+    pub lock: String,
+    pub waiter: String,
+    pub waiter_mode: Mode,
+    pub waiter_site: String,
+    pub waiting_for: Duration,
+    pub holder: String,
+    pub holder_mode: Mode,
+    pub holder_site: String,
+    pub held_for: Duration,
+}
+
+// One edge per (waiting task, holding task) pair on the same lock. A cycle
+// through the `waiter` and `holder` fields is a live deadlock.
+pub fn wait_for_graph() -> Vec<WaitEdge> {
+    let now = Instant::now();
+
+    let mut held: HashMap<u64, Vec<HeldEntry>> = HashMap::new();
+    for entry in registry::held_all() {
+        held.entry(entry.lock_id).or_default().push(entry);
+    }
+
+    let mut edges = Vec::new();
+    for waiter in registry::pending_all() {
+        for holder in held.get(&waiter.lock_id).into_iter().flatten() {
+            edges.push(WaitEdge {
+                lock_id: waiter.lock_id,
+                lock: holder.name.clone(),
+                waiter: waiter.task.to_string(),
+                waiter_mode: waiter.mode,
+                waiter_site: site(waiter.caller),
+                waiting_for: now.saturating_duration_since(waiter.since),
+                holder: holder.task.to_string(),
+                holder_mode: holder.mode,
+                holder_site: site(holder.caller),
+                held_for: now.saturating_duration_since(holder.since),
+            });
+        }
+    }
+    edges.sort_by(|a, b| {
+        (&a.waiter, &a.lock, a.lock_id, &a.holder).cmp(&(&b.waiter, &b.lock, b.lock_id, &b.holder))
+    });
+    edges
+}
+
+fn nanos(duration: Duration) -> u128 {
+    duration.as_nanos()
+}
+
+impl RegistrySnapshot {
+    // Same shape as the serde output, except durations are integer `_ns`
+    // fields; available without the `serde` feature. Names, scopes, groups,
+    // metadata, tasks and handoff labels go through the redactor, if one is set.
+    pub fn to_json(&self) -> String {
+        let mut redaction = Redaction::new(Export::Snapshot);
+        let locks: Vec<String> = self
+            .locks
+            .iter()
+            .map(|lock| lock.to_json(&mut redaction))
+            .collect();
+        format!("{{\"locks\":[{}]}}", locks.join(","))
+    }
+}
+
+impl LockSnapshot {
+    pub(crate) fn to_json(&self, redaction: &mut Redaction) -> String {
+        let mut out = format!(
+            "{{\"id\":{},\"name\":{},\"type_name\":{},\"scope\":{},\"group\":{},\
+             \"metadata\":{},\"state\":{},\"holders\":[",
+            self.id,
+            json_string(&redaction.apply(&self.name)),
+            json_string(self.type_name),
+            json_string(&redaction.apply(&self.scope)),
+            match &self.group {
+                Some(group) => json_string(&redaction.apply(group)),
+                None => "null".to_owned(),
+            },
+            match &self.metadata {
+                Some(metadata) => json_string(&redaction.apply(metadata)),
+                None => "null".to_owned(),
+            },
+            json_string(self.state.as_str()),
+        );
+        for (index, holder) in self.holders.iter().enumerate() {
+            let _ = write!(
+                out,
+                "{}{{\"mode\":{},\"task\":{},\"site\":{},\"held_for_ns\":{},\"handoff\":{},\
+                 \"operation\":{}}}",
+                if index == 0 { "" } else { "," },
+                json_string(holder.mode.as_str()),
+                json_string(&redaction.apply(&holder.task)),
+                json_string(&holder.site),
+                nanos(holder.held_for),
+                match &holder.handoff {
+                    Some(label) => json_string(&redaction.apply(label)),
+                    None => "null".to_owned(),
+                },
+                match &holder.operation {
+                    Some(operation) => json_string(&redaction.apply(operation)),
+                    None => "null".to_owned(),
+                },
+            );
+        }
+        out.push_str("],\"waiters\":[");
+        for (index, waiter) in self.waiters.iter().enumerate() {
+            let _ = write!(
+                out,
+                "{}{{\"mode\":{},\"task\":{},\"site\":{},\"waiting_for_ns\":{},\
+                 \"operation\":{}}}",
+                if index == 0 { "" } else { "," },
+                json_string(waiter.mode.as_str()),
+                json_string(&redaction.apply(&waiter.task)),
+                json_string(&waiter.site),
+                nanos(waiter.waiting_for),
+                match &waiter.operation {
+                    Some(operation) => json_string(&redaction.apply(operation)),
+                    None => "null".to_owned(),
+                },
+            );
+        }
+        let stats = &self.stats;
+        let _ = write!(
+            out,
+            "],\"stats\":{{\"reads\":{},\"writes\":{},\"contended\":{},\"total_wait_ns\":{},\
+             \"max_wait_ns\":{},\"total_hold_ns\":{},\"max_hold_ns\":{},\"p50_hold_ns\":{},\
+             \"p99_hold_ns\":{},\"max_queue_depth\":{},\"migrations\":{},\"handoffs\":{},\
+             \"release_handoffs\":{},\"stuck\":{},\"evictions\":{}}}}}",
+            stats.reads,
+            stats.writes,
+            stats.contended,
+            nanos(stats.total_wait),
+            nanos(stats.max_wait),
+            nanos(stats.total_hold),
+            nanos(stats.max_hold),
+            nanos(stats.p50_hold),
+            nanos(stats.p99_hold),
+            stats.max_queue_depth,
+            stats.migrations,
+            stats.handoffs,
+            stats.release_handoffs,
+            stats.stuck,
+            stats.evictions,
+        );
+        out
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    background, clock::Instant, config::Idx, event, fallback, phase::Phase, registry::LockMeta,
+    severity,
+};
+#[cfg(not(target_family = "wasm"))]
+use crate::{registry, task::TaskKey};
+
+use log::Level;
+use tokio::task::JoinHandle;
+
+use std::{collections::VecDeque, panic::Location, sync::Mutex, time::Duration};
+
+const KEPT: usize = 256;
+
+static STALLS: Mutex<VecDeque<(Instant, Instant)>> = Mutex::new(VecDeque::new());
+
+// A task that sleeps for `interval` and measures how late it wakes. Waking
+// `threshold` or more late means a worker was kept busy, usually by blocking
+// code; guards marked with `warn_if_held_through_blocking` check their hold
+// against these stalls on release. Dropping the handle stops it. There is
+// no runtime to watch outside one, so there the detector does not start.
+pub fn spawn_stall_detector(interval: Duration, threshold: Duration) -> StallDetectorHandle {
+    let task = background::try_spawn(async move {
+        loop {
+            let expected = Instant::now() + interval;
+            tokio::time::sleep(interval).await;
+            let woke = Instant::now();
+            let late = woke.saturating_duration_since(expected);
+            if late >= threshold {
+                record(expected, woke);
+                fallback::log!(
+                    severity::severity_policy().anomaly,
+                    "[META] runtime stalled for {:?}",
+                    late
+                );
+            }
+        }
+    });
+    if task.is_none() {
+        fallback::log!(
+            Level::Warn,
+            "[META] spawn_stall_detector called outside a tokio runtime; not started"
+        );
+    }
+    StallDetectorHandle { task }
+}
+
+#[derive(Debug)]
+#[must_use = "the detector stops when this handle is dropped"]
+pub struct StallDetectorHandle {
+    task: Option<JoinHandle<()>>,
+}
+
+impl Drop for StallDetectorHandle {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+fn record(start: Instant, end: Instant) {
+    let mut stalls = STALLS.lock().unwrap_or_else(|e| e.into_inner());
+    if stalls.len() == KEPT {
+        stalls.pop_front();
+    }
+    stalls.push_back((start, end));
+}
+
+// How long the runtime was stalled between `since` and `until`, and in how
+// many stalls.
+pub(crate) fn overlap(since: Instant, until: Instant) -> (Duration, usize) {
+    let stalls = STALLS.lock().unwrap_or_else(|e| e.into_inner());
+    stalls
+        .iter()
+        .filter(|(start, end)| *end > since && *start < until)
+        .fold((Duration::ZERO, 0), |(total, count), (start, end)| {
+            let start = if *start > since { *start } else { since };
+            let end = if *end < until { *end } else { until };
+            (total + end.saturating_duration_since(start), count + 1)
+        })
+}
+
+// `tokio::task::block_in_place`, first reporting every guard the current task
+// holds, since each one is held for as long as `f` blocks. Not on wasm, which
+// has no multi-threaded runtime to block in.
+#[cfg(not(target_family = "wasm"))]
+#[track_caller]
+pub fn block_in_place<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let caller = Location::caller();
+    for entry in registry::held_by(TaskKey::current()) {
+        if let Some(meta) = registry::lock(entry.lock_id) {
+            report(
+                &meta,
+                entry.mode,
+                entry.idx,
+                entry.caller,
+                caller,
+                "block_in_place".to_owned(),
+            );
+        }
+    }
+    tokio::task::block_in_place(f)
+}
+
+pub(crate) fn report(
+    meta: &LockMeta,
+    mode: crate::Mode,
+    idx: u64,
+    acquired_at: &'static Location<'static>,
+    caller: &'static Location<'static>,
+    details: String,
+) {
+    let message = format!(
+        "[{}] Held through blocking ({}:{}) {} acquired_at={}:{}:{}",
+        mode,
+        meta.name(),
+        Idx(idx),
+        details,
+        acquired_at.file(),
+        acquired_at.line(),
+        acquired_at.column(),
+    );
+    event::emit_message(
+        severity::severity_policy().anomaly,
+        meta,
+        mode,
+        Phase::Blocking,
+        idx,
+        caller,
+        message,
+    );
+}
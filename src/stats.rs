@@ -0,0 +1,714 @@
+use crate::{
+    clock::Instant,
+    registry,
+    retention::{self, RetentionPolicy, Tracked},
+};
+
+use std::{
+    collections::HashMap,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+const BUCKETS: usize = 64;
+
+// (waiting writer's site, holder's site).
+type Pair = (&'static Location<'static>, &'static Location<'static>);
+
+#[derive(Debug)]
+pub(crate) struct Histogram {
+    buckets: [AtomicU64; BUCKETS],
+}
+
+impl Histogram {
+    pub(crate) fn new() -> Self {
+        Self {
+            buckets: [(); BUCKETS].map(|_| AtomicU64::new(0)),
+        }
+    }
+
+    pub(crate) fn record(&self, duration: Duration) {
+        let nanos = duration_nanos(duration);
+        let bucket = (64 - nanos.leading_zeros() as usize).min(BUCKETS - 1);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn total(&self) -> u64 {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub(crate) fn counts(&self) -> [u64; BUCKETS] {
+        let mut counts = [0; BUCKETS];
+        for (count, bucket) in counts.iter_mut().zip(self.buckets.iter()) {
+            *count = bucket.load(Ordering::Relaxed);
+        }
+        counts
+    }
+
+    fn absorb(&self, other: &Histogram) {
+        for (bucket, count) in self.buckets.iter().zip(other.counts()) {
+            bucket.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn percentile(&self, quantile: f64) -> Duration {
+        percentile(&self.counts(), quantile)
+    }
+}
+
+fn percentile(counts: &[u64; BUCKETS], quantile: f64) -> Duration {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return Duration::ZERO;
+    }
+
+    let target = ((total as f64) * quantile).ceil().max(1.0) as u64;
+    let mut seen = 0;
+    for (bucket, count) in counts.iter().enumerate() {
+        seen += count;
+        if seen >= target {
+            return bucket_upper_bound(bucket);
+        }
+    }
+
+    bucket_upper_bound(BUCKETS - 1)
+}
+
+fn bucket_upper_bound(bucket: usize) -> Duration {
+    match bucket {
+        0 => Duration::ZERO,
+        b if b >= 64 => Duration::from_nanos(u64::MAX),
+        b => Duration::from_nanos((1u64 << b) - 1),
+    }
+}
+
+pub(crate) fn duration_nanos(duration: Duration) -> u64 {
+    duration.as_nanos().min(u64::MAX as u128) as u64
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SiteStats {
+    pub count: u64,
+    pub total_wait: Duration,
+    pub total_hold: Duration,
+    pub unmutated_writes: u64,
+    // Part of `total_hold` spent with the task suspended, and how often it
+    // yielded; only counted with `set_hold_breakdown` on.
+    pub total_suspended: Duration,
+    pub yields: u64,
+    // Reported through `record_work`; zero where nothing was.
+    pub work_bytes: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct Stats {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    contended: AtomicU64,
+    reads_waited: AtomicU64,
+    writes_waited: AtomicU64,
+    total_wait: AtomicU64,
+    max_wait: AtomicU64,
+    total_hold: AtomicU64,
+    max_hold: AtomicU64,
+    wait: Histogram,
+    hold: Histogram,
+    pending: AtomicU64,
+    max_queue_depth: AtomicU64,
+    migrations: AtomicU64,
+    handoffs: AtomicU64,
+    release_handoffs: AtomicU64,
+    stuck: AtomicU64,
+    sites: Mutex<HashMap<&'static Location<'static>, Tracked<SiteStats>>>,
+    pairs: Mutex<HashMap<Pair, Tracked<u64>>>,
+    evictions: AtomicU64,
+}
+
+impl Stats {
+    pub(crate) fn new() -> Self {
+        Self {
+            reads: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            contended: AtomicU64::new(0),
+            reads_waited: AtomicU64::new(0),
+            writes_waited: AtomicU64::new(0),
+            total_wait: AtomicU64::new(0),
+            max_wait: AtomicU64::new(0),
+            total_hold: AtomicU64::new(0),
+            max_hold: AtomicU64::new(0),
+            wait: Histogram::new(),
+            hold: Histogram::new(),
+            pending: AtomicU64::new(0),
+            max_queue_depth: AtomicU64::new(0),
+            migrations: AtomicU64::new(0),
+            handoffs: AtomicU64::new(0),
+            release_handoffs: AtomicU64::new(0),
+            stuck: AtomicU64::new(0),
+            sites: Mutex::new(HashMap::new()),
+            pairs: Mutex::new(HashMap::new()),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record_got(&self, write: bool, contended: bool, waited: Duration) {
+        if write {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+        }
+        if contended {
+            self.contended.fetch_add(1, Ordering::Relaxed);
+            if write {
+                self.writes_waited.fetch_add(1, Ordering::Relaxed);
+            } else {
+                self.reads_waited.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let nanos = duration_nanos(waited);
+        self.total_wait.fetch_add(nanos, Ordering::Relaxed);
+        self.max_wait.fetch_max(nanos, Ordering::Relaxed);
+        self.wait.record(waited);
+    }
+
+    pub(crate) fn start_waiting(&self, entry: u64) -> Waiting<'_> {
+        let ahead = self.pending.fetch_add(1, Ordering::Relaxed);
+        self.max_queue_depth.fetch_max(ahead + 1, Ordering::Relaxed);
+        Waiting {
+            stats: self,
+            ahead,
+            entry,
+        }
+    }
+
+    pub(crate) fn waiters(&self) -> u64 {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    // Returns the count it replaced.
+    pub(crate) fn resync_waiters(&self, waiters: u64) -> u64 {
+        self.pending.swap(waiters, Ordering::Relaxed)
+    }
+
+    // A writer that waits is counted once against each hold outstanding when
+    // it started waiting, whichever of them it ends up waiting out.
+    pub(crate) fn record_contention(
+        &self,
+        waiter: &'static Location<'static>,
+        holders: impl IntoIterator<Item = &'static Location<'static>>,
+    ) {
+        let mut pairs = self.pairs.lock().unwrap_or_else(|e| e.into_inner());
+        let cap = cap(|policy| policy.max_pairs);
+        for holder in holders {
+            *retention::entry(&mut pairs, (waiter, holder), cap, &self.evictions) += 1;
+        }
+    }
+
+    pub(crate) fn record_migration(&self) {
+        self.migrations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_handoff(&self) {
+        self.handoffs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_woken(&self, woke: u64) {
+        if woke != 0 {
+            self.release_handoffs.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn set_stuck(&self, stuck: bool) {
+        if stuck {
+            self.stuck.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.stuck.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_release(
+        &self,
+        caller: &'static Location<'static>,
+        waited: Duration,
+        held: Duration,
+        unmutated: bool,
+    ) {
+        let nanos = duration_nanos(held);
+        self.total_hold.fetch_add(nanos, Ordering::Relaxed);
+        self.max_hold.fetch_max(nanos, Ordering::Relaxed);
+        self.hold.record(held);
+
+        let mut sites = self.sites.lock().unwrap_or_else(|e| e.into_inner());
+        let site = self.site(&mut sites, caller);
+        site.count += 1;
+        site.total_wait += waited;
+        site.total_hold += held;
+        site.unmutated_writes += u64::from(unmutated);
+    }
+
+    pub(crate) fn record_suspension(
+        &self,
+        caller: &'static Location<'static>,
+        suspended: Duration,
+        yields: u64,
+    ) {
+        let mut sites = self.sites.lock().unwrap_or_else(|e| e.into_inner());
+        let site = self.site(&mut sites, caller);
+        site.total_suspended += suspended;
+        site.yields += yields;
+    }
+
+    pub(crate) fn record_work(&self, caller: &'static Location<'static>, bytes: u64) {
+        let mut sites = self.sites.lock().unwrap_or_else(|e| e.into_inner());
+        let site = self.site(&mut sites, caller);
+        site.work_bytes = site.work_bytes.saturating_add(bytes);
+    }
+
+    fn site<'a>(
+        &self,
+        sites: &'a mut HashMap<&'static Location<'static>, Tracked<SiteStats>>,
+        caller: &'static Location<'static>,
+    ) -> &'a mut SiteStats {
+        let cap = cap(|policy| policy.max_sites);
+        retention::entry(sites, caller, cap, &self.evictions)
+    }
+
+    // Returns the sites and pairs evicted.
+    pub(crate) fn compact(&self, policy: &RetentionPolicy, now: Instant) -> (u64, u64) {
+        let mut sites = self.sites.lock().unwrap_or_else(|e| e.into_inner());
+        let sites = retention::trim(&mut sites, policy.max_sites, policy, now);
+        let mut pairs = self.pairs.lock().unwrap_or_else(|e| e.into_inner());
+        let pairs = retention::trim(&mut pairs, policy.max_pairs, policy, now);
+        self.evictions.fetch_add(sites + pairs, Ordering::Relaxed);
+        (sites, pairs)
+    }
+
+    pub(crate) fn snapshot(&self) -> LockStats {
+        LockStats {
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            contended: self.contended.load(Ordering::Relaxed),
+            total_wait: Duration::from_nanos(self.total_wait.load(Ordering::Relaxed)),
+            max_wait: Duration::from_nanos(self.max_wait.load(Ordering::Relaxed)),
+            p99_wait: self.wait.percentile(0.99),
+            releases: self.hold.total(),
+            total_hold: Duration::from_nanos(self.total_hold.load(Ordering::Relaxed)),
+            max_hold: Duration::from_nanos(self.max_hold.load(Ordering::Relaxed)),
+            p50_hold: self.hold.percentile(0.5),
+            p99_hold: self.hold.percentile(0.99),
+            max_queue_depth: self.max_queue_depth.load(Ordering::Relaxed),
+            migrations: self.migrations.load(Ordering::Relaxed),
+            handoffs: self.handoffs.load(Ordering::Relaxed),
+            release_handoffs: self.release_handoffs.load(Ordering::Relaxed),
+            stuck: self.stuck.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn fairness(&self) -> Fairness {
+        let reads = self.reads.load(Ordering::Relaxed);
+        let writes = self.writes.load(Ordering::Relaxed);
+        let reader_waited = self.reads_waited.load(Ordering::Relaxed);
+        let writer_waited = self.writes_waited.load(Ordering::Relaxed);
+        Fairness {
+            reader_immediate: reads.saturating_sub(reader_waited),
+            reader_waited,
+            writer_immediate: writes.saturating_sub(writer_waited),
+            writer_waited,
+        }
+    }
+
+    // Percentiles come from the merged histograms rather than from the
+    // members' own percentiles, which cannot be combined.
+    pub(crate) fn rollup<'a>(all: impl IntoIterator<Item = &'a Stats>) -> LockStats {
+        let mut total = LockStats::default();
+        let mut wait = [0; BUCKETS];
+        let mut hold = [0; BUCKETS];
+        for stats in all {
+            let snapshot = stats.snapshot();
+            total.reads += snapshot.reads;
+            total.writes += snapshot.writes;
+            total.contended += snapshot.contended;
+            total.total_wait += snapshot.total_wait;
+            total.max_wait = total.max_wait.max(snapshot.max_wait);
+            total.releases += snapshot.releases;
+            total.total_hold += snapshot.total_hold;
+            total.max_hold = total.max_hold.max(snapshot.max_hold);
+            total.max_queue_depth = total.max_queue_depth.max(snapshot.max_queue_depth);
+            total.migrations += snapshot.migrations;
+            total.handoffs += snapshot.handoffs;
+            total.release_handoffs += snapshot.release_handoffs;
+            total.stuck += snapshot.stuck;
+            total.evictions += snapshot.evictions;
+            for (sum, count) in wait.iter_mut().zip(stats.wait.counts()) {
+                *sum += count;
+            }
+            for (sum, count) in hold.iter_mut().zip(stats.hold.counts()) {
+                *sum += count;
+            }
+        }
+        total.p99_wait = percentile(&wait, 0.99);
+        total.p50_hold = percentile(&hold, 0.5);
+        total.p99_hold = percentile(&hold, 0.99);
+        total
+    }
+
+    // Folds a dropped lock's numbers into a long-lived total. Queue depth and
+    // the maxima are kept as maxima; the histograms add bucket by bucket.
+    pub(crate) fn absorb(&self, other: &Stats) {
+        let pairs = [
+            (&self.reads, &other.reads),
+            (&self.writes, &other.writes),
+            (&self.contended, &other.contended),
+            (&self.reads_waited, &other.reads_waited),
+            (&self.writes_waited, &other.writes_waited),
+            (&self.total_wait, &other.total_wait),
+            (&self.total_hold, &other.total_hold),
+            (&self.migrations, &other.migrations),
+            (&self.handoffs, &other.handoffs),
+            (&self.release_handoffs, &other.release_handoffs),
+            (&self.evictions, &other.evictions),
+        ];
+        for (total, value) in pairs {
+            total.fetch_add(value.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        for (max, value) in [
+            (&self.max_wait, &other.max_wait),
+            (&self.max_hold, &other.max_hold),
+            (&self.max_queue_depth, &other.max_queue_depth),
+        ] {
+            max.fetch_max(value.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        self.wait.absorb(&other.wait);
+        self.hold.absorb(&other.hold);
+
+        let mut sites = self.sites.lock().unwrap_or_else(|e| e.into_inner());
+        for (location, other) in other.sites() {
+            let site = self.site(&mut sites, location);
+            site.count += other.count;
+            site.total_wait += other.total_wait;
+            site.total_hold += other.total_hold;
+            site.unmutated_writes += other.unmutated_writes;
+        }
+        drop(sites);
+
+        let mut pairs = self.pairs.lock().unwrap_or_else(|e| e.into_inner());
+        let cap = cap(|policy| policy.max_pairs);
+        for (pair, count) in other.pairs() {
+            *retention::entry(&mut pairs, pair, cap, &self.evictions) += count;
+        }
+    }
+
+    pub(crate) fn sites(&self) -> Vec<(&'static Location<'static>, SiteStats)> {
+        let sites = self.sites.lock().unwrap_or_else(|e| e.into_inner());
+        sites
+            .iter()
+            .map(|(site, stats)| (*site, stats.value))
+            .collect()
+    }
+
+    pub(crate) fn pairs(&self) -> Vec<(Pair, u64)> {
+        let pairs = self.pairs.lock().unwrap_or_else(|e| e.into_inner());
+        pairs
+            .iter()
+            .map(|(pair, count)| (*pair, count.value))
+            .collect()
+    }
+}
+
+// Reads the policy only while one is set.
+fn cap(limit: impl FnOnce(&RetentionPolicy) -> Option<usize>) -> Option<usize> {
+    if retention::active() {
+        limit(&retention::retention_policy())
+    } else {
+        None
+    }
+}
+
+// Sites are keyed by file:line across every live lock, so a line that takes
+// several locks reports their combined cost. Longest total hold first.
+pub fn callsite_stats() -> Vec<(String, SiteStats)> {
+    let mut merged: HashMap<String, SiteStats> = HashMap::new();
+    for meta in registry::all_locks() {
+        for (location, site) in meta.stats.sites() {
+            let entry = merged
+                .entry(format!("{}:{}", location.file(), location.line()))
+                .or_default();
+            entry.count += site.count;
+            entry.total_wait += site.total_wait;
+            entry.total_hold += site.total_hold;
+            entry.unmutated_writes += site.unmutated_writes;
+            entry.total_suspended += site.total_suspended;
+            entry.yields += site.yields;
+            entry.work_bytes += site.work_bytes;
+        }
+    }
+
+    let mut sites: Vec<(String, SiteStats)> = merged.into_iter().collect();
+    sites.sort_by(|a, b| {
+        b.1.total_hold
+            .cmp(&a.1.total_hold)
+            .then_with(|| a.0.cmp(&b.0))
+    });
+    sites
+}
+
+// `file:line`, as `callsite_stats` keys sites.
+pub type Site = String;
+
+// Who contends with whom: (waiting writer's site, holder's site, times), most
+// frequent first, merged across every live lock as in `callsite_stats`. Only
+// writers are counted, since a reader only waits behind a writer.
+pub fn contention_pairs() -> Vec<(Site, Site, u64)> {
+    let site = |location: &Location<'_>| format!("{}:{}", location.file(), location.line());
+    let mut merged: HashMap<(Site, Site), u64> = HashMap::new();
+    for meta in registry::all_locks() {
+        for ((waiter, holder), count) in meta.stats.pairs() {
+            *merged.entry((site(waiter), site(holder))).or_insert(0) += count;
+        }
+    }
+
+    let mut pairs: Vec<(Site, Site, u64)> = merged
+        .into_iter()
+        .map(|((waiter, holder), count)| (waiter, holder, count))
+        .collect();
+    pairs.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| (&a.0, &a.1).cmp(&(&b.0, &b.1))));
+    pairs
+}
+
+// Held across the contended await so the pending count is given back on Got
+// and when the acquiring future is dropped mid-wait.
+#[derive(Debug)]
+pub(crate) struct Waiting<'a> {
+    stats: &'a Stats,
+    ahead: u64,
+    entry: u64,
+}
+
+impl Waiting<'_> {
+    pub(crate) fn ahead(&self) -> u64 {
+        self.ahead
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        // A resync that ran while this waiter was between its entry and its
+        // count can leave the count short; it stays at zero rather than wrap.
+        let _ = self
+            .stats
+            .pending
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+        registry::unregister_pending(self.entry);
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LockStats {
+    pub reads: u64,
+    pub writes: u64,
+    pub contended: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+    pub p99_wait: Duration,
+    pub releases: u64,
+    pub total_hold: Duration,
+    pub max_hold: Duration,
+    pub p50_hold: Duration,
+    pub p99_hold: Duration,
+    pub max_queue_depth: u64,
+    // Guards released from a different task or thread than they were taken on.
+    // A guard passed on with `transfer` counts as a handoff instead.
+    pub migrations: u64,
+    pub handoffs: u64,
+    // Releases made while waiters were queued, so the lock went to one of
+    // them rather than falling idle.
+    pub release_handoffs: u64,
+    // A gauge, not a counter: holds and waits the sweeper currently reports
+    // as past the first rung of the escalation ladder.
+    pub stuck: u64,
+    // Call sites and contention pairs dropped under the retention policy; the
+    // per-site numbers are partial when this is not zero.
+    pub evictions: u64,
+}
+
+// An acquire counts as waited when its first try failed and it had to queue.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Fairness {
+    pub reader_immediate: u64,
+    pub reader_waited: u64,
+    pub writer_immediate: u64,
+    pub writer_waited: u64,
+}
+
+impl Fairness {
+    pub fn reader_wait_ratio(&self) -> f64 {
+        let total = self.reader_immediate + self.reader_waited;
+        ratio(self.reader_waited, total)
+    }
+
+    pub fn writer_wait_ratio(&self) -> f64 {
+        let total = self.writer_immediate + self.writer_waited;
+        ratio(self.writer_waited, total)
+    }
+}
+
+fn ratio(part: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        n => part as f64 / n as f64,
+    }
+}
+
+impl LockStats {
+    pub fn acquisitions(&self) -> u64 {
+        self.reads + self.writes
+    }
+
+    pub fn mean_wait(&self) -> Duration {
+        mean(self.total_wait, self.acquisitions())
+    }
+
+    pub fn mean_hold(&self) -> Duration {
+        mean(self.total_hold, self.releases)
+    }
+
+    pub fn contended_pct(&self) -> f64 {
+        match self.acquisitions() {
+            0 => 0.0,
+            n => self.contended as f64 * 100.0 / n as f64,
+        }
+    }
+}
+
+// A lock's counters at one moment. Two taken under the same load, before and
+// after a change, give the interval's deltas through `diff`.
+#[derive(Debug, Clone, Copy)]
+pub struct StatsSnapshot {
+    pub stats: LockStats,
+    taken_at: Instant,
+}
+
+impl StatsSnapshot {
+    pub(crate) fn take(stats: &Stats) -> Self {
+        Self {
+            stats: stats.snapshot(),
+            taken_at: Instant::now(),
+        }
+    }
+
+    // `self` is the later snapshot. Counters only grow, so deltas saturate at
+    // zero if `earlier` was in fact taken after `self`.
+    pub fn diff(&self, earlier: &StatsSnapshot) -> StatsDiff {
+        let (now, then) = (&self.stats, &earlier.stats);
+        StatsDiff {
+            interval: self.taken_at.saturating_duration_since(earlier.taken_at),
+            reads: now.reads.saturating_sub(then.reads),
+            writes: now.writes.saturating_sub(then.writes),
+            contended: now.contended.saturating_sub(then.contended),
+            releases: now.releases.saturating_sub(then.releases),
+            total_wait: now.total_wait.saturating_sub(then.total_wait),
+            total_hold: now.total_hold.saturating_sub(then.total_hold),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StatsDiff {
+    pub interval: Duration,
+    pub reads: u64,
+    pub writes: u64,
+    pub contended: u64,
+    pub releases: u64,
+    pub total_wait: Duration,
+    pub total_hold: Duration,
+}
+
+impl StatsDiff {
+    pub fn acquisitions(&self) -> u64 {
+        self.reads + self.writes
+    }
+
+    pub fn mean_wait(&self) -> Duration {
+        mean(self.total_wait, self.acquisitions())
+    }
+
+    pub fn mean_hold(&self) -> Duration {
+        mean(self.total_hold, self.releases)
+    }
+
+    pub fn contended_pct(&self) -> f64 {
+        match self.acquisitions() {
+            0 => 0.0,
+            n => self.contended as f64 * 100.0 / n as f64,
+        }
+    }
+}
+
+fn mean(total: Duration, count: u64) -> Duration {
+    match count {
+        0 => Duration::ZERO,
+        n => Duration::from_nanos(duration_nanos(total) / n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Phase, RwLock};
+
+    use std::{sync::Arc, time::Duration};
+
+    #[tokio::test]
+    async fn a_convoy_sets_the_queue_high_water_mark() {
+        let lock = Arc::new(RwLock::new_named(0u8, "stats-convoy"));
+        let id = lock.id();
+        let mut gots =
+            crate::subscribe_where(move |event| event.lock_id == id && event.phase == Phase::Got);
+        let write = lock.write().await;
+
+        // A cancelled waiter leaves the queue as it goes.
+        let cancelled = tokio::time::timeout(Duration::from_millis(10), lock.read()).await;
+        assert!(cancelled.is_err());
+        assert_eq!(lock.waiter_count(), 0);
+
+        let convoy: Vec<_> = (0..3)
+            .map(|_| {
+                let lock = lock.clone();
+                tokio::spawn(async move { drop(lock.write().await) })
+            })
+            .collect();
+        while lock.waiter_count() < 3 {
+            tokio::task::yield_now().await;
+        }
+        drop(write);
+        for task in convoy {
+            task.await.unwrap();
+        }
+
+        let queued: Vec<String> = std::iter::from_fn(|| gots.try_recv().ok())
+            .filter_map(|event| {
+                let queued = event
+                    .message
+                    .split(' ')
+                    .find(|field| field.starts_with("queued_behind="))?;
+                Some(queued.to_owned())
+            })
+            .collect();
+        assert_eq!(
+            queued,
+            ["queued_behind=0", "queued_behind=1", "queued_behind=2"]
+        );
+        assert_eq!(lock.stats().max_queue_depth, 3);
+        assert_eq!(lock.waiter_count(), 0);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::{
+    advisory,
+    csv::csv_field,
+    json::json_string,
+    redact::{Export, Redaction},
+    registry::{self, LockMeta},
+    stats::{LockStats, SiteStats},
+};
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    io,
+    panic::Location,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+static EXIT_GUARDS: AtomicUsize = AtomicUsize::new(0);
+static RETIRED: Mutex<Vec<Row>> = Mutex::new(Vec::new());
+
+#[derive(Clone)]
+pub(crate) struct Row {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    scope: String,
+    group: Option<String>,
+    type_name: &'static str,
+    pub(crate) created_at: Option<&'static Location<'static>>,
+    pub(crate) stats: LockStats,
+    top_site: Option<(&'static Location<'static>, SiteStats)>,
+}
+
+pub(crate) fn rows() -> Vec<Row> {
+    let mut rows: Vec<Row> = registry::all_locks().iter().map(|meta| row(meta)).collect();
+    rows.extend(
+        RETIRED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(Row::clone),
+    );
+    rows.sort_by(|a, b| {
+        b.stats
+            .total_wait
+            .cmp(&a.stats.total_wait)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    rows
+}
+
+// Rows are redacted on the way out; sorting and advisories see the originals.
+fn redacted(rows: Vec<Row>, redaction: &mut Redaction) -> Vec<Row> {
+    rows.into_iter()
+        .map(|mut row| {
+            row.name = redaction.apply(&row.name).into_owned();
+            row.scope = redaction.apply(&row.scope).into_owned();
+            row.group = row.group.map(|group| redaction.apply(&group).into_owned());
+            row
+        })
+        .collect()
+}
+
+fn row(meta: &LockMeta) -> Row {
+    Row {
+        id: meta.id,
+        name: meta.name().to_string(),
+        scope: meta.scope().path().to_owned(),
+        group: meta.group.get().cloned(),
+        type_name: meta.type_name,
+        created_at: meta.created_at.get().copied(),
+        stats: meta.stats.snapshot(),
+        top_site: meta
+            .stats
+            .sites()
+            .into_iter()
+            .max_by_key(|(_, site)| site.total_hold),
+    }
+}
+
+fn site_label(site: &Option<(&'static Location<'static>, SiteStats)>) -> String {
+    match site {
+        Some((location, _)) => format!("{}:{}", location.file(), location.line()),
+        None => "-".to_owned(),
+    }
+}
+
+pub fn summary() -> String {
+    let header = [
+        "name", "type", "acq (r/w)", "contended", "p50 hold", "p99 hold", "max wait", "top site",
+    ];
+    let mut table: Vec<[String; 8]> = vec![header.map(str::to_owned)];
+
+    let mut redaction = Redaction::new(Export::Summary);
+    for row in redacted(rows(), &mut redaction) {
+        table.push([
+            row.name,
+            row.type_name.to_owned(),
+            format!("{}/{}", row.stats.reads, row.stats.writes),
+            format!("{:.1}%", row.stats.contended_pct()),
+            format!("{:?}", row.stats.p50_hold),
+            format!("{:?}", row.stats.p99_hold),
+            format!("{:?}", row.stats.max_wait),
+            site_label(&row.top_site),
+        ]);
+    }
+
+    let mut out = aligned(&table);
+    let advisories = advisory::advisories();
+    if !advisories.is_empty() {
+        out.push_str("\nadvisories:\n");
+        for mut advisory in advisories {
+            advisory.name = redaction.apply(&advisory.name).into_owned();
+            let _ = writeln!(out, "  {}", advisory);
+        }
+    }
+
+    out
+}
+
+// Columns padded to their widest cell and two spaces apart.
+pub(crate) fn aligned<const N: usize>(table: &[[String; N]]) -> String {
+    let mut widths = [0; N];
+    for line in table {
+        for (width, cell) in widths.iter_mut().zip(line.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    for line in table {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        let _ = writeln!(out, "{}", cells.join("  ").trim_end());
+    }
+    out
+}
+
+pub fn summary_json() -> String {
+    let mut redaction = Redaction::new(Export::Summary);
+    let locks: Vec<String> = redacted(rows(), &mut redaction)
+        .into_iter()
+        .map(|row| {
+            format!(
+                "{{\"id\":{},\"name\":{},\"scope\":{},\"group\":{},\"type\":{},\
+                 \"reads\":{},\"writes\":{},\"contended_pct\":{:.3},\
+                 \"p50_hold_ns\":{},\"p99_hold_ns\":{},\"max_wait_ns\":{},\"total_wait_ns\":{},\
+                 \"top_site\":{}}}",
+                row.id,
+                json_string(&row.name),
+                json_string(&row.scope),
+                match &row.group {
+                    Some(group) => json_string(group),
+                    None => "null".to_owned(),
+                },
+                json_string(row.type_name),
+                row.stats.reads,
+                row.stats.writes,
+                row.stats.contended_pct(),
+                nanos(row.stats.p50_hold),
+                nanos(row.stats.p99_hold),
+                nanos(row.stats.max_wait),
+                nanos(row.stats.total_wait),
+                match &row.top_site {
+                    Some(_) => json_string(&site_label(&row.top_site)),
+                    None => "null".to_owned(),
+                },
+            )
+        })
+        .collect();
+
+    format!("{{\"locks\":[{}]}}", locks.join(","))
+}
+
+// Sorted by name rather than by cost, so exports from two runs diff cleanly.
+// Ids differ between runs, so they only break ties.
+pub fn export_stats_csv(mut writer: impl io::Write) -> io::Result<()> {
+    let mut rows = rows();
+    rows.sort_by(|a, b| {
+        a.name
+            .cmp(&b.name)
+            .then_with(|| a.scope.cmp(&b.scope))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    writeln!(
+        writer,
+        "id,name,type,scope,reads,writes,contended_pct,mean_wait_ns,p99_wait_ns,max_wait_ns,\
+         mean_hold_ns,p99_hold_ns,max_hold_ns"
+    )?;
+    let mut redaction = Redaction::new(Export::Summary);
+    for row in redacted(rows, &mut redaction) {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{:.3},{},{},{},{},{},{}",
+            row.id,
+            csv_field(&row.name),
+            csv_field(row.type_name),
+            csv_field(&row.scope),
+            row.stats.reads,
+            row.stats.writes,
+            row.stats.contended_pct(),
+            nanos(row.stats.mean_wait()),
+            nanos(row.stats.p99_wait),
+            nanos(row.stats.max_wait),
+            nanos(row.stats.mean_hold()),
+            nanos(row.stats.p99_hold),
+            nanos(row.stats.max_hold),
+        )?;
+    }
+    Ok(())
+}
+
+// Only live locks keep call sites. Sites differing only by column share a row;
+// locks sharing a name do not.
+pub fn export_call_sites_csv(mut writer: impl io::Write) -> io::Result<()> {
+    let mut sites: BTreeMap<(String, u64, &'static str, u32), SiteStats> = BTreeMap::new();
+    for meta in registry::all_locks() {
+        for (location, site) in meta.stats.sites() {
+            let entry = sites
+                .entry((meta.name().to_string(), meta.id, location.file(), location.line()))
+                .or_default();
+            entry.count += site.count;
+            entry.total_wait += site.total_wait;
+            entry.total_hold += site.total_hold;
+            entry.unmutated_writes += site.unmutated_writes;
+            entry.total_suspended += site.total_suspended;
+            entry.yields += site.yields;
+            entry.work_bytes += site.work_bytes;
+        }
+    }
+
+    writeln!(
+        writer,
+        "lock_id,lock,file,line,count,total_wait_ns,total_hold_ns,work_bytes"
+    )?;
+    let mut redaction = Redaction::new(Export::Summary);
+    for ((name, id, file, line), site) in sites {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{}",
+            id,
+            csv_field(&redaction.apply(&name)),
+            csv_field(file),
+            line,
+            site.count,
+            nanos(site.total_wait),
+            nanos(site.total_hold),
+            site.work_bytes,
+        )?;
+    }
+    Ok(())
+}
+
+// Locks dropped while an exit guard is alive keep their final row so the
+// summary still covers them.
+pub(crate) fn retire(meta: &LockMeta) {
+    if EXIT_GUARDS.load(Ordering::Relaxed) > 0 {
+        let row = row(meta);
+        RETIRED.lock().unwrap_or_else(|e| e.into_inner()).push(row);
+    }
+}
+
+pub fn print_summary_on_exit() -> SummaryGuard {
+    EXIT_GUARDS.fetch_add(1, Ordering::Relaxed);
+    SummaryGuard { _private: () }
+}
+
+#[derive(Debug)]
+#[must_use = "the summary is printed when this guard is dropped"]
+pub struct SummaryGuard {
+    _private: (),
+}
+
+impl Drop for SummaryGuard {
+    fn drop(&mut self) {
+        eprint!("{}", summary());
+        if EXIT_GUARDS.fetch_sub(1, Ordering::Relaxed) == 1 {
+            RETIRED.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u128 {
+    duration.as_nanos()
+}
+
+#[cfg(all(test, not(feature = "test-clock")))]
+mod tests {
+    use super::{export_call_sites_csv, export_stats_csv};
+    use crate::RwLock;
+
+    use std::{io, time::Duration};
+
+    // The header and this test's rows; other tests' locks share the exports.
+    fn rows(export: fn(&mut Vec<u8>) -> io::Result<()>, ids: &[u64]) -> Vec<String> {
+        let mut out = Vec::new();
+        export(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut lines = out.lines();
+        let mut rows = vec![lines.next().unwrap().to_owned()];
+        rows.extend(lines.filter_map(|line| {
+            let id = line.split(',').next()?.parse().ok()?;
+            ids.contains(&id).then(|| line.to_owned())
+        }));
+        rows
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn csv_exports_match_the_golden_output() {
+        // Made in reverse of name order, so the rows show the sort.
+        let second = RwLock::new_named(0u8, "csv-golden");
+        let first = RwLock::new_named(0u8, r#"csv, "golden""#);
+        let (read_line, read) = (line!(), first.read().await);
+        tokio::time::sleep(Duration::from_millis(3)).await;
+        drop(read);
+        let (write_line, write) = (line!(), second.write().await);
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        drop(write);
+
+        let (a, b) = (first.id(), second.id());
+        let stats = rows(|out| export_stats_csv(out), &[a, b]);
+        assert_eq!(
+            stats,
+            [
+                "id,name,type,scope,reads,writes,contended_pct,mean_wait_ns,p99_wait_ns,\
+                 max_wait_ns,mean_hold_ns,p99_hold_ns,max_hold_ns"
+                    .to_owned(),
+                format!(r#"{},"csv, ""golden""",u8,,1,0,0.000,0,0,0,3000000,4194303,3000000"#, a),
+                format!("{},csv-golden,u8,,0,1,0.000,0,0,0,5000000,8388607,5000000", b),
+            ]
+        );
+        let sites = rows(|out| export_call_sites_csv(out), &[a, b]);
+        assert_eq!(
+            sites,
+            [
+                "lock_id,lock,file,line,count,total_wait_ns,total_hold_ns,work_bytes".to_owned(),
+                format!(r#"{},"csv, ""golden""",src/summary.rs,{},1,0,3000000,0"#, a, read_line),
+                format!("{},csv-golden,src/summary.rs,{},1,0,5000000,0", b, write_line),
+            ]
+        );
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{
+    background::Ticker,
+    clock::Instant,
+    config::Idx,
+    event, metrics_facade,
+    phase::{Mode, Phase},
+    registry::{self, HeldEntry, LockMeta},
+    retention, severity,
+    task::TaskKey,
+};
+
+use log::Level;
+
+use std::{
+    collections::HashMap,
+    panic::Location,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+const DEFAULT_LADDER: [Duration; 3] = [
+    Duration::from_secs(1),
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+];
+
+static LADDER: Mutex<Option<Vec<Duration>>> = Mutex::new(None);
+
+// Ages at which a hold or wait is reported again, each louder than the last:
+// info for the first rung, error for the last, warn in between. An empty
+// ladder stops the reports.
+pub fn set_escalation_ladder(thresholds: impl Into<Vec<Duration>>) {
+    let mut thresholds = thresholds.into();
+    thresholds.sort();
+    thresholds.dedup();
+    *LADDER.lock().unwrap_or_else(|e| e.into_inner()) = Some(thresholds);
+}
+
+fn ladder() -> Vec<Duration> {
+    LADDER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| DEFAULT_LADDER.to_vec())
+}
+
+// One task walks the held and pending registries every `interval`, so
+// guards need no timer of their own; started outside a runtime, a thread
+// does. It also ages out idle entries under the retention policy's
+// `max_idle`. Dropping the handle stops it.
+pub fn spawn_sweeper(interval: Duration) -> SweeperHandle {
+    let mut sweeper = Sweeper::default();
+    SweeperHandle {
+        _ticker: Ticker::every(interval, move || sweeper.sweep()),
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "the sweeper stops when this handle is dropped"]
+pub struct SweeperHandle {
+    _ticker: Ticker,
+}
+
+// What a registry entry looked like when it was swept, whichever map it is in.
+struct Entry {
+    lock_id: u64,
+    mode: Mode,
+    idx: u64,
+    caller: &'static Location<'static>,
+    since: Instant,
+    task: TaskKey,
+    holding: bool,
+}
+
+struct Escalation {
+    meta: Arc<LockMeta>,
+    rung: usize,
+}
+
+struct Beat {
+    meta: Arc<LockMeta>,
+    every: Duration,
+    next: Instant,
+}
+
+// Keyed by registry entry id; held and pending entries share one sequence.
+// Lives inside the task, so aborting it runs `Drop` and the gauges go back.
+#[derive(Default)]
+struct Sweeper {
+    escalated: HashMap<u64, Escalation>,
+    // `None` for holds of locks without a heartbeat, so each is looked up once.
+    beats: HashMap<u64, Option<Beat>>,
+}
+
+impl Sweeper {
+    fn sweep(&mut self) {
+        let ladder = ladder();
+        let now = Instant::now();
+        let held = registry::held_with_ids();
+        self.beat(now, &held);
+        retention::sweep();
+        let held = held.into_iter().map(|(id, entry)| {
+            let entry = Entry {
+                lock_id: entry.lock_id,
+                mode: entry.mode,
+                idx: entry.idx,
+                caller: entry.caller,
+                since: entry.since,
+                task: entry.task,
+                holding: true,
+            };
+            (id, entry)
+        });
+        let pending = registry::pending_with_ids().into_iter().map(|(id, entry)| {
+            let entry = Entry {
+                lock_id: entry.lock_id,
+                mode: entry.mode,
+                idx: entry.idx,
+                caller: entry.caller,
+                since: entry.since,
+                task: entry.task,
+                holding: false,
+            };
+            (id, entry)
+        });
+
+        let mut seen = Vec::new();
+        for (id, entry) in held.chain(pending) {
+            let age = now.saturating_duration_since(entry.since);
+            let rung = ladder
+                .iter()
+                .take_while(|&&threshold| age >= threshold)
+                .count();
+            if rung == 0 {
+                continue;
+            }
+            seen.push(id);
+            let previous = self
+                .escalated
+                .get(&id)
+                .map_or(0, |escalation| escalation.rung);
+            if rung <= previous {
+                continue;
+            }
+            // The lock can go away between reading the registry and here.
+            let meta = match self.escalated.get(&id) {
+                Some(escalation) => escalation.meta.clone(),
+                None => match registry::lock(entry.lock_id) {
+                    Some(meta) => meta,
+                    None => continue,
+                },
+            };
+            if previous == 0 {
+                meta.stats.set_stuck(true);
+                metrics_facade::on_stuck(&meta, true);
+            }
+            report(&meta, &entry, age, rung, &ladder);
+            self.escalated.insert(id, Escalation { meta, rung });
+        }
+
+        // Whatever was not seen again has been released or stopped waiting.
+        seen.sort_unstable();
+        self.escalated.retain(|id, escalation| {
+            let present = seen.binary_search(id).is_ok();
+            if !present {
+                clear(&escalation.meta);
+            }
+            present
+        });
+    }
+}
+
+impl Sweeper {
+    fn beat(&mut self, now: Instant, held: &[(u64, HeldEntry)]) {
+        for (id, entry) in held {
+            let beat = self.beats.entry(*id).or_insert_with(|| {
+                let meta = registry::lock(entry.lock_id)?;
+                let (after, every) = *meta.heartbeat.get()?;
+                Some(Beat {
+                    meta,
+                    every,
+                    next: entry.since + after,
+                })
+            });
+            let beat = match beat {
+                Some(beat) if now >= beat.next => beat,
+                _ => continue,
+            };
+            beat.next = now + beat.every;
+            still_holding(
+                &beat.meta,
+                entry,
+                now.saturating_duration_since(entry.since),
+            );
+        }
+        // Held entries come sorted by id.
+        self.beats
+            .retain(|id, _| held.binary_search_by_key(id, |(id, _)| *id).is_ok());
+    }
+}
+
+impl Drop for Sweeper {
+    fn drop(&mut self) {
+        for escalation in self.escalated.values() {
+            clear(&escalation.meta);
+        }
+    }
+}
+
+fn clear(meta: &LockMeta) {
+    meta.stats.set_stuck(false);
+    metrics_facade::on_stuck(meta, false);
+}
+
+fn report(meta: &LockMeta, entry: &Entry, age: Duration, rung: usize, ladder: &[Duration]) {
+    if meta.scope().is_muted() {
+        return;
+    }
+    let level = if rung == ladder.len() {
+        Level::Error
+    } else if rung == 1 {
+        Level::Info
+    } else {
+        severity::severity_policy().anomaly
+    };
+    let message = format!(
+        "[{}] Stuck ({}:{}) {} {:?} by {} past {:?} ({}/{})",
+        entry.mode,
+        meta.name(),
+        Idx(entry.idx),
+        if entry.holding { "held" } else { "waiting" },
+        age,
+        entry.task,
+        ladder[rung - 1],
+        rung,
+        ladder.len(),
+    );
+    event::emit_message(
+        level,
+        meta,
+        entry.mode,
+        Phase::Stuck,
+        entry.idx,
+        entry.caller,
+        message,
+    );
+}
+
+fn still_holding(meta: &LockMeta, entry: &HeldEntry, held: Duration) {
+    if meta.scope().is_muted() {
+        return;
+    }
+    let message = format!(
+        "[{}] Still holding ({}:{}) for {:?}",
+        entry.mode,
+        meta.name(),
+        Idx(entry.idx),
+        held
+    );
+    event::emit_message(
+        Level::Info,
+        meta,
+        entry.mode,
+        Phase::Heartbeat,
+        entry.idx,
+        entry.caller,
+        message,
+    );
+}
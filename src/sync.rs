@@ -0,0 +1,597 @@
+use crate::{
+    access::AccessRule,
+    clock::Instant,
+    event,
+    filter::ReleaseFilter,
+    guard::{Acquisition, GuardState},
+    phase::Phase,
+    registry::{self, LockMeta},
+    ring::EventRing,
+    value::{self, ValueHooks},
+    AcquireReport, AcquireTiming, CounterResync, Fairness, FairnessReport, HoldInfo, LockConfig,
+    LockEvent, LockStats, Scope, StatsSnapshot, READ, WRITE,
+};
+
+use std::{
+    any::{self, Any},
+    fmt::{self, Debug},
+    hash::Hash,
+    ops::{Deref, DerefMut},
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+// Blocking counterpart of the tokio-backed lock. It lives alongside the async
+// type rather than replacing it so enabling the feature never changes the API
+// seen by other crates in the same build.
+pub struct RwLock<T> {
+    lock: parking_lot::RwLock<T>,
+    pub(crate) meta: Arc<LockMeta>,
+    idx: AtomicU64,
+    hooks: ValueHooks<T>,
+}
+
+impl<T> RwLock<T> {
+    #[track_caller]
+    pub fn new(inner: T) -> RwLock<T> {
+        LockConfig::builder().sync_rwlock(inner)
+    }
+
+    #[track_caller]
+    pub fn new_debug(inner: T) -> RwLock<T>
+    where
+        T: Debug,
+    {
+        Self::new(inner).with_value_debug(value::DEFAULT_DEBUG_LEN)
+    }
+
+    #[track_caller]
+    pub fn new_named(inner: T, name: impl Into<String>) -> RwLock<T> {
+        LockConfig::builder().name(name).sync_rwlock(inner)
+    }
+
+    // Named after the construction site instead of a random word.
+    #[track_caller]
+    pub fn new_here(inner: T) -> RwLock<T> {
+        let name = LockConfig::site_name(Location::caller());
+        LockConfig::builder().name(name).sync_rwlock(inner)
+    }
+
+    #[track_caller]
+    pub fn new_named_with_level(inner: T, name: impl Into<String>, level: u32) -> RwLock<T> {
+        LockConfig::builder()
+            .name(name)
+            .order_level(level)
+            .sync_rwlock(inner)
+    }
+
+    pub(crate) fn with_scope(
+        inner: T,
+        scope: Scope,
+        name: String,
+        level: Option<u32>,
+    ) -> RwLock<T> {
+        Self {
+            lock: parking_lot::RwLock::new(inner),
+            meta: LockMeta::registered(scope, name, any::type_name::<T>(), level),
+            idx: AtomicU64::new(0),
+            hooks: ValueHooks::new(),
+        }
+    }
+
+    pub fn with_release_filter<F>(self, filter: F) -> Self
+    where
+        F: Fn(&HoldInfo<'_>) -> bool + Send + Sync + 'static,
+    {
+        let _ = self.meta.release_filter.set(ReleaseFilter::new(filter));
+        self
+    }
+
+    // Writes from outside these modules are logged at error with their call
+    // site, or panic under `set_strict_access`. `myapp::maintenance` covers
+    // `src/maintenance.rs` and `src/maintenance/` of `myapp`; the first
+    // restriction set sticks.
+    pub fn restrict_writes_to_modules(self, modules: &[&str]) -> Self {
+        let _ = self.meta.write_access.set(AccessRule::modules(modules));
+        self
+    }
+
+    pub fn restrict_reads_to_modules(self, modules: &[&str]) -> Self {
+        let _ = self.meta.read_access.set(AccessRule::modules(modules));
+        self
+    }
+
+    // For modules a file path cannot name, such as inline ones.
+    pub fn restrict_writes<F>(self, allowed: F) -> Self
+    where
+        F: Fn(&Location<'_>) -> bool + Send + Sync + 'static,
+    {
+        let _ = self.meta.write_access.set(AccessRule::new(allowed));
+        self
+    }
+
+    pub fn restrict_reads<F>(self, allowed: F) -> Self
+    where
+        F: Fn(&Location<'_>) -> bool + Send + Sync + 'static,
+    {
+        let _ = self.meta.read_access.set(AccessRule::new(allowed));
+        self
+    }
+
+    // Write guards that only read are reported on release. Mutation through
+    // interior mutability behind `&T` is not seen and shows up as a false
+    // positive; this turns the report off for the whole lock.
+    pub fn allow_unmutated_writes(self) -> Self {
+        self.meta.unmutated_writes_ok.store(true, Ordering::Relaxed);
+        self
+    }
+
+    // A read Got that leaves more than `max` readers active is logged at warn
+    // at least, to surface read storms. Zero, the default, turns it off.
+    pub fn set_reader_warn_threshold(&self, max: usize) {
+        self.meta.reader_warn.store(max, Ordering::Relaxed);
+    }
+
+    // Rebuilds the holder, reader and waiter counts from the registered holds
+    // and waits. If nothing holds the lock at that moment, a hold still
+    // registered has no guard behind it and is purged and reported. A guard
+    // leaked with `mem::forget` keeps the inner lock too, so it still counts.
+    pub fn resync_counters(&self) -> CounterResync {
+        let probe = self.lock.try_write();
+        registry::resync(&self.meta, probe.is_some())
+    }
+
+    // Keeps this lock's last `capacity` events for `recent_events`, whatever
+    // directives and sinks are set. Off unless set; the memory bound is
+    // `capacity` events for this lock.
+    pub fn with_event_ring(self, capacity: usize) -> Self {
+        let _ = self.meta.event_ring.set(EventRing::new(capacity));
+        self
+    }
+
+    pub fn with_change_tracking(mut self) -> Self
+    where
+        T: Hash,
+    {
+        self.hooks.set_hash_fingerprint();
+        self
+    }
+
+    pub fn with_fingerprint<F>(mut self, fingerprint: F) -> Self
+    where
+        F: Fn(&T) -> u64 + Send + Sync + 'static,
+    {
+        self.hooks.set_fingerprint(fingerprint);
+        self
+    }
+
+    pub fn with_value_debug(mut self, max_len: usize) -> Self
+    where
+        T: Debug,
+    {
+        self.hooks.set_debug(max_len);
+        self
+    }
+
+    // Unique for the life of the process and never reused, unlike names; the
+    // `lock_id` on events, snapshots and exports.
+    pub fn id(&self) -> u64 {
+        self.meta.id
+    }
+
+    // Can change under `relabel`, so it is handed out rather than borrowed.
+    pub fn name(&self) -> Arc<str> {
+        self.meta.name()
+    }
+
+    pub fn level(&self) -> Option<u32> {
+        self.meta.level
+    }
+
+    pub fn metadata<M: Any>(&self) -> Option<&M> {
+        self.meta.metadata()
+    }
+
+    pub fn scope(&self) -> Scope {
+        self.meta.scope()
+    }
+
+    pub fn stats(&self) -> LockStats {
+        self.meta.stats.snapshot()
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot::take(&self.meta.stats)
+    }
+
+    // The last `n` events held by the event ring, oldest first; empty when it
+    // is off.
+    pub fn recent_events(&self, n: usize) -> Vec<LockEvent> {
+        self.meta
+            .event_ring
+            .get()
+            .map(|ring| ring.recent(n))
+            .unwrap_or_default()
+    }
+
+    pub fn fairness(&self) -> Fairness {
+        self.meta.stats.fairness()
+    }
+
+    // Empty unless `set_fairness_audit` was on while the lock was contended.
+    pub fn fairness_report(&self) -> FairnessReport {
+        self.meta.inversions.report()
+    }
+
+    pub fn waiter_count(&self) -> u64 {
+        self.meta.stats.waiters()
+    }
+
+    #[doc(hidden)]
+    pub fn ordering_key(&self) -> u64 {
+        self.meta.id
+    }
+
+    pub fn was_poisoned(&self) -> bool {
+        self.meta.poisoned.load(Ordering::Relaxed)
+    }
+
+    pub fn clear_poison(&self) {
+        self.meta.poisoned.store(false, Ordering::Relaxed);
+    }
+
+    #[track_caller]
+    pub fn assert_unlocked(&self) {
+        crate::testing::assert_unlocked(&self.meta);
+    }
+
+    pub fn into_inner(self) -> T {
+        self.lock.into_inner()
+    }
+
+    #[track_caller]
+    pub fn into_inner_logged(self) -> T {
+        event::destroyed(
+            &self.meta,
+            self.idx.load(Ordering::Relaxed),
+            Location::caller(),
+        );
+        self.into_inner()
+    }
+
+    pub fn on_release<F>(&self, callback: F)
+    where
+        F: Fn(&LockEvent) + Send + Sync + 'static,
+    {
+        self.meta.availability.add_callback(Arc::new(callback));
+    }
+
+    #[track_caller]
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.acquire_read(Acquisition::new(Location::caller()))
+    }
+
+    #[track_caller]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.acquire_write(Acquisition::new(Location::caller()))
+    }
+
+    #[track_caller]
+    pub fn read_timed(&self) -> (RwLockReadGuard<'_, T>, AcquireTiming) {
+        let guard = self.acquire_read(Acquisition::new(Location::caller()));
+        let timing = guard.state.timing();
+        (guard, timing)
+    }
+
+    #[track_caller]
+    pub fn write_timed(&self) -> (RwLockWriteGuard<'_, T>, AcquireTiming) {
+        let guard = self.acquire_write(Acquisition::new(Location::caller()));
+        let timing = guard.state.timing();
+        (guard, timing)
+    }
+
+    #[track_caller]
+    pub fn read_verbose(&self) -> (RwLockReadGuard<'_, T>, AcquireReport) {
+        let guard = self.acquire_read(Acquisition::new(Location::caller()));
+        let report = guard.state.acquire_report();
+        (guard, report)
+    }
+
+    #[track_caller]
+    pub fn write_verbose(&self) -> (RwLockWriteGuard<'_, T>, AcquireReport) {
+        let guard = self.acquire_write(Acquisition::new(Location::caller()));
+        let report = guard.state.acquire_report();
+        (guard, report)
+    }
+
+    #[track_caller]
+    pub fn write_audited(&self, reason: &str) -> RwLockWriteGuard<'_, T> {
+        self.acquire_write(Acquisition {
+            reason: Some(reason.to_owned()),
+            ..Acquisition::new(Location::caller())
+        })
+    }
+
+    #[track_caller]
+    pub fn read_with<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let guard = self.acquire_read(Acquisition {
+            scoped: true,
+            ..Acquisition::new(Location::caller())
+        });
+        f(&guard)
+    }
+
+    #[track_caller]
+    pub fn write_with<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let mut guard = self.acquire_write(Acquisition {
+            scoped: true,
+            ..Acquisition::new(Location::caller())
+        });
+        f(&mut guard)
+    }
+
+    #[track_caller]
+    pub fn clone_inner(&self) -> T
+    where
+        T: Clone,
+    {
+        self.read_with(T::clone)
+    }
+
+    fn acquire_read(&self, acquisition: Acquisition) -> RwLockReadGuard<'_, T> {
+        let mut acquisition = acquisition.begin(&self.meta, &self.idx, READ);
+        let start = Instant::now();
+        let (guard, contended) = match self.lock.try_read() {
+            Some(guard) => (guard, false),
+            None => {
+                let _waiting = acquisition.start_waiting(&self.meta, READ);
+                (self.lock.read(), true)
+            }
+        };
+        acquisition.record_wait(&self.meta, READ, start, contended);
+
+        RwLockReadGuard {
+            guard,
+            state: GuardState::got(self.meta.clone(), acquisition, READ),
+        }
+    }
+
+    fn acquire_write(&self, acquisition: Acquisition) -> RwLockWriteGuard<'_, T> {
+        let mut acquisition = acquisition.begin(&self.meta, &self.idx, WRITE);
+        let start = Instant::now();
+        let (guard, contended) = match self.lock.try_write() {
+            Some(guard) => (guard, false),
+            None => {
+                let _waiting = acquisition.start_waiting(&self.meta, WRITE);
+                (self.lock.write(), true)
+            }
+        };
+        acquisition.record_wait(&self.meta, WRITE, start, contended);
+
+        let fingerprint = self.hooks.fingerprint(&guard);
+        RwLockWriteGuard {
+            guard,
+            state: GuardState::got(self.meta.clone(), acquisition, WRITE),
+            mutated: false,
+            hooks: &self.hooks,
+            fingerprint,
+        }
+    }
+}
+
+// Same double-checked initialization as the tokio-backed lock.
+impl<V> RwLock<Option<V>> {
+    #[track_caller]
+    pub fn get_or_init_with<F>(&self, f: F) -> V
+    where
+        V: Clone,
+        F: FnOnce() -> V,
+    {
+        let caller = Location::caller();
+        let guard = self.acquire_read(Acquisition::new(caller));
+        let current: &Option<V> = &guard;
+        if let Some(value) = current {
+            return value.clone();
+        }
+        guard.state.acquisition.emit(
+            &self.meta,
+            READ,
+            Phase::Init,
+            caller,
+            " empty, taking write",
+        );
+        drop(guard);
+
+        let mut guard = self.acquire_write(Acquisition::new(caller));
+        let current: &Option<V> = &guard;
+        if let Some(value) = current {
+            let value = value.clone();
+            guard.allow_no_mutation();
+            guard.state.acquisition.emit(
+                &self.meta,
+                WRITE,
+                Phase::Init,
+                caller,
+                " already initialized by another task",
+            );
+            return value;
+        }
+        let started = Instant::now();
+        let value = f();
+        let slot: &mut Option<V> = &mut guard;
+        *slot = Some(value.clone());
+        let details = format!(" initialized in {:?}", started.elapsed());
+        guard
+            .state
+            .acquisition
+            .emit(&self.meta, WRITE, Phase::Init, caller, &details);
+        value
+    }
+}
+
+impl<T> Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RwLock")
+            .field("name", &self.meta.name())
+            .field("idx", &self.idx.load(Ordering::Relaxed))
+            .field("kind", &"parking_lot")
+            .finish()
+    }
+}
+
+pub struct RwLockReadGuard<'a, T> {
+    guard: parking_lot::RwLockReadGuard<'a, T>,
+    state: GuardState,
+}
+
+impl<T> RwLockReadGuard<'_, T> {
+    pub fn acquired_at(&self) -> &'static Location<'static> {
+        self.state.acquisition.caller
+    }
+
+    pub fn record_work(&mut self, bytes: usize, note: &str) {
+        self.state.record_work(bytes, note);
+    }
+
+    pub fn debug_value(&self) -> &T
+    where
+        T: Debug,
+    {
+        &self.guard
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    #[track_caller]
+    fn drop(&mut self) {
+        self.state
+            .release(READ, Location::caller(), false, String::new);
+    }
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.state.fmt_guard(f, "RwLockReadGuard", READ)
+    }
+}
+
+pub struct RwLockWriteGuard<'a, T> {
+    guard: parking_lot::RwLockWriteGuard<'a, T>,
+    state: GuardState,
+    mutated: bool,
+    hooks: &'a ValueHooks<T>,
+    fingerprint: Option<u64>,
+}
+
+impl<T> RwLockWriteGuard<'_, T> {
+    pub fn acquired_at(&self) -> &'static Location<'static> {
+        self.state.acquisition.caller
+    }
+
+    pub fn record_work(&mut self, bytes: usize, note: &str) {
+        self.state.record_work(bytes, note);
+    }
+
+    pub fn debug_value(&self) -> &T
+    where
+        T: Debug,
+    {
+        &self.guard
+    }
+
+    // Opts this guard out of the release-without-mutation report.
+    pub fn allow_no_mutation(&mut self) {
+        self.state.skip_mutation_check();
+    }
+
+    // See `RwLockWriteGuard::release_with_reason`.
+    #[track_caller]
+    pub fn release_with_reason(mut self, reason: &str) {
+        self.state.give_reason(reason, Location::caller());
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    #[track_caller]
+    fn drop(&mut self) {
+        let (guard, hooks, fingerprint) = (&self.guard, self.hooks, self.fingerprint);
+        self.state
+            .release(WRITE, Location::caller(), self.mutated, || {
+                hooks.release_annotations(guard, fingerprint)
+            });
+    }
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.mutated = true;
+        &mut self.guard
+    }
+}
+
+impl<T> Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.state.fmt_guard(f, "RwLockWriteGuard", WRITE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RwLock;
+
+    use std::mem;
+
+    // Forcing the inner lock open behind a forgotten guard leaves holds with no
+    // guard behind them; a resync finds the lock idle and purges them.
+    #[test]
+    fn forced_unlocks_surface_as_leaks() {
+        let reads = RwLock::new_named(0u8, "sync-leak-read");
+        mem::forget(reads.read());
+        mem::forget(reads.read());
+        unsafe {
+            reads.lock.force_unlock_read();
+            reads.lock.force_unlock_read();
+        }
+        let report = reads.resync_counters();
+        assert_eq!((report.holders_before, report.holders), (2, 0));
+        assert_eq!((report.readers_before, report.readers), (2, 0));
+        assert_eq!(report.leaked.len(), 2);
+        assert!(report.leaked[0].contains("sync-leak-read"));
+        assert!(!reads.resync_counters().changed());
+
+        let write = RwLock::new_named(0u8, "sync-leak-write");
+        mem::forget(write.write());
+        assert!(!write.resync_counters().changed());
+        unsafe { write.lock.force_unlock_write() };
+        let report = write.resync_counters();
+        assert_eq!((report.holders_before, report.holders), (1, 0));
+        assert_eq!(report.leaked.len(), 1);
+        drop(write.write());
+        assert!(!write.resync_counters().changed());
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread::ThreadId,
+};
+
+// Names outlive their tasks, so only the most recently named are kept.
+const MAX_NAMES: usize = 4096;
+
+static NAMED: AtomicBool = AtomicBool::new(false);
+static NAMES: Mutex<Option<Names>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Names {
+    by_task: HashMap<TaskKey, String>,
+    order: VecDeque<TaskKey>,
+}
+
+fn names() -> MutexGuard<'static, Option<Names>> {
+    NAMES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum TaskKey {
+    Task(tokio::task::Id),
+    Thread(ThreadId),
+}
+
+impl TaskKey {
+    pub(crate) fn current() -> Self {
+        match tokio::task::try_id() {
+            Some(id) => TaskKey::Task(id),
+            None => TaskKey::Thread(std::thread::current().id()),
+        }
+    }
+
+    pub(crate) fn name(&self) -> Option<String> {
+        if !NAMED.load(Ordering::Relaxed) {
+            return None;
+        }
+        names().as_ref()?.by_task.get(self).cloned()
+    }
+}
+
+// Tokio keeps `task::Builder::name` to itself, so tasks name themselves here.
+// The name follows the task id in held and pending dumps, snapshots,
+// wait-for edges and deadlock reports, and is set on `LockEvent::task_name`.
+// Outside a task it names the current thread.
+pub fn name_current_task(name: impl Into<String>) {
+    let task = TaskKey::current();
+    let mut names = names();
+    let names = names.get_or_insert_with(Names::default);
+    if names.by_task.insert(task, name.into()).is_none() {
+        names.order.push_back(task);
+        if names.order.len() > MAX_NAMES {
+            if let Some(oldest) = names.order.pop_front() {
+                names.by_task.remove(&oldest);
+            }
+        }
+    }
+    NAMED.store(true, Ordering::Relaxed);
+}
+
+// The thread is kept even inside a task, so a guard that stays with its task
+// but hops threads (`block_in_place`, a runtime shutdown) still shows up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Origin {
+    task: TaskKey,
+    thread: ThreadId,
+}
+
+impl Origin {
+    pub(crate) fn current() -> Self {
+        Self {
+            task: TaskKey::current(),
+            thread: std::thread::current().id(),
+        }
+    }
+
+    pub(crate) fn task(&self) -> TaskKey {
+        self.task
+    }
+
+    pub(crate) fn migration(&self, now: &Origin) -> Option<(TaskKey, TaskKey)> {
+        if self.task != now.task {
+            Some((self.task, now.task))
+        } else if self.thread != now.thread {
+            Some((TaskKey::Thread(self.thread), TaskKey::Thread(now.thread)))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for TaskKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskKey::Task(id) => write!(f, "task={}", id)?,
+            TaskKey::Thread(id) => write!(f, "thread={:?}", id)?,
+        }
+        match self.name() {
+            Some(name) => write!(f, " '{}'", name),
+            None => Ok(()),
+        }
+    }
+}
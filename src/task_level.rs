@@ -0,0 +1,73 @@
+use crate::{registry::LockMeta, task::TaskKey};
+
+use log::Level;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+static NEXT_OVERRIDE: AtomicU64 = AtomicU64::new(1);
+static OVERRIDES: Mutex<Option<Overrides>> = Mutex::new(None);
+
+type Overrides = HashMap<TaskKey, Vec<(u64, Level)>>;
+
+fn overrides() -> MutexGuard<'static, Option<Overrides>> {
+    OVERRIDES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Applies to events raised by the task (or thread, outside tokio) that created
+// the guard. Nested guards stack; the most recent one still alive wins.
+pub fn scoped_level(level: Level) -> ScopedLevel {
+    let task = TaskKey::current();
+    let id = NEXT_OVERRIDE.fetch_add(1, Ordering::Relaxed);
+    overrides()
+        .get_or_insert_with(HashMap::new)
+        .entry(task)
+        .or_default()
+        .push((id, level));
+    ACTIVE.fetch_add(1, Ordering::Release);
+    ScopedLevel { task, id }
+}
+
+#[derive(Debug)]
+#[must_use = "the level is restored when this guard is dropped"]
+pub struct ScopedLevel {
+    task: TaskKey,
+    id: u64,
+}
+
+impl Drop for ScopedLevel {
+    fn drop(&mut self) {
+        let mut overrides = overrides();
+        if let Some(by_task) = overrides.as_mut() {
+            if let Some(stack) = by_task.get_mut(&self.task) {
+                stack.retain(|(id, _)| *id != self.id);
+                if stack.is_empty() {
+                    by_task.remove(&self.task);
+                }
+            }
+        }
+        ACTIVE.fetch_sub(1, Ordering::Release);
+    }
+}
+
+fn current() -> Option<Level> {
+    if ACTIVE.load(Ordering::Acquire) == 0 {
+        return None;
+    }
+    overrides()
+        .as_ref()?
+        .get(&TaskKey::current())?
+        .last()
+        .map(|(_, level)| *level)
+}
+
+// The task override is narrower than the lock's scope, so it takes precedence.
+pub(crate) fn level_override(meta: &LockMeta) -> Option<Level> {
+    current().or_else(|| meta.scope().level_override())
+}
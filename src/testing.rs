@@ -0,0 +1,195 @@
+use crate::{
+    background,
+    event::LockEvent,
+    phase::Phase,
+    recorder,
+    registry::{self, HeldEntry, LockMeta},
+    task::TaskKey,
+    WRITE,
+};
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    future::{self, Future},
+    task::Poll,
+    time::Duration,
+};
+
+fn describe_all(entries: &[HeldEntry]) -> String {
+    if entries.is_empty() {
+        return "  (none)\n".to_owned();
+    }
+    let mut out = String::new();
+    for entry in entries {
+        let _ = writeln!(out, "  {}", entry.describe());
+    }
+    out
+}
+
+fn pending_locks() -> String {
+    let mut out = String::new();
+    for meta in registry::all_locks() {
+        let waiters = meta.stats.waiters();
+        if waiters > 0 {
+            let _ = writeln!(out, "  ({}) {} waiting", meta.name(), waiters);
+        }
+    }
+    out
+}
+
+#[track_caller]
+pub(crate) fn assert_unlocked(meta: &LockMeta) {
+    let entries = registry::held_on(meta.id);
+    if !entries.is_empty() {
+        panic!(
+            "lock ({}) is still held by {} guard(s):\n{}",
+            meta.name(),
+            entries.len(),
+            describe_all(&entries)
+        );
+    }
+}
+
+#[track_caller]
+pub fn assert_no_locks_held() {
+    let entries = registry::held_all();
+    if !entries.is_empty() {
+        panic!(
+            "{} traced guard(s) still held:\n{}",
+            entries.len(),
+            describe_all(&entries)
+        );
+    }
+}
+
+#[track_caller]
+pub fn check_write_held(lock_id: u64, name: &str) {
+    let task = TaskKey::current();
+    let entries = registry::held_by(task);
+    if entries
+        .iter()
+        .any(|entry| entry.lock_id == lock_id && entry.mode == WRITE)
+    {
+        return;
+    }
+
+    let others: Vec<HeldEntry> = registry::held_on(lock_id)
+        .into_iter()
+        .filter(|entry| entry.task != task)
+        .collect();
+    panic!(
+        "lock ({}) must be write-held by {} here; it holds:\n{}other holders of ({}):\n{}",
+        name,
+        task,
+        describe_all(&entries),
+        name,
+        describe_all(&others)
+    );
+}
+
+#[macro_export]
+macro_rules! assert_write_held {
+    ($lock:expr) => {
+        if ::std::cfg!(debug_assertions) {
+            let lock = &$lock;
+            $crate::__private::check_write_held(lock.ordering_key(), &lock.name());
+        }
+    };
+}
+
+// Fails if a task acquired a lock named `first` while it held one named
+// `second`, going by the events recorded so far, which are left in the
+// buffer. The check is only as complete as the recording: it needs
+// `set_recording_capacity` on before the run, room for every event, and the
+// Acquire, Got and Release events of both locks let through by the
+// directives, scopes and release filters. A lock handed off is held by the
+// task that resumed it.
+#[track_caller]
+pub fn assert_acquired_before(first: &str, second: &str) {
+    if !recorder::enabled() {
+        panic!("assert_acquired_before needs recording on; see set_recording_capacity");
+    }
+    let events = recorder::recorded_events();
+    // The Got of each `second` acquisition still held, by lock and idx.
+    let mut held: HashMap<(u64, u64), (TaskKey, &LockEvent)> = HashMap::new();
+    let mut violations = String::new();
+    for event in &events {
+        let task = match event.task {
+            Some(id) => TaskKey::Task(id),
+            None => TaskKey::Thread(event.thread),
+        };
+        let key = (event.lock_id, event.idx);
+        if event.name == second {
+            match event.phase {
+                Phase::Got => {
+                    held.insert(key, (task, event));
+                }
+                Phase::Resume => {
+                    if let Some(entry) = held.get_mut(&key) {
+                        entry.0 = task;
+                    }
+                }
+                Phase::Release => {
+                    held.remove(&key);
+                }
+                _ => {}
+            }
+        }
+        if event.name == first && event.phase == Phase::Acquire {
+            for (holder, got) in held.values() {
+                if *holder == task {
+                    let _ = writeln!(
+                        violations,
+                        "  {} acquired ({}) at {}:{} (seq {}) while holding ({}) got at {}:{} \
+                         (seq {})",
+                        task,
+                        first,
+                        event.location.file(),
+                        event.location.line(),
+                        event.seq,
+                        second,
+                        got.location.file(),
+                        got.location.line(),
+                        got.seq,
+                    );
+                }
+            }
+        }
+    }
+    if !violations.is_empty() {
+        panic!(
+            "({}) must be acquired before ({}), but:\n{}",
+            first, second, violations
+        );
+    }
+}
+
+// The future is kept alive while the dump is taken, so its own pending
+// acquisition still shows up.
+pub async fn with_lock_timeout<F>(duration: Duration, future: F) -> F::Output
+where
+    F: Future,
+{
+    tokio::pin!(future);
+    let deadline = background::sleep(duration);
+    tokio::pin!(deadline);
+
+    let output = future::poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        deadline.as_mut().poll(cx).map(|()| None)
+    })
+    .await;
+
+    match output {
+        Some(output) => output,
+        None => panic!(
+            "timed out after {:?}\nheld:\n{}pending:\n{}",
+            duration,
+            describe_all(&registry::held_all()),
+            pending_locks()
+        ),
+    }
+}
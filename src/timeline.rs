@@ -0,0 +1,339 @@
+use crate::{
+    binary::BinaryEvent,
+    event::LockEvent,
+    phase::{Mode, Phase},
+    recorder, registry,
+    task::TaskKey,
+};
+
+use std::{
+    collections::HashMap,
+    ops::{Bound, RangeBounds},
+    time::SystemTime,
+};
+
+// One event as the timeline needs it, whichever capture it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceEvent {
+    pub timestamp: SystemTime,
+    pub lock_id: u64,
+    pub mode: Mode,
+    pub phase: Phase,
+    pub idx: u64,
+    // None where the capture does not keep it, as in the binary log.
+    pub task: Option<String>,
+    pub caller: String,
+}
+
+// A capture of lock events, oldest first: a ring's `recent_events`, the
+// recorder's `drain_recorded_events`, or what `replay_binary` read back.
+pub trait EventSource {
+    fn source_events(&self) -> Vec<SourceEvent>;
+}
+
+impl EventSource for [LockEvent] {
+    fn source_events(&self) -> Vec<SourceEvent> {
+        self.iter()
+            .map(|event| SourceEvent {
+                timestamp: event.timestamp,
+                lock_id: event.lock_id,
+                mode: event.mode,
+                phase: event.phase,
+                idx: event.idx,
+                task: Some(match event.task {
+                    Some(id) => TaskKey::Task(id).to_string(),
+                    None => TaskKey::Thread(event.thread).to_string(),
+                }),
+                caller: site(
+                    event.location.file(),
+                    event.location.line(),
+                    event.location.column(),
+                ),
+            })
+            .collect()
+    }
+}
+
+impl EventSource for Vec<LockEvent> {
+    fn source_events(&self) -> Vec<SourceEvent> {
+        self.as_slice().source_events()
+    }
+}
+
+impl EventSource for [BinaryEvent] {
+    fn source_events(&self) -> Vec<SourceEvent> {
+        self.iter()
+            .map(|event| SourceEvent {
+                timestamp: event.timestamp,
+                lock_id: event.lock_id,
+                mode: event.mode,
+                phase: event.phase,
+                idx: event.idx,
+                task: None,
+                caller: site(&event.file, event.line, event.column),
+            })
+            .collect()
+    }
+}
+
+impl EventSource for Vec<BinaryEvent> {
+    fn source_events(&self) -> Vec<SourceEvent> {
+        self.as_slice().source_events()
+    }
+}
+
+impl EventSource for [SourceEvent] {
+    fn source_events(&self) -> Vec<SourceEvent> {
+        self.to_vec()
+    }
+}
+
+impl EventSource for Vec<SourceEvent> {
+    fn source_events(&self) -> Vec<SourceEvent> {
+        self.clone()
+    }
+}
+
+fn site(file: &str, line: u32, column: u32) -> String {
+    format!("{}:{}:{}", file, line, column)
+}
+
+// A `from` or `to` of None is an end the capture does not have: a ring that
+// dropped the Got, say, or a hold still open when it was taken. `caller` is
+// where the acquisition was made, None when its Acquire and Got are missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Interval {
+    // From the Acquire to the Got, or to the GiveUp or Cancelled ending it.
+    Wait {
+        mode: Mode,
+        idx: u64,
+        task: Option<String>,
+        from: Option<SystemTime>,
+        to: Option<SystemTime>,
+        caller: Option<String>,
+    },
+    // From the Got to the Release.
+    Hold {
+        mode: Mode,
+        idx: u64,
+        task: Option<String>,
+        from: Option<SystemTime>,
+        to: Option<SystemTime>,
+        caller: Option<String>,
+    },
+}
+
+impl Interval {
+    pub fn mode(&self) -> Mode {
+        match self {
+            Interval::Wait { mode, .. } | Interval::Hold { mode, .. } => *mode,
+        }
+    }
+
+    pub fn idx(&self) -> u64 {
+        match self {
+            Interval::Wait { idx, .. } | Interval::Hold { idx, .. } => *idx,
+        }
+    }
+
+    pub fn task(&self) -> Option<&str> {
+        match self {
+            Interval::Wait { task, .. } | Interval::Hold { task, .. } => task.as_deref(),
+        }
+    }
+
+    pub fn from(&self) -> Option<SystemTime> {
+        match self {
+            Interval::Wait { from, .. } | Interval::Hold { from, .. } => *from,
+        }
+    }
+
+    pub fn to(&self) -> Option<SystemTime> {
+        match self {
+            Interval::Wait { to, .. } | Interval::Hold { to, .. } => *to,
+        }
+    }
+
+    pub fn caller(&self) -> Option<&str> {
+        match self {
+            Interval::Wait { caller, .. } | Interval::Hold { caller, .. } => caller.as_deref(),
+        }
+    }
+
+    pub fn is_hold(&self) -> bool {
+        matches!(self, Interval::Hold { .. })
+    }
+
+    // Open ends reach to the edge of whatever range is asked about.
+    fn overlaps(&self, range: &impl RangeBounds<SystemTime>) -> bool {
+        let after_start = match (self.to(), range.start_bound()) {
+            (None, _) | (_, Bound::Unbounded) => true,
+            (Some(to), Bound::Included(start)) => to >= *start,
+            (Some(to), Bound::Excluded(start)) => to > *start,
+        };
+        let before_end = match (self.from(), range.end_bound()) {
+            (None, _) | (_, Bound::Unbounded) => true,
+            (Some(from), Bound::Included(end)) => from <= *end,
+            (Some(from), Bound::Excluded(end)) => from < *end,
+        };
+        after_start && before_end
+    }
+}
+
+// One lock's waits and holds, ordered by start, the open-started first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timeline {
+    pub lock_id: u64,
+    pub intervals: Vec<Interval>,
+}
+
+impl Timeline {
+    // Events of other locks, and ones not about an acquisition, are skipped.
+    // Each acquisition is matched up by its idx, so the capture needs logging
+    // on, which is what hands out idxs. A phase missing from the capture
+    // leaves its interval open rather than failing the rest.
+    pub fn from_source<S>(source: &S, lock_id: u64, range: impl RangeBounds<SystemTime>) -> Self
+    where
+        S: EventSource + ?Sized,
+    {
+        let mut open: HashMap<u64, Open> = HashMap::new();
+        let mut intervals = Vec::new();
+        for event in source.source_events() {
+            if event.lock_id != lock_id || !matches!(event.mode, Mode::Read | Mode::Write) {
+                continue;
+            }
+            let idx = event.idx;
+            match event.phase {
+                Phase::Acquire => {
+                    // A second Acquire for the idx means the first one's end
+                    // was lost.
+                    if let Some(earlier) = open.remove(&idx) {
+                        intervals.push(earlier.close(idx, None));
+                    }
+                    open.insert(
+                        idx,
+                        Open {
+                            holding: false,
+                            mode: event.mode,
+                            task: event.task,
+                            from: event.timestamp,
+                            caller: Some(event.caller),
+                        },
+                    );
+                }
+                Phase::Got => {
+                    let caller = match open.remove(&idx) {
+                        Some(wait) if !wait.holding => {
+                            let caller = wait.caller.clone();
+                            intervals.push(wait.close(idx, Some(event.timestamp)));
+                            caller
+                        }
+                        Some(earlier) => {
+                            intervals.push(earlier.close(idx, None));
+                            Some(event.caller)
+                        }
+                        None => Some(event.caller),
+                    };
+                    open.insert(
+                        idx,
+                        Open {
+                            holding: true,
+                            mode: event.mode,
+                            task: event.task,
+                            from: event.timestamp,
+                            caller,
+                        },
+                    );
+                }
+                Phase::GiveUp | Phase::Cancelled => {
+                    if let Some(wait) = open.remove(&idx) {
+                        intervals.push(wait.close(idx, Some(event.timestamp)));
+                    }
+                }
+                Phase::Release => match open.remove(&idx) {
+                    Some(hold) if hold.holding => {
+                        intervals.push(hold.close(idx, Some(event.timestamp)));
+                    }
+                    earlier => {
+                        // The Got was lost; any wait before it ended somewhere
+                        // unknown, and the hold started there.
+                        if let Some(wait) = earlier {
+                            intervals.push(wait.close(idx, None));
+                        }
+                        intervals.push(Interval::Hold {
+                            mode: event.mode,
+                            idx,
+                            task: event.task,
+                            from: None,
+                            to: Some(event.timestamp),
+                            caller: None,
+                        });
+                    }
+                },
+                // A handed-off hold is the resuming task's from here on; the
+                // interval keeps the task that took it.
+                _ => {}
+            }
+        }
+        intervals.extend(open.into_iter().map(|(idx, rest)| rest.close(idx, None)));
+        intervals.retain(|interval| interval.overlaps(&range));
+        intervals.sort_by_key(|interval| (interval.from(), interval.idx(), interval.is_hold()));
+        Self { lock_id, intervals }
+    }
+
+    pub fn holds(&self) -> impl Iterator<Item = &Interval> {
+        self.intervals.iter().filter(|interval| interval.is_hold())
+    }
+
+    pub fn waits(&self) -> impl Iterator<Item = &Interval> {
+        self.intervals.iter().filter(|interval| !interval.is_hold())
+    }
+}
+
+// An Acquire or Got still waiting for the event that ends its interval.
+struct Open {
+    holding: bool,
+    mode: Mode,
+    task: Option<String>,
+    from: SystemTime,
+    caller: Option<String>,
+}
+
+impl Open {
+    fn close(self, idx: u64, to: Option<SystemTime>) -> Interval {
+        let (mode, task, from, caller) = (self.mode, self.task, Some(self.from), self.caller);
+        if self.holding {
+            Interval::Hold {
+                mode,
+                idx,
+                task,
+                from,
+                to,
+                caller,
+            }
+        } else {
+            Interval::Wait {
+                mode,
+                idx,
+                task,
+                from,
+                to,
+                caller,
+            }
+        }
+    }
+}
+
+// The timeline of a live lock from its event ring, or from the recorder's
+// buffer, left undrained, when the lock has no ring. Empty for an unknown id
+// or when neither is on.
+pub fn timeline(lock_id: u64, range: impl RangeBounds<SystemTime>) -> Timeline {
+    let ring = registry::lock(lock_id)
+        .and_then(|meta| meta.event_ring.get().map(|ring| ring.recent(usize::MAX)));
+    let events = match ring {
+        Some(events) => events,
+        None if recorder::enabled() => recorder::recorded_events(),
+        None => Vec::new(),
+    };
+    Timeline::from_source(&events, lock_id, range)
+}
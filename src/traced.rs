@@ -0,0 +1,248 @@
+use std::{
+    future::Future,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+// The lock a `TracedLock` wraps: any async RwLock that can be tried without
+// waiting, so an acquire knows whether it was contended. The tracing, stats
+// and registry are `TracedLock`'s; this only has to hand out guards. A guard
+// must deref to the value inside the lock, not a copy of it, since mapped
+// guards keep a pointer to it across moves of the guard.
+pub trait RawAsyncRwLock<T: ?Sized> {
+    type ReadGuard<'a>: Deref<Target = T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
+
+    // What `Debug` on the lock shows as its `kind`.
+    const KIND: &'static str = "custom";
+
+    fn new(inner: T) -> Self
+    where
+        Self: Sized,
+        T: Sized;
+
+    fn raw_read(&self) -> impl Future<Output = Self::ReadGuard<'_>>;
+
+    fn raw_write(&self) -> impl Future<Output = Self::WriteGuard<'_>>;
+
+    fn try_raw_read(&self) -> Option<Self::ReadGuard<'_>>;
+
+    fn try_raw_write(&self) -> Option<Self::WriteGuard<'_>>;
+
+    fn into_inner(self) -> T
+    where
+        Self: Sized,
+        T: Sized;
+}
+
+impl<T: ?Sized> RawAsyncRwLock<T> for tokio::sync::RwLock<T> {
+    type ReadGuard<'a>
+        = tokio::sync::RwLockReadGuard<'a, T>
+    where
+        Self: 'a;
+    type WriteGuard<'a>
+        = tokio::sync::RwLockWriteGuard<'a, T>
+    where
+        Self: 'a;
+
+    const KIND: &'static str = "tokio";
+
+    fn new(inner: T) -> Self
+    where
+        T: Sized,
+    {
+        tokio::sync::RwLock::new(inner)
+    }
+
+    fn raw_read(&self) -> impl Future<Output = Self::ReadGuard<'_>> {
+        self.read()
+    }
+
+    fn raw_write(&self) -> impl Future<Output = Self::WriteGuard<'_>> {
+        self.write()
+    }
+
+    fn try_raw_read(&self) -> Option<Self::ReadGuard<'_>> {
+        self.try_read().ok()
+    }
+
+    fn try_raw_write(&self) -> Option<Self::WriteGuard<'_>> {
+        self.try_write().ok()
+    }
+
+    fn into_inner(self) -> T
+    where
+        T: Sized,
+    {
+        tokio::sync::RwLock::into_inner(self)
+    }
+}
+
+// For backends whose guards can own an `Arc` of the lock, which gives a
+// `TracedLock` over them `read_owned`, `write_owned` and `transfer`.
+pub trait RawOwnedRwLock<T>: RawAsyncRwLock<T> {
+    type OwnedReadGuard: Deref<Target = T>;
+    type OwnedWriteGuard: DerefMut<Target = T>;
+
+    fn raw_read_owned(self: Arc<Self>) -> impl Future<Output = Self::OwnedReadGuard>;
+
+    fn raw_write_owned(self: Arc<Self>) -> impl Future<Output = Self::OwnedWriteGuard>;
+
+    fn try_raw_read_owned(self: Arc<Self>) -> Option<Self::OwnedReadGuard>;
+
+    fn try_raw_write_owned(self: Arc<Self>) -> Option<Self::OwnedWriteGuard>;
+}
+
+impl<T> RawOwnedRwLock<T> for tokio::sync::RwLock<T> {
+    type OwnedReadGuard = tokio::sync::OwnedRwLockReadGuard<T>;
+    type OwnedWriteGuard = tokio::sync::OwnedRwLockWriteGuard<T>;
+
+    fn raw_read_owned(self: Arc<Self>) -> impl Future<Output = Self::OwnedReadGuard> {
+        self.read_owned()
+    }
+
+    fn raw_write_owned(self: Arc<Self>) -> impl Future<Output = Self::OwnedWriteGuard> {
+        self.write_owned()
+    }
+
+    fn try_raw_read_owned(self: Arc<Self>) -> Option<Self::OwnedReadGuard> {
+        self.try_read_owned().ok()
+    }
+
+    fn try_raw_write_owned(self: Arc<Self>) -> Option<Self::OwnedWriteGuard> {
+        self.try_write_owned().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RawAsyncRwLock;
+    use crate::{Mode, Phase, TracedLock};
+
+    use std::{
+        future::{self, Future},
+        sync,
+        task::Poll,
+    };
+
+    // A backend tokio knows nothing about: `std`'s lock, polled until free.
+    struct SpinLock<T>(sync::RwLock<T>);
+
+    impl<T> RawAsyncRwLock<T> for SpinLock<T> {
+        type ReadGuard<'a>
+            = sync::RwLockReadGuard<'a, T>
+        where
+            Self: 'a;
+        type WriteGuard<'a>
+            = sync::RwLockWriteGuard<'a, T>
+        where
+            Self: 'a;
+
+        const KIND: &'static str = "spin";
+
+        fn new(inner: T) -> Self {
+            SpinLock(sync::RwLock::new(inner))
+        }
+
+        fn raw_read(&self) -> impl Future<Output = Self::ReadGuard<'_>> {
+            future::poll_fn(move |cx| match self.try_raw_read() {
+                Some(guard) => Poll::Ready(guard),
+                None => {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            })
+        }
+
+        fn raw_write(&self) -> impl Future<Output = Self::WriteGuard<'_>> {
+            future::poll_fn(move |cx| match self.try_raw_write() {
+                Some(guard) => Poll::Ready(guard),
+                None => {
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+            })
+        }
+
+        fn try_raw_read(&self) -> Option<Self::ReadGuard<'_>> {
+            self.0.try_read().ok()
+        }
+
+        fn try_raw_write(&self) -> Option<Self::WriteGuard<'_>> {
+            self.0.try_write().ok()
+        }
+
+        fn into_inner(self) -> T {
+            self.0.into_inner().unwrap()
+        }
+    }
+
+    type Lock<T> = TracedLock<T, SpinLock<T>>;
+
+    #[tokio::test]
+    async fn a_custom_backend_is_traced_like_tokio() {
+        let lock = Lock::new_named(1u32, "traced-spin");
+        let id = lock.id();
+        let mut events = crate::subscribe_where(move |event| event.lock_id == id);
+        assert!(format!("{:?}", lock).contains("kind: \"spin\""));
+
+        assert_eq!(**lock.read().await, 1);
+        **lock.write().await += 1;
+        assert_eq!(lock.read_with(|value| *value).await, 2);
+
+        let mut seen = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            seen.push((event.mode, event.phase, event.idx));
+        }
+        let expected = [(Mode::Read, 1), (Mode::Write, 2), (Mode::Read, 3)];
+        let expected = expected.iter().flat_map(|&(mode, idx)| {
+            [Phase::Acquire, Phase::Got, Phase::Release]
+                .iter()
+                .map(move |&phase| (mode, phase, idx))
+        });
+        let phases = |phase| [Phase::Acquire, Phase::Got, Phase::Release].contains(&phase);
+        seen.retain(|&(_, phase, _)| phases(phase));
+        assert_eq!(seen, expected.collect::<Vec<_>>());
+
+        let stats = lock.stats();
+        assert_eq!((stats.reads, stats.writes, stats.releases), (2, 1, 3));
+        assert_eq!(stats.contended, 0);
+        assert_eq!(lock.into_inner(), 2);
+    }
+
+    #[tokio::test]
+    async fn a_custom_backend_counts_contention_and_mutation() {
+        let lock = Lock::new_named(0u32, "traced-spin-contended");
+        let id = lock.id();
+        let mut unmutated = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.phase == Phase::Unmutated
+        });
+
+        let mut writer = lock.write().await;
+        let (_, (read, report)) = tokio::join!(
+            async move {
+                tokio::task::yield_now().await;
+                **writer = 1;
+                drop(writer);
+            },
+            lock.read_verbose()
+        );
+        drop(read);
+        assert!(report.contended);
+        assert_eq!(lock.stats().contended, 1);
+        assert_eq!(lock.waiter_count(), 0);
+
+        drop(lock.write().await);
+        let event = unmutated
+            .try_recv()
+            .expect("the unmutated write went unreported");
+        assert_eq!(event.idx, 3);
+        **lock.write().await += 1;
+        assert!(unmutated.try_recv().is_err());
+        assert_eq!(lock.stats().writes, 3);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{clock::Instant, fallback, severity, task::TaskKey};
+
+use std::{
+    collections::HashMap,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+static NEXT_TRANSACTION: AtomicU64 = AtomicU64::new(1);
+static OPEN: Mutex<Option<Open>> = Mutex::new(None);
+
+type Open = HashMap<TaskKey, Vec<u64>>;
+
+fn open() -> MutexGuard<'static, Option<Open>> {
+    OPEN.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Every acquisition the current task (or thread, outside tokio) makes while
+// the guard is alive carries `txn=<id>`, matching the id on the begin and end
+// lines. Nested transactions stack; the innermost one is reported.
+#[track_caller]
+pub fn begin_transaction(name: impl Into<String>) -> TransactionGuard {
+    let caller = Location::caller();
+    let name = name.into();
+    let task = TaskKey::current();
+    let id = NEXT_TRANSACTION.fetch_add(1, Ordering::Relaxed);
+    open()
+        .get_or_insert_with(HashMap::new)
+        .entry(task)
+        .or_default()
+        .push(id);
+    ACTIVE.fetch_add(1, Ordering::Release);
+    fallback::log!(
+        severity::severity_policy().acquire,
+        "[TXN] begin ({}) txn={} {}: {}:{}:{}",
+        name,
+        id,
+        task,
+        caller.file(),
+        caller.line(),
+        caller.column(),
+    );
+    TransactionGuard {
+        name,
+        id,
+        task,
+        caller,
+        started: Instant::now(),
+    }
+}
+
+#[derive(Debug)]
+#[must_use = "the transaction ends when this guard is dropped"]
+pub struct TransactionGuard {
+    name: String,
+    id: u64,
+    task: TaskKey,
+    caller: &'static Location<'static>,
+    started: Instant,
+}
+
+impl TransactionGuard {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Drop for TransactionGuard {
+    fn drop(&mut self) {
+        let mut open = open();
+        if let Some(by_task) = open.as_mut() {
+            if let Some(stack) = by_task.get_mut(&self.task) {
+                stack.retain(|id| *id != self.id);
+                if stack.is_empty() {
+                    by_task.remove(&self.task);
+                }
+            }
+        }
+        drop(open);
+        ACTIVE.fetch_sub(1, Ordering::Release);
+        fallback::log!(
+            severity::severity_policy().release,
+            "[TXN] end ({}) txn={} after {:?}: {}:{}:{}",
+            self.name,
+            self.id,
+            self.started.elapsed(),
+            self.caller.file(),
+            self.caller.line(),
+            self.caller.column(),
+        );
+    }
+}
+
+pub(crate) fn current() -> Option<u64> {
+    if ACTIVE.load(Ordering::Acquire) == 0 {
+        return None;
+    }
+    open().as_ref()?.get(&TaskKey::current())?.last().copied()
+}
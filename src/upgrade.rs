@@ -0,0 +1,270 @@
+use crate::{
+    access, clock::Instant, config::Idx, event, guard::Acquisition, phase::Phase,
+    registry::LockMeta, severity, traced::RawAsyncRwLock, RwLockReadGuard, RwLockWriteGuard,
+    TracedLock, READ, WRITE,
+};
+
+use tokio::sync::MutexGuard;
+
+use std::{fmt, future::Future, ops::Deref, panic::Location};
+
+fn report(meta: &LockMeta, idx: u64, caller: &'static Location<'static>, what: &str) {
+    event::emit_message(
+        severity::severity_policy().anomaly,
+        meta,
+        READ,
+        Phase::Upgrade,
+        idx,
+        caller,
+        format!("[UPGRADE] ({}:{}) {}", meta.name(), Idx(idx), what),
+    );
+}
+
+impl<'a, T: ?Sized, L: RawAsyncRwLock<T> + ?Sized> RwLockReadGuard<'a, T, L> {
+    // Releases the read and makes a single `try_write`. On failure the read is
+    // taken again, waiting if a writer got in during the gap, so the returned
+    // guard is a fresh acquisition that may observe newer state. Guards built
+    // with `RwLockReadGuard::new` have no lock to retry and come back as is.
+    #[track_caller]
+    pub fn try_upgrade(
+        self,
+    ) -> impl Future<Output = Result<RwLockWriteGuard<'a, T, L>, RwLockReadGuard<'a, T, L>>> {
+        let caller = Location::caller();
+
+        async move {
+            let source = match self.source {
+                Some(source) => source,
+                None => return Err(self),
+            };
+            let (idx, sampled) = (self.state.acquisition.idx, self.state.acquisition.sampled);
+            drop(self);
+
+            if sampled {
+                report(
+                    source.meta,
+                    idx,
+                    caller,
+                    "read released, trying write (not atomic, state may change)",
+                );
+            }
+
+            let mut acquisition = source.begin(WRITE, Acquisition::new(caller));
+            let start = Instant::now();
+            match source.lock.try_raw_write() {
+                Some(guard) => {
+                    acquisition.record_wait(source.meta, WRITE, start, false);
+                    Ok(RwLockWriteGuard::acquired(
+                        guard,
+                        source.meta.clone(),
+                        acquisition,
+                        Some(source.hooks),
+                    ))
+                }
+                None => {
+                    acquisition.waited = start.elapsed();
+                    acquisition.emit(source.meta, WRITE, Phase::GiveUp, caller, "");
+                    if sampled {
+                        report(
+                            source.meta,
+                            idx,
+                            caller,
+                            "write unavailable, reacquiring read (state may have changed)",
+                        );
+                    }
+                    let acquisition = source.begin(READ, Acquisition::new(caller));
+                    Err(source.acquire_read(acquisition).await)
+                }
+            }
+        }
+    }
+}
+
+// Holding one excludes other upgradable readers, never plain ones, so two
+// upgraders cannot each wait for the other's read to go away.
+pub struct RwLockUpgradableReadGuard<'a, T, L: RawAsyncRwLock<T> = tokio::sync::RwLock<T>> {
+    lock: &'a TracedLock<T, L>,
+    guard: RwLockReadGuard<'a, T, L>,
+    upgrader: MutexGuard<'a, ()>,
+}
+
+impl<'a, T, L: RawAsyncRwLock<T>> RwLockUpgradableReadGuard<'a, T, L> {
+    pub(crate) fn new(
+        lock: &'a TracedLock<T, L>,
+        guard: RwLockReadGuard<'a, T, L>,
+        upgrader: MutexGuard<'a, ()>,
+    ) -> Self {
+        Self {
+            lock,
+            guard,
+            upgrader,
+        }
+    }
+
+    // Tokio has no atomic upgrade, so the read is released before the write is
+    // queued and a plain writer already waiting gets in first; check the state
+    // again afterwards or use `upgrade_if`. The write keeps the read's idx.
+    // Other upgraders wait until the write is held, which makes upgraders
+    // strictly one at a time: a long-lived upgradable guard starves them, but
+    // never plain readers or writers.
+    #[track_caller]
+    pub fn upgrade(self) -> impl Future<Output = RwLockWriteGuard<'a, T, L>> {
+        let caller = Location::caller();
+        let Self {
+            lock,
+            guard,
+            upgrader,
+        } = self;
+        let mut acquisition = Acquisition::new(caller);
+        acquisition.idx = guard.state.acquisition.idx;
+        acquisition.sampled = guard.state.acquisition.sampled;
+        drop(guard);
+        acquisition.emit(&lock.meta, WRITE, Phase::Upgrade, caller, "");
+
+        async move {
+            access::check(&lock.meta, WRITE, acquisition.idx, caller);
+            let guard = lock.acquire_write(acquisition).await;
+            drop(upgrader);
+            guard
+        }
+    }
+
+    // Closes the gap in `upgrade`: `still_valid` sees the state under the
+    // write, and on `false` the write is released and `None` returned.
+    #[track_caller]
+    pub fn upgrade_if<F>(
+        self,
+        still_valid: F,
+    ) -> impl Future<Output = Option<RwLockWriteGuard<'a, T, L>>>
+    where
+        F: FnOnce(&T) -> bool,
+    {
+        let upgrade = self.upgrade();
+
+        async move {
+            let mut guard = upgrade.await;
+            if still_valid(&guard) {
+                return Some(guard);
+            }
+            // Backing out is the point here, not a forgotten write.
+            guard.state.skip_mutation_check();
+            None
+        }
+    }
+
+    // Counted against the read hold; an upgrade starts a new hold.
+    pub fn record_work(&mut self, bytes: usize, note: &str) {
+        self.guard.state.record_work(bytes, note);
+    }
+
+    pub fn debug_value(&self) -> &T
+    where
+        T: fmt::Debug,
+    {
+        &self.guard
+    }
+}
+
+impl<T, L: RawAsyncRwLock<T>> fmt::Debug for RwLockUpgradableReadGuard<'_, T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.guard
+            .state
+            .fmt_guard(f, "RwLockUpgradableReadGuard", READ)
+    }
+}
+
+impl<T, L: RawAsyncRwLock<T>> Deref for RwLockUpgradableReadGuard<'_, T, L> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Phase, RwLock};
+
+    use std::{sync::Arc, time::Duration};
+
+    #[tokio::test]
+    async fn upgradable_readers_take_turns() {
+        let lock = Arc::new(RwLock::new_named(0u32, "upgrade-turns"));
+        let id = lock.id();
+        let mut upgrades = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.phase == Phase::Upgrade
+        });
+
+        let first = lock.upgradable_read().await;
+        let idx = first.guard.state.acquisition.idx;
+        let second = tokio::time::timeout(Duration::from_millis(20), lock.upgradable_read());
+        assert!(second.await.is_err(), "two upgradable readers at once");
+        drop(lock.read().await);
+
+        let waiting = tokio::spawn({
+            let lock = lock.clone();
+            async move {
+                let second = lock.upgradable_read().await;
+                let seen = *second;
+                **second.upgrade().await = seen + 1;
+            }
+        });
+        let seen = *first;
+        let mut write = first.upgrade().await;
+        assert_eq!(write.state.acquisition.idx, idx);
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished(), "an upgrader got in under a write");
+        **write = seen + 1;
+        drop(write);
+        waiting.await.unwrap();
+
+        assert_eq!(**lock.read().await, 2);
+        assert_eq!(upgrades.try_recv().unwrap().idx, idx);
+        assert!(upgrades.try_recv().is_ok());
+    }
+
+    // Each upgrade writes back what it read, so one let through after a plain
+    // writer changed the value would lose that write.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn upgraders_racing_plain_writers_lose_no_writes() {
+        let lock = Arc::new(RwLock::new_named(0u32, "upgrade-race"));
+        let upgraders: Vec<_> = (0..8)
+            .map(|_| {
+                let lock = lock.clone();
+                tokio::spawn(async move {
+                    let mut applied = 0;
+                    for _ in 0..50 {
+                        let read = lock.upgradable_read().await;
+                        let seen = *read;
+                        tokio::task::yield_now().await;
+                        if let Some(mut write) = read.upgrade_if(|value| *value == seen).await {
+                            **write = seen + 1;
+                            applied += 1;
+                        }
+                    }
+                    applied
+                })
+            })
+            .collect();
+        let writers: Vec<_> = (0..8)
+            .map(|_| {
+                let lock = lock.clone();
+                tokio::spawn(async move {
+                    for _ in 0..50 {
+                        **lock.write().await += 1;
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+
+        let mut applied = 0;
+        for upgrader in upgraders {
+            applied += upgrader.await.unwrap();
+        }
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        assert!(applied > 0);
+        assert_eq!(**lock.read().await, applied + 8 * 50);
+    }
+}
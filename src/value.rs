@@ -0,0 +1,94 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt::{self, Debug},
+    hash::{Hash, Hasher},
+};
+
+// Keeps `new_debug` locks from dumping whole collections into every Release.
+pub(crate) const DEFAULT_DEBUG_LEN: usize = 64;
+
+type Fingerprint<T> = Box<dyn Fn(&T) -> u64 + Send + Sync>;
+type Describe<T> = Box<dyn Fn(&T) -> String + Send + Sync>;
+
+pub(crate) struct ValueHooks<T: ?Sized> {
+    fingerprint: Option<Fingerprint<T>>,
+    describe: Option<Describe<T>>,
+}
+
+impl<T: ?Sized> ValueHooks<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            fingerprint: None,
+            describe: None,
+        }
+    }
+
+    pub(crate) fn set_fingerprint<F>(&mut self, fingerprint: F)
+    where
+        F: Fn(&T) -> u64 + Send + Sync + 'static,
+    {
+        self.fingerprint = Some(Box::new(fingerprint));
+    }
+
+    pub(crate) fn set_hash_fingerprint(&mut self)
+    where
+        T: Hash,
+    {
+        self.set_fingerprint(|value: &T| {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        });
+    }
+
+    pub(crate) fn set_debug(&mut self, max_len: usize)
+    where
+        T: Debug,
+    {
+        self.describe = Some(Box::new(move |value: &T| {
+            truncate(format!("{:?}", value), max_len)
+        }));
+    }
+
+    pub(crate) fn fingerprint(&self, value: &T) -> Option<u64> {
+        self.fingerprint.as_ref().map(|fingerprint| fingerprint(value))
+    }
+
+    // Annotations for a write Release, given the fingerprint taken at Got.
+    pub(crate) fn release_annotations(&self, value: &T, initial: Option<u64>) -> String {
+        let mut out = String::new();
+        if let (Some(initial), Some(current)) = (initial, self.fingerprint(value)) {
+            out.push_str(if initial == current {
+                " changed=false"
+            } else {
+                " changed=true"
+            });
+        }
+        if let Some(describe) = &self.describe {
+            out.push_str(" value=");
+            out.push_str(&describe(value));
+        }
+        out
+    }
+}
+
+impl<T: ?Sized> Debug for ValueHooks<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValueHooks")
+            .field("fingerprint", &self.fingerprint.is_some())
+            .field("describe", &self.describe.is_some())
+            .finish()
+    }
+}
+
+fn truncate(mut value: String, max_len: usize) -> String {
+    if value.len() > max_len {
+        let mut end = max_len;
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        value.truncate(end);
+        value.push('…');
+    }
+    value
+}
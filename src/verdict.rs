@@ -0,0 +1,179 @@
+use crate::{
+    binary, config, directives, event, fallback, folded, history, recorder, registry::LockMeta,
+};
+
+use log::{Level, LevelFilter};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Bumped by every setting a verdict is computed from. A lock's cached verdict
+// carries the generation it was computed at and is recomputed on a mismatch,
+// so a hot lock reads one global atomic instead of walking its scopes and the
+// directives on every acquisition.
+static GENERATION: AtomicU64 = AtomicU64::new(1);
+
+const LEVEL_BITS: u64 = 0b111;
+const LOGGING: u64 = 1 << 3;
+const MUTED: u64 = 1 << 4;
+const SAMPLED: u64 = 1 << 5;
+const FOLDED: u64 = 1 << 6;
+const TYPE_NAME: u64 = 1 << 7;
+const GENERATION_SHIFT: u32 = 8;
+
+pub(crate) fn invalidate() {
+    GENERATION.fetch_add(1, Ordering::Release);
+}
+
+pub(crate) fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+// What the settings say about one lock's events. The sinks are read live,
+// being single atomics already, and so is the log's level, which another
+// crate can change without telling this one.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Verdict {
+    logging: bool,
+    // The most verbose level the directives let through for the lock's name.
+    directive: LevelFilter,
+    muted: bool,
+    // Whether the scopes keep only some acquisitions.
+    sampled: bool,
+    // The global switches an acquisition reads before its Acquire, cached
+    // here so it reads none of them.
+    folded: bool,
+    type_name: bool,
+}
+
+impl Verdict {
+    pub(crate) fn of(meta: &LockMeta) -> Self {
+        let generation = generation();
+        let cached = meta.verdict.load(Ordering::Relaxed);
+        if cached >> GENERATION_SHIFT == generation {
+            return Self::decode(cached);
+        }
+        let verdict = Self {
+            logging: config::logging_enabled() && directives::traced(&meta.name()),
+            directive: directives::max_level(&meta.name()),
+            muted: meta.scope().is_muted(),
+            sampled: meta.scope().sample_rate() != 1,
+            folded: folded::enabled(),
+            type_name: config::type_name_on_acquire(),
+        };
+        meta.verdict.store(
+            generation << GENERATION_SHIFT | verdict.encode(),
+            Ordering::Relaxed,
+        );
+        verdict
+    }
+
+    pub(crate) fn logging(self) -> bool {
+        self.logging
+    }
+
+    pub(crate) fn folded(self) -> bool {
+        self.folded
+    }
+
+    pub(crate) fn type_name(self) -> bool {
+        self.type_name
+    }
+
+    pub(crate) fn allows(self, level: Level) -> bool {
+        level <= self.directive
+    }
+
+    // Whether an event at `level` would reach the log, a sink or the lock's
+    // ring, which keeps events the directives filter out.
+    pub(crate) fn reaches(self, meta: &LockMeta, level: Level) -> bool {
+        if !self.logging {
+            return false;
+        }
+        if meta.event_ring.get().is_some() {
+            return true;
+        }
+        self.allows(level) && (sinks() || level <= fallback::max_level())
+    }
+
+    // Nothing from the lock goes anywhere, whatever its level.
+    pub(crate) fn silent(self, meta: &LockMeta) -> bool {
+        !self.reaches(meta, Level::Error)
+    }
+
+    pub(crate) fn sample(self, meta: &LockMeta) -> bool {
+        if self.muted {
+            false
+        } else if self.sampled {
+            meta.scope().sample()
+        } else {
+            true
+        }
+    }
+
+    fn encode(self) -> u64 {
+        let mut bits = self.directive as u64;
+        if self.logging {
+            bits |= LOGGING;
+        }
+        if self.muted {
+            bits |= MUTED;
+        }
+        if self.sampled {
+            bits |= SAMPLED;
+        }
+        if self.folded {
+            bits |= FOLDED;
+        }
+        if self.type_name {
+            bits |= TYPE_NAME;
+        }
+        bits
+    }
+
+    fn decode(bits: u64) -> Self {
+        Self {
+            logging: bits & LOGGING != 0,
+            directive: LevelFilter::iter()
+                .nth((bits & LEVEL_BITS) as usize)
+                .unwrap_or(LevelFilter::Trace),
+            muted: bits & MUTED != 0,
+            sampled: bits & SAMPLED != 0,
+            folded: bits & FOLDED != 0,
+            type_name: bits & TYPE_NAME != 0,
+        }
+    }
+}
+
+fn sinks() -> bool {
+    binary::enabled()
+        || event::has_subscribers()
+        || recorder::enabled()
+        || history::recording_task().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Phase, RwLock};
+
+    // The switch is read through the lock's cached verdict, which must not
+    // outlive a change to it.
+    #[tokio::test]
+    async fn cached_verdicts_follow_the_type_name_switch() {
+        let lock = RwLock::new_named(0u8, "verdict-type-name");
+        let id = lock.id();
+        let mut acquires = crate::subscribe_where(move |event| {
+            event.lock_id == id && event.phase == Phase::Acquire
+        });
+
+        drop(lock.read().await);
+        crate::set_type_name_on_acquire(true);
+        drop(lock.read().await);
+        crate::set_type_name_on_acquire(false);
+        drop(lock.read().await);
+
+        let typed: Vec<bool> = std::iter::from_fn(|| acquires.try_recv().ok())
+            .map(|event| event.message.contains(" type=u8"))
+            .collect();
+        assert_eq!(typed, [false, true, false]);
+    }
+}